@@ -0,0 +1,135 @@
+use std::{io::Write, path::PathBuf};
+
+use color_eyre::eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{provider::DnsListResponse, DATA_DIR, PROFILE};
+
+/// A change eurus made, appended as a line to `audit.jsonl` in the data dir.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Change {
+    DnsCreate {
+        zone: String,
+        new: Value,
+        /// The `cf-ray` of the api response, for Cloudflare support.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ray: Option<String>,
+    },
+    DnsUpdate {
+        zone: String,
+        /// Unknown when the record wasn't listed before it was changed.
+        old: Option<Value>,
+        new: Value,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ray: Option<String>,
+    },
+    DnsDelete {
+        zone: String,
+        id: String,
+        old: Option<Value>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ray: Option<String>,
+    },
+    Maintenance {
+        domain: String,
+        enabled: bool,
+        /// When it's set to turn off again, in RFC 3339.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        until: Option<String>,
+    },
+    Compose {
+        file: PathBuf,
+        /// A unified diff from the previous contents.
+        diff: String,
+    },
+    /// A service proxied before eurus was used on it, taken over by
+    /// `web adopt`.
+    Adopt {
+        file: PathBuf,
+        service: String,
+        domains: Vec<String>,
+    },
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    time: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    profile: Option<&'a String>,
+    #[serde(flatten)]
+    change: &'a Change,
+}
+
+/// An entry read back from the audit log.
+#[derive(Debug, Deserialize)]
+pub struct Logged {
+    pub time: String,
+    #[serde(default)]
+    pub profile: Option<String>,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+fn path() -> PathBuf {
+    DATA_DIR.join("audit.jsonl")
+}
+
+/// Appends the change to the audit log. The log is only ever appended to, so
+/// it keeps the whole history of what eurus did on this machine.
+pub fn record(change: &Change) -> Result<()> {
+    let entry = Entry {
+        time: chrono::Utc::now().to_rfc3339(),
+        profile: PROFILE.get(),
+        change,
+    };
+
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .create(*DATA_DIR)
+        .context("Failed to create data directory")?;
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path())
+        .context("Could not open the audit log.")?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+
+    Ok(())
+}
+
+/// Records the change, only warning when the log can't be written since the
+/// change itself already happened.
+pub fn record_or_warn(change: &Change) {
+    if let Err(e) = record(change) {
+        let _ = cliclack::log::warning(format!("Could not write to the audit log: {e}"));
+    }
+}
+
+/// The entries of the audit log, oldest first. Lines that can't be read,
+/// like ones written by a newer eurus, are skipped.
+pub fn read() -> Result<Vec<Logged>> {
+    let text = match std::fs::read_to_string(path()) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e).context("Could not read the audit log."),
+    };
+
+    Ok(text
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// A record as the log has it, either as the provider returned it or as it
+/// was sent, which has no id for records made in a batch.
+pub fn logged_record(value: &Value) -> Option<DnsListResponse> {
+    let mut value = value.clone();
+    value
+        .as_object_mut()?
+        .entry("id")
+        .or_insert(Value::String(String::new()));
+
+    serde_json::from_value(value).ok()
+}
@@ -0,0 +1,194 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use color_eyre::eyre::{bail, ensure, Context, ContextCompat, Result};
+use serde_json::{json, Value};
+
+use crate::{logging, CLIENT};
+
+pub static DEFAULT_ADMIN_URL: &str = "http://localhost:2019";
+
+/// A running caddy instance, configured through its admin api.
+pub struct CaddyAdmin {
+    url: String,
+}
+
+/// A site served by a running caddy: the hosts it matches and the addresses
+/// it proxies them to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveRoute {
+    pub hosts: Vec<String>,
+    pub upstreams: Vec<String>,
+}
+
+/// Hashes a password for `basic_auth` with a local `caddy hash-password`.
+pub fn hash_password(password: &str) -> Result<String> {
+    let mut child = Command::new("caddy")
+        .arg("hash-password")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Could not run caddy.")?;
+    // Read from stdin when it isn't a terminal, so it doesn't show up in `ps`.
+    child
+        .stdin
+        .take()
+        .context("Could not write to caddy.")?
+        .write_all(format!("{password}\n").as_bytes())?;
+    let output = child.wait_with_output()?;
+    ensure!(
+        output.status.success(),
+        "caddy hash-password failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Routes added by eurus are tagged with this id so they can be replaced.
+fn route_id(domain: &str) -> String {
+    format!("eurus-{domain}")
+}
+
+impl CaddyAdmin {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn get(&self, path: &str) -> Result<Value> {
+        let response = logging::send((*CLIENT).get(format!("{}{path}", self.url))).await?;
+        if !response.status().is_success() {
+            bail!("Caddy returned {} for {path}.", response.status());
+        }
+
+        Ok(response.json().await?)
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<()> {
+        let response = logging::send(request).await?;
+        let status = response.status();
+        if !status.is_success() {
+            bail!(
+                "Caddy rejected the config ({status}): {}",
+                response.text().await?
+            );
+        }
+
+        Ok(())
+    }
+
+    /// The name of the http server that serves https, which is where routes
+    /// for new sites belong.
+    async fn https_server(&self) -> Result<String> {
+        let servers = self.get("/config/apps/http/servers").await?;
+        let servers = servers
+            .as_object()
+            .context("Caddy has no http servers configured.")?;
+
+        servers
+            .iter()
+            .find(|(_, s)| {
+                s["listen"].as_array().is_some_and(|l| {
+                    l.iter()
+                        .any(|a| a.as_str().is_some_and(|a| a.ends_with(":443")))
+                })
+            })
+            .or(servers.iter().next())
+            .map(|(name, _)| name.clone())
+            .context("Caddy has no http servers configured.")
+    }
+
+    /// Every route in the running config that matches on a host and ends in a
+    /// reverse proxy, including ones nested in subroutes.
+    pub async fn routes(&self) -> Result<Vec<LiveRoute>> {
+        let servers = self.get("/config/apps/http/servers").await?;
+        let mut routes = vec![];
+
+        for server in servers.as_object().into_iter().flat_map(|s| s.values()) {
+            collect_routes(&server["routes"], &[], &mut routes);
+        }
+
+        Ok(routes)
+    }
+
+    /// Adds (or replaces) a route that reverse proxies `domain` to `upstream`.
+    pub async fn add_route(&self, domain: &str, upstream: &str) -> Result<()> {
+        let id = route_id(domain);
+        let route = json!({
+            "@id": id,
+            "match": [{ "host": [domain] }],
+            "handle": [{
+                "handler": "subroute",
+                "routes": [{
+                    "handle": [{
+                        "handler": "reverse_proxy",
+                        "upstreams": [{ "dial": upstream }],
+                    }],
+                }],
+            }],
+            "terminal": true,
+        });
+
+        if self.get(&format!("/id/{id}")).await.is_ok() {
+            return self
+                .send(
+                    (*CLIENT)
+                        .patch(format!("{}/id/{id}", self.url))
+                        .json(&route),
+                )
+                .await;
+        }
+
+        // Inserting at the front keeps catch-all routes from shadowing it.
+        let server = self.https_server().await?;
+        self.send(
+            (*CLIENT)
+                .put(format!(
+                    "{}/config/apps/http/servers/{server}/routes/0",
+                    self.url
+                ))
+                .json(&route),
+        )
+        .await
+    }
+}
+
+/// Walks `routes`, carrying the hosts matched by enclosing routes down into
+/// subroutes, which is how both caddy-docker-proxy and the Caddyfile adapter
+/// nest them.
+fn collect_routes(routes: &Value, hosts: &[String], found: &mut Vec<LiveRoute>) {
+    for route in routes.as_array().into_iter().flatten() {
+        let matched: Vec<String> = route["match"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|m| m["host"].as_array().into_iter().flatten())
+            .filter_map(|h| h.as_str().map(String::from))
+            .collect();
+        let hosts = match matched.is_empty() {
+            true => hosts.to_vec(),
+            false => matched,
+        };
+
+        for handler in route["handle"].as_array().into_iter().flatten() {
+            match handler["handler"].as_str() {
+                Some("subroute") => collect_routes(&handler["routes"], &hosts, found),
+                Some("reverse_proxy") if !hosts.is_empty() => found.push(LiveRoute {
+                    hosts: hosts.clone(),
+                    upstreams: handler["upstreams"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|u| u["dial"].as_str().map(String::from))
+                        .collect(),
+                }),
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Stopping library operations part way, for embedding them in other async
+//! applications and for Ctrl-C in long running commands.
+//!
+//! Operations that take a [`CancellationToken`] only stop between steps that
+//! change something, so a change that was sent is always finished and
+//! recorded. Reads and waits are dropped as soon as the token is cancelled.
+
+use std::future::Future;
+
+pub use tokio_util::sync::CancellationToken;
+
+use crate::error::{exit, EurusError};
+
+/// Runs `future` until it finishes or `token` is cancelled, in which case it
+/// is dropped and [`EurusError::Cancelled`] returned. Only for futures that
+/// are safe to stop anywhere, like listing records.
+pub async fn or_cancelled<T, E: From<EurusError>>(
+    token: &CancellationToken,
+    future: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    tokio::select! {
+        biased;
+        _ = token.cancelled() => Err(EurusError::Cancelled.into()),
+        result = future => result,
+    }
+}
+
+/// Fails with [`EurusError::Cancelled`] once `token` is cancelled, to check
+/// between steps.
+pub fn check(token: &CancellationToken) -> Result<(), EurusError> {
+    match token.is_cancelled() {
+        true => Err(EurusError::Cancelled),
+        false => Ok(()),
+    }
+}
+
+/// A token cancelled by Ctrl-C. A second Ctrl-C exits right away, for when
+/// the step being finished hangs.
+pub fn on_ctrl_c() -> Result<CancellationToken, ctrlc::Error> {
+    let token = CancellationToken::new();
+    let handler = token.clone();
+    ctrlc::set_handler(move || {
+        if handler.is_cancelled() {
+            std::process::exit(exit::CANCELLED);
+        }
+        handler.cancel();
+    })?;
+
+    Ok(token)
+}
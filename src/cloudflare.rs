@@ -0,0 +1,1084 @@
+use std::net::IpAddr;
+
+use async_trait::async_trait;
+use ipnet::IpNet;
+use reqwest::Client;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    error::{EurusError, Result},
+    logging,
+    provider::{
+        batch_individually, DnsCreateUpdate, DnsListResponse, DnsProvider, ZoneDetailsResponse,
+    },
+    quota, retry, CLIENT,
+};
+
+static BASE_URL: &str = "https://api.cloudflare.com/client/v4";
+
+/// The environment variable that overrides the api base url, before
+/// `api_base_url` in the config.
+pub const BASE_URL_VAR: &str = "EURUS_API_BASE_URL";
+static PER_PAGE: u32 = 100;
+
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct CloudflareResponse<T> {
+    pub errors: Vec<CloudflareError>,
+    pub result: Option<T>,
+    #[serde(default)]
+    pub result_info: Option<ResultInfo>,
+}
+
+/// Paging details of list endpoints.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct ResultInfo {
+    pub page: u32,
+    pub total_pages: u32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CloudflareError {
+    pub code: i32,
+    pub message: String,
+}
+
+impl<T> CloudflareResponse<T> {
+    fn into_result(self) -> Result<T> {
+        if !self.errors.is_empty() {
+            return Err(api_error(&self.errors));
+        }
+
+        self.result
+            .ok_or_else(|| EurusError::api("Cloudflare api returned no result."))
+    }
+}
+
+fn api_error(errors: &[CloudflareError]) -> EurusError {
+    EurusError::ApiError {
+        status: None,
+        codes: errors.iter().map(|e| e.code).collect(),
+        message: format!(
+            "Cloudflare api returned an error: {}",
+            errors
+                .iter()
+                .map(|e| e.message.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        ray: None,
+    }
+}
+
+/// Reads the body of an api response. A failed status is reported with the
+/// errors Cloudflare sent, or what the status usually means when the body has
+/// none, such as the html pages its edge returns.
+async fn read_response<T: DeserializeOwned>(
+    response: reqwest::Response,
+) -> Result<CloudflareResponse<T>> {
+    let status = response.status();
+    let text = response.text().await?;
+
+    if !status.is_success() {
+        let errors = serde_json::from_str::<CloudflareResponse<serde_json::Value>>(&text)
+            .map(|res| res.errors)
+            .unwrap_or_default();
+        return Err(match errors.is_empty() {
+            true => EurusError::api(status_message(status)),
+            false => api_error(&errors),
+        });
+    }
+
+    serde_json::from_str(&text)
+        .map_err(|e| EurusError::api(format!("Unexpected response from the Cloudflare api: {e}")))
+}
+
+/// What a failed status without any error codes usually means.
+fn status_message(status: reqwest::StatusCode) -> String {
+    let hint = match status.as_u16() {
+        400 => "the request wasn't accepted",
+        401 => "the api key is missing or wasn't accepted",
+        403 => "the api key isn't allowed to do this",
+        404 => "the zone or record wasn't found",
+        429 => "too many api calls were made recently",
+        500..=599 => "Cloudflare is having problems, try again later",
+        _ => "the request failed",
+    };
+    format!("Cloudflare api returned {status}, {hint}.")
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct DeletedRecord {
+    id: String,
+}
+
+/// How requests to the api are authenticated.
+#[derive(Debug, Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    /// An api token, sent as a bearer token.
+    #[default]
+    Token,
+    /// The legacy Global API Key, sent along with the account's email.
+    GlobalKey,
+}
+
+/// The api base url from `EURUS_API_BASE_URL`, then `configured`, falling
+/// back to Cloudflare's.
+fn base_url(configured: &str) -> String {
+    std::env::var(BASE_URL_VAR)
+        .ok()
+        .filter(|url| !url.is_empty())
+        .unwrap_or_else(|| match configured.is_empty() {
+            true => BASE_URL.to_string(),
+            false => configured.to_string(),
+        })
+        .trim_end_matches('/')
+        .to_string()
+}
+
+pub struct Cloudflare {
+    api_key: String,
+    base_url: String,
+    client: Client,
+    /// Set when authenticating with the Global API Key.
+    email: Option<String>,
+    batching: bool,
+}
+
+impl Cloudflare {
+    pub fn new(api_key: &str) -> Self {
+        Self {
+            api_key: api_key.to_string(),
+            base_url: base_url(""),
+            client: CLIENT.clone(),
+            email: None,
+            batching: true,
+        }
+    }
+
+    /// A client using the credentials and settings from the config. Fails when
+    /// `cloudflare_key_cmd` does.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let cloudflare = Self::new(&config.cloudflare_key()?)
+            .with_base_url(&base_url(&config.api_base_url))
+            .with_batching(config.dns_batch);
+        Ok(match config.cloudflare_auth {
+            AuthMethod::Token => cloudflare,
+            AuthMethod::GlobalKey => cloudflare.with_global_key(&config.cloudflare_email),
+        })
+    }
+
+    /// Sends requests to `base_url` instead of the Cloudflare api, e.g. a mock
+    /// server in tests.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Authenticates with `api_key` as the Global API Key of `email`'s account.
+    pub fn with_global_key(mut self, email: &str) -> Self {
+        self.email = Some(email.to_string());
+        self
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.email {
+            Some(email) => request
+                .header("X-Auth-Email", email)
+                .header("X-Auth-Key", &self.api_key),
+            None => request.bearer_auth(&self.api_key),
+        }
+    }
+
+    /// Whether [`DnsProvider::batch`] uses the batch endpoint, or makes one
+    /// call per record.
+    pub fn with_batching(mut self, batching: bool) -> Self {
+        self.batching = batching;
+        self
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: reqwest::RequestBuilder) -> Result<T> {
+        Ok(self.send_page(request).await?.0)
+    }
+
+    async fn send_page<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<(T, Option<ResultInfo>)> {
+        quota::acquire().await;
+        let response = retry::send(self.authorize(request)).await?;
+        let status = response.status();
+        let ray = logging::ray(&response);
+
+        let res = read_response::<T>(response)
+            .await
+            .map_err(|e| e.with_status(status).with_ray(ray.clone()))?;
+        let info = res.result_info.clone();
+
+        Ok((
+            res.into_result()
+                .map_err(|e| e.with_status(status).with_ray(ray))?,
+            info,
+        ))
+    }
+
+    /// Reads every page of a list endpoint.
+    async fn list<T: DeserializeOwned>(&self, url: &str) -> Result<Vec<T>> {
+        let mut items = vec![];
+        let mut page = 1;
+
+        loop {
+            let (result, info): (Vec<T>, _) = self
+                .send_page(
+                    self.client
+                        .get(url)
+                        .query(&[("page", page), ("per_page", PER_PAGE)]),
+                )
+                .await?;
+            items.extend(result);
+
+            match info {
+                Some(info) if info.page < info.total_pages => page = info.page + 1,
+                _ => break,
+            }
+        }
+
+        Ok(items)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct Account {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CreatedZone {
+    pub id: String,
+    pub name: String,
+    pub name_servers: Vec<String>,
+}
+
+/// The result of verifying an api token.
+#[derive(Debug, Deserialize, Clone)]
+pub struct TokenStatus {
+    pub id: String,
+    /// `active`, `disabled` or `expired`.
+    pub status: String,
+    #[serde(default)]
+    pub expires_on: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct User {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct ZonePermissions {
+    #[serde(default)]
+    permissions: Vec<String>,
+}
+
+impl Cloudflare {
+    pub async fn verify_token(&self) -> Result<TokenStatus> {
+        self.send(
+            self.client
+                .get(format!("{}/user/tokens/verify", self.base_url)),
+        )
+        .await
+    }
+
+    /// The user the Global API Key belongs to, which also checks the key.
+    pub async fn user(&self) -> Result<User> {
+        self.send(self.client.get(format!("{}/user", self.base_url)))
+            .await
+    }
+
+    /// What the credentials may do in the zone, e.g. `#dns_records:edit`.
+    pub async fn zone_permissions(&self, zone_id: &str) -> Result<Vec<String>> {
+        let zone: ZonePermissions = self
+            .send(
+                self.client
+                    .get(format!("{}/zones/{zone_id}", self.base_url)),
+            )
+            .await?;
+        Ok(zone.permissions)
+    }
+
+    pub async fn list_accounts(&self) -> Result<Vec<Account>> {
+        self.list(&format!("{}/accounts", self.base_url)).await
+    }
+
+    /// Every zone the token can access.
+    pub async fn list_zones(&self) -> Result<Vec<ZoneDetailsResponse>> {
+        self.list(&format!("{}/zones", self.base_url)).await
+    }
+
+    /// The zones in a single account.
+    pub async fn list_zones_in(&self, account_id: &str) -> Result<Vec<ZoneDetailsResponse>> {
+        self.list(&format!("{}/zones?account.id={account_id}", self.base_url))
+            .await
+    }
+
+    /// Registers a new zone in the given account.
+    pub async fn create_zone(&self, name: &str, account_id: &str) -> Result<CreatedZone> {
+        self.send(
+            self.client
+                .post(format!("{}/zones", self.base_url))
+                .json(&serde_json::json!({
+                    "name": name,
+                    "account": { "id": account_id },
+                    "type": "full",
+                })),
+        )
+        .await
+    }
+}
+
+/// A cloudflared tunnel.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct Tunnel {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub status: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct TunnelConfiguration {
+    #[serde(default)]
+    pub config: TunnelConfig,
+}
+
+/// The remotely managed configuration of a tunnel. Only the ingress rules are
+/// typed, everything else is passed back untouched.
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct TunnelConfig {
+    #[serde(default)]
+    pub ingress: Vec<IngressRule>,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct IngressRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    pub service: String,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl TunnelConfig {
+    /// Routes the hostname to the service, replacing any rule it already has.
+    /// New rules go before the catch-all rule, which has to be last.
+    pub fn set_ingress(&mut self, hostname: &str, service: &str) {
+        if let Some(rule) = self
+            .ingress
+            .iter_mut()
+            .find(|r| r.hostname.as_deref() == Some(hostname))
+        {
+            rule.service = service.to_string();
+            return;
+        }
+
+        let rule = IngressRule {
+            hostname: Some(hostname.to_string()),
+            service: service.to_string(),
+            other: Default::default(),
+        };
+        match self.ingress.iter().position(|r| r.hostname.is_none()) {
+            Some(catch_all) => self.ingress.insert(catch_all, rule),
+            None => {
+                self.ingress.push(rule);
+                self.ingress.push(IngressRule {
+                    hostname: None,
+                    service: "http_status:404".to_string(),
+                    other: Default::default(),
+                });
+            }
+        }
+    }
+}
+
+impl Tunnel {
+    /// The hostname a CNAME has to point at to route through this tunnel.
+    pub fn target(&self) -> String {
+        format!("{}.cfargotunnel.com", self.id)
+    }
+}
+
+impl Cloudflare {
+    pub async fn list_tunnels(&self, account_id: &str) -> Result<Vec<Tunnel>> {
+        self.list(&format!(
+            "{}/accounts/{account_id}/cfd_tunnel?is_deleted=false",
+            self.base_url
+        ))
+        .await
+    }
+
+    pub async fn tunnel_config(&self, account_id: &str, tunnel_id: &str) -> Result<TunnelConfig> {
+        let res: TunnelConfiguration = self
+            .send(self.client.get(format!(
+                "{}/accounts/{account_id}/cfd_tunnel/{tunnel_id}/configurations",
+                self.base_url
+            )))
+            .await?;
+
+        Ok(res.config)
+    }
+
+    pub async fn set_tunnel_config(
+        &self,
+        account_id: &str,
+        tunnel_id: &str,
+        config: &TunnelConfig,
+    ) -> Result<()> {
+        self.send::<TunnelConfiguration>(
+            self.client
+                .put(format!(
+                    "{}/accounts/{account_id}/cfd_tunnel/{tunnel_id}/configurations",
+                    self.base_url
+                ))
+                .json(&serde_json::json!({ "config": config })),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// The ruleset phase single redirects live in.
+static REDIRECT_PHASE: &str = "http_request_dynamic_redirect";
+
+/// A rule in one of a zone's rulesets. Fields eurus doesn't use are kept so
+/// writing the ruleset back doesn't lose them.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Rule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub expression: String,
+    pub action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct Ruleset {
+    #[serde(default)]
+    rules: Vec<Rule>,
+}
+
+impl Rule {
+    /// A redirect from `from`, a host with an optional path that may contain
+    /// `*`s, to the URL `to`. `$1` and so on in `to` are what the `*`s matched.
+    /// When neither has a path, the request's path is kept.
+    pub fn redirect_pattern(from: &str, to: &str, status: u16) -> Self {
+        let from = from
+            .trim_start_matches("https://")
+            .trim_start_matches("http://");
+        let to = match to.contains("://") {
+            true => to.to_string(),
+            false => format!("https://{to}"),
+        };
+        let (host, path) = match from.split_once('/') {
+            Some((host, path)) => (host, Some(format!("/{path}"))),
+            // `$1` without a path in `from` means the whole path.
+            None => (from, to.contains('$').then(|| "/*".to_string())),
+        };
+        let to_has_path = to
+            .split_once("://")
+            .is_some_and(|(_, rest)| rest.trim_end_matches('/').contains('/'));
+
+        let host_matches = format!("http.host eq \"{host}\"");
+        let (expression, target) = match path {
+            Some(path) if path.contains('*') => (
+                format!("({host_matches} and http.request.uri.path wildcard r\"{path}\")"),
+                serde_json::json!({
+                    "expression": format!(
+                        "wildcard_replace(http.request.uri.path, r\"{path}\", r\"{}\")",
+                        wildcard_references(&to)
+                    ),
+                }),
+            ),
+            Some(path) => (
+                format!("({host_matches} and http.request.uri.path eq \"{path}\")"),
+                serde_json::json!({ "value": to }),
+            ),
+            None if !to_has_path => (
+                format!("({host_matches})"),
+                serde_json::json!({
+                    "expression": format!(
+                        "concat(\"{}\", http.request.uri.path)",
+                        to.trim_end_matches('/')
+                    ),
+                }),
+            ),
+            None => (
+                format!("({host_matches})"),
+                serde_json::json!({ "value": to }),
+            ),
+        };
+
+        Rule {
+            id: None,
+            expression,
+            action: "redirect".to_string(),
+            description: Some(format!("Redirect {from} to {to}")),
+            other: serde_json::json!({
+                "action_parameters": {
+                    "from_value": {
+                        "status_code": status,
+                        "target_url": target,
+                        "preserve_query_string": true,
+                    },
+                },
+                "enabled": true,
+            })
+            .as_object()
+            .cloned()
+            .unwrap_or_default(),
+        }
+    }
+
+    /// Where the rule redirects to, as its target URL or expression.
+    pub fn redirect_target(&self) -> Option<String> {
+        let target = self
+            .other
+            .get("action_parameters")?
+            .get("from_value")?
+            .get("target_url")?;
+        target
+            .get("value")
+            .or_else(|| target.get("expression"))?
+            .as_str()
+            .map(str::to_string)
+    }
+
+    pub fn redirect_status(&self) -> Option<u64> {
+        self.other
+            .get("action_parameters")?
+            .get("from_value")?
+            .get("status_code")?
+            .as_u64()
+    }
+}
+
+/// Turns `$1` into the `${1}` that `wildcard_replace` expects.
+fn wildcard_references(to: &str) -> String {
+    let mut out = String::new();
+    let mut chars = to.chars().peekable();
+    while let Some(c) = chars.next() {
+        let digits: String = match c {
+            '$' => std::iter::from_fn(|| chars.next_if(char::is_ascii_digit)).collect(),
+            _ => String::new(),
+        };
+        match digits.is_empty() {
+            true => out.push(c),
+            false => out.push_str(&format!("${{{digits}}}")),
+        }
+    }
+    out
+}
+
+impl Cloudflare {
+    /// The zone's redirect rules, empty when it has never had any.
+    pub async fn redirect_rules(&self, zone_id: &str) -> Result<Vec<Rule>> {
+        let res = self
+            .send::<Ruleset>(self.client.get(format!(
+                "{}/zones/{zone_id}/rulesets/phases/{REDIRECT_PHASE}/entrypoint",
+                self.base_url
+            )))
+            .await;
+
+        match res {
+            Ok(ruleset) => Ok(ruleset.rules),
+            Err(EurusError::ApiError {
+                status: Some(404), ..
+            }) => Ok(vec![]),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Replaces the zone's redirect rules.
+    pub async fn set_redirect_rules(&self, zone_id: &str, rules: &[Rule]) -> Result<()> {
+        // These are set by Cloudflare and rejected when sent back.
+        let rules: Vec<_> = rules
+            .iter()
+            .cloned()
+            .map(|mut r| {
+                r.other.remove("version");
+                r.other.remove("last_updated");
+                r
+            })
+            .collect();
+
+        self.send::<Ruleset>(
+            self.client
+                .put(format!(
+                    "{}/zones/{zone_id}/rulesets/phases/{REDIRECT_PHASE}/entrypoint",
+                    self.base_url
+                ))
+                .json(&serde_json::json!({ "rules": rules })),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// The zone settings `zone settings` manages, with their names in the
+/// dashboard.
+pub static ZONE_SETTINGS: [(&str, &str); 4] = [
+    ("ssl", "SSL mode"),
+    ("always_use_https", "Always Use HTTPS"),
+    ("automatic_https_rewrites", "Automatic HTTPS Rewrites"),
+    ("min_tls_version", "Minimum TLS Version"),
+];
+
+/// One of a zone's settings. The ones eurus changes have string values like
+/// `on`, `strict` or `1.2`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ZoneSetting {
+    pub id: String,
+    pub value: serde_json::Value,
+    #[serde(default)]
+    pub editable: bool,
+}
+
+impl ZoneSetting {
+    pub fn display_value(&self) -> String {
+        match &self.value {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        }
+    }
+}
+
+impl Cloudflare {
+    /// The settings of the zone in [`ZONE_SETTINGS`], in that order.
+    pub async fn zone_settings(&self, zone_id: &str) -> Result<Vec<ZoneSetting>> {
+        let settings: Vec<ZoneSetting> = self
+            .send(
+                self.client
+                    .get(format!("{}/zones/{zone_id}/settings", self.base_url)),
+            )
+            .await?;
+
+        Ok(ZONE_SETTINGS
+            .iter()
+            .filter_map(|(id, _)| settings.iter().find(|s| s.id == *id).cloned())
+            .collect())
+    }
+
+    pub async fn set_zone_setting(
+        &self,
+        zone_id: &str,
+        id: &str,
+        value: &str,
+    ) -> Result<ZoneSetting> {
+        self.send(
+            self.client
+                .patch(format!("{}/zones/{zone_id}/settings/{id}", self.base_url))
+                .json(&serde_json::json!({ "value": value })),
+        )
+        .await
+    }
+}
+
+/// How many URLs a single purge request may list on most plans.
+static PURGE_LIMIT: usize = 30;
+
+impl Cloudflare {
+    /// Drops everything Cloudflare has cached for the zone.
+    pub async fn purge_everything(&self, zone_id: &str) -> Result<()> {
+        self.send::<serde_json::Value>(
+            self.client
+                .post(format!("{}/zones/{zone_id}/purge_cache", self.base_url))
+                .json(&serde_json::json!({ "purge_everything": true })),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Drops the cached copies of the URLs, in as many requests as the
+    /// per-request limit needs.
+    pub async fn purge_urls(&self, zone_id: &str, urls: &[String]) -> Result<()> {
+        for urls in urls.chunks(PURGE_LIMIT) {
+            self.send::<serde_json::Value>(
+                self.client
+                    .post(format!("{}/zones/{zone_id}/purge_cache", self.base_url))
+                    .json(&serde_json::json!({ "files": urls })),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Cloudflare {
+    /// Performs an arbitrary authenticated api call, returning the status and
+    /// the raw response body.
+    pub async fn raw(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<(reqwest::StatusCode, serde_json::Value)> {
+        let mut request = self.authorize(self.client.request(
+            method,
+            format!("{}/{}", self.base_url, path.trim_start_matches('/')),
+        ));
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+
+        quota::acquire().await;
+        let response = retry::send(request).await?;
+        let status = response.status();
+        let text = response.text().await?;
+
+        Ok((
+            status,
+            serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text)),
+        ))
+    }
+
+    /// Deletes the zone and all of its records. Callers are expected to have
+    /// confirmed this with the user first.
+    pub async fn delete_zone(&self, zone_id: &str) -> Result<()> {
+        self.send::<DeletedRecord>(
+            self.client
+                .delete(format!("{}/zones/{zone_id}", self.base_url)),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DnsProvider for Cloudflare {
+    async fn get_zone(&self, zone_id: &str) -> Result<ZoneDetailsResponse> {
+        self.send(
+            self.client
+                .get(format!("{}/zones/{zone_id}", self.base_url)),
+        )
+        .await
+    }
+
+    async fn list_records(&self, zone_id: &str) -> Result<Vec<DnsListResponse>> {
+        self.list(&format!("{}/zones/{zone_id}/dns_records", self.base_url))
+            .await
+    }
+
+    async fn upsert_record(
+        &self,
+        zone_id: &str,
+        record: &DnsCreateUpdate,
+    ) -> Result<DnsListResponse> {
+        let request = match &record.id {
+            Some(id) => self.client.patch(format!(
+                "{}/zones/{zone_id}/dns_records/{id}",
+                self.base_url
+            )),
+            None => self
+                .client
+                .post(format!("{}/zones/{zone_id}/dns_records", self.base_url)),
+        };
+
+        self.send(request.json(&record_body(record)?)).await
+    }
+
+    async fn delete_record(&self, zone_id: &str, record_id: &str) -> Result<()> {
+        self.send::<DeletedRecord>(self.client.delete(format!(
+            "{}/zones/{zone_id}/dns_records/{record_id}",
+            self.base_url
+        )))
+        .await?;
+
+        Ok(())
+    }
+
+    fn batches_atomically(&self) -> bool {
+        self.batching
+    }
+
+    async fn batch(
+        &self,
+        zone_id: &str,
+        upserts: &[DnsCreateUpdate],
+        deletes: &[String],
+    ) -> Result<()> {
+        if !self.batching || upserts.len() + deletes.len() < 2 {
+            return batch_individually(self, zone_id, upserts, deletes).await;
+        }
+
+        let (patches, posts): (Vec<_>, Vec<_>) = upserts.iter().partition(|r| r.id.is_some());
+        let body = serde_json::json!({
+            "deletes": deletes.iter().map(|id| serde_json::json!({ "id": id })).collect::<Vec<_>>(),
+            "patches": patches.into_iter().map(record_body).collect::<Result<Vec<_>>>()?,
+            "posts": posts.into_iter().map(record_body).collect::<Result<Vec<_>>>()?,
+        });
+
+        let res = self
+            .send::<serde_json::Value>(
+                self.client
+                    .post(format!(
+                        "{}/zones/{zone_id}/dns_records/batch",
+                        self.base_url
+                    ))
+                    .json(&body),
+            )
+            .await;
+        match res {
+            // Accounts without the batch endpoint still get the changes, just
+            // not atomically.
+            Err(EurusError::ApiError {
+                status: Some(404 | 405),
+                ..
+            }) => batch_individually(self, zone_id, upserts, deletes).await,
+            res => res.map(|_| ()),
+        }
+    }
+}
+
+/// The address ranges of Cloudflare's edge, which proxied records resolve to.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct EdgeIps {
+    pub ipv4_cidrs: Vec<String>,
+    pub ipv6_cidrs: Vec<String>,
+}
+
+impl EdgeIps {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.ipv4_cidrs
+            .iter()
+            .chain(&self.ipv6_cidrs)
+            .filter_map(|cidr| cidr.parse::<IpNet>().ok())
+            .any(|net| net.contains(&ip))
+    }
+}
+
+/// Cloudflare's edge ranges, which are public so no credentials are needed.
+/// They're fetched from the api base url in the config.
+pub async fn edge_ips(config: &Config) -> Result<EdgeIps> {
+    let url = format!("{}/ips", base_url(&config.api_base_url));
+    let response = logging::send((*CLIENT).get(url)).await?;
+    let status = response.status();
+    read_response::<EdgeIps>(response)
+        .await?
+        .into_result()
+        .map_err(|e| e.with_status(status))
+}
+
+/// Hostnames on Cloudflare's own services that a CNAME can point at, which
+/// only work when the record is set up a certain way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialTarget {
+    /// `<id>.cfargotunnel.com`, a Cloudflare Tunnel.
+    Tunnel,
+    /// `<project>.pages.dev`
+    Pages,
+    /// `<bucket>.r2.dev`, an R2 bucket's development URL.
+    R2,
+}
+
+impl SpecialTarget {
+    pub fn detect(target: &str) -> Option<Self> {
+        let target = target.trim().trim_end_matches('.').to_lowercase();
+        [
+            ("cfargotunnel.com", Self::Tunnel),
+            ("pages.dev", Self::Pages),
+            ("r2.dev", Self::R2),
+        ]
+        .into_iter()
+        .find(|(suffix, _)| target.ends_with(&format!(".{suffix}")))
+        .map(|(_, special)| special)
+    }
+
+    /// Why a record pointing at the target won't be served, if it won't.
+    pub fn problem(self, proxied: bool) -> Option<&'static str> {
+        match (self, proxied) {
+            (Self::Tunnel, false) => Some(
+                "Cloudflare only routes tunnels through proxied records, an unproxied CNAME to \
+                 cfargotunnel.com doesn't resolve.",
+            ),
+            (Self::R2, _) => Some(
+                "Cloudflare doesn't serve buckets for domains pointed at r2.dev. Connect the \
+                 domain in the bucket's settings instead, which creates the record.",
+            ),
+            _ => None,
+        }
+    }
+
+    /// What else has to be set up on Cloudflare's side.
+    pub fn note(self) -> Option<&'static str> {
+        match self {
+            Self::Pages => Some(
+                "Also add the domain to the Pages project's custom domains, until then \
+                 Cloudflare answers with a 522.",
+            ),
+            Self::Tunnel => {
+                Some("The tunnel needs a public hostname (or ingress rule) for the domain as well.")
+            }
+            Self::R2 => None,
+        }
+    }
+}
+
+/// Records with structured data are built from it by Cloudflare, so the
+/// content is left out as it would otherwise have to match exactly.
+fn record_body(record: &DnsCreateUpdate) -> Result<serde_json::Value> {
+    record.validate()?;
+    let mut body = serde_json::to_value(record)?;
+    if record.data.is_some() {
+        body.as_object_mut().map(|b| b.remove("content"));
+    }
+
+    Ok(body)
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphqlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Option<Vec<GraphqlError>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphqlError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnalyticsViewer {
+    viewer: AnalyticsZones,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnalyticsZones {
+    zones: Vec<HostAnalytics>,
+}
+
+/// Hourly traffic for a single hostname.
+#[derive(Debug, Deserialize, Default)]
+pub struct HostAnalytics {
+    pub series: Vec<AnalyticsGroup>,
+    pub threats: Vec<AnalyticsGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnalyticsGroup {
+    pub count: u64,
+    #[serde(default)]
+    pub dimensions: Option<AnalyticsDimensions>,
+    #[serde(default)]
+    pub sum: Option<AnalyticsSum>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsDimensions {
+    pub datetime_hour: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsSum {
+    pub edge_response_bytes: u64,
+}
+
+static HOST_ANALYTICS_QUERY: &str = r#"
+query HostAnalytics($zoneTag: string, $filter: ZoneHttpRequestsAdaptiveGroupsFilter_InputObject, $threats: ZoneHttpRequestsAdaptiveGroupsFilter_InputObject) {
+  viewer {
+    zones(filter: { zoneTag: $zoneTag }) {
+      series: httpRequestsAdaptiveGroups(limit: 1000, filter: $filter, orderBy: [datetimeHour_ASC]) {
+        count
+        dimensions { datetimeHour }
+        sum { edgeResponseBytes }
+      }
+      threats: httpRequestsAdaptiveGroups(limit: 1, filter: $threats) {
+        count
+      }
+    }
+  }
+}"#;
+
+impl Cloudflare {
+    async fn graphql<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<T> {
+        quota::acquire().await;
+        let response = retry::send(
+            self.authorize(self.client.post(format!("{}/graphql", self.base_url)))
+                .json(&serde_json::json!({ "query": query, "variables": variables })),
+        )
+        .await?;
+        let status = response.status();
+        let ray = logging::ray(&response);
+        if !status.is_success() {
+            return Err(EurusError::api(status_message(status))
+                .with_status(status)
+                .with_ray(ray));
+        }
+        let res: GraphqlResponse<T> = response.json().await.map_err(|e| {
+            EurusError::api(format!(
+                "Unexpected response from Cloudflare analytics: {e}"
+            ))
+        })?;
+
+        if let Some(errors) = res.errors.filter(|e| !e.is_empty()) {
+            let messages: Vec<_> = errors.into_iter().map(|e| e.message).collect();
+            return Err(EurusError::api(format!(
+                "Cloudflare analytics returned an error: {}",
+                messages.join(", ")
+            ))
+            .with_ray(ray));
+        }
+
+        res.data
+            .ok_or_else(|| EurusError::api("Cloudflare analytics returned no data."))
+    }
+
+    /// Fetches the requests, bandwidth and blocked requests for `host` over the
+    /// given time range.
+    pub async fn host_analytics(
+        &self,
+        zone_id: &str,
+        host: &str,
+        since: chrono::DateTime<chrono::Utc>,
+        until: chrono::DateTime<chrono::Utc>,
+    ) -> Result<HostAnalytics> {
+        let filter = serde_json::json!({
+            "clientRequestHTTPHost": host,
+            "datetime_geq": since.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            "datetime_lt": until.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        });
+        let mut threats = filter.clone();
+        threats["securityAction"] = "block".into();
+
+        let res: AnalyticsViewer = self
+            .graphql(
+                HOST_ANALYTICS_QUERY,
+                serde_json::json!({ "zoneTag": zone_id, "filter": filter, "threats": threats }),
+            )
+            .await?;
+
+        Ok(res.viewer.zones.into_iter().next().unwrap_or_default())
+    }
+}
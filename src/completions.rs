@@ -0,0 +1,93 @@
+use std::{collections::BTreeSet, path::PathBuf};
+
+use clap_complete::{env::Shells, CompletionCandidate};
+use color_eyre::eyre::{ContextCompat, Result};
+use serde_json::Value;
+
+use crate::{config, presets, templates};
+
+/// The variable the registration scripts set when asking eurus for
+/// completions.
+pub static VAR: &str = "COMPLETE";
+
+/// Writes the script that registers eurus' completions with `shell`. The
+/// script calls back into eurus, so zones and profiles added later complete
+/// too.
+pub fn write_registration(shell: &str, out: &mut dyn std::io::Write) -> Result<()> {
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(shell)
+        .with_context(|| format!("Completions aren't available for {shell}."))?;
+    let bin = std::env::current_exe()
+        .map(|p| p.display().to_string())
+        .unwrap_or("eurus".to_string());
+
+    Ok(completer.write_registration(VAR, "eurus", "eurus", &bin, out)?)
+}
+
+pub fn shells() -> Vec<&'static str> {
+    Shells::builtins().names().collect()
+}
+
+/// The config as plain json. Completions only need names, so this skips
+/// loading keys from the keyring or `cloudflare_key_cmd`.
+fn config() -> Value {
+    // `--config` isn't parsed while completing, but the variable is set.
+    let paths = match std::env::var_os("EURUS_CONFIG") {
+        Some(path) => vec![PathBuf::from(path)],
+        // Until eurus runs once and converts it.
+        None => vec![config::config_path(), config::legacy_path()],
+    };
+    paths
+        .into_iter()
+        .find_map(|path| {
+            let contents = std::fs::read_to_string(&path).ok()?;
+            config::parse(&path, &contents).ok()
+        })
+        .unwrap_or_default()
+}
+
+fn candidates(names: BTreeSet<String>) -> Vec<CompletionCandidate> {
+    names.into_iter().map(CompletionCandidate::new).collect()
+}
+
+fn zone_names(zones: &Value) -> impl Iterator<Item = String> + '_ {
+    zones
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|z| z["name"].as_str().map(String::from))
+}
+
+/// The zones in the config, including those of every profile.
+pub fn zones() -> Vec<CompletionCandidate> {
+    let config = config();
+    let mut names: BTreeSet<_> = zone_names(&config["zones"]).collect();
+    if let Some(profiles) = config["profiles"].as_object() {
+        names.extend(profiles.values().flat_map(|p| zone_names(&p["zones"])));
+    }
+
+    candidates(names)
+}
+
+pub fn profiles() -> Vec<CompletionCandidate> {
+    let config = config();
+    let names = config["profiles"]
+        .as_object()
+        .map(|p| p.keys().cloned().collect())
+        .unwrap_or_default();
+
+    candidates(names)
+}
+
+/// The built in templates and those in the templates folder.
+pub fn templates() -> Vec<CompletionCandidate> {
+    candidates(templates::all().unwrap_or_default().into_keys().collect())
+}
+
+/// The built in presets and those in the config.
+pub fn presets() -> Vec<CompletionCandidate> {
+    let configured = serde_json::from_value(config()["presets"].clone()).unwrap_or_default();
+
+    candidates(presets::all(&configured).into_keys().collect())
+}
@@ -0,0 +1,561 @@
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{ensure, Context, ContextCompat, Result};
+use docker_compose_types::{
+    Compose, ComposeNetwork, DependsOnOptions, Labels, MapOrEmpty, NetworkSettings, Networks,
+    Ports, Service,
+};
+
+use crate::env;
+
+/// Parses a compose file. docker_compose_types doesn't know the top level
+/// `include`, so it's left out here, and kept in the file by in-place edits.
+/// It also only takes `deploy.labels` as a list, so a mapping is turned into
+/// one.
+pub fn parse(contents: &str) -> Result<Compose, serde_yml::Error> {
+    match serde_yml::from_str(contents) {
+        Err(e) => {
+            let mut value: serde_yml::Value = serde_yml::from_str(contents)?;
+            let included = value
+                .as_mapping_mut()
+                .and_then(|m| m.remove("include"))
+                .is_some();
+            match included | deploy_labels_as_list(&mut value) {
+                true => serde_yml::from_value(value),
+                false => Err(e),
+            }
+        }
+        result => result,
+    }
+}
+
+/// Rewrites `deploy.labels` given as a mapping, which the compose types only
+/// take as a list. Returns whether any were.
+fn deploy_labels_as_list(value: &mut serde_yml::Value) -> bool {
+    let Some(services) = value.get_mut("services").and_then(|s| s.as_mapping_mut()) else {
+        return false;
+    };
+
+    let mut changed = false;
+    for service in services.values_mut() {
+        let Some(labels) = service.get_mut("deploy").and_then(|d| d.get_mut("labels")) else {
+            continue;
+        };
+        if let Some(map) = labels.as_mapping() {
+            let list = map
+                .iter()
+                .map(|(key, value)| {
+                    let text = |v: &serde_yml::Value| match v {
+                        serde_yml::Value::String(s) => s.clone(),
+                        serde_yml::Value::Null => String::new(),
+                        other => serde_yml::to_string(other)
+                            .unwrap_or_default()
+                            .trim()
+                            .to_string(),
+                    };
+                    serde_yml::Value::String(format!("{}={}", text(key), text(value)))
+                })
+                .collect();
+            *labels = serde_yml::Value::Sequence(list);
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+/// Sorts the labels and networks of every service, and the top level
+/// networks, so rewrites come out the same whatever order things were added
+/// in. With `map_labels`, labels written as a list become a mapping.
+pub fn canonicalize(compose: &mut Compose, map_labels: bool) {
+    for service in compose.services.0.values_mut().flatten() {
+        if let Labels::List(list) = &service.labels {
+            if map_labels {
+                service.labels = Labels::Map(
+                    list.iter()
+                        .map(|l| {
+                            let (key, value) = l.split_once('=').unwrap_or((l, ""));
+                            (key.to_string(), value.to_string())
+                        })
+                        .collect(),
+                );
+            }
+        }
+        match &mut service.labels {
+            // By key, so `caddy` comes before `caddy.reverse_proxy`.
+            Labels::List(list) => list.sort_by(|a, b| {
+                let key = |l: &String| l.split('=').next().unwrap_or_default().to_string();
+                key(a).cmp(&key(b)).then_with(|| a.cmp(b))
+            }),
+            Labels::Map(map) => map.sort_keys(),
+        }
+        match &mut service.networks {
+            Networks::Simple(networks) => networks.sort(),
+            Networks::Advanced(networks) => networks.0.sort_keys(),
+        }
+    }
+    compose.networks.0.sort_keys();
+}
+
+/// The services of several compose files merged the way docker compose
+/// does: mappings are merged, lists added up and anything else replaced by
+/// the later file.
+pub fn merge_services(files: &[&Compose]) -> Compose {
+    let mut services = serde_yml::Mapping::new();
+    for compose in files {
+        for (name, service) in &compose.services.0 {
+            let Ok(value) = serde_yml::to_value(service) else {
+                continue;
+            };
+            match services.get_mut(name.as_str()) {
+                Some(base) => merge_value(base, value),
+                None => {
+                    services.insert(name.as_str().into(), value);
+                }
+            }
+        }
+    }
+
+    let mut merged = Compose::default();
+    for (name, value) in services {
+        if let (Some(name), Ok(service)) = (name.as_str(), serde_yml::from_value(value)) {
+            merged.services.0.insert(name.to_string(), service);
+        }
+    }
+    merged
+}
+
+fn merge_value(base: &mut serde_yml::Value, over: serde_yml::Value) {
+    use serde_yml::Value;
+
+    match (base, over) {
+        (Value::Mapping(base), Value::Mapping(over)) => {
+            for (key, value) in over {
+                // A command is replaced as a whole rather than added to.
+                let replaced = matches!(key.as_str(), Some("command" | "entrypoint"));
+                match base.get_mut(&key) {
+                    Some(base) if !replaced => merge_value(base, value),
+                    _ => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(over)) => {
+            for value in over {
+                if !base.contains(&value) {
+                    base.push(value);
+                }
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
+
+/// Declares `network` as external in the compose file and connects `service`
+/// to it. The network may be declared under a different key with a
+/// parameterized `name:`, which is matched against the `resolved` compose.
+pub fn attach_network(
+    compose: &mut Compose,
+    resolved: &Compose,
+    service: &mut Service,
+    network: &str,
+) {
+    let network_key = network_key(resolved, network);
+
+    // get or make the network settings for the traefik network
+    let mut network = compose
+        .networks
+        .0
+        .get(&network_key)
+        .map(|n| match n {
+            MapOrEmpty::Empty => NetworkSettings {
+                ..Default::default()
+            },
+            MapOrEmpty::Map(m) => m.clone(),
+        })
+        .unwrap_or(NetworkSettings {
+            ..Default::default()
+        }); // Should never be None
+
+    network.external = Some(ComposeNetwork::Bool(true));
+
+    compose
+        .networks
+        .0
+        .insert(network_key.clone(), MapOrEmpty::Map(network));
+    tracing::debug!("Attaching the service to network {network_key}");
+
+    match &mut service.networks {
+        Networks::Simple(a) => {
+            if !a.contains(&network_key) {
+                a.push(network_key);
+            }
+        }
+        Networks::Advanced(a) => {
+            a.0.insert(network_key, MapOrEmpty::Empty);
+        }
+    }
+}
+
+/// The key `network` is declared under in the `resolved` compose file.
+fn network_key(resolved: &Compose, network: &str) -> String {
+    resolved
+        .networks
+        .0
+        .iter()
+        .find(|(key, n)| match n {
+            MapOrEmpty::Map(NetworkSettings {
+                name: Some(name), ..
+            }) => *name == network,
+            _ => **key == network,
+        })
+        .map(|(key, _)| key.clone())
+        .unwrap_or(network.to_string())
+}
+
+/// Whether `service` of the `resolved` compose file is connected to `network`,
+/// found like in [`attach_network`].
+pub fn on_network(resolved: &Compose, service: &Service, network: &str) -> bool {
+    let network_key = network_key(resolved, network);
+    match &service.networks {
+        Networks::Simple(a) => a.contains(&network_key),
+        Networks::Advanced(a) => a.0.contains_key(&network_key),
+    }
+}
+
+/// Disconnects the service called `name` from `network`, found like in
+/// [`attach_network`], and drops the network's declaration when no other
+/// service is connected to it. Returns whether the service was connected.
+pub fn detach_network(
+    compose: &mut Compose,
+    resolved: &Compose,
+    name: &str,
+    network: &str,
+) -> bool {
+    let network_key = network_key(resolved, network);
+
+    let Some(Some(service)) = compose.services.0.get_mut(name) else {
+        return false;
+    };
+    let detached = match &mut service.networks {
+        Networks::Simple(a) => {
+            let before = a.len();
+            a.retain(|n| *n != network_key);
+            a.len() != before
+        }
+        Networks::Advanced(a) => a.0.shift_remove(&network_key).is_some(),
+    };
+
+    let used = compose
+        .services
+        .0
+        .values()
+        .flatten()
+        .any(|s| match &s.networks {
+            Networks::Simple(a) => a.contains(&network_key),
+            Networks::Advanced(a) => a.0.contains_key(&network_key),
+        });
+    if detached && !used {
+        tracing::debug!("No service is on network {network_key} anymore, removing it");
+        compose.networks.0.shift_remove(&network_key);
+    }
+
+    detached
+}
+
+/// The container side of the service's TCP `ports:` and then its `expose:`,
+/// without duplicates. Port ranges are left out.
+pub fn container_ports(service: &Service) -> Vec<u16> {
+    let short = |port: &str| -> Option<u16> {
+        let (port, protocol) = port.split_once('/').unwrap_or((port, "tcp"));
+        match protocol {
+            "tcp" => port.rsplit(':').next()?.trim().parse().ok(),
+            _ => None,
+        }
+    };
+
+    let ports: Vec<u16> = match &service.ports {
+        Ports::Short(p) => p.iter().filter_map(|p| short(p)).collect(),
+        Ports::Long(p) => p
+            .iter()
+            .filter(|p| p.protocol.as_deref().is_none_or(|p| p == "tcp"))
+            .map(|p| p.target)
+            .collect(),
+    };
+
+    let mut unique = vec![];
+    for port in ports
+        .into_iter()
+        .chain(service.expose.iter().filter_map(|p| short(p)))
+    {
+        if !unique.contains(&port) {
+            unique.push(port);
+        }
+    }
+    unique
+}
+
+static COMPOSE_PATHS: [&str; 4] = [
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yaml",
+    "docker-compose.yml",
+];
+/// Read after the main file when docker compose finds the files itself.
+static OVERRIDE_PATHS: [&str; 4] = [
+    "compose.override.yaml",
+    "compose.override.yml",
+    "docker-compose.override.yaml",
+    "docker-compose.override.yml",
+];
+/// The files of a compose project in the order docker compose merges them:
+/// the given `files`, or the main file at `compose_path` and its override,
+/// each after the files it pulls in with `include`.
+pub fn project_files(files: &[PathBuf], compose_path: Option<String>) -> Result<Vec<PathBuf>> {
+    let roots = match files {
+        [] => {
+            let main = find_compose_file(compose_path)?;
+            let overrides = OVERRIDE_PATHS
+                .iter()
+                .map(|p| main.with_file_name(p))
+                .find(|p| p.exists());
+            [main].into_iter().chain(overrides).collect()
+        }
+        files => {
+            for file in files {
+                ensure!(file.exists(), "{} does not exist.", file.display());
+            }
+            files.to_vec()
+        }
+    };
+
+    let mut all = vec![];
+    for file in roots {
+        with_includes(&file, &mut all)?;
+    }
+    Ok(all)
+}
+
+/// Adds the files `file` includes, recursively, and then `file` itself.
+fn with_includes(file: &Path, all: &mut Vec<PathBuf>) -> Result<()> {
+    if all.iter().any(|f| f == file) {
+        return Ok(());
+    }
+
+    let dir = file.parent().unwrap_or(Path::new("."));
+    let contents = std::fs::read_to_string(file)
+        .with_context(|| format!("Could not read {}.", file.display()))?;
+    let contents = env::interpolate(&contents, &env::load_variables(dir)).unwrap_or(contents);
+    let yaml: serde_yml::Value = serde_yml::from_str(&contents)
+        .with_context(|| format!("{} is not valid yaml.", file.display()))?;
+
+    // Each entry is a path, or a mapping with one or more under `path`.
+    let includes = yaml["include"].as_sequence().cloned().unwrap_or_default();
+    for include in includes {
+        let paths = match include {
+            serde_yml::Value::String(path) => vec![path],
+            include => match &include["path"] {
+                serde_yml::Value::String(path) => vec![path.clone()],
+                serde_yml::Value::Sequence(paths) => paths
+                    .iter()
+                    .filter_map(|p| p.as_str().map(String::from))
+                    .collect(),
+                _ => vec![],
+            },
+        };
+        for path in paths {
+            let included = dir.join(path);
+            ensure!(
+                included.exists(),
+                "{} includes {}, which does not exist.",
+                file.display(),
+                included.display()
+            );
+            with_includes(&included, all)?;
+        }
+    }
+
+    all.push(file.to_path_buf());
+    Ok(())
+}
+
+/// How many directories deep [`find_projects`] looks.
+static PROJECT_DEPTH: usize = 4;
+
+/// The name docker compose gives the project of `file` when it doesn't set
+/// one: its directory's name, lowercased.
+pub fn project_name(file: &Path) -> Option<String> {
+    let file = file.canonicalize().unwrap_or(file.to_path_buf());
+    Some(file.parent()?.file_name()?.to_string_lossy().to_lowercase())
+}
+
+/// A compose file running caddy-docker-proxy on `network`, for `eurus init`.
+/// A `label_prefix` other than `caddy` is passed on so it reads the same
+/// labels eurus writes.
+pub fn caddy_stack(network: &str, label_prefix: &str) -> String {
+    let prefix = match label_prefix {
+        "caddy" => String::new(),
+        prefix => format!("\n      - CADDY_DOCKER_LABEL_PREFIX={prefix}"),
+    };
+    format!(
+        r#"services:
+  caddy:
+    image: lucaslorentz/caddy-docker-proxy:ci-alpine
+    ports:
+      - 80:80
+      - 443:443
+      - 443:443/udp
+    environment:
+      - CADDY_INGRESS_NETWORKS={network}{prefix}
+    networks:
+      - {network}
+    volumes:
+      - /var/run/docker.sock:/var/run/docker.sock:ro
+      # Certificates and keys, losing them means issuing everything again.
+      - caddy_data:/data
+      - caddy_config:/config
+    restart: unless-stopped
+
+networks:
+  {network}:
+    external: true
+
+volumes:
+  caddy_data: {{}}
+  caddy_config: {{}}
+"#
+    )
+}
+
+/// Uses the given compose file, or looks for one in the given directory
+/// (defaulting to the current one).
+pub fn find_compose_file(compose_path: Option<String>) -> Result<PathBuf> {
+    let path = PathBuf::from(compose_path.unwrap_or(".".to_string()));
+    let file = if path.is_dir() {
+        COMPOSE_PATHS
+            .iter()
+            .map(|p| path.join(p))
+            .find(|p| p.exists())
+            .context("Could not find valid docker-compose file.")?
+    } else {
+        path
+    };
+
+    ensure!(Path::new(&file).exists(), "The file provided should exist.");
+
+    Ok(file)
+}
+
+/// Whether the file has one of the names docker compose looks for.
+pub fn is_compose_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| COMPOSE_PATHS.iter().any(|p| name == *p))
+}
+
+/// The compose files of every project under `root`, like the
+/// `apps/*/compose.yaml` of a monorepo, sorted by path. Hidden directories and
+/// `node_modules` are skipped, and a project's own subdirectories aren't
+/// searched.
+pub fn find_projects(root: &Path) -> Vec<PathBuf> {
+    let mut projects = vec![];
+    let mut dirs = vec![(root.to_path_buf(), 0)];
+
+    while let Some((dir, depth)) = dirs.pop() {
+        if let Some(file) = COMPOSE_PATHS
+            .iter()
+            .map(|p| dir.join(p))
+            .find(|p| p.exists())
+        {
+            projects.push(file);
+            continue;
+        }
+        if depth == PROJECT_DEPTH {
+            continue;
+        }
+
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') || name == "node_modules" {
+                continue;
+            }
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                dirs.push((entry.path(), depth + 1));
+            }
+        }
+    }
+
+    projects.sort();
+    projects
+}
+
+/// Renames a service in place, keeping its position, and points the
+/// `depends_on` and `links` of the other services at the new name. Returns the
+/// services whose references were changed.
+pub fn rename_service(compose: &mut Compose, old: &str, new: &str) -> Result<Vec<String>> {
+    ensure!(
+        !compose.services.0.contains_key(new),
+        "There is already a service called {new}."
+    );
+    let index = compose
+        .services
+        .0
+        .get_index_of(old)
+        .with_context(|| format!("There is no service called {old}."))?;
+
+    tracing::debug!("Renaming service {old} to {new}");
+    let service = compose.services.0.shift_remove(old).flatten();
+    compose
+        .services
+        .0
+        .shift_insert(index, new.to_string(), service);
+
+    let mut updated = vec![];
+    for (name, service) in compose.services.0.iter_mut() {
+        let Some(service) = service else {
+            continue;
+        };
+        let mut changed = false;
+
+        match &mut service.depends_on {
+            DependsOnOptions::Simple(services) => {
+                for s in services.iter_mut().filter(|s| *s == old) {
+                    *s = new.to_string();
+                    changed = true;
+                }
+            }
+            DependsOnOptions::Conditional(services) => {
+                if let Some(index) = services.get_index_of(old) {
+                    let condition = services.shift_remove(old).unwrap();
+                    services.shift_insert(index, new.to_string(), condition);
+                    changed = true;
+                }
+            }
+        }
+
+        // Links are either `service` or `service:alias`.
+        for link in service.links.iter_mut() {
+            let (target, alias) = match link.split_once(':') {
+                Some((target, alias)) => (target, Some(alias)),
+                None => (link.as_str(), None),
+            };
+            if target == old {
+                *link = match alias {
+                    Some(alias) => format!("{new}:{alias}"),
+                    None => new.to_string(),
+                };
+                changed = true;
+            }
+        }
+
+        if changed {
+            updated.push(name.clone());
+        }
+    }
+
+    Ok(updated)
+}
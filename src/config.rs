@@ -0,0 +1,737 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::{bail, ensure, Context, ContextCompat, Result};
+use serde::{Deserialize, Serialize};
+use toml_edit::{DocumentMut, Item, Table, Value};
+
+use crate::{
+    caddy,
+    cloudflare::AuthMethod,
+    credentials, ddns, edits,
+    error::EurusError,
+    hooks::Hooks,
+    idn, logging,
+    notify::Notify,
+    presets,
+    provider::{Provider, AUTO_TTL},
+    proxy::ProxyBackend,
+    reconcile,
+    secrets::SecretStorage,
+    CONFIG_DIR, CONFIG_PATH, PROFILE,
+};
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq, Eq)]
+pub struct ZoneInfo {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub provider: Provider,
+    /// Whether new records in this zone are proxied by default.
+    #[serde(default = "default_proxied")]
+    pub proxied: bool,
+    /// The TTL of records that aren't proxied, automatic when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+    /// The id of the Cloudflare account the zone is in, for the api calls
+    /// that are made per account like tunnels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    /// The type `dns` offers for new records in this zone, CNAME when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record_type: Option<String>,
+    /// The target `dns` offers for records of the default type, `@` being
+    /// the zone itself. The zone is offered when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+fn default_proxied() -> bool {
+    true
+}
+
+impl ZoneInfo {
+    pub fn default_ttl(&self) -> u32 {
+        self.ttl.unwrap_or(AUTO_TTL)
+    }
+
+    pub fn default_type(&self) -> String {
+        self.record_type
+            .as_deref()
+            .unwrap_or("CNAME")
+            .to_uppercase()
+    }
+
+    /// The target to offer for a record of `record_type`.
+    pub fn default_target(&self, record_type: &str) -> &str {
+        match &self.target {
+            Some(target) if target != "@" && record_type == self.default_type() => target,
+            _ => &self.name,
+        }
+    }
+}
+
+impl Display for ZoneInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.id)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct Config {
+    /// See [`CONFIG_VERSION`].
+    #[serde(default)]
+    pub version: u32,
+    pub zones: Vec<ZoneInfo>,
+    pub cloudflare_key: String,
+    pub caddy_network: String,
+    #[serde(default)]
+    pub digitalocean_key: String,
+    #[serde(default)]
+    pub hetzner_key: String,
+    #[serde(default)]
+    pub proxy: ProxyBackend,
+    #[serde(default)]
+    pub traefik_network: String,
+    #[serde(default)]
+    pub nginx_network: String,
+    #[serde(default)]
+    pub hooks: Hooks,
+    #[serde(default)]
+    pub notify: Notify,
+    #[serde(default = "default_caddy_admin_url")]
+    pub caddy_admin_url: String,
+    #[serde(default)]
+    pub caddyfile_path: Option<PathBuf>,
+    /// The address of the Kubernetes ingress controller, that `k8s --dns`
+    /// points records at instead of asking kubectl.
+    #[serde(default)]
+    pub k8s_ingress: Option<String>,
+    #[serde(default)]
+    pub ddns_records: Vec<String>,
+    #[serde(default = "default_ddns_resolver")]
+    pub ddns_resolver: String,
+    /// Several resolvers to ask at once instead of `ddns_resolver`, which
+    /// have to agree on the address.
+    #[serde(default)]
+    pub ddns_resolvers: Vec<String>,
+    #[serde(default)]
+    pub ddns_consensus: ddns::Consensus,
+    #[serde(default)]
+    pub ddns_strategies: BTreeMap<String, ddns::Strategy>,
+    /// AAAA records kept pointed at the current IPv6 prefix, by name, with
+    /// the interface identifier of the host, e.g. `::211:32ff:fe12:3456`.
+    #[serde(default)]
+    pub ddns_ipv6_records: BTreeMap<String, String>,
+    /// How much of the host's address is the prefix the ISP may change.
+    #[serde(default = "default_ddns_prefix_length")]
+    pub ddns_prefix_length: u8,
+    #[serde(default = "default_ddns_ipv6_resolver")]
+    pub ddns_ipv6_resolver: String,
+    /// Use Cloudflare's batch endpoint for changes to several records.
+    #[serde(default = "default_dns_batch")]
+    pub dns_batch: bool,
+    /// Warn about origin certificates expiring within this many days.
+    #[serde(default = "default_cert_warning_days")]
+    pub cert_warning_days: u32,
+    /// Healthcheck commands for images, overriding the built in ones. `{port}`
+    /// is replaced with the service's port.
+    #[serde(default)]
+    pub healthchecks: BTreeMap<String, String>,
+    /// Presets for services created by `web new`, added to the built in ones.
+    #[serde(default)]
+    pub presets: BTreeMap<String, presets::Preset>,
+    /// Warn about CNAMEs that pass through more records than this in the zone.
+    #[serde(default = "default_cname_max_depth")]
+    pub cname_max_depth: usize,
+    /// A shell command that prints the Cloudflare key, used when
+    /// `cloudflare_key` is empty.
+    #[serde(default)]
+    pub cloudflare_key_cmd: String,
+    #[serde(default)]
+    pub cloudflare_auth: AuthMethod,
+    /// Sends Cloudflare api requests here instead, e.g. an api gateway or a
+    /// mock server. `EURUS_API_BASE_URL` takes precedence.
+    #[serde(default)]
+    pub api_base_url: String,
+    /// The account email, needed with the Global API Key.
+    #[serde(default)]
+    pub cloudflare_email: String,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Scanned for compose projects to pick from when `web` is run somewhere
+    /// without a compose file.
+    #[serde(default)]
+    pub projects_root: Option<PathBuf>,
+    /// The label prefix caddy-docker-proxy was set up with, if not `caddy`.
+    #[serde(default)]
+    pub label_prefix: String,
+    /// Sort labels and networks whenever a compose file is written, like
+    /// `web --canonical`.
+    #[serde(default)]
+    pub canonical_compose: bool,
+    /// Comment written on the records eurus creates or updates, e.g.
+    /// `eurus {version} on {hostname} for {project}/{service}`.
+    #[serde(default)]
+    pub comment_template: String,
+    /// How many timestamped backups of each compose file edits keep.
+    #[serde(default = "default_backups")]
+    pub backups: usize,
+    /// How many seconds zone listings are cached in the data dir, 0 to always
+    /// fetch them.
+    #[serde(default = "default_cache_ttl")]
+    pub cache_ttl: u64,
+    /// Keep the records eurus deletes in a local trash to restore them with
+    /// `dns trash restore`.
+    #[serde(default)]
+    pub trash: bool,
+    /// Where `web new` keeps the secrets it generates.
+    #[serde(default)]
+    pub secret_storage: SecretStorage,
+    /// Whether `cloudflare_key` came from `cloudflare_key_cmd` or the keyring,
+    /// in which case it isn't written back to the file.
+    #[serde(skip)]
+    pub key_external: bool,
+}
+
+/// Credentials and zones used instead of the top level ones when the profile
+/// is picked with `--profile`.
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct Profile {
+    #[serde(default)]
+    pub cloudflare_key: String,
+    #[serde(default)]
+    pub cloudflare_key_cmd: String,
+    #[serde(default)]
+    pub cloudflare_auth: AuthMethod,
+    #[serde(default)]
+    pub cloudflare_email: String,
+    #[serde(default)]
+    pub zones: Vec<ZoneInfo>,
+    #[serde(default)]
+    pub digitalocean_key: String,
+    #[serde(default)]
+    pub hetzner_key: String,
+}
+
+/// Swaps the top level credentials and zones with those of the profile. Done
+/// when loading so the rest of eurus only sees the profile's, and again when
+/// saving to put both back where they belong.
+fn swap_profile(config: &mut serde_json::Value, name: &str) {
+    static PROFILE_KEYS: [&str; 7] = [
+        "cloudflare_key",
+        "cloudflare_key_cmd",
+        "cloudflare_auth",
+        "cloudflare_email",
+        "zones",
+        "digitalocean_key",
+        "hetzner_key",
+    ];
+
+    let Some(root) = config.as_object_mut() else {
+        return;
+    };
+    let mut profile = root
+        .get_mut("profiles")
+        .and_then(|p| p.as_object_mut())
+        .and_then(|p| p.remove(name))
+        .unwrap_or(serde_json::json!({}));
+
+    for key in PROFILE_KEYS {
+        let empty = match key {
+            "zones" => serde_json::json!([]),
+            "cloudflare_auth" => serde_json::json!(AuthMethod::default()),
+            _ => serde_json::json!(""),
+        };
+        let top = root.remove(key).unwrap_or(empty.clone());
+        let theirs = profile
+            .as_object_mut()
+            .and_then(|p| p.remove(key))
+            .unwrap_or(empty);
+        root.insert(key.to_string(), theirs);
+        profile[key] = top;
+    }
+
+    root.entry("profiles")
+        .or_insert(serde_json::json!({}))
+        .as_object_mut()
+        .map(|p| p.insert(name.to_string(), profile));
+}
+
+fn default_caddy_admin_url() -> String {
+    caddy::DEFAULT_ADMIN_URL.to_string()
+}
+
+fn default_dns_batch() -> bool {
+    true
+}
+
+fn default_cert_warning_days() -> u32 {
+    14
+}
+
+fn default_cname_max_depth() -> usize {
+    3
+}
+
+fn default_cache_ttl() -> u64 {
+    60
+}
+
+fn default_backups() -> usize {
+    edits::DEFAULT_BACKUPS
+}
+
+fn default_ddns_resolver() -> String {
+    ddns::DEFAULT_RESOLVER.to_string()
+}
+
+fn default_ddns_prefix_length() -> u8 {
+    64
+}
+
+fn default_ddns_ipv6_resolver() -> String {
+    ddns::DEFAULT_RESOLVER_IPV6.to_string()
+}
+
+impl Config {
+    /// Whether there is a Cloudflare key, without running `cloudflare_key_cmd`.
+    pub fn has_cloudflare_key(&self) -> bool {
+        !self.cloudflare_key.is_empty() || !self.cloudflare_key_cmd.is_empty()
+    }
+
+    /// The Cloudflare key, from `cloudflare_key_cmd` when it isn't set.
+    pub fn cloudflare_key(&self) -> std::result::Result<String, EurusError> {
+        match self.cloudflare_key.is_empty() && !self.cloudflare_key_cmd.is_empty() {
+            true => credentials::key_from_cmd(&self.cloudflare_key_cmd),
+            false => Ok(self.cloudflare_key.clone()),
+        }
+    }
+
+    /// The resolvers to find the public IPv4 address with.
+    pub fn ipv4_resolvers(&self) -> Vec<String> {
+        match self.ddns_resolvers.is_empty() {
+            true => vec![self.ddns_resolver.clone()],
+            false => self.ddns_resolvers.clone(),
+        }
+    }
+
+    /// [`Config::comment_template`] with the placeholders filled in, or
+    /// nothing when there isn't one. Cut to the 100 characters Cloudflare
+    /// allows on every plan.
+    pub fn record_comment(&self, project: Option<&str>, service: Option<&str>) -> Option<String> {
+        if self.comment_template.is_empty() {
+            return None;
+        }
+
+        let comment = self
+            .comment_template
+            .replace("{version}", env!("CARGO_PKG_VERSION"))
+            .replace("{hostname}", &reconcile::hostname().unwrap_or_default())
+            .replace("{project}", project.unwrap_or_default())
+            .replace("{service}", service.unwrap_or_default())
+            .replace(
+                "{date}",
+                &chrono::Local::now().format("%Y-%m-%d").to_string(),
+            );
+        Some(comment.chars().take(100).collect())
+    }
+
+    /// The docker network the given proxy is attached to.
+    pub fn proxy_network(&mut self, backend: ProxyBackend) -> &mut String {
+        match backend {
+            ProxyBackend::Caddy => &mut self.caddy_network,
+            ProxyBackend::Traefik => &mut self.traefik_network,
+            ProxyBackend::NginxProxy => &mut self.nginx_network,
+        }
+    }
+
+    pub fn network(&self, backend: ProxyBackend) -> &str {
+        match backend {
+            ProxyBackend::Caddy => &self.caddy_network,
+            ProxyBackend::Traefik => &self.traefik_network,
+            ProxyBackend::NginxProxy => &self.nginx_network,
+        }
+    }
+}
+
+/// The layout of the config eurus writes, kept in its `version` setting.
+/// Changes that older configs wouldn't load with bump this and add a step to
+/// [`MIGRATIONS`].
+pub static CONFIG_VERSION: u32 = 1;
+
+/// Each step upgrades a config from the version at its index to the next.
+static MIGRATIONS: [fn(&mut serde_json::Value); CONFIG_VERSION as usize] = [fill_required];
+
+/// Configs from before versioning could lack the settings without a
+/// default, and failed to load.
+fn fill_required(config: &mut serde_json::Value) {
+    if let Some(root) = config.as_object_mut() {
+        root.entry("zones").or_insert(serde_json::json!([]));
+        root.entry("cloudflare_key")
+            .or_insert(serde_json::json!(""));
+        root.entry("caddy_network").or_insert(serde_json::json!(""));
+    }
+}
+
+/// Brings the config up to [`CONFIG_VERSION`], returning the version it was
+/// at. Configs without a version are from before it was added, version 0.
+pub fn upgrade(config: &mut serde_json::Value) -> Result<u32> {
+    ensure!(config.is_object(), "Configuration is malformed.");
+    let from = config["version"].as_u64().unwrap_or(0) as u32;
+    ensure!(
+        from <= CONFIG_VERSION,
+        "The config is version {from}, but this eurus only knows up to version {CONFIG_VERSION}. \
+         Update eurus to use it."
+    );
+
+    for step in &MIGRATIONS[from as usize..] {
+        step(config);
+    }
+    config["version"] = serde_json::json!(CONFIG_VERSION);
+
+    Ok(from)
+}
+
+/// The parsed config from `path`, upgraded first if it's from an older
+/// version. The upgraded config is written back, with the old `contents`
+/// kept next to it as e.g. `config.toml.v0.bak`.
+fn upgraded(
+    path: &Path,
+    mut config: serde_json::Value,
+    contents: &str,
+) -> Result<serde_json::Value> {
+    let from = upgrade(&mut config)?;
+    if from == CONFIG_VERSION {
+        return Ok(config);
+    }
+
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let backup = path.with_extension(format!("{extension}.v{from}.bak"));
+    std::fs::write(&backup, contents).context("Could not back up the config.")?;
+    tracing::info!("Upgrading {} from version {from}", path.display());
+    std::fs::write(path, serialize(path, &config, Some(contents))?)?;
+    cliclack::log::info(format!(
+        "Upgraded the config to version {CONFIG_VERSION}, the old one is at {}.",
+        backup.display()
+    ))?;
+
+    Ok(config)
+}
+
+/// Where the config is read from and written to.
+pub fn config_path() -> PathBuf {
+    CONFIG_PATH
+        .get()
+        .cloned()
+        .unwrap_or_else(|| CONFIG_DIR.join("config.toml"))
+}
+
+/// Where the config was kept before it moved to TOML.
+pub fn legacy_path() -> PathBuf {
+    CONFIG_DIR.join("config.json")
+}
+
+/// Converts a `config.json` from before the config was TOML, leaving the old
+/// file next to it as `config.json.bak`. Configs picked with `--config` are
+/// used in whatever format they're in.
+pub fn migrate() -> Result<()> {
+    let (path, legacy) = (config_path(), legacy_path());
+    if CONFIG_PATH.get().is_some() || path.exists() || !legacy.exists() {
+        return Ok(());
+    }
+
+    let config = parse(&legacy, &std::fs::read_to_string(&legacy)?)?;
+    tracing::info!("Converting {} to {}", legacy.display(), path.display());
+    std::fs::write(&path, serialize(&path, &config, None)?)?;
+    std::fs::rename(&legacy, legacy.with_extension("json.bak"))?;
+    cliclack::log::info(format!(
+        "Converted the config to TOML, it is now at {}.",
+        path.display()
+    ))?;
+
+    Ok(())
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "json")
+}
+
+/// Parses a config file, as JSON when it ends in `.json` and TOML otherwise.
+pub fn parse(path: &Path, contents: &str) -> Result<serde_json::Value> {
+    match is_json(path) {
+        true => serde_json::from_str(contents).context("Configuration is malformed."),
+        false => toml::from_str(contents).context("Configuration is malformed."),
+    }
+}
+
+/// The config in the format of `path`. When `previous` is the TOML already
+/// there, its comments and layout are kept for everything that didn't change.
+pub fn serialize(
+    path: &Path,
+    config: &serde_json::Value,
+    previous: Option<&str>,
+) -> Result<String> {
+    if is_json(path) {
+        return Ok(serde_json::to_string_pretty(config)?);
+    }
+
+    // TOML has no null, and a missing key reads the same.
+    let mut config = config.clone();
+    strip_nulls(&mut config);
+    let new: DocumentMut = toml::to_string(&config)?.parse()?;
+
+    Ok(match previous.and_then(|p| p.parse::<DocumentMut>().ok()) {
+        Some(mut document) => {
+            merge_table(document.as_table_mut(), new.as_table());
+            document.to_string()
+        }
+        None => new.to_string(),
+    })
+}
+
+fn strip_nulls(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(strip_nulls);
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(strip_nulls),
+        _ => {}
+    }
+}
+
+fn merge_table(old: &mut Table, new: &Table) {
+    let removed: Vec<_> = old
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !new.contains_key(key))
+        .collect();
+    for key in removed {
+        old.remove(&key);
+    }
+
+    for (key, item) in new.iter() {
+        match old.get_mut(key) {
+            Some(existing) => merge_item(existing, item),
+            None => {
+                old.insert(key, item.clone());
+            }
+        }
+    }
+}
+
+fn merge_item(old: &mut Item, new: &Item) {
+    match (old, new) {
+        (Item::Table(old), Item::Table(new)) => merge_table(old, new),
+        (Item::ArrayOfTables(old), Item::ArrayOfTables(new)) if old.len() == new.len() => {
+            for (old, new) in old.iter_mut().zip(new.iter()) {
+                merge_table(old, new);
+            }
+        }
+        (Item::Value(old), Item::Value(new)) => {
+            let bare = |v: &Value| {
+                let mut v = v.clone();
+                v.decor_mut().clear();
+                v.to_string()
+            };
+            if bare(old) != bare(new) {
+                let decor = old.decor().clone();
+                *old = new.clone();
+                *old.decor_mut() = decor;
+            }
+        }
+        (old, new) => *old = new.clone(),
+    }
+}
+
+fn create_config_dir(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .create(dir)
+            .context("Failed to create config directory")?;
+    }
+
+    Ok(())
+}
+
+pub fn get_config() -> Result<Config> {
+    read_config().map_err(|e| {
+        e.wrap_err(EurusError::ConfigError(format!(
+            "Could not read the config at {}.",
+            config_path().display()
+        )))
+    })
+}
+
+fn read_config() -> Result<Config> {
+    let path = config_path();
+    create_config_dir(&path)?;
+    migrate()?;
+
+    tracing::info!("Reading {}", path.display());
+    let contents = std::fs::read_to_string(&path)?;
+    let mut config = upgraded(&path, parse(&path, &contents)?, &contents)?;
+    if let Some(profile) = PROFILE.get() {
+        swap_profile(&mut config, profile);
+    }
+
+    let mut config: Config =
+        serde_json::from_value(config).context("Configuration is malformed.")?;
+    // `cloudflare_key_cmd` is only run once a client needs the key.
+    if config.cloudflare_key.is_empty() && config.cloudflare_key_cmd.is_empty() {
+        if let Some(key) = credentials::cloudflare_key() {
+            config.cloudflare_key = key;
+            config.key_external = true;
+        }
+    }
+
+    Ok(config)
+}
+
+pub fn save_config(config: &Config) -> Result<()> {
+    let key_external = config.key_external;
+    let mut config = serde_json::to_value(config)?;
+    if key_external {
+        config["cloudflare_key"] = serde_json::json!("");
+    }
+    if let Some(profile) = PROFILE.get() {
+        swap_profile(&mut config, profile);
+    }
+    config["version"] = serde_json::json!(CONFIG_VERSION);
+
+    let path = config_path();
+    tracing::info!("Writing {}", path.display());
+    let previous = std::fs::read_to_string(&path).ok();
+    std::fs::write(&path, serialize(&path, &config, previous.as_deref())?)?;
+
+    Ok(())
+}
+
+/// The config file as it is on disk, without a profile swapped in or keys
+/// loaded from elsewhere. A missing file is an empty config, with only the
+/// settings that have no default.
+pub fn read_raw() -> Result<serde_json::Value> {
+    migrate()?;
+
+    let path = config_path();
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => upgraded(&path, parse(&path, &contents)?, &contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(serde_json::json!({
+            "version": CONFIG_VERSION,
+            "zones": [],
+            "cloudflare_key": "",
+            "caddy_network": "",
+        })),
+        Err(e) => Err(e).context("Could not read the config."),
+    }
+}
+
+/// Writes the config as given, after checking it still loads.
+pub fn write_raw(config: &serde_json::Value) -> Result<()> {
+    validate(config)?;
+    let mut config = config.clone();
+    config["version"] = serde_json::json!(CONFIG_VERSION);
+
+    let path = config_path();
+    create_config_dir(&path)?;
+    tracing::info!("Writing {}", path.display());
+    let previous = std::fs::read_to_string(&path).ok();
+    std::fs::write(&path, serialize(&path, &config, previous.as_deref())?)?;
+
+    Ok(())
+}
+
+pub fn validate(config: &serde_json::Value) -> Result<()> {
+    serde_json::from_value::<Config>(config.clone()).context("The config is invalid")?;
+
+    Ok(())
+}
+
+/// Looks up a setting by its dotted path, like `hooks.pre_dns` or
+/// `zones.0.proxied`.
+pub fn get_path<'a>(config: &'a serde_json::Value, key: &str) -> Option<&'a serde_json::Value> {
+    config.pointer(&format!("/{}", key.replace('.', "/")))
+}
+
+/// Sets a setting by its dotted path, creating the objects along the way.
+/// Keys that aren't settings are rejected, apart from new entries of maps like
+/// `profiles` or `hooks`, which is what the default config has as empty
+/// objects.
+pub fn set_path(config: &mut serde_json::Value, key: &str, value: serde_json::Value) -> Result<()> {
+    let defaults = serde_json::to_value(Config::default())?;
+    let mut schema = Some(&defaults);
+    let mut current = config;
+    for part in key.split('.') {
+        current = match current {
+            serde_json::Value::Object(map) => {
+                let fields = schema.and_then(|s| s.as_object()).filter(|s| !s.is_empty());
+                if let Some(fields) = fields {
+                    ensure!(
+                        fields.contains_key(part) || map.contains_key(part),
+                        EurusError::ValidationError(format!("There is no setting `{key}`."))
+                    );
+                }
+                schema = schema.and_then(|s| s.get(part));
+                map.entry(part)
+                    .or_insert(serde_json::Value::Object(Default::default()))
+            }
+            serde_json::Value::Array(items) => {
+                schema = None;
+                let index: usize = part
+                    .parse()
+                    .with_context(|| format!("`{part}` in `{key}` has to be an index."))?;
+                items
+                    .get_mut(index)
+                    .with_context(|| format!("There is no item {index} in `{key}`."))?
+            }
+            _ => bail!("`{key}` goes through a value that isn't an object."),
+        };
+    }
+    *current = value;
+
+    Ok(())
+}
+
+/// Hides the keys in the config, and the passwords and tokens in urls like
+/// `notify.mqtt`, for showing it.
+pub fn redact(config: &mut serde_json::Value) {
+    match config {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match value {
+                    serde_json::Value::String(s) if key.ends_with("_key") && !s.is_empty() => {
+                        *s = "<hidden>".to_string();
+                    }
+                    serde_json::Value::String(s) if s.contains("://") => {
+                        // Urls without anything to hide are left as written.
+                        if let Some(redacted) = reqwest::Url::parse(s)
+                            .ok()
+                            .map(|url| logging::redact(&url))
+                            .filter(|r| r.contains("REDACTED"))
+                        {
+                            *s = redacted;
+                        }
+                    }
+                    value => redact(value),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => (),
+    }
+}
+
+/// Finds the configured zone a fully qualified name belongs to.
+pub fn zone_for_name<'a>(config: &'a Config, name: &str) -> Option<&'a ZoneInfo> {
+    let name = idn::to_ascii(name).unwrap_or(name.to_string());
+    config
+        .zones
+        .iter()
+        .filter(|z| name == z.name || name.ends_with(&format!(".{}", z.name)))
+        .max_by_key(|z| z.name.len())
+}
@@ -0,0 +1,63 @@
+use std::sync::OnceLock;
+
+use keyring::Entry;
+
+use crate::{
+    error::{EurusError, Result},
+    hooks, PROFILE,
+};
+
+static SERVICE: &str = "eurus";
+
+/// The keyring entry of the Cloudflare key, one per profile.
+fn entry() -> keyring::Result<Entry> {
+    let profile = PROFILE.get().map_or("default", String::as_str);
+    Entry::new(SERVICE, &format!("cloudflare_key:{profile}"))
+}
+
+/// The Cloudflare key stored in the system keyring, if there is one.
+pub fn cloudflare_key() -> Option<String> {
+    entry().and_then(|e| e.get_password()).ok()
+}
+
+/// What `cloudflare_key_cmd` printed, or why it failed, so it runs at most
+/// once per invocation.
+static KEY_FROM_CMD: OnceLock<std::result::Result<String, String>> = OnceLock::new();
+
+/// The key printed by `cloudflare_key_cmd`, running it the first time it's
+/// needed.
+pub fn key_from_cmd(command: &str) -> Result<String> {
+    KEY_FROM_CMD
+        .get_or_init(|| run_key_cmd(command).map_err(|e| e.to_string()))
+        .clone()
+        .map_err(EurusError::ConfigError)
+}
+
+/// Runs `cloudflare_key_cmd` and takes what it prints as the key.
+fn run_key_cmd(command: &str) -> Result<String> {
+    let output = hooks::shell(command)
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .map_err(|e| EurusError::ConfigError(format!("Could not run `{command}`: {e}")))?;
+    if !output.status.success() {
+        return Err(EurusError::ConfigError(format!(
+            "`{command}` exited with {}.",
+            output.status
+        )));
+    }
+
+    let key = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if key.is_empty() {
+        return Err(EurusError::ConfigError(format!(
+            "`{command}` printed no key."
+        )));
+    }
+
+    Ok(key)
+}
+
+pub fn set_cloudflare_key(key: &str) -> Result<()> {
+    entry()
+        .and_then(|e| e.set_password(key))
+        .map_err(|e| EurusError::ConfigError(format!("Could not write to the keyring: {e}")))
+}
@@ -0,0 +1,250 @@
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use color_eyre::eyre::{Context, ContextCompat, Report, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use subtle::ConstantTimeEq;
+use tokio::sync::Mutex;
+
+use crate::{
+    cancel::CancellationToken,
+    config::{zone_for_name, Config},
+    provider,
+    reconcile::{self, RecordSpec, ServiceSpec, Spec},
+    scan,
+};
+
+static INDEX: &str = include_str!("dashboard.html");
+
+#[derive(Clone)]
+struct Dashboard {
+    config: Arc<Config>,
+    token: Arc<str>,
+    /// The reconcile file whose plan can be applied.
+    spec: Option<PathBuf>,
+    /// Held while applying, so two clicks don't apply the same plan twice.
+    applying: Arc<Mutex<()>>,
+    /// Cancelled to shut down, which also stops a plan being applied after
+    /// its current step.
+    cancel: CancellationToken,
+}
+
+/// A failed api call, sent as its message with a status.
+struct Failure(StatusCode, String);
+
+impl<E: Into<Report>> From<E> for Failure {
+    fn from(e: E) -> Self {
+        Self(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e.into()))
+    }
+}
+
+impl IntoResponse for Failure {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type Reply = std::result::Result<Json<Value>, Failure>;
+
+/// Serves the dashboard on `listen` until `cancel` is cancelled. Everything
+/// below `/api` needs `token` as a bearer token.
+pub async fn serve(
+    config: Config,
+    listen: SocketAddr,
+    token: String,
+    spec: Option<PathBuf>,
+    cancel: CancellationToken,
+) -> Result<()> {
+    let state = Dashboard {
+        config: Arc::new(config),
+        token: token.into(),
+        spec,
+        applying: Arc::new(Mutex::new(())),
+        cancel: cancel.clone(),
+    };
+
+    let api = Router::new()
+        .route("/status", get(status))
+        .route("/zones", get(zones))
+        .route("/zones/{zone}/records", get(records))
+        .route("/plan", get(plan))
+        .route("/plan/apply", post(apply))
+        .route("/records", post(create_record))
+        .route("/services", post(proxy_service))
+        .route("/reconcile", post(run_reconcile))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authorize));
+    let app = Router::new()
+        .route("/", get(|| async { Html(INDEX) }))
+        .nest("/api", api)
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Could not listen on {listen}."))?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(cancel.cancelled_owned())
+        .await
+        .context("The dashboard stopped.")
+}
+
+async fn authorize(State(state): State<Dashboard>, request: Request, next: Next) -> Response {
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+
+    // Compared in constant time, so the response time doesn't give it away.
+    let matches = token.is_some_and(|t| bool::from(t.as_bytes().ct_eq(state.token.as_bytes())));
+    match matches {
+        true => next.run(request).await,
+        false => {
+            Failure(StatusCode::UNAUTHORIZED, "Missing or wrong token.".into()).into_response()
+        }
+    }
+}
+
+async fn status(State(state): State<Dashboard>) -> Reply {
+    Ok(Json(json!(scan::managed_domains(&state.config).await?)))
+}
+
+async fn zones(State(state): State<Dashboard>) -> Reply {
+    Ok(Json(json!(state.config.zones)))
+}
+
+async fn records(State(state): State<Dashboard>, Path(zone): Path<String>) -> Reply {
+    let Some(zone) = zone_for_name(&state.config, &zone) else {
+        return Err(Failure(
+            StatusCode::NOT_FOUND,
+            format!("There is no zone {zone} in the config."),
+        ));
+    };
+    let records = provider::for_zone(&state.config, zone)?
+        .list_records(&zone.id)
+        .await?;
+
+    Ok(Json(json!(records)))
+}
+
+async fn planned(state: &Dashboard) -> std::result::Result<Vec<reconcile::Change>, Failure> {
+    let spec = state.spec.as_ref().ok_or(Failure(
+        StatusCode::NOT_FOUND,
+        "The dashboard was started without --spec.".into(),
+    ))?;
+    let spec = reconcile::load(spec)?.for_host(reconcile::hostname().as_deref());
+
+    Ok(reconcile::plan(&state.config, &spec, &state.cancel).await?)
+}
+
+async fn plan(State(state): State<Dashboard>) -> Reply {
+    let changes: Vec<_> = planned(&state)
+        .await?
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+    Ok(Json(json!({ "file": state.spec, "changes": changes })))
+}
+
+#[derive(Deserialize)]
+struct Apply {
+    /// The plan as it was shown, so a plan that changed since isn't applied
+    /// unseen.
+    changes: Vec<String>,
+}
+
+async fn apply(State(state): State<Dashboard>, Json(shown): Json<Apply>) -> Reply {
+    let _applying = state
+        .applying
+        .try_lock()
+        .ok()
+        .context("Another plan is being applied.")?;
+
+    let changes = planned(&state).await?;
+    let current: Vec<_> = changes.iter().map(ToString::to_string).collect();
+    if current != shown.changes {
+        return Err(Failure(
+            StatusCode::CONFLICT,
+            "The plan changed since it was shown, reload to see the new one.".into(),
+        ));
+    }
+
+    let count = changes.len();
+    reconcile::apply(&state.config, changes, &state.cancel).await?;
+    announce(&state, current).await;
+
+    Ok(Json(json!({ "applied": count })))
+}
+
+/// Announces applied changes through the configured [`crate::notify`] targets.
+async fn announce(state: &Dashboard, changes: Vec<String>) {
+    let message = format!("Applied {} changes.", changes.len());
+    state
+        .config
+        .notify
+        .publish("serve", &message, &json!({ "changes": changes }))
+        .await;
+}
+
+#[derive(Deserialize)]
+struct Options {
+    /// Only plan the changes, without making them.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Plans and applies `spec` like `eurus reconcile --apply`, for automation
+/// that posts the records and services it wants instead of keeping a file.
+async fn reconcile_spec(state: &Dashboard, spec: Spec, dry_run: bool) -> Reply {
+    let _applying = state.applying.lock().await;
+
+    let changes = reconcile::plan(&state.config, &spec, &state.cancel).await?;
+    let planned: Vec<_> = changes.iter().map(ToString::to_string).collect();
+    if !dry_run && !changes.is_empty() {
+        reconcile::apply(&state.config, changes, &state.cancel).await?;
+        announce(state, planned.clone()).await;
+    }
+
+    Ok(Json(json!({ "changes": planned, "applied": !dry_run })))
+}
+
+async fn create_record(
+    State(state): State<Dashboard>,
+    Query(options): Query<Options>,
+    Json(record): Json<RecordSpec>,
+) -> Reply {
+    let spec = Spec {
+        records: vec![record],
+        ..Default::default()
+    };
+    reconcile_spec(&state, spec, options.dry_run).await
+}
+
+async fn proxy_service(
+    State(state): State<Dashboard>,
+    Query(options): Query<Options>,
+    Json(service): Json<ServiceSpec>,
+) -> Reply {
+    let spec = Spec {
+        services: vec![service],
+        ..Default::default()
+    };
+    reconcile_spec(&state, spec, options.dry_run).await
+}
+
+async fn run_reconcile(
+    State(state): State<Dashboard>,
+    Query(options): Query<Options>,
+    Json(spec): Json<Spec>,
+) -> Reply {
+    let spec = spec.for_host(reconcile::hostname().as_deref());
+    reconcile_spec(&state, spec, options.dry_run).await
+}
@@ -0,0 +1,673 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, UdpSocket},
+    str::FromStr,
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, Timelike};
+use color_eyre::eyre::{bail, ensure, eyre, Context, ContextCompat, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cancel::{or_cancelled, CancellationToken},
+    config::{zone_for_name, Config},
+    hooks::Event,
+    logging,
+    metrics::Metrics,
+    provider::{self, DnsCreateUpdate, AUTO_TTL},
+    CLIENT, DATA_DIR,
+};
+
+pub static DEFAULT_RESOLVER: &str = "https://api.ipify.org";
+/// Resolver value that asks OpenDNS over plain DNS instead of using http.
+static OPENDNS: &str = "opendns";
+static OPENDNS_SERVER: &str = "resolver1.opendns.com:53";
+static OPENDNS_NAME: &str = "myip.opendns.com";
+
+/// Finds this machine's public IPv4 address. `resolver` is either a url that
+/// responds with the address as plain text, or `opendns`.
+pub async fn public_ipv4(resolver: &str) -> Result<Ipv4Addr> {
+    if resolver == OPENDNS {
+        return tokio::task::spawn_blocking(|| query_a(OPENDNS_SERVER, OPENDNS_NAME)).await?;
+    }
+
+    let text = logging::send((*CLIENT).get(resolver))
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    text.trim()
+        .parse()
+        .with_context(|| format!("{resolver} did not return an IPv4 address: {text}"))
+}
+
+/// How many of several resolvers have to return the same address for it to
+/// be trusted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Consensus {
+    /// More than half of them, so one broken or lying resolver is outvoted.
+    #[default]
+    Majority,
+    /// Every one of them.
+    All,
+}
+
+/// Asks every resolver for the public IPv4 address at once, and returns the
+/// address enough of them agree on.
+pub async fn agreed_ipv4(resolvers: &[String], consensus: Consensus) -> Result<Ipv4Addr> {
+    if let [resolver] = resolvers {
+        return public_ipv4(resolver).await;
+    }
+    ensure!(!resolvers.is_empty(), "There are no resolvers to ask.");
+
+    let answers = futures::future::join_all(resolvers.iter().map(|r| public_ipv4(r))).await;
+    let mut counts: BTreeMap<Ipv4Addr, usize> = BTreeMap::new();
+    for answer in answers.iter().flatten() {
+        *counts.entry(*answer).or_default() += 1;
+    }
+    let needed = match consensus {
+        Consensus::Majority => resolvers.len() / 2 + 1,
+        Consensus::All => resolvers.len(),
+    };
+    if let Some((ip, _)) = counts.iter().find(|(_, n)| **n >= needed) {
+        return Ok(*ip);
+    }
+
+    let answers: Vec<_> = resolvers
+        .iter()
+        .zip(&answers)
+        .map(|(resolver, answer)| match answer {
+            Ok(ip) => format!("{resolver}: {ip}"),
+            Err(e) => format!("{resolver}: {e}"),
+        })
+        .collect();
+    bail!(
+        "The resolvers don't agree on the public IP, {needed} of {} have to:\n{}",
+        resolvers.len(),
+        answers.join("\n")
+    )
+}
+
+pub static DEFAULT_RESOLVER_IPV6: &str = "https://api6.ipify.org";
+/// Resolver value that reads the prefix from this machine's own addresses,
+/// optionally `interface:<name>` for one interface.
+static INTERFACE: &str = "interface";
+
+/// An IPv6 prefix, like the /56 an ISP delegates to a router.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Prefix {
+    address: Ipv6Addr,
+    length: u8,
+}
+
+impl Prefix {
+    /// The prefix of `address` that is `length` bits long.
+    pub fn new(address: Ipv6Addr, length: u8) -> Result<Self> {
+        ensure!(length <= 128, "/{length} is not a valid prefix length.");
+        let mask = u128::MAX.checked_shl(128 - length as u32).unwrap_or(0);
+        Ok(Self {
+            address: Ipv6Addr::from(address.to_bits() & mask),
+            length,
+        })
+    }
+
+    /// The address in this prefix with the bits past it taken from
+    /// `interface_id`, e.g. `::211:32ff:fe12:3456`.
+    pub fn address_for(&self, interface_id: Ipv6Addr) -> Ipv6Addr {
+        let mask = u128::MAX.checked_shl(128 - self.length as u32).unwrap_or(0);
+        Ipv6Addr::from(self.address.to_bits() | (interface_id.to_bits() & !mask))
+    }
+}
+
+impl FromStr for Prefix {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (address, length) = s
+            .split_once('/')
+            .with_context(|| format!("`{s}` is not a prefix like 2001:db8:1234::/56."))?;
+        Self::new(
+            address
+                .parse()
+                .with_context(|| format!("`{address}` is not an IPv6 address."))?,
+            length
+                .parse()
+                .with_context(|| format!("`{length}` is not a prefix length."))?,
+        )
+    }
+}
+
+impl std::fmt::Display for Prefix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.address, self.length)
+    }
+}
+
+/// Finds the prefix this machine's public IPv6 address is in. `resolver` is
+/// either a url that responds with the address as plain text, or `interface`
+/// to take it from the addresses the router handed out, e.g. through router
+/// advertisements.
+pub async fn public_prefix(resolver: &str, length: u8) -> Result<Prefix> {
+    let address = match resolver.strip_prefix(INTERFACE) {
+        Some(rest) => {
+            let interface = rest.strip_prefix(':');
+            interface_ipv6(interface)?.with_context(|| match interface {
+                Some(name) => format!("{name} has no public IPv6 address."),
+                None => "No interface has a public IPv6 address.".to_string(),
+            })?
+        }
+        None => {
+            let text = logging::send((*CLIENT).get(resolver))
+                .await?
+                .error_for_status()?
+                .text()
+                .await?;
+            text.trim()
+                .parse()
+                .with_context(|| format!("{resolver} did not return an IPv6 address: {text}"))?
+        }
+    };
+
+    Prefix::new(address, length)
+}
+
+/// This machine's global IPv6 address, if it has one.
+pub fn host_ipv6() -> Option<Ipv6Addr> {
+    interface_ipv6(None).ok().flatten()
+}
+
+/// The bits of `address` past a prefix of `length`, the interface identifier
+/// `ddns_ipv6_records` keeps.
+pub fn interface_id(address: Ipv6Addr, length: u8) -> Ipv6Addr {
+    let mask = u128::MAX.checked_shl(128 - length as u32).unwrap_or(0);
+    Ipv6Addr::from(address.to_bits() & !mask)
+}
+
+/// The first global, stable IPv6 address of the interface (or any), from
+/// `/proc/net/if_inet6`.
+fn interface_ipv6(interface: Option<&str>) -> Result<Option<Ipv6Addr>> {
+    // Flags of addresses that shouldn't be published: temporary (privacy),
+    // deprecated and tentative ones.
+    const SKIPPED: u8 = 0x01 | 0x20 | 0x40;
+
+    let table = std::fs::read_to_string("/proc/net/if_inet6")
+        .context("Reading the interfaces' addresses is only supported on Linux.")?;
+    for line in table.lines() {
+        let [address, _, _, scope, flags, name] = line.split_whitespace().collect::<Vec<_>>()[..]
+        else {
+            continue;
+        };
+        let (Ok(address), Ok(scope), Ok(flags)) = (
+            u128::from_str_radix(address, 16),
+            u8::from_str_radix(scope, 16),
+            u8::from_str_radix(flags, 16),
+        ) else {
+            continue;
+        };
+        let address = Ipv6Addr::from(address);
+        if scope == 0
+            && flags & SKIPPED == 0
+            && !address.is_unique_local()
+            && interface.is_none_or(|i| i == name)
+        {
+            return Ok(Some(address));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Sends a single A query over udp and returns the first address in the answer.
+fn query_a(server: &str, name: &str) -> Result<Ipv4Addr> {
+    let mut query = vec![0x45, 0x75, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in name.split('.') {
+        query.push(label.len() as u8);
+        query.extend(label.as_bytes());
+    }
+    query.extend([0, 0, 1, 0, 1]);
+
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_read_timeout(Some(Duration::from_secs(5)))?;
+    socket.connect(server)?;
+    socket.send(&query)?;
+
+    let mut buf = [0; 512];
+    let len = socket
+        .recv(&mut buf)
+        .with_context(|| format!("No response from {server}."))?;
+    let res = &buf[..len];
+    ensure!(
+        res.len() > 12 && res[..2] == query[..2],
+        "Invalid response from {server}."
+    );
+
+    let answers = u16::from_be_bytes([res[6], res[7]]);
+    // The question is echoed back unchanged, so it can be skipped by length.
+    let mut pos = query.len();
+    for _ in 0..answers {
+        pos = skip_name(res, pos).context("Truncated DNS response.")?;
+        let header = res.get(pos..pos + 10).context("Truncated DNS response.")?;
+        let record_type = u16::from_be_bytes([header[0], header[1]]);
+        let rdlength = u16::from_be_bytes([header[8], header[9]]) as usize;
+        pos += 10;
+
+        if record_type == 1 && rdlength == 4 {
+            let ip = res.get(pos..pos + 4).context("Truncated DNS response.")?;
+            return Ok(Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]));
+        }
+        pos += rdlength;
+    }
+
+    bail!("{server} did not return an address for {name}.")
+}
+
+fn skip_name(res: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *res.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            // A compression pointer ends the name.
+            l if l & 0xC0 == 0xC0 => return Some(pos + 2),
+            l => pos += l as usize + 1,
+        }
+    }
+}
+
+/// When a record may follow a new IP, for connections whose address flaps.
+/// Set under `ddns_strategies` in the config, by record name or for all the
+/// records of a zone by its name.
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+pub struct Strategy {
+    /// Only update once the same new IP was seen this many checks in a row.
+    #[serde(default)]
+    pub stable_checks: u32,
+    /// Only update within these hours of the local day, e.g. `2-5`, or `22-6`
+    /// across midnight.
+    #[serde(default)]
+    pub hours: Option<String>,
+    /// Leave at least this long between updates, e.g. `30m`.
+    #[serde(default)]
+    pub min_interval: Option<String>,
+}
+
+impl Strategy {
+    fn for_name<'a>(config: &'a Config, name: &str) -> Option<&'a Self> {
+        config.ddns_strategies.get(name).or_else(|| {
+            zone_for_name(config, name).and_then(|z| config.ddns_strategies.get(&z.name))
+        })
+    }
+
+    /// Why the record has to wait, if it does.
+    fn defer(&self, state: &RecordState, now: DateTime<Local>) -> Result<Option<String>> {
+        if state.seen < self.stable_checks {
+            return Ok(Some(format!(
+                "the new IP was seen {} of {} times",
+                state.seen, self.stable_checks
+            )));
+        }
+        if let Some(hours) = &self.hours {
+            let (start, end) = parse_hours(hours)?;
+            let hour = now.hour();
+            let inside = match start <= end {
+                true => (start..end).contains(&hour),
+                false => hour >= start || hour < end,
+            };
+            if !inside {
+                return Ok(Some(format!("updates are only made between {hours}")));
+            }
+        }
+        if let (Some(interval), Some(updated)) = (&self.min_interval, state.updated) {
+            let interval = parse_interval(interval).map_err(|e| eyre!(e))?;
+            if now.timestamp() < updated + interval.as_secs() as i64 {
+                return Ok(Some(format!(
+                    "it was updated less than {} ago",
+                    self.min_interval.as_deref().unwrap_or_default()
+                )));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Parses an hour range like `2-5` into its start and (exclusive) end.
+fn parse_hours(hours: &str) -> Result<(u32, u32)> {
+    let (start, end) = hours
+        .split_once('-')
+        .with_context(|| format!("`{hours}` is not an hour range like 2-5."))?;
+    let parse = |h: &str| -> Result<u32> {
+        let h = h.trim().parse()?;
+        ensure!(h <= 24, "{h} is not an hour of the day.");
+        Ok(h)
+    };
+    Ok((parse(start)?, parse(end)?))
+}
+
+/// What the strategies need to remember between checks, kept in
+/// `ddns_state.json` in the data dir so runs from cron share it.
+#[derive(Debug, Deserialize, Serialize, Default)]
+struct State {
+    /// By name and record type, e.g. `home.example.com A`.
+    #[serde(default)]
+    records: BTreeMap<String, RecordState>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+struct RecordState {
+    /// The IP the record is waiting to be pointed at.
+    candidate: Option<IpAddr>,
+    /// How many checks in a row saw the candidate.
+    seen: u32,
+    /// When it was last updated, in unix seconds.
+    updated: Option<i64>,
+}
+
+impl State {
+    fn load() -> Self {
+        std::fs::read_to_string(DATA_DIR.join("ddns_state.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<()> {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .create(*DATA_DIR)
+            .context("Failed to create data directory")?;
+        std::fs::write(
+            DATA_DIR.join("ddns_state.json"),
+            serde_json::to_string(self)?,
+        )?;
+
+        Ok(())
+    }
+}
+
+/// What a run of [`update`] did.
+#[derive(Debug, Default, Serialize)]
+pub struct Updates {
+    pub updated: Vec<String>,
+    /// The records their strategy holds back for now, with why.
+    pub deferred: Vec<(String, String)>,
+}
+
+/// Points the A record of each name at `ip`, leaving records that already
+/// match alone, and those whose [`Strategy`] says to wait.
+pub async fn update(config: &Config, names: &[String], ip: Ipv4Addr) -> Result<Updates> {
+    let targets: Vec<_> = names
+        .iter()
+        .map(|name| (name.clone(), IpAddr::V4(ip)))
+        .collect();
+    update_records(config, &targets).await
+}
+
+/// Points the AAAA records in `ddns_ipv6_records` at their interface
+/// identifier within `prefix`, like [`update`] does for A records.
+pub async fn update_ipv6(config: &Config, prefix: Prefix) -> Result<Updates> {
+    let targets = config
+        .ddns_ipv6_records
+        .iter()
+        .map(|(name, interface_id)| {
+            let interface_id: Ipv6Addr = interface_id.parse().with_context(|| {
+                format!("The interface identifier of {name}, {interface_id}, is not valid.")
+            })?;
+            Ok((name.clone(), IpAddr::V6(prefix.address_for(interface_id))))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    update_records(config, &targets).await
+}
+
+async fn update_records(config: &Config, targets: &[(String, IpAddr)]) -> Result<Updates> {
+    let mut state = State::load();
+    let now = Local::now();
+    let mut updates = Updates::default();
+
+    for (name, ip) in targets {
+        let record_type = match ip {
+            IpAddr::V4(_) => "A",
+            IpAddr::V6(_) => "AAAA",
+        };
+        let zone = zone_for_name(config, name)
+            .with_context(|| format!("{name} is not in any configured zone."))?;
+        let provider = provider::for_zone(config, zone)?;
+        let existing = provider
+            .list_records(&zone.id)
+            .await?
+            .into_iter()
+            .find(|r| r.name == *name && r.record_type == record_type);
+
+        let record_state = state
+            .records
+            .entry(format!("{name} {record_type}"))
+            .or_default();
+        if existing
+            .as_ref()
+            .is_some_and(|r| r.content.parse() == Ok(*ip))
+        {
+            record_state.candidate = None;
+            record_state.seen = 0;
+            continue;
+        }
+
+        match record_state.candidate == Some(*ip) {
+            true => record_state.seen += 1,
+            false => {
+                record_state.candidate = Some(*ip);
+                record_state.seen = 1;
+            }
+        }
+        if let Some(strategy) = Strategy::for_name(config, name) {
+            if let Some(reason) = strategy.defer(record_state, now)? {
+                updates.deferred.push((name.clone(), reason));
+                continue;
+            }
+        }
+
+        let body = DnsCreateUpdate {
+            name: name.clone(),
+            proxied: existing.as_ref().map_or(zone.proxied, |r| r.proxied),
+            ttl: existing.as_ref().map_or(AUTO_TTL, |r| r.ttl),
+            id: existing.map(|r| r.id),
+            record_type: record_type.to_string(),
+            content: ip.to_string(),
+            comment: config.record_comment(None, None),
+            ..Default::default()
+        };
+
+        let payload = serde_json::json!({ "zone": zone, "record": body });
+        config.hooks.run(Event::PreDns, &payload)?;
+        provider.upsert_record(&zone.id, &body).await?;
+        config.hooks.run(Event::PostDns, &payload)?;
+
+        *record_state = RecordState {
+            updated: Some(now.timestamp()),
+            ..Default::default()
+        };
+        updates.updated.push(name.clone());
+    }
+
+    state.save()?;
+
+    Ok(updates)
+}
+
+/// Parses intervals like `30s`, `5m` or `1h`. A bare number is in seconds.
+pub fn parse_interval(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (amount, unit) = input.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("`{input}` is not a valid interval."))?;
+
+    let seconds = match unit {
+        "" | "s" => amount,
+        "m" => amount * 60,
+        "h" => amount * 60 * 60,
+        _ => return Err(format!("Unknown unit `{unit}`, use s, m or h.")),
+    };
+    if seconds == 0 {
+        return Err("The interval has to be longer than zero.".to_string());
+    }
+
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Keeps the records pointed at the public IP, checking every `interval` until
+/// `cancel` is cancelled. Failed checks are logged and retried on the next
+/// tick rather than ending the loop.
+pub async fn watch(
+    config: &Config,
+    names: &[String],
+    resolvers: &[String],
+    prefix: Option<Prefix>,
+    interval: Duration,
+    metrics: &Metrics,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let ipv6_names: Vec<_> = config.ddns_ipv6_records.keys().cloned().collect();
+    log(format!(
+        "Watching {} every {}s.",
+        [names, &ipv6_names[..]].concat().join(", "),
+        interval.as_secs()
+    ));
+
+    // Only set once every record is done with the IP, deferred ones are
+    // checked again on each tick.
+    let mut last = None;
+    let mut last_prefix = None;
+    while !cancel.is_cancelled() {
+        let mut failure = None;
+        if !names.is_empty() {
+            match or_cancelled(cancel, agreed_ipv4(resolvers, config.ddns_consensus)).await {
+                Err(_) if cancel.is_cancelled() => break,
+                Ok(ip) if last == Some(ip) => (),
+                Ok(ip) => {
+                    log(format!("Public IP is {ip}."));
+                    metrics.info(
+                        "eurus_ddns_public_ip_info",
+                        "The public IPv4 address last seen.",
+                        &[("ip", &ip.to_string())],
+                    );
+                    match update(config, names, ip).await {
+                        Ok(updates) => {
+                            record_updates(metrics, &updates);
+                            if announce(config, &updates, &ip.to_string()).await {
+                                last = Some(ip);
+                            }
+                        }
+                        Err(e) => failure = Some(failed(metrics, "update", "update records", e)),
+                    }
+                }
+                Err(e) => failure = Some(failed(metrics, "resolve", "get the public IP", e)),
+            }
+        }
+
+        if !ipv6_names.is_empty() {
+            let current = match prefix {
+                Some(prefix) => Ok(prefix),
+                None => public_prefix(&config.ddns_ipv6_resolver, config.ddns_prefix_length).await,
+            };
+            match current {
+                Ok(prefix) if last_prefix == Some(prefix) => (),
+                Ok(prefix) => {
+                    log(format!("IPv6 prefix is {prefix}."));
+                    metrics.info(
+                        "eurus_ddns_ipv6_prefix_info",
+                        "The IPv6 prefix last seen.",
+                        &[("prefix", &prefix.to_string())],
+                    );
+                    match update_ipv6(config, prefix).await {
+                        Ok(updates) => {
+                            record_updates(metrics, &updates);
+                            if announce(config, &updates, &prefix.to_string()).await {
+                                last_prefix = Some(prefix);
+                            }
+                        }
+                        Err(e) => failure = Some(failed(metrics, "update", "update records", e)),
+                    }
+                }
+                Err(e) => failure = Some(failed(metrics, "resolve", "get the IPv6 prefix", e)),
+            }
+        }
+
+        metrics.inc("eurus_ddns_checks_total", "Checks of the public IP.", &[]);
+        match failure {
+            Some(failure) => metrics.failed(failure),
+            None => {
+                metrics.now(
+                    "eurus_ddns_last_success_timestamp_seconds",
+                    "When a check last succeeded, as a unix timestamp.",
+                );
+                metrics.succeeded();
+            }
+        }
+
+        tokio::select! {
+            _ = cancel.cancelled() => (),
+            _ = tokio::time::sleep(interval) => (),
+        }
+    }
+
+    log("Shutting down.".to_string());
+
+    Ok(())
+}
+
+/// Counts the records an update changed and the ones it's waiting on.
+fn record_updates(metrics: &Metrics, updates: &Updates) {
+    for name in &updates.updated {
+        metrics.inc(
+            "eurus_ddns_updates_total",
+            "Records pointed at a new address.",
+            &[("record", name)],
+        );
+    }
+    metrics.set(
+        "eurus_ddns_deferred_records",
+        "Records their ddns strategy holds back, waiting for a stable IP, the allowed hours or the minimum interval.",
+        &[],
+        updates.deferred.len() as f64,
+    );
+}
+
+/// Logs and counts a failed step of a check, returning what failed.
+fn failed(metrics: &Metrics, stage: &str, doing: &str, error: impl Display) -> String {
+    let message = format!("Could not {doing}: {error}");
+    log(message.clone());
+    metrics.inc(
+        "eurus_ddns_errors_total",
+        "Checks that failed, by the step that failed.",
+        &[("stage", stage)],
+    );
+    message
+}
+
+/// Logs and publishes what an update did. Returns whether every record is
+/// done, i.e. none were deferred.
+async fn announce(config: &Config, updates: &Updates, target: &str) -> bool {
+    for name in &updates.updated {
+        log(format!("Updated {name}."));
+    }
+    for (name, reason) in &updates.deferred {
+        log(format!("Not updating {name} yet, {reason}."));
+    }
+    if !updates.updated.is_empty() {
+        let message = format!("Pointed {} at {target}.", updates.updated.join(", "));
+        let data = serde_json::json!({ "target": target, "updated": updates.updated });
+        config.notify.publish("ddns", &message, &data).await;
+    }
+
+    updates.deferred.is_empty()
+}
+
+fn log(message: String) {
+    println!("{} {message}", Local::now().format("%Y-%m-%d %H:%M:%S"));
+}
@@ -0,0 +1,209 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use color_eyre::eyre::{ensure, Context, ContextCompat, Result};
+use serde::Deserialize;
+
+/// A running container, with the compose project it belongs to if any.
+#[derive(Debug, Clone)]
+pub struct Container {
+    pub name: String,
+    pub image: String,
+    pub service: Option<String>,
+    pub config_files: Vec<PathBuf>,
+    /// The container's IP address on each network it is connected to.
+    pub addresses: Vec<String>,
+    /// The names of the networks it is connected to.
+    pub networks: Vec<String>,
+    /// The TCP ports the container exposes.
+    pub ports: Vec<u16>,
+    pub labels: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Inspect {
+    name: String,
+    config: InspectConfig,
+    network_settings: InspectNetworks,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct InspectConfig {
+    #[serde(default)]
+    image: String,
+    #[serde(default)]
+    labels: Option<BTreeMap<String, String>>,
+    #[serde(default)]
+    exposed_ports: Option<BTreeMap<String, serde_json::Value>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct InspectNetworks {
+    #[serde(default)]
+    networks: BTreeMap<String, InspectNetwork>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct InspectNetwork {
+    #[serde(rename = "IPAddress")]
+    ip_address: String,
+}
+
+/// Lists the running containers through the docker cli.
+pub fn running() -> Result<Vec<Container>> {
+    let ids = docker(&["ps", "-q"])?;
+    let ids: Vec<_> = ids.split_whitespace().collect();
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    inspect_all(&ids)
+}
+
+/// The running containers of the compose project made of `files`.
+pub fn project_containers(files: &[PathBuf]) -> Result<Vec<Container>> {
+    let files: Vec<_> = files
+        .iter()
+        .map(|f| f.canonicalize().unwrap_or(f.clone()))
+        .collect();
+
+    Ok(running()?
+        .into_iter()
+        .filter(|c| {
+            c.config_files
+                .iter()
+                .any(|f| files.contains(&f.canonicalize().unwrap_or(f.clone())))
+        })
+        .collect())
+}
+
+/// Inspects a single container by name or id, whether it's running or not.
+pub fn inspect(container: &str) -> Result<Container> {
+    inspect_all(&[container])?
+        .pop()
+        .with_context(|| format!("There is no container called {container}."))
+}
+
+fn inspect_all(containers: &[&str]) -> Result<Vec<Container>> {
+    let output = docker(&[&["inspect"], containers].concat())?;
+    let inspected: Vec<Inspect> =
+        serde_json::from_str(&output).context("docker inspect returned unexpected output.")?;
+
+    Ok(inspected
+        .into_iter()
+        .map(|c| {
+            let labels = c.config.labels.unwrap_or_default();
+            Container {
+                name: c.name.trim_start_matches('/').to_string(),
+                image: c.config.image,
+                service: labels.get("com.docker.compose.service").cloned(),
+                config_files: labels
+                    .get("com.docker.compose.project.config_files")
+                    .map(|f| f.split(',').map(PathBuf::from).collect())
+                    .unwrap_or_default(),
+                addresses: c
+                    .network_settings
+                    .networks
+                    .values()
+                    .map(|n| n.ip_address.clone())
+                    .filter(|a| !a.is_empty())
+                    .collect(),
+                networks: c.network_settings.networks.into_keys().collect(),
+                ports: c
+                    .config
+                    .exposed_ports
+                    .unwrap_or_default()
+                    .keys()
+                    .filter_map(|p| p.strip_suffix("/tcp")?.parse().ok())
+                    .collect(),
+                labels,
+            }
+        })
+        .collect())
+}
+
+/// Whether a network with exactly this name exists.
+pub fn network_exists(name: &str) -> Result<bool> {
+    let output = docker(&[
+        "network",
+        "ls",
+        "--filter",
+        &format!("name=^{name}$"),
+        "--format",
+        "{{.Name}}",
+    ])?;
+    Ok(output.lines().any(|n| n.trim() == name))
+}
+
+/// Whether docker accepts `name` for a network.
+pub fn valid_network_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+/// Creates the network, as an overlay network that containers can attach to
+/// when this node is in a swarm, so both services and `docker run` work.
+pub fn create_network(name: &str) -> Result<()> {
+    match swarm_active() {
+        true => docker(&[
+            "network",
+            "create",
+            "--driver",
+            "overlay",
+            "--attachable",
+            name,
+        ])?,
+        false => docker(&["network", "create", name])?,
+    };
+    Ok(())
+}
+
+/// Whether this docker node is part of a swarm.
+pub fn swarm_active() -> bool {
+    docker(&["info", "--format", "{{.Swarm.LocalNodeState}}"])
+        .is_ok_and(|state| state.trim() == "active")
+}
+
+/// Runs `docker compose config` on the file, returning what it complained
+/// about if the file is invalid.
+pub fn check_compose(file: &Path) -> Result<std::result::Result<(), String>> {
+    let dir = file.parent().filter(|d| !d.as_os_str().is_empty());
+    let mut command = Command::new("docker");
+    command
+        .args(["compose", "-f"])
+        .arg(file.file_name().unwrap_or(file.as_os_str()))
+        .args(["config", "-q"]);
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let output = command.output().context("Failed to run docker.")?;
+
+    Ok(match output.status.success() {
+        true => Ok(()),
+        false => Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+    })
+}
+
+fn docker(args: &[&str]) -> Result<String> {
+    let output = Command::new("docker")
+        .args(args)
+        .output()
+        .context("Failed to run docker.")?;
+    ensure!(
+        output.status.success(),
+        "docker {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,266 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::{bail, ensure, Context, Result};
+use docker_compose_types::Compose;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    audit::{self, Change},
+    docker, patch, prompt, DATA_DIR,
+};
+
+/// What eurus last wrote to each compose file it edited, so changes made by
+/// anything else since then can be pointed out before the file is
+/// re-serialized over them.
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct Edits {
+    #[serde(default)]
+    pub files: BTreeMap<String, Edit>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Edit {
+    pub hash: String,
+    pub contents: String,
+}
+
+impl Edits {
+    pub fn load() -> Result<Self> {
+        match std::fs::read_to_string(DATA_DIR.join("edits.json")) {
+            Ok(s) => serde_json::from_str(&s).context("Edits file is malformed."),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .create(*DATA_DIR)
+            .context("Failed to create data directory")?;
+        std::fs::write(DATA_DIR.join("edits.json"), serde_json::to_string(self)?)?;
+
+        Ok(())
+    }
+
+    /// The contents eurus last wrote, if the file has been changed since.
+    pub fn changed_since(&self, file: &Path, current: &str) -> Option<&str> {
+        self.files
+            .get(&file_key(file))
+            .filter(|e| e.hash != hash(current))
+            .map(|e| e.contents.as_str())
+    }
+
+    pub fn record(&mut self, file: &Path, contents: &str) {
+        self.files.insert(
+            file_key(file),
+            Edit {
+                hash: hash(contents),
+                contents: contents.to_string(),
+            },
+        );
+    }
+}
+
+/// Warns when the file was changed outside of eurus since the last edit,
+/// showing what changed, and asks whether to go ahead anyway.
+pub fn confirm_unchanged(file: &Path, current: &str) -> Result<bool> {
+    let edits = Edits::load()?;
+    let Some(previous) = edits.changed_since(file, current) else {
+        return Ok(true);
+    };
+
+    cliclack::log::warning(format!(
+        "{} was changed since eurus last edited it. Check these changes are kept, a file that \
+         can't be edited in place is re-formatted as a whole:",
+        file.display()
+    ))?;
+    cliclack::note("Changes since the last edit", diff(previous, current))?;
+
+    prompt::confirm("Apply the new edits on top of these changes?").interact()
+}
+
+/// Backs up the file and writes the compose to it, remembering what was
+/// written for next time.
+pub fn write(file: &Path, compose: &Compose) -> Result<()> {
+    tracing::info!(
+        services = ?compose.services.0.keys().collect::<Vec<_>>(),
+        "Writing {}",
+        file.display()
+    );
+    let previous = std::fs::read_to_string(file).unwrap_or_default();
+    let contents = edited(&previous, compose)?;
+
+    // Only a file docker compose accepted before is checked afterwards, as
+    // e.g. an override file can't be checked on its own.
+    let valid_before = docker::check_compose(file).is_ok_and(|r| r.is_ok());
+    write_contents(file, &contents)?;
+    if let Some(Err(e)) = docker::check_compose(file).ok().filter(|_| valid_before) {
+        std::fs::write(file, &previous)?;
+        let mut edits = Edits::load()?;
+        edits.record(file, &previous);
+        edits.save()?;
+        bail!(
+            "docker compose rejected the edited {}, so it was put back as it was:\n{e}",
+            file.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// What [`write`] would write for `compose` over `previous`, for printing the
+/// edit or writing it elsewhere.
+pub fn render(previous: &str, compose: &Compose) -> Result<String> {
+    edited(previous, compose)
+}
+
+/// The file with only what changed in the compose rewritten, so comments and
+/// formatting elsewhere are kept. Falls back to writing out the whole compose
+/// when the file can't be edited in place.
+fn edited(previous: &str, compose: &Compose) -> Result<String> {
+    let new = serde_yml::to_value(compose)?;
+    let patched = crate::compose::parse(previous)
+        .ok()
+        .and_then(|old| serde_yml::to_value(old).ok())
+        .and_then(|old| patch::patch(previous, &old, &new))
+        // Checked by parsing it again, e.g. an edit to an anchored node
+        // would also change its aliases.
+        .filter(|patched| {
+            crate::compose::parse(patched)
+                .ok()
+                .and_then(|c| serde_yml::to_value(c).ok())
+                .is_some_and(|c| c == new)
+        });
+
+    match patched {
+        Some(patched) => Ok(patched),
+        None => {
+            let includes = serde_yml::from_str::<serde_yml::Value>(previous)
+                .is_ok_and(|v| v.get("include").is_some());
+            ensure!(
+                !includes,
+                "The file can't be edited in place, and writing it out whole would drop its \
+                 `include`."
+            );
+            tracing::info!("Could not edit the file in place, writing it out whole");
+            Ok(serde_yml::to_string(compose)?)
+        }
+    }
+}
+
+/// How many backups of each file are kept when the config doesn't say.
+pub static DEFAULT_BACKUPS: usize = 10;
+
+/// The folder next to the file where [`write`] keeps its previous contents.
+fn backup_dir(file: &Path) -> PathBuf {
+    file.parent()
+        .unwrap_or(Path::new("."))
+        .join(".eurus-backups")
+}
+
+fn file_name(file: &Path) -> String {
+    file.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// The backups of the file, newest first. A `.bak` next to the file, which
+/// older versions kept, comes last.
+pub fn backups(file: &Path) -> Vec<PathBuf> {
+    let prefix = format!("{}.", file_name(file));
+    let mut backups: Vec<_> = std::fs::read_dir(backup_dir(file))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            let name = file_name(p);
+            name.starts_with(&prefix) && name.ends_with(".bak")
+        })
+        .collect();
+    // The timestamps in the names sort in the order they were made.
+    backups.sort_by(|a, b| b.cmp(a));
+
+    let legacy = PathBuf::from(format!("{}.bak", file.display()));
+    if legacy.exists() {
+        backups.push(legacy);
+    }
+
+    backups
+}
+
+/// Copies the file into the backup folder and removes the oldest backups past
+/// the `backups` setting.
+fn back_up(file: &Path) -> Result<()> {
+    let dir = backup_dir(file);
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .create(&dir)
+        .context("Could not create the backup folder.")?;
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S%.3f");
+    std::fs::copy(file, dir.join(format!("{}.{stamp}.bak", file_name(file))))?;
+
+    let keep = crate::config::read_raw()
+        .ok()
+        .and_then(|c| c["backups"].as_u64())
+        .map_or(DEFAULT_BACKUPS, |n| n as usize);
+    for old in backups(file)
+        .into_iter()
+        .filter(|b| b.starts_with(&dir))
+        .skip(keep.max(1))
+    {
+        tracing::debug!("Removing the old backup {}", old.display());
+        std::fs::remove_file(old)?;
+    }
+
+    Ok(())
+}
+
+/// Puts `backup` back in place of the file. The current contents are backed
+/// up first, so the restore can be undone too.
+pub fn restore(file: &Path, backup: &Path) -> Result<()> {
+    let contents = std::fs::read_to_string(backup).context("Could not read the backup.")?;
+    write_contents(file, &contents)
+}
+
+fn write_contents(file: &Path, contents: &str) -> Result<()> {
+    let previous = std::fs::read_to_string(file).unwrap_or_default();
+
+    if file.exists() {
+        back_up(file)?;
+    }
+    std::fs::write(file, contents)?;
+
+    audit::record_or_warn(&Change::Compose {
+        file: std::fs::canonicalize(file).unwrap_or(file.to_path_buf()),
+        diff: diff(&previous, contents),
+    });
+
+    let mut edits = Edits::load()?;
+    edits.record(file, contents);
+    edits.save()
+}
+
+/// A unified diff from `old` to `new`.
+pub fn diff(old: &str, new: &str) -> String {
+    similar::TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(2)
+        .to_string()
+}
+
+fn hash(contents: &str) -> String {
+    Sha256::digest(contents.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn file_key(file: &Path) -> String {
+    let file = std::fs::canonicalize(file).unwrap_or(file.to_path_buf());
+    file.display().to_string()
+}
@@ -0,0 +1,184 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::error::{EurusError, Result};
+
+/// Variables available for `${VAR}` substitution in a compose file.
+pub type Variables = HashMap<String, String>;
+
+/// Variable names checked (in order) for a default upstream port.
+static PORT_VARIABLES: [&str; 5] = ["APP_PORT", "PORT", "HTTP_PORT", "WEB_PORT", "SERVER_PORT"];
+/// Variable names checked (in order) for the name of the caddy network.
+static NETWORK_VARIABLES: [&str; 4] = [
+    "CADDY_NETWORK",
+    "CADDY_NET",
+    "PROXY_NETWORK",
+    "TRAEFIK_NETWORK",
+];
+
+/// Loads the variables docker compose would use for a project: the `.env` file
+/// next to the compose file, overridden by the process environment.
+pub fn load_variables(project_dir: &Path) -> Variables {
+    let mut vars = load_dotenv(project_dir);
+    vars.extend(std::env::vars());
+    vars
+}
+
+/// Loads only the project's `.env` file, if there is one.
+pub fn load_dotenv(project_dir: &Path) -> Variables {
+    std::fs::read_to_string(project_dir.join(".env"))
+        .map(|c| parse_dotenv(&c))
+        .unwrap_or_default()
+}
+
+/// Parses the contents of a `.env` file. Blank lines and comments are skipped,
+/// an optional `export ` prefix is allowed, and surrounding quotes are removed.
+pub fn parse_dotenv(contents: &str) -> Variables {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| {
+            let l = l.strip_prefix("export ").unwrap_or(l);
+            let (key, value) = l.split_once('=')?;
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Finds a port variable for the given service in a `.env` file, either
+/// `<SERVICE>_PORT` or one of the common generic names. Returns it as a
+/// `${VAR}` reference so the label stays parameterized.
+pub fn default_port(dotenv: &Variables, service: &str) -> Option<String> {
+    let service_var = format!("{}_PORT", service.to_uppercase().replace('-', "_"));
+
+    let var = std::iter::once(service_var.as_str())
+        .chain(PORT_VARIABLES)
+        .find(|v| dotenv.get(*v).is_some_and(|p| p.parse::<u16>().is_ok()))?;
+    Some(format!("${{{var}}}"))
+}
+
+/// Finds the caddy network name in a `.env` file.
+pub fn default_network(dotenv: &Variables) -> Option<&String> {
+    NETWORK_VARIABLES
+        .iter()
+        .find_map(|v| dotenv.get(*v).filter(|n| !n.is_empty()))
+}
+
+/// Whether the string contains anything that compose would substitute.
+pub fn has_variables(input: &str) -> bool {
+    input.contains('$')
+}
+
+/// Expands `$VAR`, `${VAR}` and the `:-`, `-`, `:?`, `?`, `:+` and `+` modifiers the
+/// same way docker compose does. `$$` is an escaped dollar sign.
+pub fn interpolate(input: &str, vars: &Variables) -> Result<String> {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.peek() {
+            Some('$') => {
+                chars.next();
+                out.push('$');
+            }
+            Some('{') => {
+                chars.next();
+                let mut expr = String::new();
+                let mut depth = 1;
+                for c in chars.by_ref() {
+                    match c {
+                        '{' => depth += 1,
+                        '}' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => (),
+                    }
+                    expr.push(c);
+                }
+                if depth != 0 {
+                    return Err(EurusError::ComposeError(format!(
+                        "Unterminated variable reference `${{{expr}`."
+                    )));
+                }
+                out.push_str(&expand(&expr, vars)?);
+            }
+            Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                out.push_str(vars.get(&name).map(String::as_str).unwrap_or_default());
+            }
+            _ => out.push('$'),
+        }
+    }
+
+    Ok(out)
+}
+
+fn expand(expr: &str, vars: &Variables) -> Result<String> {
+    let split = expr
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(expr.len());
+    let (name, rest) = expr.split_at(split);
+    let value = vars.get(name);
+    let set = value.is_some();
+    let non_empty = value.is_some_and(|v| !v.is_empty());
+
+    let (op, arg) = match rest.strip_prefix(':') {
+        Some(r) => (
+            format!(":{}", r.chars().next().unwrap_or_default()),
+            r.get(1..),
+        ),
+        None => (
+            rest.chars().next().map(String::from).unwrap_or_default(),
+            rest.get(1..),
+        ),
+    };
+    let arg = || interpolate(arg.unwrap_or_default(), vars);
+
+    Ok(match op.as_str() {
+        "" => value.cloned().unwrap_or_default(),
+        ":-" if non_empty => value.cloned().unwrap_or_default(),
+        ":-" => arg()?,
+        "-" if set => value.cloned().unwrap_or_default(),
+        "-" => arg()?,
+        ":?" if non_empty => value.cloned().unwrap_or_default(),
+        "?" if set => value.cloned().unwrap_or_default(),
+        ":?" | "?" => {
+            return Err(EurusError::ComposeError(format!(
+                "Required variable `{name}` is not set: {}",
+                arg()?
+            )))
+        }
+        ":+" if non_empty => arg()?,
+        "+" if set => arg()?,
+        ":+" | "+" => String::new(),
+        _ => {
+            return Err(EurusError::ComposeError(format!(
+                "Invalid variable reference `${{{expr}}}`."
+            )))
+        }
+    })
+}
+
+/// Resolves a port that may be given as a literal or through variables.
+pub fn resolve_port(input: &str, vars: &Variables) -> Result<u16> {
+    interpolate(input.trim(), vars)?
+        .parse()
+        .map_err(|_| EurusError::ValidationError("Not a valid port number.".to_string()))
+}
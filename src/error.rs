@@ -0,0 +1,136 @@
+use std::io::ErrorKind;
+
+/// The failures the provider and compose layers can return, so callers can
+/// tell them apart without parsing messages. The cli turns these into eyre
+/// reports like any other error.
+#[derive(Debug, thiserror::Error)]
+pub enum EurusError {
+    #[error("{0}")]
+    ConfigError(String),
+    /// An api call failed. `codes` holds the provider's error codes when it
+    /// sent any, and `status` the http status when the request got that far.
+    /// `ray` is Cloudflare's `cf-ray` id for the request, which their support
+    /// asks for.
+    #[error("{message}{}", ray.as_ref().map(|r| format!(" (cf-ray {r})")).unwrap_or_default())]
+    ApiError {
+        status: Option<u16>,
+        codes: Vec<i32>,
+        message: String,
+        ray: Option<String>,
+    },
+    #[error("{0}")]
+    ComposeError(String),
+    #[error("{0}")]
+    ValidationError(String),
+    /// A zone, record or domain that was asked for doesn't exist.
+    #[error("{0}")]
+    NotFound(String),
+    #[error("Cancelled.")]
+    Cancelled,
+    /// Reading or writing a file other than the config failed.
+    #[error("{0}")]
+    Io(std::io::Error),
+}
+
+/// The exit codes of the cli, which are part of its interface for scripts.
+pub mod exit {
+    pub const SUCCESS: i32 = 0;
+    /// Anything without a more specific code, like a failed check.
+    pub const FAILURE: i32 = 1;
+    pub const CONFIG: i32 = 2;
+    /// The api rejected the request or the key, or couldn't be reached.
+    pub const API: i32 = 3;
+    pub const NOT_FOUND: i32 = 4;
+    pub const COMPOSE: i32 = 5;
+    pub const INVALID_INPUT: i32 = 6;
+    /// Unknown arguments or flags, `EX_USAGE` from sysexits.h.
+    pub const USAGE: i32 = 64;
+    /// What shells use for Ctrl-C.
+    pub const CANCELLED: i32 = 130;
+}
+
+pub type Result<T, E = EurusError> = std::result::Result<T, E>;
+
+impl EurusError {
+    pub fn api(message: impl Into<String>) -> Self {
+        Self::ApiError {
+            status: None,
+            codes: vec![],
+            message: message.into(),
+            ray: None,
+        }
+    }
+
+    pub fn with_status(mut self, status: reqwest::StatusCode) -> Self {
+        if let Self::ApiError { status: s, .. } = &mut self {
+            *s = Some(status.as_u16());
+        }
+        self
+    }
+
+    pub fn with_ray(mut self, ray: Option<String>) -> Self {
+        if let Self::ApiError { ray: r, .. } = &mut self {
+            *r = ray;
+        }
+        self
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::ConfigError(_) => exit::CONFIG,
+            Self::ApiError {
+                status: Some(404), ..
+            } => exit::NOT_FOUND,
+            Self::ApiError { codes, .. }
+                if codes.iter().any(|c| [7000, 7003, 81044].contains(c)) =>
+            {
+                exit::NOT_FOUND
+            }
+            Self::ApiError { .. } => exit::API,
+            Self::ComposeError(_) => exit::COMPOSE,
+            Self::ValidationError(_) => exit::INVALID_INPUT,
+            Self::NotFound(_) => exit::NOT_FOUND,
+            Self::Cancelled => exit::CANCELLED,
+            Self::Io(_) => exit::FAILURE,
+        }
+    }
+
+    /// Whether the api said the resource doesn't exist, or is no longer
+    /// accessible with the configured credentials.
+    pub fn is_gone(&self) -> bool {
+        matches!(
+            self,
+            Self::ApiError {
+                status: Some(403 | 404),
+                ..
+            }
+        )
+    }
+}
+
+impl From<reqwest::Error> for EurusError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::api(format!("Request failed: {e}"))
+    }
+}
+
+impl From<serde_json::Error> for EurusError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::api(format!("Unexpected response: {e}"))
+    }
+}
+
+impl From<serde_yml::Error> for EurusError {
+    fn from(e: serde_yml::Error) -> Self {
+        Self::ComposeError(format!("The compose yaml was invalid: {e}"))
+    }
+}
+
+impl From<std::io::Error> for EurusError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            ErrorKind::Interrupted => Self::Cancelled,
+            _ => Self::Io(e),
+        }
+    }
+}
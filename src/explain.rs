@@ -0,0 +1,134 @@
+/// What a Cloudflare error code usually means, and the eurus command that
+/// fixes it when there is one.
+#[derive(Debug, Clone, Copy)]
+pub struct Explanation {
+    pub meaning: &'static str,
+    pub fix: Option<&'static str>,
+}
+
+static EXPLANATIONS: &[(&[i32], Explanation)] = &[
+    (
+        &[10000],
+        Explanation {
+            meaning: "Cloudflare didn't accept the api key, or the token lacks the Zone.DNS edit \
+                      permission for the zone.",
+            fix: Some(
+                "`eurus verify` shows what the key may do in each zone. Store a new key with \
+                 `eurus config set-key`.",
+            ),
+        },
+    ),
+    (
+        &[6003, 6111, 9103],
+        Explanation {
+            meaning:
+                "Cloudflare didn't accept the api key. It may be mistyped, revoked or expired.",
+            fix: Some(
+                "Store a new key with `eurus config set-key`, then check it with `eurus verify`.",
+            ),
+        },
+    ),
+    (
+        &[9106, 9107],
+        Explanation {
+            meaning: "The Global API Key needs the account's email, which wasn't sent.",
+            fix: Some("Set it with `eurus config set cloudflare_email <email>`."),
+        },
+    ),
+    (
+        &[9109],
+        Explanation {
+            meaning: "The key isn't allowed to do this. The zone id may be wrong, or the token \
+                      lacks the permission or access to the zone.",
+            fix: Some("`eurus verify` shows what the key may do in each zone."),
+        },
+    ),
+    (
+        &[7003, 7000],
+        Explanation {
+            meaning: "Cloudflare doesn't know the zone or record id, usually because the zone id \
+                      in the config is wrong or the zone was deleted.",
+            fix: Some(
+                "Re-add the zone with `eurus zone remove <zone>` and `eurus zone add <zone>`.",
+            ),
+        },
+    ),
+    (
+        &[81057, 81058],
+        Explanation {
+            meaning: "An identical record already exists, so there is nothing to create.",
+            fix: Some("`eurus dns list <zone>` shows the existing records."),
+        },
+    ),
+    (
+        &[81053, 81054],
+        Explanation {
+            meaning: "A record with this name and type already exists. A CNAME can't share \
+                      its name with any other record, and other records can't share a name \
+                      with a CNAME.",
+            fix: Some(
+                "Remove the other record first, or change it with `eurus switch <domain> <value>`.",
+            ),
+        },
+    ),
+    (
+        &[81044],
+        Explanation {
+            meaning: "The record no longer exists, it was probably deleted elsewhere.",
+            fix: Some("`eurus dns list <zone>` shows the current records."),
+        },
+    ),
+    (
+        &[9005, 9006],
+        Explanation {
+            meaning: "The value isn't a valid address for the record type, A records need an \
+                      IPv4 address and AAAA records an IPv6 one.",
+            fix: None,
+        },
+    ),
+    (
+        &[9007],
+        Explanation {
+            meaning: "A CNAME has to point at a hostname, not an address or url.",
+            fix: None,
+        },
+    ),
+    (
+        &[9004, 9021],
+        Explanation {
+            meaning: "The TTL is out of range. It has to be 1 (automatic) or between 30 and \
+                      86400 seconds, and proxied records are always automatic.",
+            fix: None,
+        },
+    ),
+    (
+        &[1061],
+        Explanation {
+            meaning: "The domain is already a zone in a Cloudflare account.",
+            fix: Some("If it's in this account, add it to the config with `eurus zone import`."),
+        },
+    ),
+    (
+        &[1049],
+        Explanation {
+            meaning: "Cloudflare only accepts registered domains as zones, not subdomains or \
+                      unregistered names.",
+            fix: None,
+        },
+    ),
+    (
+        &[971, 10429],
+        Explanation {
+            meaning: "Too many api calls were made recently, and the key is blocked for a few \
+                      minutes.",
+            fix: Some("Wait a few minutes. `eurus quota` shows the recent calls."),
+        },
+    ),
+];
+
+pub fn explain(code: i32) -> Option<Explanation> {
+    EXPLANATIONS
+        .iter()
+        .find(|(codes, _)| codes.contains(&code))
+        .map(|(_, explanation)| *explanation)
+}
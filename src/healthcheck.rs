@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+
+use docker_compose_types::{Healthcheck, HealthcheckTest};
+
+/// Commands for images whose health is better checked another way than
+/// requesting `/`, or that ship wget but not curl. `{port}` is replaced with
+/// the port the service listens on. Images are matched by name, ignoring the
+/// registry and tag.
+static TEMPLATES: [(&str, &str); 7] = [
+    (
+        "grafana/grafana",
+        "wget -q --spider http://localhost:{port}/api/health || exit 1",
+    ),
+    (
+        "vaultwarden/server",
+        "curl -fsS http://localhost:{port}/alive || exit 1",
+    ),
+    (
+        "gitea/gitea",
+        "curl -fsS http://localhost:{port}/api/healthz || exit 1",
+    ),
+    (
+        "jellyfin/jellyfin",
+        "curl -fsS http://localhost:{port}/health || exit 1",
+    ),
+    (
+        "nextcloud",
+        "curl -fsS http://localhost:{port}/status.php || exit 1",
+    ),
+    ("louislam/uptime-kuma", "extra/healthcheck"),
+    (
+        "caddy",
+        "wget -q --spider http://localhost:{port}/ || exit 1",
+    ),
+];
+
+/// The healthcheck command for a service running `image` on `port`. Templates
+/// from the config take precedence over the built in ones.
+pub fn command(image: &str, port: &str, templates: &BTreeMap<String, String>) -> String {
+    let name = image_name(image);
+    let template = templates
+        .get(&name)
+        .map(String::as_str)
+        .or_else(|| TEMPLATES.iter().find(|(i, _)| *i == name).map(|(_, t)| *t))
+        // Alpine based images only have busybox's wget.
+        .unwrap_or(match image.contains("alpine") {
+            true => "wget -q --spider http://localhost:{port}/ || exit 1",
+            false => "curl -fsS http://localhost:{port}/ || exit 1",
+        });
+
+    template.replace("{port}", port)
+}
+
+pub fn healthcheck(command: &str) -> Healthcheck {
+    Healthcheck {
+        test: Some(HealthcheckTest::Multiple(vec![
+            "CMD-SHELL".to_string(),
+            command.to_string(),
+        ])),
+        interval: Some("30s".to_string()),
+        timeout: Some("5s".to_string()),
+        retries: 3,
+        start_period: Some("30s".to_string()),
+        disable: false,
+    }
+}
+
+/// `ghcr.io/org/app:1.2` becomes `org/app`, and `library/nginx` `nginx`.
+fn image_name(image: &str) -> String {
+    let image = image.split('@').next().unwrap_or(image);
+    let mut parts: Vec<_> = image.split('/').collect();
+    if parts.len() > 1 && (parts[0].contains('.') || parts[0].contains(':')) {
+        parts.remove(0);
+    }
+    if let Some(last) = parts.last_mut() {
+        *last = last.split(':').next().unwrap_or(last);
+    }
+    if parts.first() == Some(&"library") {
+        parts.remove(0);
+    }
+
+    parts.join("/")
+}
@@ -0,0 +1,178 @@
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use color_eyre::eyre::{ensure, Context, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+/// Shell commands run around dns and web operations. Each one receives a JSON
+/// description of the operation on stdin and the same as `EURUS_` variables,
+/// and a failing `pre_` hook aborts it. Each can be a single command or a list.
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+pub struct Hooks {
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub pre_dns: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub post_dns: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub pre_web: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub post_web: Vec<String>,
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Commands {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match Commands::deserialize(deserializer)? {
+        Commands::One(command) => vec![command],
+        Commands::Many(commands) => commands,
+    })
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    PreDns,
+    PostDns,
+    PreWeb,
+    PostWeb,
+}
+
+impl Hooks {
+    fn commands(&self, event: Event) -> &[String] {
+        match event {
+            Event::PreDns => &self.pre_dns,
+            Event::PostDns => &self.post_dns,
+            Event::PreWeb => &self.pre_web,
+            Event::PostWeb => &self.post_web,
+        }
+    }
+
+    /// Runs the hooks for `event`, writing `payload` to each one's stdin.
+    pub fn run(&self, event: Event, payload: &impl Serialize) -> Result<()> {
+        let commands = self.commands(event);
+        if commands.is_empty() {
+            return Ok(());
+        }
+
+        let mut payload = serde_json::json!({
+            "event": event,
+            "data": payload,
+        });
+        // Hooks for a compose file run next to it, so `docker compose` acts on
+        // that project, with the file's path made absolute to match.
+        let file = payload["data"]["file"]
+            .as_str()
+            .and_then(|file| std::path::absolute(file).ok());
+        if let Some(file) = &file {
+            payload["data"]["file"] = serde_json::json!(file);
+        }
+        let dir = file
+            .as_deref()
+            .and_then(Path::parent)
+            .filter(|dir| dir.is_dir())
+            .map(Path::to_path_buf);
+        let mut vars = vec![];
+        env_vars("EURUS", &payload["data"], &mut vars);
+        env_vars("EURUS_EVENT", &payload["event"], &mut vars);
+        let payload = serde_json::to_vec(&payload)?;
+
+        for command in commands {
+            let mut command_line = shell(command);
+            if let Some(dir) = &dir {
+                command_line.current_dir(dir);
+            }
+            let mut child = command_line
+                .envs(vars.iter().cloned())
+                .stdin(Stdio::piped())
+                .spawn()
+                .with_context(|| format!("Failed to run hook `{command}`."))?;
+
+            // A hook that doesn't read stdin closes it early, which is fine.
+            if let Some(mut stdin) = child.stdin.take() {
+                let _ = stdin.write_all(&payload);
+            }
+
+            let status = child.wait()?;
+            ensure!(status.success(), "Hook `{command}` exited with {status}.");
+        }
+
+        Ok(())
+    }
+}
+
+/// Flattens the payload into variables named after the path to each value,
+/// like `EURUS_RECORD_NAME`. Lists of plain values are joined with commas,
+/// and other lists are numbered like `EURUS_DELETED_0_NAME`.
+fn env_vars(prefix: &str, value: &Value, vars: &mut Vec<(String, String)>) {
+    let plain = |value: &Value| match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(_) | Value::Number(_) => Some(value.to_string()),
+        _ => None,
+    };
+    let key = |name: &str| {
+        let name: String = name
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() {
+                true => c.to_ascii_uppercase(),
+                false => '_',
+            })
+            .collect();
+        format!("{prefix}_{name}")
+    };
+
+    match value {
+        Value::Object(map) => {
+            for (name, value) in map {
+                env_vars(&key(name), value, vars);
+            }
+        }
+        Value::Array(items) => match items.iter().map(plain).collect::<Option<Vec<_>>>() {
+            Some(items) => vars.push((prefix.to_string(), items.join(","))),
+            None => {
+                for (i, item) in items.iter().enumerate() {
+                    env_vars(&key(&i.to_string()), item, vars);
+                }
+            }
+        },
+        Value::Null => {}
+        value => vars.extend(plain(value).map(|v| (prefix.to_string(), v))),
+    }
+}
+
+/// Runs `command` through the platform's shell.
+pub fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.args(["/C", command]);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.args(["-c", command]);
+        c
+    }
+}
@@ -0,0 +1,49 @@
+use crate::error::{EurusError, Result};
+
+/// Converts a domain as typed by the user to the ascii (punycode) form that
+/// providers expect. Ascii names are returned as they are.
+pub fn to_ascii(name: &str) -> Result<String> {
+    if name.is_ascii() {
+        return Ok(name.to_string());
+    }
+
+    idna::domain_to_ascii(name)
+        .map_err(|_| EurusError::ValidationError(format!("{name} is not a valid domain name.")))
+}
+
+/// The full name of a record in `zone` as typed in a prompt: `@` (or nothing)
+/// for the zone itself, `*` or `*.sub` for wildcards, and names relative to
+/// the zone or already ending in it.
+pub fn record_name(input: &str, zone: &str) -> Result<String> {
+    let input = input.trim().trim_end_matches('.');
+    let name = match input {
+        "" | "@" => return Ok(zone.to_string()),
+        _ => to_ascii(input)?.to_lowercase(),
+    };
+
+    // A wildcard can only be the whole leftmost label.
+    let wildcard = name == "*" || name.starts_with("*.");
+    if name.contains('*') && (!wildcard || name[1..].contains('*')) {
+        return Err(EurusError::ValidationError(format!(
+            "{input} is not a valid wildcard, use `*` or `*.sub` as the leftmost part."
+        )));
+    }
+
+    Ok(match name == zone || name.ends_with(&format!(".{zone}")) {
+        true => name,
+        false => format!("{name}.{zone}"),
+    })
+}
+
+/// The unicode form of a punycode domain, for showing to the user. Names that
+/// don't decode cleanly are shown as they are.
+pub fn to_unicode(name: &str) -> String {
+    if !name.split('.').any(|l| l.starts_with("xn--")) {
+        return name.to_string();
+    }
+
+    match idna::domain_to_unicode(name) {
+        (unicode, Ok(())) => unicode,
+        _ => name.to_string(),
+    }
+}
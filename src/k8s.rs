@@ -0,0 +1,156 @@
+//! Ingress and Gateway API manifests for services running in Kubernetes, and
+//! finding the address the cluster's ingress controller is reachable on.
+
+use std::process::Command;
+
+use color_eyre::eyre::{ensure, Context, Result};
+use serde_json::{json, Value};
+
+use crate::error::EurusError;
+
+/// A Kubernetes Service to route a set of domains to.
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub service: String,
+    pub namespace: String,
+    pub domains: Vec<String>,
+    /// The port number or name of the Service.
+    pub port: String,
+    /// The `ingressClassName` of an Ingress, the cluster default when unset.
+    pub class: Option<String>,
+    /// A cert-manager ClusterIssuer to get certificates for the domains from.
+    pub issuer: Option<String>,
+}
+
+impl Route {
+    fn metadata(&self) -> Value {
+        let mut metadata = json!({
+            "name": self.service,
+            "namespace": self.namespace,
+            "labels": { "app.kubernetes.io/managed-by": "eurus" },
+        });
+        if let Some(issuer) = &self.issuer {
+            metadata["annotations"] = json!({ "cert-manager.io/cluster-issuer": issuer });
+        }
+        metadata
+    }
+
+    fn port(&self) -> Value {
+        match self.port.parse::<u16>() {
+            Ok(number) => json!({ "number": number }),
+            Err(_) => json!({ "name": self.port }),
+        }
+    }
+
+    /// A `networking.k8s.io/v1` Ingress with a rule per domain.
+    pub fn ingress(&self) -> Value {
+        let rules: Vec<_> = self
+            .domains
+            .iter()
+            .map(|domain| {
+                json!({
+                    "host": domain,
+                    "http": { "paths": [{
+                        "path": "/",
+                        "pathType": "Prefix",
+                        "backend": { "service": { "name": self.service, "port": self.port() } },
+                    }] },
+                })
+            })
+            .collect();
+
+        let mut spec = json!({ "rules": rules });
+        if let Some(class) = &self.class {
+            spec["ingressClassName"] = json!(class);
+        }
+        if self.issuer.is_some() {
+            spec["tls"] = json!([{
+                "hosts": self.domains,
+                "secretName": format!("{}-tls", self.service),
+            }]);
+        }
+
+        json!({
+            "apiVersion": "networking.k8s.io/v1",
+            "kind": "Ingress",
+            "metadata": self.metadata(),
+            "spec": spec,
+        })
+    }
+
+    /// A Gateway API HTTPRoute attached to `gateway`, given as `name` or
+    /// `namespace/name`.
+    pub fn http_route(&self, gateway: &str) -> Result<Value> {
+        let port: u16 = self.port.parse().map_err(|_| {
+            EurusError::ValidationError(format!(
+                "HTTPRoutes need a port number, not {}.",
+                self.port
+            ))
+        })?;
+        let parent = match gateway.split_once('/') {
+            Some((namespace, name)) => json!({ "name": name, "namespace": namespace }),
+            None => json!({ "name": gateway }),
+        };
+
+        Ok(json!({
+            "apiVersion": "gateway.networking.k8s.io/v1",
+            "kind": "HTTPRoute",
+            "metadata": self.metadata(),
+            "spec": {
+                "parentRefs": [parent],
+                "hostnames": self.domains,
+                "rules": [{ "backendRefs": [{ "name": self.service, "port": port }] }],
+            },
+        }))
+    }
+}
+
+/// The manifest as YAML, ready for `kubectl apply -f`.
+pub fn to_yaml(manifest: &Value) -> Result<String> {
+    serde_yml::to_string(manifest).context("Failed to write the manifest.")
+}
+
+/// The addresses of the LoadBalancer services in the cluster, as the
+/// namespace/name of the service and its address. Ingress controllers and
+/// gateways come first.
+pub fn load_balancers() -> Result<Vec<(String, String)>> {
+    let output = Command::new("kubectl")
+        .args(["get", "services", "--all-namespaces", "-o", "json"])
+        .output()
+        .context("Failed to run kubectl.")?;
+    ensure!(
+        output.status.success(),
+        "kubectl get services failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    let services: Value =
+        serde_json::from_slice(&output.stdout).context("Failed to parse the services.")?;
+
+    let mut addresses: Vec<(String, String)> = services["items"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|s| s["spec"]["type"] == "LoadBalancer")
+        .flat_map(|s| {
+            let name = format!(
+                "{}/{}",
+                s["metadata"]["namespace"].as_str().unwrap_or_default(),
+                s["metadata"]["name"].as_str().unwrap_or_default()
+            );
+            s["status"]["loadBalancer"]["ingress"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|i| i["ip"].as_str().or(i["hostname"].as_str()))
+                .map(move |address| (name.clone(), address.to_string()))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    addresses.sort_by_key(|(name, _)| {
+        !["ingress", "traefik", "gateway", "envoy", "istio"]
+            .iter()
+            .any(|n| name.contains(n))
+    });
+
+    Ok(addresses)
+}
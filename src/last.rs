@@ -0,0 +1,71 @@
+use std::{collections::BTreeMap, path::Path};
+
+use color_eyre::eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::DATA_DIR;
+
+/// What was entered in each project directory on the previous run, offered as
+/// the defaults of the same prompts next time.
+#[derive(Debug, Deserialize, Serialize, Default)]
+struct LastRuns {
+    #[serde(default)]
+    projects: BTreeMap<String, LastRun>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+pub struct LastRun {
+    /// The id of the zone.
+    pub zone: Option<String>,
+    pub name: Option<String>,
+    pub record_type: Option<String>,
+    pub target: Option<String>,
+    pub service: Option<String>,
+    pub port: Option<String>,
+    pub domain: Option<String>,
+}
+
+impl LastRun {
+    /// The values from the last run in `dir`, or none if they can't be read.
+    pub fn load(dir: &Path) -> Self {
+        read()
+            .ok()
+            .and_then(|mut l| l.projects.remove(&project_key(dir)))
+            .unwrap_or_default()
+    }
+
+    /// Changes the stored values for `dir`.
+    pub fn update(dir: &Path, change: impl FnOnce(&mut LastRun)) -> Result<()> {
+        let mut runs = read()?;
+        change(runs.projects.entry(project_key(dir)).or_default());
+
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .create(*DATA_DIR)
+            .context("Failed to create data directory")?;
+        std::fs::write(DATA_DIR.join("last.json"), serde_json::to_string(&runs)?)?;
+
+        Ok(())
+    }
+
+    /// Changes the stored values for `dir`, only warning when they can't be
+    /// saved since the run itself already succeeded.
+    pub fn update_or_warn(dir: &Path, change: impl FnOnce(&mut LastRun)) {
+        if let Err(e) = Self::update(dir, change) {
+            let _ =
+                cliclack::log::warning(format!("Could not save the answers for next time: {e}"));
+        }
+    }
+}
+
+fn read() -> Result<LastRuns> {
+    match std::fs::read_to_string(DATA_DIR.join("last.json")) {
+        Ok(s) => serde_json::from_str(&s).context("Last run file is malformed."),
+        Err(_) => Ok(LastRuns::default()),
+    }
+}
+
+pub fn project_key(dir: &Path) -> String {
+    let dir = std::fs::canonicalize(dir).unwrap_or(dir.to_path_buf());
+    dir.display().to_string()
+}
@@ -0,0 +1,80 @@
+//! The Cloudflare and other provider clients, config handling and compose
+//! editing behind the `eurus` cli, usable without going through it.
+
+#![allow(dead_code)]
+
+pub mod audit;
+pub mod caddy;
+pub mod cancel;
+pub mod cloudflare;
+pub mod completions;
+pub mod compose;
+pub mod config;
+pub mod credentials;
+pub mod dashboard;
+pub mod ddns;
+pub mod docker;
+pub mod edits;
+pub mod env;
+pub mod error;
+pub mod explain;
+pub mod healthcheck;
+pub mod hooks;
+pub mod idn;
+pub mod k8s;
+pub mod last;
+pub mod lint;
+pub mod lock;
+pub mod logging;
+pub mod maintenance;
+pub mod metrics;
+pub mod notes;
+pub mod notify;
+pub mod patch;
+pub mod presets;
+pub mod probe;
+pub mod prompt;
+pub mod provider;
+pub mod proxy;
+pub mod quota;
+pub mod reconcile;
+pub mod remote;
+pub mod resolve;
+pub mod retry;
+pub mod scan;
+pub mod secrets;
+pub mod session;
+pub mod switch;
+pub mod systemd;
+pub mod templates;
+pub mod trash;
+pub mod tui;
+pub mod update;
+pub mod watch;
+pub mod zonefile;
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{LazyLock, OnceLock},
+};
+
+use directories::ProjectDirs;
+use reqwest::Client;
+
+pub static CLIENT: LazyLock<Client> = LazyLock::new(Client::new);
+pub static PROJECT_DIR: LazyLock<ProjectDirs> =
+    LazyLock::new(|| ProjectDirs::from("gay", "vaskel", "eurus").unwrap());
+pub static CONFIG_DIR: LazyLock<&Path> = LazyLock::new(|| PROJECT_DIR.config_dir());
+pub static DATA_DIR: LazyLock<&Path> = LazyLock::new(|| PROJECT_DIR.data_dir());
+/// The config file picked with `--config` or `EURUS_CONFIG`, used instead of
+/// `config.toml` in [`CONFIG_DIR`].
+pub static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+/// The profile picked with `--profile`, if any.
+pub static PROFILE: OnceLock<String> = OnceLock::new();
+/// The caddy-docker-proxy label prefix picked with `--label-prefix`, see
+/// [`proxy::label_prefix`].
+pub static LABEL_PREFIX: OnceLock<String> = OnceLock::new();
+/// Whether `--refresh` was passed, so cached zones are fetched again.
+pub static REFRESH: OnceLock<bool> = OnceLock::new();
+/// Whether `--yes` was passed, see [`prompt`].
+pub static ASSUME_YES: OnceLock<bool> = OnceLock::new();
@@ -0,0 +1,347 @@
+//! Checks for `eurus config lint`: settings that load fine but are probably
+//! mistakes, and fixes for the ones that can be fixed without guessing.
+
+use std::path::Path;
+
+use color_eyre::eyre::Result;
+use futures::future::join_all;
+use serde_json::Value;
+
+use crate::{
+    config::{Config, Profile, ZoneInfo},
+    credentials, idn,
+    provider::{self, Provider},
+};
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Problem {
+    pub message: String,
+    /// Whether [`fix`] or [`fix_permissions`] takes care of it.
+    pub fixable: bool,
+}
+
+impl Problem {
+    fn new(message: String, fixable: bool) -> Self {
+        Self { message, fixable }
+    }
+}
+
+/// The settings a type knows, from serializing its default.
+fn known_keys(value: impl serde::Serialize) -> Vec<String> {
+    match serde_json::to_value(value) {
+        Ok(Value::Object(map)) => map.keys().cloned().collect(),
+        _ => vec![],
+    }
+}
+
+/// Each list of zones in the config with where it is, the top level one and
+/// those of the profiles.
+fn zone_lists(raw: &Value) -> Vec<(String, &Vec<Value>)> {
+    let mut lists = vec![];
+    if let Some(zones) = raw["zones"].as_array() {
+        lists.push(("zones".to_string(), zones));
+    }
+    for (name, profile) in raw["profiles"].as_object().into_iter().flatten() {
+        if let Some(zones) = profile["zones"].as_array() {
+            lists.push((format!("profiles.{name}.zones"), zones));
+        }
+    }
+    lists
+}
+
+/// The form zone names are compared in: punycode, lowercase and without a
+/// trailing dot.
+fn normalized(name: &str) -> String {
+    let name = name.trim().trim_end_matches('.').to_lowercase();
+    idn::to_ascii(&name).unwrap_or(name)
+}
+
+/// Whether two zone entries are the same once their names are normalized.
+fn same_entry(a: &Value, b: &Value) -> bool {
+    let normal = |zone: &Value| {
+        let mut zone = zone.clone();
+        if let Some(name) = zone["name"].as_str().map(normalized) {
+            zone["name"] = Value::String(name);
+        }
+        zone
+    };
+    normal(a) == normal(b)
+}
+
+/// Checks the config as it is on disk, without going over the network.
+pub fn check(raw: &Value) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    let known = known_keys(Config::default());
+    for key in raw.as_object().into_iter().flat_map(|m| m.keys()) {
+        if !known.contains(key) {
+            problems.push(Problem::new(
+                format!("`{key}` isn't a setting, it may be misspelled or no longer used."),
+                false,
+            ));
+        }
+    }
+    let known = known_keys(Profile::default());
+    for (name, profile) in raw["profiles"].as_object().into_iter().flatten() {
+        for key in profile.as_object().into_iter().flat_map(|m| m.keys()) {
+            if !known.contains(key) {
+                problems.push(Problem::new(
+                    format!("`profiles.{name}.{key}` isn't a profile setting."),
+                    false,
+                ));
+            }
+        }
+    }
+
+    for (path, zones) in zone_lists(raw) {
+        let mut seen: Vec<&Value> = vec![];
+        for (i, zone) in zones.iter().enumerate() {
+            let id = zone["id"].as_str().unwrap_or_default();
+            let name = zone["name"].as_str().unwrap_or_default();
+            if id.is_empty() || name.is_empty() {
+                problems.push(Problem::new(
+                    format!("`{path}.{i}` needs both an `id` and a `name`."),
+                    false,
+                ));
+                continue;
+            }
+            if normalized(name) != name {
+                problems.push(Problem::new(
+                    format!(
+                        "`{path}.{i}` has the name {name}, which is written differently from \
+                             how providers return it, {}.",
+                        normalized(name)
+                    ),
+                    true,
+                ));
+            }
+
+            let same_zone = |other: &Value| {
+                other["id"] == zone["id"]
+                    || other["name"].as_str().map(normalized) == Some(normalized(name))
+            };
+            match seen.iter().find(|other| same_zone(other)) {
+                // Identical entries are safe to drop, otherwise which one is
+                // right can't be told.
+                Some(other) if same_entry(other, zone) => problems.push(Problem::new(
+                    format!("`{path}` lists {name} ({id}) more than once."),
+                    true,
+                )),
+                Some(other) => problems.push(Problem::new(
+                    format!(
+                        "`{path}` has {name} twice, with different settings ({} and {id}).",
+                        other["id"].as_str().unwrap_or_default()
+                    ),
+                    false,
+                )),
+                None => seen.push(zone),
+            }
+        }
+    }
+
+    problems.extend(check_required(raw));
+    problems
+}
+
+/// Settings that are empty while something needs them.
+fn check_required(raw: &Value) -> Vec<Problem> {
+    let mut problems = vec![];
+    let text = |key: &str| raw[key].as_str().unwrap_or_default();
+
+    let config: Config = match serde_json::from_value(raw.clone()) {
+        Ok(config) => config,
+        Err(e) => return vec![Problem::new(format!("The config doesn't load: {e}"), false)],
+    };
+    let uses = |provider: Provider| config.zones.iter().any(|z| z.provider == provider);
+
+    if uses(Provider::Cloudflare)
+        && text("cloudflare_key").is_empty()
+        && text("cloudflare_key_cmd").is_empty()
+        && credentials::cloudflare_key().is_none()
+    {
+        problems.push(Problem::new(
+            "There are Cloudflare zones, but no `cloudflare_key`, `cloudflare_key_cmd` or key in \
+             the keyring."
+                .to_string(),
+            false,
+        ));
+    }
+    if config.cloudflare_auth == crate::cloudflare::AuthMethod::GlobalKey
+        && config.cloudflare_email.is_empty()
+    {
+        problems.push(Problem::new(
+            "`cloudflare_auth` is `global_key`, which needs `cloudflare_email`.".to_string(),
+            false,
+        ));
+    }
+    for (provider, key) in [
+        (Provider::DigitalOcean, "digitalocean_key"),
+        (Provider::Hetzner, "hetzner_key"),
+    ] {
+        if uses(provider) && text(key).is_empty() {
+            problems.push(Problem::new(
+                format!("There are {provider} zones, but `{key}` is empty."),
+                false,
+            ));
+        }
+    }
+
+    if uses(Provider::Route53)
+        && std::env::var("AWS_ACCESS_KEY_ID").is_err()
+        && std::env::var("AWS_PROFILE").is_err()
+        && !directories::BaseDirs::new()
+            .is_some_and(|d| d.home_dir().join(".aws/credentials").exists())
+    {
+        problems.push(Problem::new(
+            "There are Route 53 zones, but no AWS credentials.".to_string(),
+            false,
+        ));
+    }
+
+    let network = config.network(config.proxy);
+    if network.is_empty() {
+        problems.push(Problem::new(
+            format!(
+                "`proxy` is {}, but its network isn't set, `web` will ask for it.",
+                config.proxy
+            ),
+            false,
+        ));
+    }
+    for name in &config.ddns_records {
+        if crate::config::zone_for_name(&config, name).is_none() {
+            problems.push(Problem::new(
+                format!("`ddns_records` has {name}, which isn't in any zone."),
+                false,
+            ));
+        }
+    }
+
+    problems
+}
+
+/// Checks that every zone's records can be read with the configured
+/// credentials.
+pub async fn check_reachable(config: &Config) -> Vec<Problem> {
+    let mut zones: Vec<&ZoneInfo> = vec![];
+    for zone in &config.zones {
+        if !zones.iter().any(|z| z.id == zone.id) {
+            zones.push(zone);
+        }
+    }
+    let reads = join_all(zones.iter().map(|zone| async {
+        provider::for_zone(config, zone)?
+            .list_records(&zone.id)
+            .await
+    }))
+    .await;
+
+    zones
+        .into_iter()
+        .zip(reads)
+        .filter_map(|(zone, read)| {
+            let e = read.err()?;
+            Some(Problem::new(format!("{zone} can't be reached: {e}"), false))
+        })
+        .collect()
+}
+
+/// Checks that a config holding keys can only be read by its owner.
+pub fn check_permissions(path: &Path, raw: &Value) -> Vec<Problem> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let has_keys = ["cloudflare_key", "digitalocean_key", "hetzner_key"]
+            .iter()
+            .any(|k| raw[k].as_str().is_some_and(|v| !v.is_empty()))
+            || raw["profiles"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .any(|(_, p)| {
+                    p.as_object().into_iter().flatten().any(|(k, v)| {
+                        k.ends_with("_key") && v.as_str().is_some_and(|v| !v.is_empty())
+                    })
+                });
+        let mode = std::fs::metadata(path).map(|m| m.permissions().mode());
+        if let Some(mode) = mode.ok().filter(|m| has_keys && m & 0o077 != 0) {
+            return vec![Problem::new(
+                format!(
+                    "{} has keys in it, but others can read it (mode {:o}).",
+                    path.display(),
+                    mode & 0o777
+                ),
+                true,
+            )];
+        }
+    }
+
+    vec![]
+}
+
+/// Fixes the problems [`check`] marks as fixable: zone names are normalized
+/// and zones listed twice are removed. Returns what was fixed.
+pub fn fix(raw: &mut Value) -> Vec<String> {
+    let mut fixed = vec![];
+
+    if let Some(zones) = raw.get_mut("zones") {
+        fix_zones(zones, &mut fixed);
+    }
+    for profile in raw
+        .get_mut("profiles")
+        .and_then(Value::as_object_mut)
+        .into_iter()
+        .flat_map(|p| p.values_mut())
+    {
+        if let Some(zones) = profile.get_mut("zones") {
+            fix_zones(zones, &mut fixed);
+        }
+    }
+
+    fixed
+}
+
+fn fix_zones(zones: &mut Value, fixed: &mut Vec<String>) {
+    let Some(zones) = zones.as_array_mut() else {
+        return;
+    };
+
+    for zone in zones.iter_mut() {
+        let Some(name) = zone["name"].as_str().filter(|n| !n.is_empty()) else {
+            continue;
+        };
+        let normal = normalized(name);
+        if normal != name {
+            fixed.push(format!("Renamed the zone {name} to {normal}."));
+            zone["name"] = Value::String(normal);
+        }
+    }
+
+    let mut seen: Vec<Value> = vec![];
+    zones.retain(|zone| {
+        if !seen.contains(zone) {
+            seen.push(zone.clone());
+            return true;
+        }
+        fixed.push(format!(
+            "Removed the second entry for {}.",
+            serde_json::from_value::<ZoneInfo>(zone.clone())
+                .map(|z| z.to_string())
+                .unwrap_or_default()
+        ));
+        false
+    });
+}
+
+/// Makes the config readable by its owner only.
+pub fn fix_permissions(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::Write,
+};
+
+use color_eyre::eyre::{bail, Context, Result};
+
+use crate::DATA_DIR;
+
+/// Keeps a second `ddns` or `serve` from running next to the first, e.g. a
+/// cron job next to `ddns --watch`. Held until dropped, and released by the
+/// OS if the process dies, so a crash doesn't leave it behind.
+pub struct Lock(File);
+
+/// Takes `<name>.lock` in the data dir, failing with the pid of the process
+/// that holds it. `force_unlock` replaces the lock file instead, for locks
+/// held somewhere the OS doesn't release them, like a network filesystem.
+pub fn acquire(name: &str, force_unlock: bool) -> Result<Lock> {
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .create(*DATA_DIR)
+        .context("Failed to create data directory")?;
+    let path = DATA_DIR.join(format!("{name}.lock"));
+
+    if force_unlock {
+        match std::fs::remove_file(&path) {
+            Ok(()) => tracing::info!("Removed the lock at {}", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e).context("Could not remove the lock."),
+        }
+    }
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .with_context(|| format!("Could not open {}.", path.display()))?;
+    match file.try_lock() {
+        Ok(()) => (),
+        Err(TryLockError::WouldBlock) => {
+            let pid = std::fs::read_to_string(&path).unwrap_or_default();
+            bail!(
+                "Another `eurus {name}` is already running (pid {}). Pass --force-unlock if it \
+                 isn't.",
+                match pid.trim() {
+                    "" => "unknown",
+                    pid => pid,
+                }
+            );
+        }
+        Err(TryLockError::Error(e)) => {
+            return Err(e).with_context(|| format!("Could not lock {}.", path.display()))
+        }
+    }
+
+    file.set_len(0)?;
+    write!(file, "{}", std::process::id())?;
+
+    Ok(Lock(file))
+}
@@ -0,0 +1,97 @@
+use std::{cell::RefCell, future::Future};
+
+use reqwest::{RequestBuilder, Response, Url};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{filter::Targets, layer::SubscriberExt, util::SubscriberInitExt};
+
+/// Query parameters whose values are never logged.
+static SECRET_PARAMS: &[&str] = &["key", "token", "secret", "password", "signature", "auth"];
+
+tokio::task_local! {
+    static LAST_RAY: RefCell<Option<String>>;
+}
+
+/// Logs eurus' own events to stderr, `info` and up for `-v` and everything
+/// for `-vv`. Without either nothing is logged.
+pub fn init(verbosity: u8) {
+    let level = match verbosity {
+        0 => return,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_target(false),
+        )
+        .with(Targets::new().with_target("eurus", level))
+        .init();
+}
+
+/// Sends the request once, logging its method, url and status.
+pub async fn send(request: RequestBuilder) -> reqwest::Result<Response> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    let method = request.method().clone();
+    let url = redact(request.url());
+
+    let result = client.execute(request).await;
+    match &result {
+        Ok(response) => {
+            let ray = ray(response);
+            tracing::info!(%method, url, status = response.status().as_u16(), ray, "http");
+            let _ = LAST_RAY.try_with(|last| *last.borrow_mut() = ray);
+        }
+        Err(e) => tracing::info!(%method, url, error = %e, "http"),
+    }
+
+    result
+}
+
+/// The `cf-ray` id Cloudflare gives every response, which their support uses
+/// to find the request.
+pub fn ray(response: &Response) -> Option<String> {
+    response
+        .headers()
+        .get("cf-ray")
+        .and_then(|r| r.to_str().ok())
+        .map(String::from)
+}
+
+/// Runs `f`, also returning the `cf-ray` of the last response it got.
+pub async fn with_ray<F: Future>(f: F) -> (F::Output, Option<String>) {
+    LAST_RAY
+        .scope(RefCell::new(None), async {
+            let output = f.await;
+            (output, LAST_RAY.with(|last| last.borrow().clone()))
+        })
+        .await
+}
+
+/// The url with any password and secret looking query parameters replaced.
+pub fn redact(url: &Url) -> String {
+    let mut url = url.clone();
+    if url.password().is_some() {
+        let _ = url.set_password(Some("REDACTED"));
+    }
+
+    let secret = |name: &str| {
+        let name = name.to_lowercase();
+        SECRET_PARAMS.iter().any(|s| name.contains(s))
+    };
+    if url.query_pairs().any(|(k, _)| secret(&k)) {
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(k, v)| match secret(&k) {
+                true => (k.to_string(), "REDACTED".to_string()),
+                false => (k.to_string(), v.to_string()),
+            })
+            .collect();
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+
+    url.to_string()
+}
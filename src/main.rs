@@ -1,375 +1,8341 @@
-#![allow(dead_code)]
-
 use std::{
-    fmt::Display,
+    collections::BTreeMap,
     path::{Path, PathBuf},
-    sync::LazyLock,
+    sync::OnceLock,
 };
 
-use clap::{Parser, Subcommand};
-use color_eyre::eyre::{bail, ensure, Context, ContextCompat, Result};
-use directories::ProjectDirs;
-use docker_compose_types::{
-    Compose, ComposeNetwork, Labels, MapOrEmpty, NetworkSettings, Networks, Service,
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::{engine::ArgValueCandidates, CompleteEnv};
+use color_eyre::{
+    eyre::{bail, ensure, Context, ContextCompat, Report, Result},
+    Section,
 };
-use reqwest::blocking::Client;
-use serde::{Deserialize, Serialize};
-
-static BASE_URL: &str = "https://api.cloudflare.com/client/v4";
-static CLIENT: LazyLock<Client> = LazyLock::new(Client::new);
-static PROJECT_DIR: LazyLock<ProjectDirs> =
-    LazyLock::new(|| ProjectDirs::from("gay", "vaskel", "eurus").unwrap());
-static CONFIG_DIR: LazyLock<&Path> = LazyLock::new(|| PROJECT_DIR.config_dir());
-
-#[derive(Debug, Deserialize, Default, Clone)]
-struct CloudflareResponse<T> {
-    errors: Vec<CloudflareError>,
-    result: Option<T>,
-}
-
-#[derive(Debug, Deserialize, Clone)]
-struct CloudflareError {
-    code: i32,
-    message: String,
-}
-
-#[derive(Debug, Deserialize, Clone)]
-struct ZoneDetailsResponse {
-    name: String,
-    id: String,
-}
-
-#[derive(Debug, Deserialize, Serialize, Clone)]
-struct DnsListResponse {
-    name: String,
-    id: String,
-    #[serde(rename = "type")]
-    record_type: String,
-    proxied: bool,
-}
-
-#[derive(Debug, Deserialize, Serialize, Clone)]
-struct DnsCreateUpdate {
-    name: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    id: Option<String>,
-    #[serde(rename = "type")]
-    record_type: String,
-    proxied: bool,
-    content: String,
-}
-
-#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq, Eq)]
-struct ZoneInfo {
-    id: String,
-    name: String,
-}
-
-impl Display for ZoneInfo {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} ({})", self.name, self.id)
-    }
-}
+use docker_compose_types::{Compose, DependsCondition, DependsOnOptions, Ports, Service};
+use futures::{
+    future::{join_all, try_join_all},
+    StreamExt,
+};
+use indexmap::IndexMap;
 
-#[derive(Debug, Deserialize, Serialize, Default)]
-struct Config {
-    zones: Vec<ZoneInfo>,
-    cloudflare_key: String,
-    caddy_network: String,
-}
+use eurus::{
+    audit,
+    caddy::{self, CaddyAdmin},
+    cancel,
+    cloudflare::{self, AuthMethod, Cloudflare},
+    completions,
+    compose::{
+        self, attach_network, detach_network, find_compose_file, find_projects, rename_service,
+    },
+    config::{self, get_config, save_config, zone_for_name, Config, ZoneInfo},
+    credentials, dashboard, ddns, docker, edits, env,
+    error::{exit, EurusError},
+    explain, healthcheck,
+    hooks::Event,
+    idn, k8s,
+    last::LastRun,
+    lint, lock, logging,
+    maintenance::{self, Maintenance},
+    metrics,
+    notes::Notes,
+    presets, probe, prompt,
+    provider::{self, DnsCreateUpdate, DnsProvider, Provider, AUTO_TTL},
+    proxy::{self, ProxyBackend},
+    quota, reconcile, remote, resolve, scan,
+    secrets::{self, SecretStorage},
+    session::Session,
+    switch::{self, Switch, Switches},
+    systemd,
+    templates::{self, Template},
+    trash::{self, Trash},
+    tui, update, watch, zonefile, ASSUME_YES, CONFIG_PATH, DATA_DIR, LABEL_PREFIX, PROFILE,
+    REFRESH,
+};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 #[command(propagate_version = true)]
 struct Cli {
+    #[arg(
+        long,
+        global = true,
+        env = "EURUS_PROFILE",
+        add = ArgValueCandidates::new(completions::profiles),
+        help = "Use the credentials and zones of this profile from the config."
+    )]
+    profile: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        env = "EURUS_CONFIG",
+        help = "Config file to use instead of the default config.toml."
+    )]
+    config: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        help = "Print results as text or as JSON."
+    )]
+    output: OutputFormat,
+    #[arg(
+        long,
+        global = true,
+        env = "CADDY_DOCKER_LABEL_PREFIX",
+        help = "Label prefix caddy-docker-proxy was set up with, instead of caddy."
+    )]
+    label_prefix: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        help = "Fetch zones and records again instead of using the ones cached recently."
+    )]
+    refresh: bool,
+    #[arg(
+        short,
+        long,
+        global = true,
+        help = "Answer confirmations with yes and other prompts with their default."
+    )]
+    yes: bool,
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = clap::ArgAction::Count,
+        help = "Log requests and file writes to stderr, -vv for more detail."
+    )]
+    verbose: u8,
     #[command(subcommand)]
     command: Command,
 }
 
+/// How results are printed to stdout. Prompts and progress always go to
+/// stderr, so JSON output can be piped while still answering them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    /// `web --output compose|caddyfile`, from before it was `--emit`.
+    #[value(hide = true)]
+    Compose,
+    #[value(hide = true)]
+    Caddyfile,
+}
+
+static OUTPUT: OnceLock<OutputFormat> = OnceLock::new();
+
+fn json_output() -> bool {
+    OUTPUT.get() == Some(&OutputFormat::Json)
+}
+
+fn print_json(value: &impl serde::Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
 #[derive(Debug, Subcommand, Clone)]
 enum Command {
     #[command(about = "Change DNS settings via the cloudflare api.")]
-    Dns,
+    #[command(args_conflicts_with_subcommands = true)]
+    Dns {
+        #[command(flatten)]
+        record: RecordArgs,
+        #[command(subcommand)]
+        command: Option<DnsCommand>,
+    },
     #[command(about = "Edit a docker compose file to add caddy proxying.")]
-    Web { path: Option<String> },
+    #[command(args_conflicts_with_subcommands = true)]
+    Web {
+        #[command(flatten)]
+        args: WebArgs,
+        #[command(subcommand)]
+        command: Option<WebCommand>,
+    },
+    #[command(about = "Add caddy proxying to a service, create its DNS record and start it.")]
+    Deploy {
+        #[command(flatten)]
+        args: WebArgs,
+        #[command(flatten)]
+        record: RecordArgs,
+        #[arg(long, help = "Run `docker compose up -d` without asking.")]
+        up: bool,
+    },
+    #[command(about = "Write a compose file that runs caddy-docker-proxy, to start from.")]
+    Init {
+        #[arg(help = "Directory to write compose.yaml to, the current one by default.")]
+        path: Option<PathBuf>,
+        #[arg(long, help = "Network caddy proxies on, asks when not given.")]
+        network: Option<String>,
+        #[arg(long, help = "Replace a compose file that is already there.")]
+        force: bool,
+    },
+    #[command(about = "List the Cloudflare accounts and the zones in each.")]
+    Account {
+        #[command(subcommand)]
+        command: AccountCommand,
+    },
+    #[command(about = "Manage the zones eurus knows about.")]
+    Zone {
+        #[command(subcommand)]
+        command: ZoneCommand,
+    },
+    #[command(about = "List the domains eurus manages, or where compose files and DNS disagree.")]
+    Status {
+        #[arg(
+            help = "Compose file or directory of projects to compare with the DNS records and \
+                    config."
+        )]
+        path: Option<PathBuf>,
+        #[arg(
+            long,
+            conflicts_with = "path",
+            help = "Request every domain and report how it responds."
+        )]
+        probe: bool,
+    },
+    #[command(about = "Check the TLS certificates of origins.")]
+    Cert {
+        #[command(subcommand)]
+        command: CertCommand,
+    },
+    #[command(about = "Purge what Cloudflare has cached for a zone.")]
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    #[command(about = "Write an Ingress or HTTPRoute for a Kubernetes service.")]
+    K8s {
+        #[command(flatten)]
+        args: K8sArgs,
+    },
+    #[command(about = "Make records and compose files match a declarative file.")]
+    Reconcile {
+        file: PathBuf,
+        #[arg(long, help = "Make the changes instead of only showing the plan.")]
+        apply: bool,
+        #[arg(
+            long,
+            help = "Host whose section of the file applies, this machine's hostname by default."
+        )]
+        host: Option<String>,
+    },
+    #[command(about = "Route a hostname to a service through a Cloudflare tunnel.")]
+    Tunnel { path: Option<String> },
+    #[command(about = "Make a raw authenticated call to the Cloudflare api.")]
+    Api {
+        #[arg(help = "HTTP method, e.g. GET.")]
+        method: String,
+        #[arg(help = "Path below /client/v4. `:zone` is replaced with a zone id.")]
+        path: String,
+        #[arg(long, help = "JSON request body.")]
+        data: Option<String>,
+        #[arg(
+            long,
+            add = ArgValueCandidates::new(completions::zones),
+            help = "Zone to substitute for `:zone`, prompts if not given."
+        )]
+        zone: Option<String>,
+    },
+    #[command(about = "Attach local notes to records and services.")]
+    Note {
+        #[command(subcommand)]
+        command: NoteCommand,
+    },
+    #[command(about = "Check the credentials and what they may do in each zone.")]
+    Verify,
+    #[command(about = "Check a domain end to end: its record, public DNS and an HTTPS request.")]
+    Check {
+        #[arg(value_parser = idn::to_ascii)]
+        domain: String,
+    },
+    #[command(about = "Serve a maintenance page in place of a caddy proxied service.")]
+    Maintenance {
+        #[arg(value_parser = idn::to_ascii)]
+        domain: String,
+        #[arg(value_enum)]
+        state: Toggle,
+        #[arg(long, help = "Compose file or directory with the service.")]
+        path: Option<String>,
+        #[arg(
+            long = "for",
+            value_parser = ddns::parse_interval,
+            help = "Turn maintenance off again after this long, e.g. 30m."
+        )]
+        duration: Option<std::time::Duration>,
+        #[arg(
+            long,
+            default_value = "Down for maintenance, back soon.",
+            help = "Text of the maintenance page."
+        )]
+        message: String,
+        #[arg(
+            long,
+            help = "Recreate the service with `docker compose up -d` without asking."
+        )]
+        up: bool,
+    },
+    #[command(about = "Point a domain at another service or address, or back at the last one.")]
+    Switch {
+        #[arg(value_parser = idn::to_ascii)]
+        domain: String,
+        #[arg(help = "Service in the compose file, or the new value of the DNS record.")]
+        target: Option<String>,
+        #[arg(
+            long,
+            conflicts_with = "target",
+            help = "Switch back to the previous target."
+        )]
+        back: bool,
+        #[arg(long, help = "Compose file or directory with the services.")]
+        path: Option<String>,
+        #[arg(
+            long,
+            help = "Recreate the services with `docker compose up -d` without asking."
+        )]
+        up: bool,
+    },
+    #[command(about = "Show how many Cloudflare api calls eurus made recently.")]
+    Quota,
+    #[command(about = "Serve a local web dashboard with the status, records and reconcile plan.")]
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:7878", help = "Address to listen on.")]
+        listen: std::net::SocketAddr,
+        #[arg(
+            long,
+            help = "Reconcile file whose plan the dashboard shows and applies."
+        )]
+        spec: Option<PathBuf>,
+        #[arg(
+            long,
+            env = "EURUS_DASHBOARD_TOKEN",
+            hide_env_values = true,
+            help = "Token the dashboard asks for, random when not given."
+        )]
+        token: Option<String>,
+        #[arg(long, help = "Take over the lock of another dashboard that is stuck.")]
+        force_unlock: bool,
+    },
+    #[command(about = "Browse and edit zones, records and compose services full-screen.")]
+    Tui {
+        #[arg(
+            long,
+            help = "Compose file or directory whose services to show, the current directory's by default."
+        )]
+        path: Option<String>,
+    },
+    #[command(about = "Replace eurus with the latest release from GitHub.")]
+    SelfUpdate {
+        #[arg(long, help = "Only check whether there is a newer release.")]
+        check: bool,
+    },
+    #[command(about = "Print the man page, or write one for every command to a directory.")]
+    Man {
+        #[arg(
+            long,
+            help = "Directory to write eurus.1 and a page per subcommand to."
+        )]
+        dir: Option<PathBuf>,
+    },
+    #[command(about = "Write files that run eurus elsewhere, like systemd units.")]
+    Generate {
+        #[command(subcommand)]
+        command: GenerateCommand,
+    },
+    #[command(about = "Print the script that sets up tab completion for a shell.")]
+    Completions {
+        #[arg(value_parser = clap::builder::PossibleValuesParser::new(completions::shells()))]
+        shell: String,
+    },
+    #[command(about = "Change eurus' own settings.")]
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Debug, Args, Clone, Default)]
+struct RecordArgs {
+    #[arg(long, help = "TTL in seconds for the record, 1 means automatic.")]
+    ttl: Option<u32>,
+    #[arg(long, help = "Don't proxy the record through Cloudflare.")]
+    no_proxy: bool,
+    #[arg(long, help = "Comment to store on the record in Cloudflare.")]
+    comment: Option<String>,
+    #[arg(
+        long = "tag",
+        help = "Tag (`name:value`) to add to the record, can be repeated."
+    )]
+    tags: Vec<String>,
+    #[arg(
+        long = "untag",
+        value_name = "TAG",
+        help = "Tag to remove from the record, by `name` or `name:value`, can be repeated."
+    )]
+    untags: Vec<String>,
+    #[arg(long, help = "Show how the record resolves once it is saved.")]
+    explain: bool,
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "2m",
+        value_parser = ddns::parse_interval,
+        help = "Wait until public resolvers return the new value, for up to this long (2m by \
+                default)."
+    )]
+    wait: Option<std::time::Duration>,
+}
+
+#[derive(Debug, Args, Clone)]
+struct K8sArgs {
+    #[arg(help = "The Kubernetes Service to route to.")]
+    service: Option<String>,
+    #[arg(short, long, default_value = "default")]
+    namespace: String,
+    #[arg(
+        long = "domain",
+        value_parser = idn::to_ascii,
+        help = "Domain to route, can be repeated."
+    )]
+    domains: Vec<String>,
+    #[arg(long, help = "Port number or name of the Service.")]
+    port: Option<String>,
+    #[arg(
+        long,
+        help = "The ingressClassName, the cluster default when not given."
+    )]
+    class: Option<String>,
+    #[arg(
+        long,
+        help = "Write an HTTPRoute for this Gateway, as name or namespace/name, instead of an Ingress."
+    )]
+    gateway: Option<String>,
+    #[arg(long, help = "cert-manager ClusterIssuer to get certificates from.")]
+    issuer: Option<String>,
+    #[arg(
+        short,
+        long,
+        help = "Write the manifest to this file instead of stdout."
+    )]
+    out: Option<PathBuf>,
+    #[arg(long, help = "Point the domains at the ingress controller.")]
+    dns: bool,
+    #[arg(
+        long,
+        help = "Address of the ingress controller, found with kubectl when not given."
+    )]
+    ingress: Option<String>,
+}
+
+#[derive(Debug, Args, Clone)]
+struct WebArgs {
+    path: Option<String>,
+    #[arg(
+        short = 'f',
+        long = "file",
+        help = "Compose file to use, repeat it to merge several like docker compose does."
+    )]
+    files: Vec<PathBuf>,
+    #[arg(long, value_enum, help = "Proxy to configure instead of the default.")]
+    proxy: Option<ProxyBackend>,
+    #[arg(long, help = "Show a QR code for the new URL.")]
+    qr: bool,
+    #[arg(long, help = "Also add the route to caddy through its admin api.")]
+    caddy_api: bool,
+    #[arg(
+        long,
+        help = "Use the port from the service's ports or expose without asking."
+    )]
+    auto_port: bool,
+    #[arg(
+        long,
+        help = "Pick from the running containers, with the ports and networks docker reports."
+    )]
+    live: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        help = "What to produce for the service."
+    )]
+    emit: WebOutput,
+    #[arg(
+        long = "domain",
+        value_delimiter = ',',
+        help = "Domain to route to the service instead of asking, repeat it for more."
+    )]
+    domains: Vec<String>,
+    #[arg(
+        long,
+        help = "TLS for caddy: `internal` for its own CA, or \"cert.pem key.pem\"."
+    )]
+    tls: Option<String>,
+    #[arg(
+        long = "basic-auth",
+        value_name = "USER:HASH",
+        help = "Ask caddy for a password, with a hash from `caddy hash-password`. Repeat it for more users."
+    )]
+    basic_auth: Vec<String>,
+    #[arg(
+        long = "header",
+        value_name = "NAME VALUE",
+        allow_hyphen_values = true,
+        help = "Set a response header with caddy, repeat it for more."
+    )]
+    headers: Vec<String>,
+    #[arg(long, help = "Keep websockets open through caddy reloads.")]
+    websocket: bool,
+    #[arg(
+        long,
+        help = "Sort labels and networks in the files written, for predictable diffs."
+    )]
+    canonical: bool,
+    #[arg(
+        long,
+        add = ArgValueCandidates::new(completions::templates),
+        help = "Fill in the port and caddy directives for a common app, see `web templates`."
+    )]
+    template: Option<String>,
+    #[arg(
+        long,
+        help = "Edit the compose file on this machine over ssh, e.g. user@server."
+    )]
+    host: Option<String>,
+    #[arg(
+        long,
+        help = "Print the edited compose file instead of changing it, e.g. to commit it for review."
+    )]
+    stdout: bool,
+    #[arg(
+        long,
+        conflicts_with = "stdout",
+        help = "Write the edited compose file here instead of changing it."
+    )]
+    out_file: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Keep watching the projects in this directory for services added without a proxy."
+    )]
+    watch: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Write the proxy labels under `deploy.labels`, where Docker Swarm reads them."
+    )]
+    swarm: bool,
+    #[arg(
+        long,
+        value_name = "ADDR",
+        requires = "watch",
+        help = "Serve Prometheus metrics on /metrics and a health check on /health while watching."
+    )]
+    metrics: Option<std::net::SocketAddr>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum WebOutput {
+    /// Edit the compose file.
+    #[default]
+    Compose,
+    /// Print (or append to `caddyfile_path`) a Caddyfile site block.
+    Caddyfile,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Toggle {
+    On,
+    Off,
+}
+
+#[derive(Debug, Subcommand, Clone)]
+enum WebCommand {
+    #[command(about = "Add a reverse proxy route to a running caddy through its admin api.")]
+    Push {
+        #[arg(value_parser = idn::to_ascii)]
+        domain: Option<String>,
+        #[arg(help = "Address to proxy to, e.g. localhost:8080.")]
+        upstream: Option<String>,
+    },
+    #[command(
+        about = "Show the caddy config a container's labels produce, without its compose file."
+    )]
+    Inspect { container: String },
+    #[command(about = "Add a new service to the compose file.")]
+    New {
+        name: Option<String>,
+        #[arg(long, help = "Image for the service.")]
+        image: Option<String>,
+        #[arg(
+            long,
+            add = ArgValueCandidates::new(completions::presets),
+            help = "Preset for the restart policy, limits and logging."
+        )]
+        preset: Option<String>,
+        #[arg(
+            long = "secret",
+            value_name = "VARIABLE",
+            help = "Generate a secret for this variable, in addition to the preset's."
+        )]
+        secrets: Vec<String>,
+        #[arg(long, help = "Where to keep generated secrets, overriding the config.")]
+        secret_storage: Option<SecretStorage>,
+    },
+    #[command(about = "Rename a compose service and update the references to it.")]
+    RenameService { old: String, new: String },
+    #[command(about = "Remove the caddy labels from a service, and optionally its DNS records.")]
+    Remove { service: Option<String> },
+    #[command(about = "Restore the compose file from one of the backups made by edits.")]
+    Undo,
+    #[command(
+        about = "Take over services labelled for caddy before eurus, and check their DNS records."
+    )]
+    Adopt,
+    #[command(about = "List the templates `web --template` can use.")]
+    Templates,
+    #[command(about = "Sort the labels and networks in the project's compose files.")]
+    Format {
+        #[arg(long, help = "Also write labels given as a list as a mapping.")]
+        map_labels: bool,
+    },
+    #[command(about = "Show which compose services a running caddy already proxies.")]
+    ImportLive {
+        #[arg(
+            long,
+            help = "Add caddy labels to the matched services that lack them."
+        )]
+        record: bool,
+    },
 }
 
-fn get_config() -> Result<Config> {
-    std::fs::DirBuilder::new()
-        .recursive(true)
-        .create(*CONFIG_DIR)
-        .context("Failed to create config directory")?;
+#[derive(Debug, Subcommand, Clone)]
+enum DnsCommand {
+    #[command(about = "Show recent traffic for a hostname.")]
+    Analytics {
+        hostname: String,
+        #[arg(long, default_value_t = 24, help = "How many hours back to look.")]
+        hours: u32,
+    },
+    #[command(about = "List the records in a zone.")]
+    List {
+        #[arg(add = ArgValueCandidates::new(completions::zones))]
+        zone: Option<String>,
+    },
+    #[command(about = "Make zones have exactly the records in a yaml file.")]
+    Sync {
+        file: PathBuf,
+        #[arg(long, help = "Also delete the records that aren't in the file.")]
+        prune: bool,
+        #[arg(long, help = "Only show the plan.")]
+        dry_run: bool,
+    },
+    #[command(about = "Write all records of a zone to a file, for backups or as a sync spec.")]
+    Export {
+        #[arg(long, add = ArgValueCandidates::new(completions::zones))]
+        zone: Option<String>,
+        #[arg(long, value_enum, default_value_t)]
+        format: ExportFormat,
+        #[arg(long, help = "File to write to, prints the records when not given.")]
+        out_file: Option<PathBuf>,
+    },
+    #[command(about = "Create the records of a BIND zone file in a zone.")]
+    Import {
+        file: PathBuf,
+        #[arg(long, add = ArgValueCandidates::new(completions::zones))]
+        zone: Option<String>,
+    },
+    #[command(about = "Show how a record resolves, from its settings and live lookups.")]
+    Explain {
+        #[arg(value_parser = idn::to_ascii)]
+        name: String,
+    },
+    #[command(about = "Point A records at this machine's public IP.")]
+    Ddns {
+        #[arg(
+            value_parser = idn::to_ascii,
+            help = "Records to update, defaults to the ones in the config."
+        )]
+        names: Vec<String>,
+        #[arg(
+            long,
+            help = "Url that returns the IP as text, or `opendns`. Repeat it to ask several, \
+                    which have to agree."
+        )]
+        resolver: Vec<String>,
+        #[arg(long, help = "Keep running and re-check the IP on an interval.")]
+        watch: bool,
+        #[arg(
+            long,
+            value_name = "ADDR",
+            requires = "watch",
+            help = "Serve Prometheus metrics on /metrics and a health check on /health, e.g. 127.0.0.1:9150."
+        )]
+        metrics: Option<std::net::SocketAddr>,
+        #[arg(
+            long,
+            default_value = "5m",
+            value_parser = ddns::parse_interval,
+            help = "How often to check when watching, e.g. 30s, 5m or 1h."
+        )]
+        interval: std::time::Duration,
+        #[arg(
+            long,
+            help = "Delegated IPv6 prefix for the AAAA records, e.g. 2001:db8:1234::/56, \
+                    instead of detecting it."
+        )]
+        prefix: Option<ddns::Prefix>,
+        #[arg(long, help = "Take over the lock of another ddns run that is stuck.")]
+        force_unlock: bool,
+    },
+    #[command(about = "Find a domain's records in public DNS and copy them into a zone.")]
+    Discover {
+        #[arg(value_parser = idn::to_ascii)]
+        domain: String,
+        #[arg(long, help = "File with one subdomain per line to check.")]
+        wordlist: Option<PathBuf>,
+    },
+    #[command(about = "Point every record with one value at another, e.g. after moving servers.")]
+    Repoint {
+        #[arg(long, help = "The old IP or hostname.")]
+        from: String,
+        #[arg(long, help = "The new IP or hostname.")]
+        to: String,
+        #[arg(
+            long,
+            add = ArgValueCandidates::new(completions::zones),
+            help = "Only look in these zones, defaults to every configured one."
+        )]
+        zone: Vec<String>,
+    },
+    #[command(about = "Apply the zone's default TTL and proxying to records that differ.")]
+    Normalize {
+        #[arg(add = ArgValueCandidates::new(completions::zones))]
+        zone: Option<String>,
+    },
+    #[command(about = "Delete the records in a zone that match the filters.")]
+    Prune {
+        #[arg(add = ArgValueCandidates::new(completions::zones))]
+        zone: Option<String>,
+        #[arg(long = "type", help = "Only records of this type.")]
+        record_type: Option<String>,
+        #[arg(
+            long,
+            help = "Only records whose name starts with this, e.g. _acme-challenge."
+        )]
+        name_prefix: Option<String>,
+        #[arg(long, help = "Only records whose value contains this.")]
+        content: Option<String>,
+        #[arg(long, default_value_t = 4, help = "How many deletions to run at once.")]
+        concurrency: usize,
+    },
+    #[command(about = "Show or restore the records kept in the trash when they were deleted.")]
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommand,
+    },
+    #[command(about = "Undo one of the recent DNS changes in the audit log.")]
+    Rollback {
+        #[arg(long, default_value_t = 20, help = "How many recent changes to offer.")]
+        limit: usize,
+    },
+    #[command(about = "Add, list or remove Cloudflare redirect rules.")]
+    Redirect {
+        #[command(subcommand)]
+        command: RedirectCommand,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+enum RedirectCommand {
+    #[command(
+        about = "Redirect a host or path to another URL, e.g. `old.example.com/*` to \
+                 `https://new.example.com/$1`."
+    )]
+    Add {
+        #[arg(help = "Host with an optional path, where `*` matches anything.")]
+        from: String,
+        #[arg(help = "URL to redirect to, `$1` being what the first `*` matched.")]
+        to: String,
+        #[arg(
+            long,
+            default_value_t = 301,
+            help = "Status of the redirect: 301, 302, 303, 307 or 308."
+        )]
+        status: u16,
+    },
+    #[command(about = "List the redirect rules of a zone.")]
+    List {
+        #[arg(add = ArgValueCandidates::new(completions::zones))]
+        zone: Option<String>,
+    },
+    #[command(about = "Pick redirect rules of a zone to remove.")]
+    Remove {
+        #[arg(add = ArgValueCandidates::new(completions::zones))]
+        zone: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum ExportFormat {
+    /// The records as the api returns them.
+    Json,
+    /// A spec for `dns sync`.
+    #[default]
+    Yaml,
+    /// A BIND zone file.
+    Bind,
+}
+
+#[derive(Debug, Subcommand, Clone)]
+enum TrashCommand {
+    #[command(about = "List the deleted records in the trash.")]
+    List,
+    #[command(about = "Recreate deleted records and take them out of the trash.")]
+    Restore {
+        #[arg(help = "Ids of the records to restore, asks when none are given.")]
+        ids: Vec<String>,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+enum AccountCommand {
+    #[command(about = "List the accounts the api key can access, with how many zones each has.")]
+    List,
+    #[command(about = "List the zones in an account and whether eurus knows them.")]
+    Zones {
+        #[arg(help = "Account name or id, asks when there are several.")]
+        account: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+enum ZoneCommand {
+    #[command(about = "Add a new domain to Cloudflare.")]
+    Create {
+        #[arg(value_parser = idn::to_ascii)]
+        domain: String,
+    },
+    #[command(about = "List the zones in the config.")]
+    List,
+    #[command(about = "Add a zone to the config by its id or name.")]
+    Add {
+        #[arg(help = "Zone id, or the domain for Cloudflare zones.")]
+        zone: String,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t,
+            help = "Provider the zone is managed by."
+        )]
+        provider: Provider,
+        #[arg(long, help = "Don't proxy new records in this zone by default.")]
+        no_proxy: bool,
+    },
+    #[command(about = "Add zones the Cloudflare token can access to the config.")]
+    Import,
+    #[command(about = "Redirect www to the apex of a zone, or the apex to www.")]
+    Redirect {
+        #[arg(value_parser = idn::to_ascii, add = ArgValueCandidates::new(completions::zones))]
+        zone: Option<String>,
+    },
+    #[command(about = "Show or change a Cloudflare zone's SSL and HTTPS settings.")]
+    Settings {
+        #[arg(value_parser = idn::to_ascii, add = ArgValueCandidates::new(completions::zones))]
+        zone: Option<String>,
+        #[arg(
+            long,
+            value_parser = ["off", "flexible", "full", "strict"],
+            help = "How Cloudflare connects to the origin."
+        )]
+        ssl: Option<String>,
+        #[arg(long, value_parser = ["on", "off"], help = "Redirect every http request to https.")]
+        always_https: Option<String>,
+        #[arg(long, value_parser = ["on", "off"], help = "Rewrite http links in pages to https.")]
+        https_rewrites: Option<String>,
+        #[arg(
+            long,
+            value_parser = ["1.0", "1.1", "1.2", "1.3"],
+            help = "The oldest TLS version visitors may use."
+        )]
+        min_tls: Option<String>,
+    },
+    #[command(about = "Remove a zone from the config.")]
+    Remove {
+        #[arg(add = ArgValueCandidates::new(completions::zones))]
+        name: Option<String>,
+        #[arg(long, help = "Also allow deleting the zone at Cloudflare.")]
+        dangerous: bool,
+    },
+}
 
-    serde_json::from_str(&std::fs::read_to_string(&*CONFIG_DIR.join("config.json"))?)
-        .context("Configuration is malformed.")
+#[derive(Debug, Subcommand, Clone)]
+enum CertCommand {
+    #[command(about = "Warn about certificates that expire soon.")]
+    Check {
+        #[arg(
+            value_parser = idn::to_ascii,
+            help = "Domains to check, defaults to every unproxied record."
+        )]
+        domains: Vec<String>,
+        #[arg(long, help = "Warn below this many days, overriding the config.")]
+        days: Option<u32>,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+enum CacheCommand {
+    #[command(about = "Purge the given URLs, or everything cached for the zone.")]
+    Purge {
+        #[arg(
+            long,
+            value_parser = idn::to_ascii,
+            add = ArgValueCandidates::new(completions::zones),
+            help = "Zone to purge, found from the URLs when not given."
+        )]
+        zone: Option<String>,
+        #[arg(
+            long = "url",
+            help = "URL to purge, can be repeated. Without any the whole zone is purged."
+        )]
+        urls: Vec<String>,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+enum GenerateCommand {
+    #[command(about = "Print systemd units that run `dns ddns` or `web --watch`.")]
+    Systemd {
+        #[command(subcommand)]
+        daemon: SystemdDaemon,
+        #[arg(
+            long,
+            global = true,
+            help = "Write them as user units to ~/.config/systemd/user instead."
+        )]
+        install: bool,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+enum SystemdDaemon {
+    #[command(about = "A timer that runs `dns ddns` on an interval.")]
+    Ddns {
+        #[arg(
+            long,
+            default_value = "5m",
+            value_parser = ddns::parse_interval,
+            help = "How often to check, e.g. 30s, 5m or 1h."
+        )]
+        interval: std::time::Duration,
+    },
+    #[command(about = "A service that runs `web --watch` on a directory.")]
+    Watch {
+        dir: PathBuf,
+        #[arg(
+            last = true,
+            help = "Extra `web` arguments, e.g. -- --domain {service}.example.com."
+        )]
+        args: Vec<String>,
+    },
 }
 
-fn prompt_new_zone_config(api_key: &str) -> Result<Config> {
-    let zone_id = cliclack::input("Zone ID:").interact()?;
+#[derive(Debug, Subcommand, Clone)]
+enum ConfigCommand {
+    #[command(about = "Store the Cloudflare api key, in the system keyring by default.")]
+    SetKey {
+        #[arg(long, help = "Store it in plain text in the config file instead.")]
+        file: bool,
+    },
+    #[command(about = "Print where the config file is.")]
+    Path,
+    #[command(about = "Show the config, with keys hidden.")]
+    Show,
+    #[command(about = "Print a setting, e.g. `caddy_network` or `hooks.pre_dns`, or all of them.")]
+    Get { key: Option<String> },
+    #[command(about = "Change a setting, e.g. `eurus config set caddy_network proxy`.")]
+    Set {
+        key: String,
+        #[arg(help = "The value, as JSON for lists, numbers and booleans.")]
+        value: String,
+    },
+    #[command(about = "Open the config in $EDITOR, checking it before it's saved.")]
+    Edit,
+    #[command(about = "Look for mistakes in the config, like duplicate zones or missing keys.")]
+    Lint {
+        #[arg(long, help = "Fix the problems that can be fixed safely.")]
+        fix: bool,
+    },
+}
 
-    let res: CloudflareResponse<ZoneDetailsResponse> = (*CLIENT)
-        .get(format!("{}/zones/{}", BASE_URL, zone_id))
-        .bearer_auth(api_key)
-        .send()?
-        .json::<CloudflareResponse<ZoneDetailsResponse>>()?;
+#[derive(Debug, Subcommand, Clone)]
+enum NoteCommand {
+    #[command(about = "Set the note for a DNS record.")]
+    Record,
+    #[command(about = "Set the note for a compose service.")]
+    Service { path: Option<String> },
+    #[command(about = "List all notes.")]
+    List,
+}
 
-    if !res.errors.is_empty() {
-        bail!("Cloudflare api returned an error: {:?}", res.errors);
+/// Loads the config, asking for the api key if there isn't one yet.
+fn get_api_key_config() -> Result<Config> {
+    match get_config() {
+        Ok(c) if c.has_cloudflare_key() => Ok(c),
+        c => {
+            let api_key = std::env::var("CF_API_KEY")
+                .or_else(|_| prompt::input("Enter your api key.").interact())?;
+            let config = Config {
+                cloudflare_key: api_key,
+                ..c.unwrap_or_default()
+            };
+            save_config(&config)?;
+            Ok(config)
+        }
     }
+}
 
-    let conf = Config {
-        zones: vec![ZoneInfo {
-            id: zone_id,
-            name: res.result.unwrap().name, // We check for errors earlier.
-        }],
-        cloudflare_key: api_key.to_string(),
-        ..Default::default()
+async fn prompt_new_zone_config(mut config: Config) -> Result<Config> {
+    let cloudflare = Cloudflare::from_config(&config)?;
+
+    // Falls back to asking for the id when the token can't list zones.
+    let zones = match pick_zones(&cloudflare, &[]).await {
+        Ok(zones) if !zones.is_empty() => zones,
+        _ => {
+            let zone_id: String = prompt::input("Zone ID:").interact()?;
+            let zone = cloudflare.get_zone(&zone_id).await?;
+            vec![ZoneInfo {
+                id: zone_id,
+                name: zone.name,
+                provider: Provider::Cloudflare,
+                proxied: true,
+                account: zone.account.map(|a| a.id),
+                ..Default::default()
+            }]
+        }
     };
-    std::fs::write(
-        (*CONFIG_DIR).join("config.json"),
-        serde_json::to_string(&conf)?,
-    )?;
 
-    Ok(conf)
+    config.zones = zones;
+    save_config(&config)?;
+
+    Ok(config)
 }
 
-#[derive(Debug, PartialEq, Clone)]
-struct ServiceWrapper(Service, String);
-impl Eq for ServiceWrapper {}
+/// Lists the zones the token can access and asks which to add, leaving out
+/// the ones already configured.
+async fn pick_zones(cloudflare: &Cloudflare, configured: &[ZoneInfo]) -> Result<Vec<ZoneInfo>> {
+    let spinner = cliclack::spinner();
+    spinner.start("Fetching zones...");
+    let zones = cloudflare.list_zones().await;
+    spinner.stop("Fetched zones.");
 
-fn dns() -> Result<()> {
-    cliclack::intro("eurus-dns")?;
+    let mut zones: Vec<_> = zones?
+        .into_iter()
+        .filter(|z| !configured.iter().any(|c| c.id == z.id))
+        .collect();
+    if zones.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // With several accounts the zones are grouped by account, which is shown
+    // next to each.
+    let account_name =
+        |z: &provider::ZoneDetailsResponse| z.account.as_ref().map(|a| a.name.clone());
+    let several = zones
+        .iter()
+        .map(account_name)
+        .collect::<std::collections::BTreeSet<_>>()
+        .len()
+        > 1;
+    zones.sort_by_key(|z| (account_name(z), z.name.clone()));
+    let items: Vec<_> = zones
+        .iter()
+        .map(|z| {
+            let hint = match (several, account_name(z)) {
+                (true, Some(account)) => account,
+                _ => z.id.clone(),
+            };
+            (z.id.clone(), idn::to_unicode(&z.name), hint)
+        })
+        .collect();
+    let picked = prompt::multiselect("Select the zones to add")
+        .items(&items)
+        .interact()?;
+
+    Ok(zones
+        .into_iter()
+        .filter(|z| picked.contains(&z.id))
+        .map(|z| ZoneInfo {
+            id: z.id,
+            name: z.name,
+            provider: Provider::Cloudflare,
+            proxied: true,
+            account: z.account.map(|a| a.id),
+            ..Default::default()
+        })
+        .collect())
+}
 
-    let config: Config = match get_config() {
+/// Loads the config for dns commands, asking for the api key and a first zone
+/// when they haven't been set up yet.
+async fn get_dns_config() -> Result<Config> {
+    let config = match get_config() {
         Ok(c) => {
             if c.zones.is_empty() {
-                prompt_new_zone_config(&c.cloudflare_key)?
+                prompt_new_zone_config(c).await?
             } else {
                 c
             }
         }
         Err(_) => {
             let api_key = std::env::var("CF_API_KEY")
-                .or_else(|_| cliclack::input("Enter your api key.").interact())?;
-            prompt_new_zone_config(&api_key)?
+                .or_else(|_| prompt::input("Enter your api key.").interact())?;
+            prompt_new_zone_config(Config {
+                cloudflare_key: api_key,
+                ..Default::default()
+            })
+            .await?
         }
     };
 
-    let choices: Vec<_> = config.zones.iter().map(|z| (z, &z.name, "")).collect();
-    let domain = cliclack::select("Select a zone")
-        .items(&choices)
-        .interact()?;
+    Ok(config)
+}
+
+/// Checks all the zones at once for ones the provider no longer knows about.
+async fn stale_zones(config: &Config) -> Vec<bool> {
+    join_all(config.zones.iter().map(|z| async move {
+        let zone = match provider::for_zone(config, z) {
+            Ok(p) => p.get_zone(&z.id).await,
+            Err(e) => Err(e),
+        };
+        zone.is_err_and(|e| e.is_gone())
+    }))
+    .await
+}
+
+/// Asks which configured zone to use, preselecting the one `name` belongs to.
+/// Zones the provider no longer knows about are marked, and picking one offers
+/// to remove it from the config or point it at a new zone id.
+async fn select_zone(config: &mut Config, name: Option<&str>) -> Result<ZoneInfo> {
+    loop {
+        ensure!(!config.zones.is_empty(), "There are no zones configured.");
+
+        let spinner = cliclack::spinner();
+        spinner.start("Checking zones...");
+        let stale = stale_zones(config).await;
+        spinner.stop("Checked zones.");
 
-    let res: CloudflareResponse<Vec<DnsListResponse>> = (*CLIENT)
-        .get(format!("{BASE_URL}/zones/{}/dns_records", &domain.id))
-        .bearer_auth(&config.cloudflare_key)
-        .send()?
-        .json()?;
+        let choices: Vec<_> = config
+            .zones
+            .iter()
+            .zip(&stale)
+            .map(|(z, stale)| {
+                let hint = if *stale {
+                    "stale, no longer accessible"
+                } else {
+                    ""
+                };
+                (z.clone(), idn::to_unicode(&z.name), hint)
+            })
+            .collect();
+        let mut select = prompt::select("Select a zone").items(&choices);
+        let initial = match name {
+            Some(n) => zone_for_name(config, n),
+            None => {
+                let last = LastRun::load(Path::new(".")).zone;
+                config.zones.iter().find(|z| Some(&z.id) == last.as_ref())
+            }
+        };
+        if let Some(zone) = initial {
+            select = select.initial_value(zone.clone());
+        }
+        let zone = select.interact()?;
+
+        let index = config.zones.iter().position(|z| *z == zone).unwrap();
+        if !stale[index] {
+            return Ok(zone);
+        }
 
-    if !res.errors.is_empty() {
-        bail!("Cloudflare api returned an error: {:?}", res.errors);
+        let action = prompt::select(format!("{} can't be accessed anymore", zone.name))
+            .item("remap", "Point it at a new zone id", "")
+            .item("remove", "Remove it from the config", "")
+            .interact()?;
+        match action {
+            "remap" => {
+                let id: String = prompt::input("Zone ID:").interact()?;
+                let details = provider::for_zone(config, &zone)?.get_zone(&id).await?;
+                let remapped = ZoneInfo {
+                    id: details.id,
+                    name: details.name,
+                    account: details.account.map(|a| a.id).or(zone.account.clone()),
+                    ..zone
+                };
+                config.zones[index] = remapped.clone();
+                save_config(config)?;
+                return Ok(remapped);
+            }
+            _ => {
+                config.zones.remove(index);
+                save_config(config)?;
+                cliclack::log::info(format!("Removed {}.", zone.name))?;
+            }
+        }
     }
+}
 
-    let domains = res.result.unwrap().clone();
-    let subdomain: String =
-        cliclack::input("Which subdomain would you like to modify?").interact()?;
+async fn dns(record: RecordArgs, command: Option<DnsCommand>) -> Result<()> {
+    match command {
+        None => {
+            cliclack::intro("eurus-dns")?;
 
-    let record_type = cliclack::input("What record type is this?")
-        .default_input("CNAME")
-        .interact()?;
-    let target = cliclack::input("What is the target?")
-        .default_input(&domain.name)
-        .interact()?;
+            let (explain, wait) = (record.explain, record.wait);
+            let record = update_record(None, &record, None).await?;
+            if let Some(timeout) = wait {
+                wait_for_propagation(&record, timeout).await?;
+            }
 
-    let info = domains
-        .iter()
-        .find(|d| d.name == subdomain.clone())
-        .cloned();
+            if explain {
+                explain_record(&record).await?;
+            } else if json_output() {
+                print_json(&record)?;
+            } else {
+                println!("Done!");
+            }
 
-    let body = DnsCreateUpdate {
-        name: subdomain,
-        id: None,
-        proxied: true,
-        record_type,
-        content: target,
-    };
+            Ok(())
+        }
+        Some(DnsCommand::Explain { name }) => dns_explain(&name).await,
+        Some(DnsCommand::Sync {
+            file,
+            prune,
+            dry_run,
+        }) => dns_sync(&file, prune, dry_run).await,
+        Some(DnsCommand::List { zone }) => dns_list(zone.as_deref()).await,
+        Some(DnsCommand::Import { file, zone }) => dns_import(&file, zone.as_deref()).await,
+        Some(DnsCommand::Export {
+            zone,
+            format,
+            out_file,
+        }) => dns_export(zone.as_deref(), format, out_file.as_deref()).await,
+        Some(DnsCommand::Analytics { hostname, hours }) => dns_analytics(&hostname, hours).await,
+        Some(DnsCommand::Discover { domain, wordlist }) => dns_discover(&domain, wordlist).await,
+        Some(DnsCommand::Normalize { zone }) => dns_normalize(zone.as_deref()).await,
+        Some(DnsCommand::Repoint { from, to, zone }) => dns_repoint(&from, &to, &zone).await,
+        Some(DnsCommand::Prune {
+            zone,
+            record_type,
+            name_prefix,
+            content,
+            concurrency,
+        }) => {
+            let filter = PruneFilter {
+                record_type,
+                name_prefix,
+                content,
+            };
+            dns_prune(zone.as_deref(), &filter, concurrency).await
+        }
+        Some(DnsCommand::Rollback { limit }) => dns_rollback(limit).await,
+        Some(DnsCommand::Redirect {
+            command: RedirectCommand::Add { from, to, status },
+        }) => redirect_add(&from, &to, status).await,
+        Some(DnsCommand::Redirect {
+            command: RedirectCommand::List { zone },
+        }) => redirect_list(zone.as_deref()).await,
+        Some(DnsCommand::Redirect {
+            command: RedirectCommand::Remove { zone },
+        }) => redirect_remove(zone.as_deref()).await,
+        Some(DnsCommand::Trash {
+            command: TrashCommand::List,
+        }) => trash_list(),
+        Some(DnsCommand::Trash {
+            command: TrashCommand::Restore { ids },
+        }) => trash_restore(&ids).await,
+        Some(DnsCommand::Ddns {
+            names,
+            resolver,
+            watch,
+            metrics,
+            interval,
+            prefix,
+            force_unlock,
+        }) => {
+            dns_ddns(
+                names,
+                resolver,
+                watch.then_some((interval, metrics)),
+                prefix,
+                force_unlock,
+            )
+            .await
+        }
+    }
+}
 
-    let res: CloudflareResponse<DnsListResponse> = if info.is_some() {
-        (*CLIENT)
-            .patch(format!(
-                "{BASE_URL}/zones/{}/dns_records/{}",
-                &domain.id,
-                info.unwrap().id
-            ))
-            .json(&body)
-            .bearer_auth(&config.cloudflare_key)
-            .send()?
-            .json()?
-    } else {
-        (*CLIENT)
-            .post(format!("{BASE_URL}/zones/{}/dns_records", &domain.id))
-            .json(&body)
-            .bearer_auth(&config.cloudflare_key)
-            .send()?
-            .json()?
+async fn dns_list(zone: Option<&str>) -> Result<()> {
+    let mut config = get_dns_config().await?;
+    let zone = match zone.and_then(|z| zone_for_name(&config, z)) {
+        Some(zone) => zone.clone(),
+        None => select_zone(&mut config, zone).await?,
     };
 
-    if !res.errors.is_empty() {
-        bail!("Cloudflare api returned an error: {:?}", res.errors);
+    let mut records = provider::for_zone(&config, &zone)?
+        .list_records(&zone.id)
+        .await?;
+    records.sort_by(|a, b| (&a.name, &a.record_type).cmp(&(&b.name, &b.record_type)));
+
+    if json_output() {
+        return print_json(&records);
     }
 
-    println!("Done!");
+    let notes = Notes::load()?;
+    let mut groups: BTreeMap<&str, Vec<&provider::DnsListResponse>> = BTreeMap::new();
+    for record in &records {
+        groups.entry(&record.record_type).or_default().push(record);
+    }
+
+    print_grouped_table(
+        &["TYPE", "NAME", "VALUE", "TTL", "PROXIED", "COMMENT", "NOTE"],
+        groups
+            .into_iter()
+            .map(|(record_type, group)| {
+                let heading = format!("{record_type} ({})", group.len());
+                let rows = group
+                    .into_iter()
+                    .map(|r| record_row(r, notes.record(&zone.id, &r.id)))
+                    .collect();
+                (Some(heading), rows)
+            })
+            .collect(),
+    );
 
     Ok(())
 }
 
-fn add_or_ignore_label(labels: &mut Labels, key: &str, value: &str) {
-    match labels {
-        Labels::List(l) => {
-            let label = format!("{}={}", key, value);
-            if !l.contains(&label) {
-                l.push(label);
-            }
-        }
-        Labels::Map(m) => {
-            if !m.contains_key(key) {
-                m.insert(key.to_string(), value.to_string());
-            }
+/// Adds a redirect rule for `from` to its zone, replacing one that already
+/// matches the same requests.
+async fn redirect_add(from: &str, to: &str, status: u16) -> Result<()> {
+    cliclack::intro("eurus-redirect")?;
+    ensure!(
+        matches!(status, 301 | 302 | 303 | 307 | 308),
+        EurusError::ValidationError(format!(
+            "{status} isn't a redirect status, use 301, 302, 303, 307 or 308."
+        ))
+    );
+    ensure!(
+        !from.contains('"') && !to.contains('"'),
+        EurusError::ValidationError("Redirects can't contain quotes.".to_string())
+    );
+
+    let from = from
+        .trim_start_matches("https://")
+        .trim_start_matches("http://");
+    let (host, path) = from.split_once('/').unwrap_or((from, ""));
+    let host = idn::to_ascii(host)?;
+    let from = match path {
+        "" => host.clone(),
+        path => format!("{host}/{path}"),
+    };
+
+    let config = get_dns_config().await?;
+    let zone = zone_for_name(&config, &host)
+        .cloned()
+        .ok_or_else(|| EurusError::NotFound(format!("{host} isn't in any configured zone.")))?;
+    ensure!(
+        zone.provider == Provider::Cloudflare,
+        "Redirect rules need the zone to be on Cloudflare, {} uses {}.",
+        zone.name,
+        zone.provider
+    );
+
+    let provider = provider::for_zone(&config, &zone)?;
+    let records = provider.list_records(&zone.id).await?;
+    proxy_redirect_source(
+        &config,
+        &zone,
+        provider.as_ref(),
+        &records,
+        &host,
+        ("AAAA", "100::"),
+    )
+    .await?;
+
+    let cloudflare = Cloudflare::from_config(&config)?;
+    let mut rule = cloudflare::Rule::redirect_pattern(&from, to, status);
+    let mut rules = cloudflare.redirect_rules(&zone.id).await?;
+    match rules.iter_mut().find(|r| r.expression == rule.expression) {
+        Some(existing) => {
+            cliclack::log::info(format!(
+                "Replacing \"{}\".",
+                existing
+                    .description
+                    .as_deref()
+                    .unwrap_or(&existing.expression)
+            ))?;
+            rule.id = existing.id.take();
+            *existing = rule;
         }
+        None => rules.push(rule),
     }
+    cloudflare.set_redirect_rules(&zone.id, &rules).await?;
+
+    cliclack::outro(format!("{from} now redirects to {to}."))?;
+
+    Ok(())
 }
 
-fn web(compose_path: Option<String>) -> Result<()> {
-    cliclack::intro("eurus-web")?;
+async fn redirect_zone(name: Option<&str>) -> Result<(Cloudflare, ZoneInfo)> {
+    let mut config = get_dns_config().await?;
+    let zone = select_zone(&mut config, name).await?;
+    ensure!(
+        zone.provider == Provider::Cloudflare,
+        "Redirect rules need the zone to be on Cloudflare, {} uses {}.",
+        zone.name,
+        zone.provider
+    );
 
-    let config = match get_config() {
-        Ok(mut c) => {
-            if c.caddy_network.is_empty() {
-                let network = cliclack::input("Enter the network that caddy is on.").interact()?;
-                c.caddy_network = network;
-            }
-            std::fs::write(
-                (*CONFIG_DIR).join("config.json"),
-                serde_json::to_string(&c)?,
-            )?;
-            c
-        }
-        Err(_) => {
-            let network = cliclack::input("Enter the network that caddy is on.").interact()?;
-            let config = Config {
-                caddy_network: network,
-                ..Default::default()
-            };
-            std::fs::write(
-                (*CONFIG_DIR).join("config.json"),
-                serde_json::to_string(&config)?,
-            )?;
-            config
-        }
-    };
+    Ok((Cloudflare::from_config(&config)?, zone))
+}
 
-    let file = match compose_path {
-        Some(s) => PathBuf::from(s),
-        None => {
-            static COMPOSE_PATHS: [&str; 2] = ["compose.yaml", "docker-compose.yaml"];
+async fn redirect_list(name: Option<&str>) -> Result<()> {
+    let (cloudflare, zone) = redirect_zone(name).await?;
+    let rules = cloudflare.redirect_rules(&zone.id).await?;
+    if json_output() {
+        return print_json(&rules);
+    }
+    if rules.is_empty() {
+        cliclack::log::info(format!("{} has no redirect rules.", zone.name))?;
+        return Ok(());
+    }
 
-            COMPOSE_PATHS
-                .iter()
-                .find(|p| Path::new(p).exists())
-                .map(PathBuf::from)
-                .context("Could not find valid docker-compose file.")?
-        }
-    };
+    print_table(
+        &["MATCHES", "TO", "STATUS"],
+        rules
+            .iter()
+            .map(|r| {
+                vec![
+                    r.expression.clone(),
+                    r.redirect_target().unwrap_or_default(),
+                    r.redirect_status()
+                        .map(|s| s.to_string())
+                        .unwrap_or_default(),
+                ]
+            })
+            .collect(),
+    );
 
-    ensure!(Path::new(&file).exists(), "The file provided should exist.");
+    Ok(())
+}
 
-    let contents = std::fs::read_to_string(&file).context("Could not read the file contents.")?;
-    let mut compose: Compose =
-        serde_yml::from_str(&contents).context("The compose yaml was invalid.")?;
+async fn redirect_remove(name: Option<&str>) -> Result<()> {
+    cliclack::intro("eurus-redirect")?;
 
-    let services: Vec<_> = compose
-        .services
-        .0
+    let (cloudflare, zone) = redirect_zone(name).await?;
+    let mut rules = cloudflare.redirect_rules(&zone.id).await?;
+    ensure!(
+        !rules.is_empty(),
+        EurusError::NotFound(format!("{} has no redirect rules.", zone.name))
+    );
+
+    let items: Vec<_> = rules
         .iter()
-        .filter(|e| e.1.is_some())
-        .map(|(key, value)| (ServiceWrapper(value.clone().unwrap(), key.clone()), key, ""))
+        .enumerate()
+        .map(|(i, r)| {
+            (
+                i,
+                r.description.clone().unwrap_or(r.expression.clone()),
+                r.redirect_target().unwrap_or_default(),
+            )
+        })
         .collect();
-    let selected_service = cliclack::select("Select the service to add caddy to")
-        .items(&services)
+    let remove = prompt::multiselect("Which rules should be removed?")
+        .items(&items)
         .interact()?;
 
-    let domain: String = cliclack::input("Enter the domain for this service.").interact()?;
-    let port: u16 = loop {
-        let text: String = cliclack::input("Enter the port this application exposes").interact()?;
+    let mut index = 0;
+    rules.retain(|_| {
+        index += 1;
+        !remove.contains(&(index - 1))
+    });
+    cloudflare.set_redirect_rules(&zone.id, &rules).await?;
+
+    cliclack::outro(format!(
+        "Removed {} redirect rules from {}.",
+        remove.len(),
+        zone.name
+    ))?;
+
+    Ok(())
+}
+
+fn trash_list() -> Result<()> {
+    let trash = Trash::load()?;
+    if json_output() {
+        return print_json(&trash.entries);
+    }
+    if trash.entries.is_empty() {
+        cliclack::log::info("The trash is empty.")?;
+        if !get_config().is_ok_and(|c| c.trash) {
+            cliclack::log::remark("Set `trash = true` in the config to keep deleted records.")?;
+        }
+        return Ok(());
+    }
+
+    print_table(
+        &["DELETED", "ZONE", "TYPE", "NAME", "VALUE", "ID"],
+        trash
+            .entries
+            .iter()
+            .map(|e| {
+                vec![
+                    chrono::DateTime::parse_from_rfc3339(&e.deleted_at)
+                        .map(|t| {
+                            t.with_timezone(&chrono::Local)
+                                .format("%Y-%m-%d %H:%M")
+                                .to_string()
+                        })
+                        .unwrap_or(e.deleted_at.clone()),
+                    e.zone.name.clone(),
+                    e.record.record_type.clone(),
+                    idn::to_unicode(&e.record.name),
+                    e.record.value(),
+                    e.record.id.clone(),
+                ]
+            })
+            .collect(),
+    );
+
+    Ok(())
+}
+
+/// Recreates records from the trash with their old settings. They get new ids
+/// at the provider.
+async fn trash_restore(ids: &[String]) -> Result<()> {
+    let config = get_dns_config().await?;
+    let mut trash = Trash::load()?;
+    ensure!(!trash.entries.is_empty(), "The trash is empty.");
 
-        match text.parse() {
-            Ok(n) => break n,
-            Err(_) => (),
+    let ids = match ids.is_empty() {
+        true => {
+            let choices: Vec<_> = trash
+                .entries
+                .iter()
+                .map(|e| {
+                    (
+                        e.record.id.clone(),
+                        format!(
+                            "{} {}",
+                            e.record.record_type,
+                            idn::to_unicode(&e.record.name)
+                        ),
+                        format!("{}, deleted {}", e.record.value(), e.deleted_at),
+                    )
+                })
+                .collect();
+            prompt::multiselect("Select the records to restore")
+                .items(&choices)
+                .interact()?
+        }
+        false => {
+            for id in ids {
+                ensure!(
+                    trash.entries.iter().any(|e| e.record.id == *id),
+                    EurusError::NotFound(format!(
+                        "There is no record with the id {id} in the trash."
+                    ))
+                );
+            }
+            ids.to_vec()
         }
     };
 
-    let mut service = selected_service.0.clone();
+    let mut by_zone: BTreeMap<String, (ZoneInfo, Vec<trash::Entry>)> = BTreeMap::new();
+    for entry in trash.entries.iter().filter(|e| ids.contains(&e.record.id)) {
+        // The zone in the config has the current settings, if it's still there.
+        let zone = config
+            .zones
+            .iter()
+            .find(|z| z.id == entry.zone.id)
+            .unwrap_or(&entry.zone);
+        by_zone
+            .entry(zone.id.clone())
+            .or_insert((zone.clone(), vec![]))
+            .1
+            .push(entry.clone());
+    }
+
+    for (zone, entries) in by_zone.into_values() {
+        let records: Vec<_> = entries
+            .iter()
+            .map(|e| DnsCreateUpdate {
+                name: e.record.name.clone(),
+                id: None,
+                record_type: e.record.record_type.clone(),
+                proxied: e.record.proxied,
+                content: e.record.content.clone(),
+                ttl: e.record.ttl,
+                priority: e.record.priority,
+                data: e.record.data.clone().map(serde_json::Value::Object),
+                comment: e.record.comment.clone(),
+                tags: Some(e.record.tags.clone()),
+            })
+            .collect();
+        let payloads: Vec<_> = records
+            .iter()
+            .map(|r| serde_json::json!({ "zone": zone, "record": r }))
+            .collect();
+        for payload in &payloads {
+            config.hooks.run(Event::PreDns, payload)?;
+        }
+        provider::for_zone(&config, &zone)?
+            .batch(&zone.id, &records, &[])
+            .await?;
+        for payload in &payloads {
+            config.hooks.run(Event::PostDns, payload)?;
+        }
+
+        // Saved after every zone, so a failure later on doesn't leave records
+        // both restored and in the trash.
+        trash
+            .entries
+            .retain(|e| !entries.iter().any(|r| r.record.id == e.record.id));
+        trash.save()?;
+        cliclack::log::success(format!(
+            "Restored {} records in {}.",
+            entries.len(),
+            zone.name
+        ))?;
+    }
+
+    Ok(())
+}
 
-    add_or_ignore_label(&mut service.labels, "caddy", &domain);
-    add_or_ignore_label(
-        &mut service.labels,
-        "caddy.reverse_proxy",
-        &format!("{{{{ upstreams {} }}}}", port),
+/// Offers the recent DNS changes from the audit log and undoes the picked
+/// one: an update gets its old value back, a deleted record is recreated and
+/// a created one deleted.
+async fn dns_rollback(limit: usize) -> Result<()> {
+    cliclack::intro("eurus-rollback")?;
+
+    let config = get_dns_config().await?;
+    let changes: Vec<_> = audit::read()?
+        .into_iter()
+        .rev()
+        .filter(|e| e.profile.as_ref() == PROFILE.get())
+        .filter(|e| {
+            matches!(
+                e.change,
+                audit::Change::DnsCreate { .. }
+                    | audit::Change::DnsUpdate { .. }
+                    | audit::Change::DnsDelete { .. }
+            )
+        })
+        .take(limit)
+        .collect();
+    ensure!(
+        !changes.is_empty(),
+        EurusError::NotFound("The audit log has no DNS changes to roll back.".to_string())
     );
 
-    // get or make the network settings for the traefik network
-    let mut network = compose
-        .networks
-        .0
-        .get(&config.caddy_network)
-        .map(|n| match n {
-            MapOrEmpty::Empty => NetworkSettings {
-                ..Default::default()
-            },
-            MapOrEmpty::Map(m) => m.clone(),
+    let describe = |value: &serde_json::Value| {
+        audit::logged_record(value).map_or("an unknown record".to_string(), |r| {
+            format!(
+                "{} {} {}",
+                r.record_type,
+                idn::to_unicode(&r.name),
+                r.value()
+            )
         })
-        .unwrap_or(NetworkSettings {
-            ..Default::default()
-        }); // Should never be None
+    };
+    let items: Vec<_> = changes
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            let (label, hint) = match &e.change {
+                audit::Change::DnsCreate { new, .. } => (format!("Created {}", describe(new)), ""),
+                audit::Change::DnsUpdate { new, old, .. } => (
+                    format!("Updated {}", describe(new)),
+                    if old.is_some() {
+                        ""
+                    } else {
+                        "old value unknown"
+                    },
+                ),
+                audit::Change::DnsDelete { old, .. } => (
+                    format!(
+                        "Deleted {}",
+                        old.as_ref()
+                            .map_or("an unknown record".to_string(), describe)
+                    ),
+                    "",
+                ),
+                _ => unreachable!(),
+            };
+            let time = chrono::DateTime::parse_from_rfc3339(&e.time)
+                .map(|t| {
+                    t.with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string()
+                })
+                .unwrap_or(e.time.clone());
+            (i, format!("{time}  {label}"), hint)
+        })
+        .collect();
+    let picked = prompt::select("Which change should be undone?")
+        .items(&items)
+        .initial_value(0)
+        .interact()?;
+
+    let (zone, old, new) = match &changes[picked].change {
+        audit::Change::DnsCreate { zone, new, .. } => (zone, None, Some(new)),
+        audit::Change::DnsUpdate { zone, old, new, .. } => (zone, old.as_ref(), Some(new)),
+        audit::Change::DnsDelete { zone, old, .. } => (zone, old.as_ref(), None),
+        _ => unreachable!(),
+    };
+    let zone = zone_for_name(&config, zone)
+        .filter(|z| z.name == *zone)
+        .cloned()
+        .ok_or_else(|| EurusError::NotFound(format!("{zone} is no longer a configured zone.")))?;
+    let provider = provider::for_zone(&config, &zone)?;
+    let records = provider.list_records(&zone.id).await?;
 
-    network.external = Some(ComposeNetwork::Bool(true));
+    let old = old.and_then(audit::logged_record);
+    let new = new.and_then(audit::logged_record);
+    // The record as it is now, by its id or else by what was written.
+    let current = new.as_ref().and_then(|new| {
+        records.iter().find(|r| {
+            r.id == new.id
+                || (new.id.is_empty()
+                    && r.name == new.name
+                    && r.record_type == new.record_type
+                    && r.content == new.content)
+        })
+    });
 
-    compose
-        .networks
-        .0
-        .insert(config.caddy_network.clone(), MapOrEmpty::Map(network));
+    let created = matches!(changes[picked].change, audit::Change::DnsCreate { .. });
+    match (created, old, current) {
+        (false, Some(old), current) => {
+            let mut body = old.to_update();
+            body.id = current.map(|c| c.id.clone());
+            if let Some(current) =
+                current.filter(|c| Some(c.value()) != new.as_ref().map(|n| n.value()))
+            {
+                cliclack::log::warning(format!(
+                    "{} changed again since, to {}.",
+                    idn::to_unicode(&current.name),
+                    current.value()
+                ))?;
+            }
+            let question = match &body.id {
+                Some(_) => format!(
+                    "Change {} {} back to {}?",
+                    old.record_type,
+                    idn::to_unicode(&old.name),
+                    old.value()
+                ),
+                None => format!(
+                    "Recreate {} {} {}?",
+                    old.record_type,
+                    idn::to_unicode(&old.name),
+                    old.value()
+                ),
+            };
+            if !prompt::confirm(question).interact()? {
+                return Err(EurusError::Cancelled.into());
+            }
+
+            let payload = serde_json::json!({ "zone": zone, "record": body });
+            config.hooks.run(Event::PreDns, &payload)?;
+            provider.upsert_record(&zone.id, &body).await?;
+            config.hooks.run(Event::PostDns, &payload)?;
+        }
+        (true, _, Some(current)) => {
+            if !prompt::confirm(format!(
+                "Delete {} {} {}?",
+                current.record_type,
+                idn::to_unicode(&current.name),
+                current.value()
+            ))
+            .initial_value(false)
+            .interact()?
+            {
+                return Err(EurusError::Cancelled.into());
+            }
 
-    match &mut service.networks {
-        Networks::Simple(a) => {
-            if !a.contains(&config.caddy_network) {
-                a.push(config.caddy_network);
+            let payload = serde_json::json!({ "zone": zone, "deleted": [current] });
+            config.hooks.run(Event::PreDns, &payload)?;
+            if config.trash {
+                trash::keep(&zone, std::slice::from_ref(current))?;
             }
+            provider.delete_record(&zone.id, &current.id).await?;
+            config.hooks.run(Event::PostDns, &payload)?;
         }
-        Networks::Advanced(a) => {
-            a.0.insert(config.caddy_network, MapOrEmpty::Empty);
+        (true, _, None) => {
+            bail!("The created record isn't in {} anymore.", zone.name)
         }
+        (false, None, _) => bail!(
+            "The audit log doesn't have the record from before this change, so it can't be undone."
+        ),
     }
 
-    compose
-        .services
-        .0
-        .insert(selected_service.1.clone(), Some(service));
+    cliclack::outro("Rolled back.")?;
 
-    std::fs::copy(&file, format!("{}.bak", file.display()))?;
-    std::fs::write(&file, serde_yml::to_string(&compose)?)?;
+    Ok(())
+}
 
-    cliclack::outro("Done!")?;
+async fn dns_export(
+    zone: Option<&str>,
+    format: ExportFormat,
+    out_file: Option<&Path>,
+) -> Result<()> {
+    let mut config = get_dns_config().await?;
+    let zone = match zone.and_then(|z| zone_for_name(&config, z)) {
+        Some(zone) => zone.clone(),
+        None => select_zone(&mut config, zone).await?,
+    };
+
+    let records = provider::for_zone(&config, &zone)?
+        .list_records(&zone.id)
+        .await?;
+    let contents = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&records)? + "\n",
+        ExportFormat::Yaml => {
+            serde_yml::to_string(&reconcile::ZonesSpec::from_records(&zone, &records))?
+        }
+        ExportFormat::Bind => zonefile::render(&zone.name, &records),
+    };
+
+    match out_file {
+        Some(path) => {
+            std::fs::write(path, contents)
+                .with_context(|| format!("Could not write {}.", path.display()))?;
+            cliclack::log::success(format!(
+                "Exported {} records of {} to {}.",
+                records.len(),
+                zone.name,
+                path.display()
+            ))?;
+        }
+        None => print!("{contents}"),
+    }
 
     Ok(())
 }
 
-fn main() -> Result<()> {
-    color_eyre::install()?;
+/// Whether the records can't both exist: the same type, or either is a CNAME.
+fn records_conflict(a: &str, b: &str) -> bool {
+    a == b || a == "CNAME" || b == "CNAME"
+}
+
+async fn dns_import(file: &Path, zone: Option<&str>) -> Result<()> {
+    let text = std::fs::read_to_string(file)
+        .with_context(|| format!("Could not read {}.", file.display()))?;
+
+    cliclack::intro("eurus-import")?;
+
+    let mut config = get_dns_config().await?;
+    let zone = match zone.and_then(|z| zone_for_name(&config, z)) {
+        Some(zone) => zone.clone(),
+        None => select_zone(&mut config, zone).await?,
+    };
+    let provider = provider::for_zone(&config, &zone)?;
 
-    let args = Cli::parse();
+    let (mut records, outside): (Vec<_>, Vec<_>) = zonefile::parse(&text, &zone.name)?
+        .into_iter()
+        .partition(|r| r.name == zone.name || r.name.ends_with(&format!(".{}", zone.name)));
+    for record in &outside {
+        cliclack::log::warning(format!(
+            "Skipping {} {}, it isn't in {}.",
+            record.record_type, record.name, zone.name
+        ))?;
+    }
+    if zone.provider != Provider::Cloudflare {
+        records.iter_mut().for_each(|r| r.proxied = false);
+    }
+
+    let existing = provider.list_records(&zone.id).await?;
+    // Records that are already there as they are in the file are left alone.
+    records.retain(|r| {
+        !existing
+            .iter()
+            .any(|e| e.name == r.name && e.record_type == r.record_type && e.content == r.content)
+    });
+    if records.is_empty() {
+        cliclack::outro(format!(
+            "{} already has every record in the file.",
+            zone.name
+        ))?;
+        return Ok(());
+    }
+
+    let mut names: Vec<_> = records.iter().map(|r| r.name.clone()).collect();
+    names.sort();
+    names.dedup();
+    let mut deletes = vec![];
+    for name in names {
+        let conflicting: Vec<_> = existing
+            .iter()
+            .filter(|e| {
+                e.name == name
+                    && records
+                        .iter()
+                        .any(|r| r.name == name && records_conflict(&r.record_type, &e.record_type))
+            })
+            .collect();
+        if conflicting.is_empty() {
+            continue;
+        }
+
+        let cname = conflicting.iter().any(|e| e.record_type == "CNAME")
+            || records
+                .iter()
+                .any(|r| r.name == name && r.record_type == "CNAME");
+        let describe = |record_type: &str, value: String| format!("{record_type} {value}");
+        cliclack::note(
+            format!("{} already exists", idn::to_unicode(&name)),
+            format!(
+                "Current:\n{}\n\nIn the file:\n{}",
+                conflicting
+                    .iter()
+                    .map(|e| describe(&e.record_type, e.value()))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                records
+                    .iter()
+                    .filter(|r| r.name == name)
+                    .map(|r| describe(&r.record_type, r.content.clone()))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        )?;
+        let mut choice =
+            prompt::select(format!("What should happen to {}?", idn::to_unicode(&name)))
+                .item("keep", "Keep the current records", "skip the file's")
+                .item("replace", "Replace them", "with the file's");
+        // A CNAME can't share its name with anything else.
+        if !cname {
+            choice = choice.item("both", "Keep both", "add the file's next to them");
+        }
+        match choice.interact()? {
+            "keep" => records.retain(|r| {
+                r.name != name
+                    || !conflicting
+                        .iter()
+                        .any(|e| records_conflict(&r.record_type, &e.record_type))
+            }),
+            "replace" => deletes.extend(conflicting.into_iter().cloned()),
+            _ => {}
+        }
+    }
+    if records.is_empty() {
+        cliclack::outro("Nothing to import.")?;
+        return Ok(());
+    }
 
-    match args.command {
-        Command::Dns => dns(),
-        Command::Web { path } => web(path),
+    if !json_output() {
+        print_table(
+            &["TYPE", "NAME", "VALUE", "TTL", "PROXIED"],
+            records
+                .iter()
+                .map(|r| {
+                    vec![
+                        r.record_type.clone(),
+                        idn::to_unicode(&r.name),
+                        match r.priority {
+                            Some(priority) => format!("{priority} {}", r.content),
+                            None => r.content.clone(),
+                        },
+                        provider::display_ttl(r.ttl),
+                        r.proxied.to_string(),
+                    ]
+                })
+                .collect(),
+        );
+    }
+    let question = match deletes.len() {
+        0 => format!("Create these {} records?", records.len()),
+        n => format!(
+            "Create these {} records, deleting {n} current ones?",
+            records.len()
+        ),
+    };
+    if !prompt::confirm(question).interact()? {
+        return Err(EurusError::Cancelled.into());
     }
+
+    for record in records.iter_mut() {
+        record.comment = config.record_comment(None, None);
+    }
+    let mut payloads: Vec<_> = records
+        .iter()
+        .map(|r| serde_json::json!({ "zone": zone, "record": r }))
+        .collect();
+    if !deletes.is_empty() {
+        payloads.push(serde_json::json!({ "zone": zone, "deleted": deletes }));
+    }
+    for payload in &payloads {
+        config.hooks.run(Event::PreDns, payload)?;
+    }
+    if config.trash && !deletes.is_empty() {
+        trash::keep(&zone, &deletes)?;
+    }
+    let ids: Vec<_> = deletes.iter().map(|r| r.id.clone()).collect();
+    provider.batch(&zone.id, &records, &ids).await?;
+    for payload in &payloads {
+        config.hooks.run(Event::PostDns, payload)?;
+    }
+
+    if json_output() {
+        print_json(&records)?;
+    }
+    cliclack::outro(format!(
+        "Imported {} records into {}.",
+        records.len(),
+        zone.name
+    ))?;
+
+    Ok(())
+}
+
+/// A record as a row of the `dns list` table, with proxying shown as an
+/// orange cloud and DNS only as a grey one, like the Cloudflare dashboard.
+fn record_row(r: &provider::DnsListResponse, note: Option<&String>) -> Vec<String> {
+    vec![
+        r.record_type.clone(),
+        idn::to_unicode(&r.name),
+        r.value(),
+        provider::display_ttl(r.ttl),
+        match r.proxied {
+            true => console::style("☁ proxied").color256(208).to_string(),
+            false => console::style("☁ dns only").dim().to_string(),
+        },
+        match (&r.comment, r.tags.is_empty()) {
+            (comment, true) => comment.clone().unwrap_or_default(),
+            (comment, false) => format!(
+                "{} [{}]",
+                comment.as_deref().unwrap_or_default(),
+                r.tags.join(", ")
+            )
+            .trim_start()
+            .to_string(),
+        },
+        note.cloned().unwrap_or_default(),
+    ]
+}
+
+/// Brings the TTL and proxying of every record in a zone in line with its
+/// defaults. Proxied records always get an automatic TTL, and records that
+/// can't be proxied only have their TTL changed.
+async fn dns_normalize(zone: Option<&str>) -> Result<()> {
+    cliclack::intro("eurus-normalize")?;
+
+    let mut config = get_dns_config().await?;
+    let zone = match zone.and_then(|z| zone_for_name(&config, z)) {
+        Some(zone) => zone.clone(),
+        None => select_zone(&mut config, zone).await?,
+    };
+    let provider = provider::for_zone(&config, &zone)?;
+
+    let mut records = provider.list_records(&zone.id).await?;
+    records.sort_by(|a, b| (&a.name, &a.record_type).cmp(&(&b.name, &b.record_type)));
+
+    let changes: Vec<_> = records
+        .iter()
+        .filter_map(|r| {
+            // Underscore names like _dmarc are never served over HTTP.
+            let proxiable = zone.provider == Provider::Cloudflare
+                && matches!(r.record_type.as_str(), "A" | "AAAA" | "CNAME")
+                && !r.name.split('.').any(|l| l.starts_with('_'));
+            let proxied = proxiable && zone.proxied;
+            let ttl = if proxied {
+                AUTO_TTL
+            } else {
+                zone.default_ttl()
+            };
+
+            (r.proxied != proxied || r.ttl != ttl).then(|| {
+                let mut update = r.to_update();
+                update.proxied = proxied;
+                update.ttl = ttl;
+                (r, update)
+            })
+        })
+        .collect();
+    if changes.is_empty() {
+        cliclack::outro(format!(
+            "Every record in {} follows its defaults.",
+            zone.name
+        ))?;
+        return Ok(());
+    }
+
+    if !json_output() {
+        let change = |from: String, to: String| match from == to {
+            true => from,
+            false => format!("{from} -> {to}"),
+        };
+        let yes_no = |b: bool| if b { "yes" } else { "no" }.to_string();
+        print_table(
+            &["TYPE", "NAME", "TTL", "PROXIED"],
+            changes
+                .iter()
+                .map(|(current, update)| {
+                    vec![
+                        current.record_type.clone(),
+                        idn::to_unicode(&current.name),
+                        change(
+                            provider::display_ttl(current.ttl),
+                            provider::display_ttl(update.ttl),
+                        ),
+                        change(yes_no(current.proxied), yes_no(update.proxied)),
+                    ]
+                })
+                .collect(),
+        );
+    }
+    if !prompt::confirm(format!("Update these {} records?", changes.len()))
+        .initial_value(false)
+        .interact()?
+    {
+        return Err(EurusError::Cancelled.into());
+    }
+
+    let updates: Vec<_> = changes.into_iter().map(|(_, update)| update).collect();
+    let payload = serde_json::json!({ "zone": zone, "records": updates });
+    config.hooks.run(Event::PreDns, &payload)?;
+    provider.batch(&zone.id, &updates, &[]).await?;
+    config.hooks.run(Event::PostDns, &payload)?;
+
+    if json_output() {
+        print_json(&updates)?;
+    }
+    cliclack::outro(format!(
+        "Updated {} records in {}.",
+        updates.len(),
+        zone.name
+    ))?;
+
+    Ok(())
+}
+
+/// Updates the records in the zones whose value is `from` to `to`. Hostnames
+/// match regardless of case and a trailing dot.
+async fn dns_repoint(from: &str, to: &str, zones: &[String]) -> Result<()> {
+    let normalize = |value: &str| value.trim_end_matches('.').to_lowercase();
+    let (from, to) = (normalize(from), to.trim_end_matches('.').to_string());
+    ensure!(from != normalize(&to), "--from and --to are the same.");
+
+    cliclack::intro("eurus-repoint")?;
+
+    let config = get_dns_config().await?;
+    let zones: Vec<_> = match zones.is_empty() {
+        true => config.zones.iter().collect(),
+        false => zones
+            .iter()
+            .map(|z| {
+                zone_for_name(&config, z)
+                    .ok_or_else(|| EurusError::NotFound(format!("{z} is not a configured zone.")))
+            })
+            .collect::<Result<_, _>>()?,
+    };
+    ensure!(!zones.is_empty(), "There are no zones in the config.");
+
+    let spinner = cliclack::spinner();
+    spinner.start("Looking through the zones...");
+    let listings = try_join_all(zones.iter().map(|zone| async {
+        let provider = provider::for_zone(&config, zone)?;
+        let records = provider.list_records(&zone.id).await?;
+        Ok::<_, color_eyre::Report>((*zone, provider, records))
+    }))
+    .await;
+    spinner.stop("Looked through the zones.");
+    let listings = listings?;
+
+    let mut changes = vec![];
+    for (zone, provider, records) in listings {
+        let mut updates = vec![];
+        for record in records.iter().filter(|r| normalize(&r.content) == from) {
+            // The new value has to fit the record, an A record can't point at
+            // a hostname or an IPv6 address.
+            let fits = match record.record_type.as_str() {
+                "A" => to.parse::<std::net::Ipv4Addr>().is_ok(),
+                "AAAA" => to.parse::<std::net::Ipv6Addr>().is_ok(),
+                _ => to.parse::<std::net::IpAddr>().is_err(),
+            };
+            ensure!(
+                fits,
+                "{} is a {} record, which can't point at {to}.",
+                record.name,
+                record.record_type
+            );
+
+            let mut update = record.to_update();
+            update.content = to.clone();
+            update.comment = config.record_comment(None, None).or(update.comment);
+            updates.push((record.clone(), update));
+        }
+        if !updates.is_empty() {
+            changes.push((zone, provider, updates));
+        }
+    }
+    if changes.is_empty() {
+        cliclack::outro(format!("No records point at {from}."))?;
+        return Ok(());
+    }
+
+    let count: usize = changes.iter().map(|(_, _, u)| u.len()).sum();
+    if !json_output() {
+        print_table(
+            &["ZONE", "TYPE", "NAME", "VALUE"],
+            changes
+                .iter()
+                .flat_map(|(zone, _, updates)| {
+                    updates.iter().map(|(current, update)| {
+                        vec![
+                            zone.name.clone(),
+                            current.record_type.clone(),
+                            idn::to_unicode(&current.name),
+                            format!("{} -> {}", current.content, update.content),
+                        ]
+                    })
+                })
+                .collect(),
+        );
+    }
+    if !prompt::confirm(format!("Update these {count} records?"))
+        .initial_value(false)
+        .interact()?
+    {
+        return Err(EurusError::Cancelled.into());
+    }
+
+    let mut updated = vec![];
+    for (zone, provider, updates) in changes {
+        let updates: Vec<_> = updates.into_iter().map(|(_, update)| update).collect();
+        let payload = serde_json::json!({ "zone": zone, "records": updates });
+        config.hooks.run(Event::PreDns, &payload)?;
+        provider.batch(&zone.id, &updates, &[]).await?;
+        config.hooks.run(Event::PostDns, &payload)?;
+        updated.extend(updates);
+    }
+
+    if json_output() {
+        print_json(&updated)?;
+    }
+    cliclack::outro(format!("Pointed {count} records at {to}."))?;
+
+    Ok(())
+}
+
+/// Which records `dns prune` deletes. Every filter that is set has to match.
+struct PruneFilter {
+    record_type: Option<String>,
+    name_prefix: Option<String>,
+    content: Option<String>,
+}
+
+impl PruneFilter {
+    fn is_empty(&self) -> bool {
+        self.record_type.is_none() && self.name_prefix.is_none() && self.content.is_none()
+    }
+
+    fn matches(&self, record: &provider::DnsListResponse) -> bool {
+        self.record_type
+            .as_ref()
+            .is_none_or(|t| record.record_type.eq_ignore_ascii_case(t))
+            && self
+                .name_prefix
+                .as_ref()
+                .is_none_or(|p| record.name.starts_with(p.as_str()))
+            && self
+                .content
+                .as_ref()
+                .is_none_or(|c| record.content.contains(c.as_str()))
+    }
+}
+
+async fn dns_prune(zone: Option<&str>, filter: &PruneFilter, concurrency: usize) -> Result<()> {
+    ensure!(
+        !filter.is_empty(),
+        "Pass at least one of --type, --name-prefix or --content, pruning doesn't delete a \
+         whole zone."
+    );
+    ensure!(concurrency > 0, "--concurrency has to be at least 1.");
+
+    cliclack::intro("eurus-prune")?;
+
+    let mut config = get_dns_config().await?;
+    let zone = match zone.and_then(|z| zone_for_name(&config, z)) {
+        Some(zone) => zone.clone(),
+        None => select_zone(&mut config, zone).await?,
+    };
+    let provider = provider::for_zone(&config, &zone)?;
+
+    let mut records: Vec<_> = provider
+        .list_records(&zone.id)
+        .await?
+        .into_iter()
+        .filter(|r| filter.matches(r))
+        .collect();
+    if records.is_empty() {
+        cliclack::outro(format!("No records in {} match.", zone.name))?;
+        return Ok(());
+    }
+    records.sort_by(|a, b| (&a.name, &a.record_type).cmp(&(&b.name, &b.record_type)));
+
+    if !json_output() {
+        print_table(
+            &["TYPE", "NAME", "VALUE"],
+            records
+                .iter()
+                .map(|r| vec![r.record_type.clone(), idn::to_unicode(&r.name), r.value()])
+                .collect(),
+        );
+    }
+    if !prompt::confirm(format!("Delete these {} records?", records.len()))
+        .initial_value(false)
+        .interact()?
+    {
+        return Err(EurusError::Cancelled.into());
+    }
+
+    let payload = serde_json::json!({ "zone": zone, "deleted": records });
+    config.hooks.run(Event::PreDns, &payload)?;
+    if config.trash {
+        trash::keep(&zone, &records)?;
+    }
+
+    let progress = cliclack::progress_bar(records.len() as u64);
+    progress.start("Deleting records...");
+    let (provider, zone_id, progress) = (provider.as_ref(), zone.id.as_str(), &progress);
+    let results: Vec<_> = futures::stream::iter(&records)
+        .map(|r| async move {
+            let result = provider.delete_record(zone_id, &r.id).await;
+            progress.inc(1);
+            (r, result)
+        })
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+    progress.stop("Deleted records.");
+
+    let mut failed = 0;
+    let mut deleted = vec![];
+    for (record, result) in results {
+        match result {
+            Ok(()) => deleted.push(record),
+            Err(e) => {
+                failed += 1;
+                cliclack::log::error(format!(
+                    "Could not delete {} {}: {e}",
+                    record.record_type, record.name
+                ))?;
+            }
+        }
+    }
+
+    config.hooks.run(Event::PostDns, &payload)?;
+    if json_output() {
+        print_json(&deleted)?;
+    }
+    ensure!(
+        failed == 0,
+        "{failed} of {} deletions failed.",
+        records.len()
+    );
+    cliclack::outro(format!(
+        "Deleted {} records from {}.",
+        records.len(),
+        zone.name
+    ))?;
+
+    Ok(())
+}
+
+/// Prints rows as left-aligned columns, sized to fit the widest value.
+fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    print_grouped_table(headers, vec![(None, rows)]);
+}
+
+/// Like [`print_table`], with an optional heading above each group of rows
+/// and the columns lined up across all of them.
+fn print_grouped_table(headers: &[&str], groups: Vec<(Option<String>, Vec<Vec<String>>)>) {
+    let mut widths: Vec<_> = headers.iter().map(|h| h.len()).collect();
+    for row in groups.iter().flat_map(|(_, rows)| rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(console::measure_text_width(cell));
+        }
+    }
+
+    // Padded by hand, as cells may be styled.
+    let print_row = |cells: Vec<&str>| {
+        let line: Vec<_> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| {
+                let padding = width.saturating_sub(console::measure_text_width(cell));
+                format!("{cell}{}", " ".repeat(padding))
+            })
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+
+    print_row(headers.to_vec());
+    for (heading, rows) in &groups {
+        if let Some(heading) = heading {
+            println!("\n{}", console::style(heading).bold());
+        }
+        for row in rows {
+            print_row(row.iter().map(String::as_str).collect());
+        }
+    }
+}
+
+async fn dns_ddns(
+    names: Vec<String>,
+    resolvers: Vec<String>,
+    watch: Option<(std::time::Duration, Option<std::net::SocketAddr>)>,
+    prefix: Option<ddns::Prefix>,
+    force_unlock: bool,
+) -> Result<()> {
+    // Also taken for a single run, so one from cron doesn't race a watching one.
+    let _lock = lock::acquire("ddns", force_unlock)?;
+    let mut config = get_dns_config().await?;
+
+    let names = match (names.is_empty(), config.ddns_records.is_empty()) {
+        (false, _) => names,
+        (true, false) => config.ddns_records.clone(),
+        (true, true) if !config.ddns_ipv6_records.is_empty() => vec![],
+        (true, true) => {
+            let zone = select_zone(&mut config, None).await?;
+            let records: Vec<_> = provider::for_zone(&config, &zone)?
+                .list_records(&zone.id)
+                .await?
+                .into_iter()
+                .filter(|r| r.record_type == "A")
+                .map(|r| (r.name.clone(), idn::to_unicode(&r.name), r.content))
+                .collect();
+            ensure!(!records.is_empty(), "{} has no A records.", zone.name);
+
+            let selected: Vec<String> = prompt::multiselect("Select the records to keep updated")
+                .items(&records)
+                .interact()?;
+            config.ddns_records = selected.clone();
+            if let Some(address) = ddns::host_ipv6() {
+                if prompt::toggle(format!(
+                    "This host also has the IPv6 address {address}. Keep AAAA records for them \
+                     pointed at it too?"
+                ))
+                .interact()?
+                {
+                    let interface_id = ddns::interface_id(address, config.ddns_prefix_length);
+                    for name in &selected {
+                        config
+                            .ddns_ipv6_records
+                            .insert(name.clone(), interface_id.to_string());
+                    }
+                }
+            }
+            save_config(&config)?;
+            selected
+        }
+    };
+
+    let resolvers = match resolvers.is_empty() {
+        true => config.ipv4_resolvers(),
+        false => resolvers,
+    };
+    if let Some((interval, listen)) = watch {
+        let cancel = cancel::on_ctrl_c()?;
+        let metrics = metrics::Metrics::default();
+        if let Some(listen) = listen {
+            metrics.serve(listen, cancel.clone()).await?;
+        }
+        return ddns::watch(
+            &config, &names, &resolvers, prefix, interval, &metrics, &cancel,
+        )
+        .await;
+    }
+
+    // What each update was for, e.g. `at 203.0.113.7` or `in 2001:db8::/56`.
+    let mut results = vec![];
+    let mut output = serde_json::Map::new();
+    if !names.is_empty() {
+        let ip = ddns::agreed_ipv4(&resolvers, config.ddns_consensus).await?;
+        output.insert("ip".to_string(), serde_json::json!(ip));
+        results.push((format!("at {ip}"), ddns::update(&config, &names, ip).await?));
+    }
+    if !config.ddns_ipv6_records.is_empty() {
+        let prefix = match prefix {
+            Some(prefix) => prefix,
+            None => {
+                ddns::public_prefix(&config.ddns_ipv6_resolver, config.ddns_prefix_length).await?
+            }
+        };
+        output.insert("prefix".to_string(), serde_json::json!(prefix.to_string()));
+        results.push((
+            format!("in {prefix}"),
+            ddns::update_ipv6(&config, prefix).await?,
+        ));
+    }
+
+    if json_output() {
+        let (updated, deferred): (Vec<_>, Vec<_>) = results
+            .into_iter()
+            .map(|(_, updates)| (updates.updated, updates.deferred))
+            .unzip();
+        output.insert("updated".to_string(), serde_json::json!(updated.concat()));
+        output.insert("deferred".to_string(), serde_json::json!(deferred.concat()));
+        return print_json(&output);
+    }
+    for (target, updates) in results {
+        if updates.updated.is_empty() && updates.deferred.is_empty() {
+            println!("All records already point {target}.");
+        }
+        for name in updates.updated {
+            println!("Updated {name} to point {target}.");
+        }
+        for (name, reason) in updates.deferred {
+            println!("Not updating {name} to point {target} yet, {reason}.");
+        }
+    }
+
+    Ok(())
+}
+
+async fn dns_analytics(hostname: &str, hours: u32) -> Result<()> {
+    let config = get_dns_config().await?;
+    let zone = zone_for_name(&config, hostname)
+        .ok_or_else(|| EurusError::NotFound(format!("No configured zone contains {hostname}.")))?;
+    ensure!(
+        zone.provider == Provider::Cloudflare,
+        "Analytics are only available for Cloudflare zones."
+    );
+
+    let until = chrono::Utc::now();
+    let since = until - chrono::Duration::hours(hours.into());
+    let analytics = Cloudflare::from_config(&config)?
+        .host_analytics(&zone.id, hostname, since, until)
+        .await?;
+
+    let requests: u64 = analytics.series.iter().map(|g| g.count).sum();
+    let bytes: u64 = analytics
+        .series
+        .iter()
+        .filter_map(|g| g.sum.as_ref())
+        .map(|s| s.edge_response_bytes)
+        .sum();
+    let threats: u64 = analytics.threats.iter().map(|g| g.count).sum();
+    let counts: Vec<_> = analytics.series.iter().map(|g| g.count).collect();
+
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "hostname": hostname,
+            "hours": hours,
+            "requests": requests,
+            "bandwidth_bytes": bytes,
+            "threats": threats,
+            "series": counts,
+        }));
+    }
+
+    println!("{hostname} (last {hours}h)");
+    println!("  requests   {requests:>12}");
+    println!("  bandwidth  {:>12}", human_bytes(bytes));
+    println!("  threats    {threats:>12}");
+    println!("  traffic    {}", sparkline(&counts));
+
+    Ok(())
+}
+
+async fn dns_discover(domain: &str, wordlist: Option<PathBuf>) -> Result<()> {
+    cliclack::intro("eurus-dns")?;
+
+    let wordlist = match wordlist {
+        Some(path) => std::fs::read_to_string(&path)
+            .with_context(|| format!("Could not read {}.", path.display()))?,
+        None => resolve::COMMON_SUBDOMAINS.join("\n"),
+    };
+    let subdomains: Vec<_> = wordlist
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect();
+
+    let mut config = get_dns_config().await?;
+    let zone = match config.zones.iter().find(|z| z.name == domain) {
+        Some(zone) => zone.clone(),
+        None => select_zone(&mut config, Some(domain)).await?,
+    };
+    let provider = provider::for_zone(&config, &zone)?;
+
+    import_public_records(provider.as_ref(), &zone, domain, &subdomains).await?;
+
+    cliclack::outro("Done!")?;
+
+    Ok(())
+}
+
+fn sparkline(values: &[u64]) -> String {
+    static BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let max = values.iter().copied().max().unwrap_or_default().max(1);
+    values
+        .iter()
+        .map(|v| BARS[(*v * (BARS.len() as u64 - 1) / max) as usize])
+        .collect()
+}
+
+fn human_bytes(bytes: u64) -> String {
+    static UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+/// The type specific parts of a record.
+struct RecordValue {
+    content: String,
+    priority: Option<u16>,
+    data: Option<serde_json::Value>,
+}
+
+/// Asks for the value of a record named `name`, with the extra fields MX,
+/// SRV and CAA records need. `previous` is offered as the target of other
+/// records.
+fn prompt_record_value(
+    record_type: &str,
+    zone: &ZoneInfo,
+    name: &str,
+    previous: Option<&str>,
+) -> Result<RecordValue> {
+    let valid = |record_type: &str| {
+        let (record_type, name) = (record_type.to_string(), name.to_string());
+        move |content: &String| provider::check_content(&record_type, &name, content)
+    };
+    Ok(match record_type {
+        "MX" => {
+            let priority = prompt::input("Priority").default_input("10").interact()?;
+            let content = prompt::input("Mail server")
+                .default_input(&zone.name)
+                .validate(valid("MX"))
+                .interact()?;
+            RecordValue {
+                content,
+                priority: Some(priority),
+                data: None,
+            }
+        }
+        "SRV" => {
+            let priority: u16 = prompt::input("Priority").default_input("10").interact()?;
+            let weight: u16 = prompt::input("Weight").default_input("5").interact()?;
+            let port: u16 = prompt::input("Port").interact()?;
+            let target: String = prompt::input("Target")
+                .default_input(&zone.name)
+                .interact()?;
+            RecordValue {
+                content: format!("{weight} {port} {target}"),
+                priority: Some(priority),
+                data: Some(serde_json::json!({
+                    "priority": priority,
+                    "weight": weight,
+                    "port": port,
+                    "target": target,
+                })),
+            }
+        }
+        "CAA" => {
+            let flags: u8 = prompt::input("Flags").default_input("0").interact()?;
+            let tag = prompt::select("Tag")
+                .item("issue", "issue", "who may issue certificates")
+                .item(
+                    "issuewild",
+                    "issuewild",
+                    "who may issue wildcard certificates",
+                )
+                .item("iodef", "iodef", "where to report violations")
+                .interact()?;
+            let value: String = prompt::input("Value (e.g. letsencrypt.org)").interact()?;
+            RecordValue {
+                content: format!("{flags} {tag} \"{value}\""),
+                priority: None,
+                data: Some(serde_json::json!({ "flags": flags, "tag": tag, "value": value })),
+            }
+        }
+        "TXT" => {
+            let content: String = prompt::input("What is the text?")
+                .validate(valid("TXT"))
+                .interact()?;
+            // Quotes are only needed in zone files, the apis add their own.
+            let content = match content.strip_prefix('"').and_then(|c| c.strip_suffix('"')) {
+                Some(unquoted) if !unquoted.contains('"') => unquoted.to_string(),
+                _ => content,
+            };
+            RecordValue {
+                content,
+                priority: None,
+                data: None,
+            }
+        }
+        _ => RecordValue {
+            content: prompt::input("What is the target?")
+                .default_input(previous.unwrap_or(zone.default_target(record_type)))
+                .validate(valid(record_type))
+                .interact()?,
+            priority: None,
+            data: None,
+        },
+    })
+}
+
+/// Picks one of the names with records in the zone, filtered by typing, or
+/// asks for a new one. New names are taken relative to the zone unless they
+/// already end in it.
+fn select_record_name(
+    zone: &ZoneInfo,
+    records: &[provider::DnsListResponse],
+    previous: Option<&str>,
+) -> Result<String> {
+    let mut names: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for record in records {
+        let types = names.entry(&record.name).or_default();
+        if !types.contains(&record.record_type.as_str()) {
+            types.push(&record.record_type);
+        }
+    }
+
+    let items: Vec<_> = [(None, "Create new...".to_string(), String::new())]
+        .into_iter()
+        .chain(names.iter().map(|(name, types)| {
+            (
+                Some(name.to_string()),
+                idn::to_unicode(name),
+                types.join(", "),
+            )
+        }))
+        .collect();
+    let mut select = prompt::select("Which subdomain would you like to modify?")
+        .items(&items)
+        .filter_mode();
+    if let Some(previous) = previous.filter(|p| names.contains_key(p)) {
+        select = select.initial_value(Some(previous.to_string()));
+    }
+    if let Some(name) = select.interact()? {
+        return Ok(name);
+    }
+
+    let mut input = prompt::input("Name of the new record (`@` for the zone, `*` for a wildcard)")
+        .placeholder(&format!("app.{}", idn::to_unicode(&zone.name)))
+        .validate({
+            let zone = zone.name.clone();
+            move |input: &String| idn::record_name(input, &zone).map(|_| ())
+        });
+    if let Some(previous) = previous.filter(|p| !names.contains_key(p)) {
+        input = input.default_input(previous);
+    }
+
+    Ok(idn::record_name(&input.interact::<String>()?, &zone.name)?)
+}
+
+/// Stops or warns about names that can't have the record as asked, before
+/// the provider answers with a less helpful error.
+fn check_record_name(zone: &ZoneInfo, name: &str, record_type: &str, proxied: bool) -> Result<()> {
+    let apex = name == zone.name;
+    ensure!(
+        !(apex && record_type == "CNAME" && zone.provider != Provider::Cloudflare),
+        "{} can't have a CNAME at the zone apex, only Cloudflare flattens them. Use an A or AAAA \
+         record instead.",
+        zone.provider
+    );
+    ensure!(
+        !(name.starts_with('*') && record_type == "SRV"),
+        "SRV records can't be wildcards, their name is the service and protocol."
+    );
+
+    // Universal SSL covers the zone and one level below it, which includes
+    // `*.example.com` but not `*.sub.example.com` or `a.sub.example.com`.
+    let depth = name
+        .strip_suffix(&zone.name)
+        .map_or(0, |prefix| prefix.trim_end_matches('.').split('.').count());
+    if proxied && !apex && depth > 1 {
+        cliclack::log::warning(format!(
+            "{} is more than one level below {}, so Cloudflare's free Universal SSL certificate \
+             doesn't cover it. Proxied HTTPS needs an Advanced or custom certificate for it.",
+            idn::to_unicode(name),
+            zone.name
+        ))?;
+    } else if proxied && name.starts_with('*') {
+        cliclack::log::remark(
+            "Proxied wildcards only catch names without records of their own, and don't work \
+             for non-HTTP traffic.",
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Prompts for and creates or updates a single record. When `name` is given
+/// (e.g. the domain from the web flow) it is used instead of asking for one.
+/// `origin` is the compose file and service the record is for, which fill in
+/// `comment_template`.
+async fn update_record(
+    name: Option<&str>,
+    args: &RecordArgs,
+    origin: Option<(&Path, &str)>,
+) -> Result<provider::DnsListResponse> {
+    let mut config = get_dns_config().await?;
+    let domain = &select_zone(&mut config, name).await?;
+
+    let provider = provider::for_zone(&config, domain)?;
+    let domains = provider.list_records(&domain.id).await?;
+    let last = LastRun::load(Path::new("."));
+    let mut session = Session::start("dns", Path::new("."))?;
+
+    let subdomain: String = match name {
+        Some(n) => n.to_string(),
+        None => {
+            let subdomain = select_record_name(
+                domain,
+                &domains,
+                session.get("name").or(last.name.as_deref()),
+            )?;
+            session.answer("name", &subdomain)?;
+            subdomain
+        }
+    };
+
+    let notes = Notes::load().ok();
+    for info in domains.iter().filter(|d| d.name == subdomain) {
+        if let Some(note) = notes.as_ref().and_then(|n| n.record(&domain.id, &info.id)) {
+            cliclack::log::info(format!("Note: {note}"))?;
+        }
+        cliclack::log::info(format!(
+            "Currently {} {} with a TTL of {}.",
+            info.record_type,
+            info.value(),
+            provider::display_ttl(info.ttl)
+        ))?;
+    }
+
+    // A type set for the zone wins over the one used last in this directory.
+    let zone_type = domain.default_type();
+    let record_type: String = prompt::input("What record type is this?")
+        .default_input(
+            session
+                .get("type")
+                .or(domain.record_type.as_ref().map(|_| zone_type.as_str()))
+                .or(last.record_type.as_deref())
+                .unwrap_or(&zone_type),
+        )
+        .interact()?;
+    let record_type = record_type.to_uppercase();
+    session.answer("type", &record_type)?;
+
+    // SRV records live below `_service._proto.` of the name they are for.
+    let record_name = if record_type == "SRV" {
+        let service: String = prompt::input("Service (e.g. _sip)").interact()?;
+        let proto: String = prompt::input("Protocol").default_input("_tcp").interact()?;
+        format!(
+            "_{}._{}.{subdomain}",
+            service.trim_start_matches('_'),
+            proto.trim_start_matches('_')
+        )
+    } else {
+        subdomain.clone()
+    };
+    let previous_target = session.get("target").or(last
+        .target
+        .as_deref()
+        .filter(|_| domain.target.is_none() || record_type != zone_type)
+        .filter(|_| last.record_type.as_ref() == Some(&record_type)));
+    let value = prompt_record_value(&record_type, domain, &record_name, previous_target)?;
+    session.answer("target", &value.content)?;
+
+    if record_type == "CNAME" {
+        check_cname(&config, domain, &domains, &record_name, &value.content)?;
+    }
+
+    // A CNAME can't share its name with records of any other type, so those
+    // are replaced too. An address record or CNAME replaces the one record
+    // there is, but a name often has several MX, TXT, SRV or CAA records, so
+    // for those, or when there are several, ask which one (if any).
+    let replaceable: Vec<_> = domains
+        .iter()
+        .filter(|d| {
+            d.name == record_name
+                && (d.record_type == record_type
+                    || d.record_type == "CNAME"
+                    || record_type == "CNAME")
+        })
+        .collect();
+    let id = match replaceable.as_slice() {
+        [] => None,
+        [only]
+            if matches!(record_type.as_str(), "A" | "AAAA" | "CNAME")
+                || only.record_type == "CNAME" =>
+        {
+            Some(only.id.clone())
+        }
+        _ => {
+            // Adding one more is only possible where none conflict.
+            let can_add =
+                record_type != "CNAME" && replaceable.iter().all(|d| d.record_type != "CNAME");
+            let items: Vec<_> = replaceable
+                .iter()
+                .map(|d| {
+                    (
+                        Some(d.id.clone()),
+                        format!("{} {}", d.record_type, d.value()),
+                        "",
+                    )
+                })
+                .chain(can_add.then(|| (None, "Add a new record".to_string(), "")))
+                .collect();
+            prompt::select("Which record should be replaced?")
+                .items(&items)
+                .interact()?
+        }
+    };
+
+    let current = domains.iter().find(|d| Some(&d.id) == id.as_ref());
+
+    let special = match record_type.as_str() {
+        "CNAME" => cloudflare::SpecialTarget::detect(&value.content),
+        _ => None,
+    };
+
+    // Only Cloudflare can proxy records, and only address records at that.
+    let proxiable = matches!(record_type.as_str(), "A" | "AAAA" | "CNAME");
+    let proxied = match (domain.provider, args.no_proxy || !proxiable) {
+        // There is nothing to ask, a tunnel doesn't work unproxied.
+        (Provider::Cloudflare, false) if special == Some(cloudflare::SpecialTarget::Tunnel) => {
+            cliclack::log::info("Tunnel records are proxied through Cloudflare.")?;
+            true
+        }
+        (Provider::Cloudflare, false) => {
+            let proxied = prompt::toggle("Proxy through Cloudflare?")
+                .initial_value(match session.get("proxied") {
+                    Some(proxied) => proxied == "true",
+                    // Cloudflare's own services are proxied by default.
+                    None if special.is_some() => true,
+                    None => current.map_or(domain.proxied, |c| c.proxied),
+                })
+                .interact()?;
+            session.answer("proxied", proxied)?;
+            proxied
+        }
+        _ => false,
+    };
+    check_record_name(domain, &record_name, &record_type, proxied)?;
+    if let Some(special) = special {
+        if let Some(problem) = special.problem(proxied) {
+            cliclack::log::warning(problem)?;
+            if !prompt::confirm("Save the record anyway?")
+                .initial_value(false)
+                .interact()?
+            {
+                return Err(EurusError::Cancelled.into());
+            }
+        } else if let Some(note) = special.note() {
+            cliclack::log::remark(note)?;
+        }
+    }
+
+    let ttl = match args.ttl {
+        Some(ttl) => ttl,
+        None => {
+            let ttl = prompt::input("TTL in seconds (1 for automatic)")
+                .default_input(&match session.get("ttl") {
+                    Some(ttl) => ttl.to_string(),
+                    None => current.map_or(domain.default_ttl(), |c| c.ttl).to_string(),
+                })
+                .interact()?;
+            session.answer("ttl", ttl)?;
+            ttl
+        }
+    };
+
+    let (comment, tags) = match domain.provider {
+        Provider::Cloudflare => {
+            let comment = match &args.comment {
+                Some(comment) => comment.clone(),
+                None => {
+                    let mut input = prompt::input("Comment (optional)").required(false);
+                    let project = origin.and_then(|(file, _)| compose::project_name(file));
+                    let template = config
+                        .record_comment(project.as_deref(), origin.map(|(_, service)| service));
+                    if let Some(comment) = session
+                        .get("comment")
+                        .or(template.as_deref())
+                        .or(current.and_then(|c| c.comment.as_deref()))
+                    {
+                        input = input.default_input(comment);
+                    }
+                    let comment: String = input.interact()?;
+                    session.answer("comment", &comment)?;
+                    comment
+                }
+            };
+            let mut tags = current.map(|c| c.tags.clone()).unwrap_or_default();
+            tags.retain(|tag| {
+                let name = tag.split(':').next().unwrap_or_default();
+                !args.untags.iter().any(|u| u == tag || u == name)
+            });
+            tags.extend(args.tags.iter().cloned());
+            tags.sort();
+            tags.dedup();
+            // An empty comment has to be sent for Cloudflare to remove one.
+            let had_comment = current.is_some_and(|c| c.comment.is_some());
+            let comment = (!comment.is_empty() || had_comment).then_some(comment);
+            (comment, Some(tags))
+        }
+        _ => (None, None),
+    };
+
+    let body = DnsCreateUpdate {
+        name: record_name,
+        id,
+        proxied,
+        record_type,
+        content: value.content,
+        ttl,
+        priority: value.priority,
+        data: value.data,
+        comment,
+        tags,
+    };
+
+    if let Some(current) = current {
+        confirm_replace(current, &body)?;
+    }
+
+    let payload = serde_json::json!({ "zone": domain, "record": body });
+    config.hooks.run(Event::PreDns, &payload)?;
+    let record = provider.upsert_record(&domain.id, &body).await?;
+    session.finish()?;
+    config.hooks.run(Event::PostDns, &payload)?;
+    if body.record_type == "A" {
+        offer_aaaa(&config, domain, &domains, &body, provider.as_ref()).await?;
+    }
+
+    LastRun::update_or_warn(Path::new("."), |last| {
+        last.zone = Some(domain.id.clone());
+        last.name = Some(subdomain);
+        last.record_type = Some(body.record_type);
+        last.target = Some(body.content);
+    });
+
+    Ok(record)
+}
+
+/// Offers to point the AAAA record of a name at this host's IPv6 address
+/// after its A record was saved, with the same settings, so dual-stack hosts
+/// don't need a second run.
+async fn offer_aaaa(
+    config: &Config,
+    zone: &ZoneInfo,
+    records: &[provider::DnsListResponse],
+    a: &DnsCreateUpdate,
+    provider: &dyn DnsProvider,
+) -> Result<()> {
+    let Some(address) = ddns::host_ipv6() else {
+        return Ok(());
+    };
+    let current = records
+        .iter()
+        .find(|r| r.name == a.name && r.record_type == "AAAA");
+    if current.is_some_and(|r| r.content.parse() == Ok(address)) {
+        return Ok(());
+    }
+
+    let name = idn::to_unicode(&a.name);
+    let question = match current {
+        Some(current) => format!(
+            "This host's IPv6 address is {address}. Update the AAAA record of {name} from {} \
+             too?",
+            current.content
+        ),
+        None => {
+            format!("This host also has the IPv6 address {address}. Add an AAAA record for {name}?")
+        }
+    };
+    if !prompt::toggle(question).interact()? {
+        return Ok(());
+    }
+
+    let body = DnsCreateUpdate {
+        name: a.name.clone(),
+        id: current.map(|r| r.id.clone()),
+        record_type: "AAAA".to_string(),
+        proxied: a.proxied,
+        content: address.to_string(),
+        ttl: a.ttl,
+        comment: a.comment.clone(),
+        tags: a.tags.clone(),
+        ..Default::default()
+    };
+    let payload = serde_json::json!({ "zone": zone, "record": body });
+    config.hooks.run(Event::PreDns, &payload)?;
+    provider.upsert_record(&zone.id, &body).await?;
+    config.hooks.run(Event::PostDns, &payload)?;
+    cliclack::log::success(format!("Pointed the AAAA record of {name} at {address}."))?;
+
+    Ok(())
+}
+
+async fn dns_sync(file: &Path, prune: bool, dry_run: bool) -> Result<()> {
+    cliclack::intro("eurus-sync")?;
+
+    let spec = reconcile::load_zones(file)?;
+    let config = get_dns_config().await?;
+    let cancel = cancel::on_ctrl_c()?;
+
+    let spinner = cliclack::spinner();
+    spinner.start("Comparing the zones...");
+    let changes = reconcile::plan_sync(&config, &spec, prune, &cancel).await;
+    spinner.stop("Compared the zones.");
+    let changes = changes?;
+
+    if json_output() {
+        let plan: Vec<_> = changes.iter().map(ToString::to_string).collect();
+        print_json(&plan)?;
+    }
+    if changes.is_empty() {
+        cliclack::outro("The zones match, nothing to do.")?;
+        return Ok(());
+    }
+
+    let deletes = changes
+        .iter()
+        .filter(|c| matches!(c, reconcile::Change::DeleteRecord { .. }))
+        .count();
+    cliclack::note(
+        "Plan",
+        changes
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )?;
+
+    if dry_run {
+        cliclack::outro("Run again without --dry-run to make these changes.")?;
+        return Ok(());
+    }
+    if !prune {
+        cliclack::log::remark(
+            "Records that aren't in the file are kept, pass --prune to delete them.",
+        )?;
+    }
+
+    let question = match deletes {
+        0 => format!("Apply {} changes?", changes.len()),
+        deletes => format!(
+            "Apply {} changes, deleting {deletes} records?",
+            changes.len()
+        ),
+    };
+    if !prompt::confirm(question)
+        .initial_value(deletes == 0)
+        .interact()?
+    {
+        cliclack::outro_cancel("Nothing was changed.")?;
+        return Ok(());
+    }
+
+    let count = changes.len();
+    reconcile::apply(&config, changes, &cancel).await?;
+    cliclack::outro(format!("Applied {count} changes."))?;
+
+    Ok(())
+}
+
+async fn dns_explain(name: &str) -> Result<()> {
+    cliclack::intro("eurus-explain")?;
+
+    let config = get_dns_config().await?;
+    let zone = zone_for_name(&config, name).ok_or_else(|| {
+        EurusError::NotFound(format!("{name} isn't in any of the zones in the config."))
+    })?;
+    let records = provider::for_zone(&config, zone)?
+        .list_records(&zone.id)
+        .await?;
+    let record = records
+        .iter()
+        .find(|r| r.name == name && ["A", "AAAA", "CNAME"].contains(&r.record_type.as_str()))
+        .or_else(|| records.iter().find(|r| r.name == name))
+        .ok_or_else(|| EurusError::NotFound(format!("{} has no record for {name}.", zone.name)))?;
+
+    explain_record(record).await
+}
+
+/// Shows the path from a resolver to the service for `record`: whether
+/// Cloudflare stands in between, the TTL resolvers keep it for, and what
+/// public DNS returns right now, CNAMEs included.
+/// Whether the record is proxied, which resolvers see as Cloudflare's
+/// addresses, and the type to look up to see it.
+fn live_lookup(record: &provider::DnsListResponse) -> (bool, &str) {
+    let proxied = record.proxied && matches!(record.record_type.as_str(), "A" | "AAAA" | "CNAME");
+    let lookup_type = match (proxied, record.record_type.as_str()) {
+        (true, "AAAA") => "AAAA",
+        (true, _) => "A",
+        (false, record_type) => record_type,
+    };
+
+    (proxied, lookup_type)
+}
+
+/// Whether the answers to the [`live_lookup`] have the record's value, or one
+/// of Cloudflare's addresses for proxied records.
+fn answers_show(
+    record: &provider::DnsListResponse,
+    answers: &[resolve::Answer],
+    edge: &cloudflare::EdgeIps,
+) -> bool {
+    let (proxied, lookup_type) = live_lookup(record);
+    let normalize = |value: &str| value.trim_end_matches('.').trim_matches('"').to_lowercase();
+    let expected = normalize(&record.value());
+    let code = resolve::type_code(lookup_type);
+
+    answers
+        .iter()
+        .filter(|a| a.record_type == code)
+        .any(|a| match proxied {
+            true => a.data.parse().is_ok_and(|ip| edge.contains(ip)),
+            false => normalize(&a.data) == expected,
+        })
+}
+
+/// Polls the public resolvers until each returns the record's value, or a
+/// Cloudflare address for proxied records. Only warns when `timeout` passes
+/// first, since the record itself was saved.
+async fn wait_for_propagation(
+    record: &provider::DnsListResponse,
+    timeout: std::time::Duration,
+) -> Result<()> {
+    let (proxied, lookup_type) = live_lookup(record);
+    let edge = match proxied {
+        true => match cloudflare::edge_ips(&get_config()?).await {
+            Ok(edge) => edge,
+            Err(e) => {
+                cliclack::log::warning(format!(
+                    "Could not get Cloudflare's addresses to check {} against: {e}",
+                    record.name
+                ))?;
+                return Ok(());
+            }
+        },
+        false => Default::default(),
+    };
+    let live = |answers: &[resolve::Answer]| answers_show(record, answers, &edge);
+
+    let spinner = cliclack::spinner();
+    spinner.start(format!("Waiting for {} to be live...", record.name));
+    let started = std::time::Instant::now();
+    let mut pending: Vec<_> = resolve::PUBLIC_RESOLVERS.to_vec();
+    loop {
+        let mut still = vec![];
+        for (address, url) in pending {
+            match resolve::lookup_with(url, &record.name, lookup_type).await {
+                Ok(answers) if live(&answers) => {}
+                Ok(_) => still.push((address, url)),
+                Err(e) => {
+                    tracing::debug!("Lookup at {address} failed: {e}");
+                    still.push((address, url));
+                }
+            }
+        }
+        pending = still;
+        if pending.is_empty() {
+            spinner.stop(format!(
+                "{} is live on {} after {}s.",
+                record.name,
+                resolve::PUBLIC_RESOLVERS.map(|(a, _)| a).join(" and "),
+                started.elapsed().as_secs()
+            ));
+            return Ok(());
+        }
+        if started.elapsed() >= timeout {
+            spinner.error(format!(
+                "{} isn't live on {} yet.",
+                record.name,
+                pending
+                    .iter()
+                    .map(|(a, _)| *a)
+                    .collect::<Vec<_>>()
+                    .join(" and ")
+            ));
+            cliclack::log::remark(
+                "Resolvers that looked the name up before keep the old answer until its TTL runs \
+                 out, which for a name that didn't exist can be the zone's negative TTL.",
+            )?;
+            return Ok(());
+        }
+        spinner.set_message(format!(
+            "Waiting for {} to be live on {}...",
+            record.name,
+            pending
+                .iter()
+                .map(|(a, _)| *a)
+                .collect::<Vec<_>>()
+                .join(" and ")
+        ));
+        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+    }
+}
+
+async fn explain_record(record: &provider::DnsListResponse) -> Result<()> {
+    let name = &record.name;
+    let lookups: &[&str] = match record.record_type.as_str() {
+        "A" => &["A"],
+        "AAAA" => &["AAAA"],
+        "CNAME" => &["A", "AAAA"],
+        record_type => &[record_type],
+    };
+    let mut answers = vec![];
+    for record_type in lookups {
+        answers.extend(resolve::lookup(name, record_type).await?);
+    }
+    // Without the ranges the addresses just aren't labelled.
+    let edge = cloudflare::edge_ips(&get_config()?)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::debug!("Could not get Cloudflare's addresses: {e}");
+            Default::default()
+        });
+    let is_edge = |data: &str| data.parse().is_ok_and(|ip| edge.contains(ip));
+
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "record": record,
+            "answers": answers
+                .iter()
+                .map(|a| serde_json::json!({
+                    "name": a.name.trim_end_matches('.'),
+                    "type": resolve::type_name(a.record_type),
+                    "ttl": a.ttl,
+                    "data": a.data.trim_end_matches('.'),
+                    "cloudflare": is_edge(&a.data),
+                }))
+                .collect::<Vec<_>>(),
+        }));
+    }
+
+    let mut lines = vec![format!("{name} {} {}", record.record_type, record.value())];
+    lines.push(match record.proxied {
+        true => format!(
+            "Proxied: resolvers get Cloudflare's addresses, and Cloudflare connects to {} \
+             itself.",
+            record.content
+        ),
+        false => format!("DNS only: resolvers get {} directly.", record.content),
+    });
+    lines.push(match (record.proxied, record.ttl) {
+        (true, _) => "TTL: 300s, Cloudflare always uses it for proxied records.".to_string(),
+        (false, ttl) if ttl == AUTO_TTL => "TTL: 300s (auto).".to_string(),
+        (false, ttl) => format!("TTL: {ttl}s."),
+    });
+
+    lines.push(String::new());
+    lines.push("Public DNS now:".to_string());
+    if answers.is_empty() {
+        lines.push(
+            "  Nothing yet. Resolvers may still have cached that the name didn't exist.".into(),
+        );
+    }
+    for answer in &answers {
+        let data = answer.data.trim_end_matches('.');
+        let origin = match answer.record_type {
+            1 | 28 if is_edge(data) => " (Cloudflare)",
+            1 | 28 => " (origin)",
+            _ => "",
+        };
+        lines.push(format!(
+            "  {} {} {data}{origin}, {}s left",
+            answer.name.trim_end_matches('.'),
+            resolve::type_name(answer.record_type),
+            answer.ttl
+        ));
+    }
+
+    let addresses: Vec<_> = answers
+        .iter()
+        .filter(|a| [1, 28].contains(&a.record_type))
+        .map(|a| a.data.as_str())
+        .collect();
+    let remark = match (record.proxied, addresses.is_empty()) {
+        (_, true) => None,
+        (true, false) if !addresses.iter().all(|a| is_edge(a)) => {
+            Some("Public DNS still returns the old answer, it changes once the TTL above runs out.")
+        }
+        (true, false) if record.record_type == "CNAME" => Some(
+            "Cloudflare answers for proxied CNAMEs with its own addresses, so the CNAME isn't \
+             visible.",
+        ),
+        (false, false) if addresses.iter().any(|a| is_edge(a)) => Some(
+            "The addresses are Cloudflare's although the record isn't proxied, so the target is \
+             proxied by Cloudflare itself.",
+        ),
+        _ => None,
+    };
+    if let Some(remark) = remark {
+        lines.push(String::new());
+        lines.push(remark.to_string());
+    }
+
+    cliclack::note(format!("How {name} resolves"), lines.join("\n"))?;
+
+    Ok(())
+}
+
+/// Shows the record being replaced next to what replaces it and asks to go
+/// ahead, defaulting to no when the type changes.
+fn confirm_replace(current: &provider::DnsListResponse, new: &DnsCreateUpdate) -> Result<()> {
+    let new_value = match new.priority {
+        Some(priority) => format!("{priority} {}", new.content),
+        None => new.content.clone(),
+    };
+    let rows = [
+        ("Type", current.record_type.clone(), new.record_type.clone()),
+        ("Value", current.value(), new_value),
+        (
+            "Proxied",
+            current.proxied.to_string(),
+            new.proxied.to_string(),
+        ),
+        (
+            "TTL",
+            provider::display_ttl(current.ttl),
+            provider::display_ttl(new.ttl),
+        ),
+    ];
+    let width = rows.iter().map(|(_, old, _)| old.len()).max().unwrap_or(0);
+    let comparison: Vec<_> = rows
+        .iter()
+        .map(|(label, old, new)| {
+            let marker = if old == new { " " } else { "*" };
+            format!("{marker} {label:<8} {old:<width$}  ->  {new}")
+        })
+        .collect();
+    cliclack::note(
+        format!("Replacing {}", idn::to_unicode(&current.name)),
+        comparison.join("\n"),
+    )?;
+
+    let same_type = current.record_type == new.record_type;
+    if !prompt::confirm(match same_type {
+        true => "Update the record?".to_string(),
+        false => format!(
+            "Replace the {} record with a {}?",
+            current.record_type, new.record_type
+        ),
+    })
+    .initial_value(same_type)
+    .interact()?
+    {
+        return Err(EurusError::Cancelled.into());
+    }
+
+    Ok(())
+}
+
+/// Shows where a new CNAME ends up when it points at other records in the zone,
+/// refusing loops and warning about long chains.
+fn check_cname(
+    config: &Config,
+    zone: &ZoneInfo,
+    records: &[provider::DnsListResponse],
+    name: &str,
+    target: &str,
+) -> Result<()> {
+    let chain = provider::cname_chain(records, name, target)?;
+    let end = chain.last().unwrap();
+    let hops = chain.len() - 2;
+
+    if hops > 0 {
+        let resolved = records
+            .iter()
+            .find(|r| r.name.eq_ignore_ascii_case(end) && r.record_type != "CNAME")
+            .map(|r| format!(" ({} {})", r.record_type, r.value()))
+            .unwrap_or_default();
+        cliclack::log::info(format!("Resolves as {}{resolved}", chain.join(" -> ")))?;
+    }
+    if hops > config.cname_max_depth {
+        cliclack::log::warning(format!(
+            "The chain passes through {hops} records in the zone, more than the {} allowed by \
+             `cname_max_depth`. Consider pointing at {end} directly.",
+            config.cname_max_depth
+        ))?;
+    }
+    if zone.provider == Provider::Cloudflare && name.eq_ignore_ascii_case(&zone.name) {
+        cliclack::log::info(
+            "Cloudflare flattens CNAMEs at the apex, so it will be served as the target's \
+             addresses.",
+        )?;
+    }
+
+    Ok(())
+}
+
+/// A service that was configured for proxying by [`add_proxy_to_service`].
+struct ProxiedService {
+    backend: ProxyBackend,
+    file: PathBuf,
+    service: String,
+    domain: String,
+}
+
+async fn web(mut args: WebArgs, command: Option<WebCommand>) -> Result<()> {
+    cliclack::intro("eurus-web")?;
+    // The subcommands that work on a single file use the first one given.
+    if args.path.is_none() {
+        args.path = args.files.first().map(|f| f.display().to_string());
+    }
+
+    // A remote file is edited as a local copy, which is written back once the
+    // command is done.
+    if let Some(root) = &args.watch {
+        ensure!(
+            command.is_none() && args.host.is_none(),
+            "--watch only works on its own, for directories on this machine."
+        );
+        return web_watch(root, &args).await;
+    }
+
+    let checkout = match remote::Remote::parse(args.host.as_deref(), args.path.as_deref()) {
+        Some(remote) => {
+            ensure!(
+                args.files.len() <= 1,
+                "Only a single compose file can be edited over ssh."
+            );
+            ensure!(
+                !args.live,
+                "--live only sees the containers on this machine."
+            );
+            let spinner = cliclack::spinner();
+            spinner.start(format!("Fetching the compose file from {}...", remote.host));
+            let checkout = remote.fetch()?;
+            spinner.stop(format!("Fetched {} from {}.", checkout.file, remote.host));
+            args.path = Some(checkout.local.display().to_string());
+            args.files.clear();
+            args.host = Some(remote.host);
+            Some(checkout)
+        }
+        None => None,
+    };
+
+    if !matches!(
+        command,
+        Some(WebCommand::Push { .. } | WebCommand::Inspect { .. })
+    ) {
+        pick_project(&mut args)?;
+    }
+
+    match command {
+        None => {
+            for proxied in add_proxy_to_service(&args).await? {
+                show_url(&proxied.domain, args.qr)?;
+            }
+        }
+        Some(WebCommand::Push { domain, upstream }) => {
+            let domain = match domain {
+                Some(d) => d,
+                None => idn::to_ascii(
+                    &prompt::input("Enter the domain to proxy.").interact::<String>()?,
+                )?,
+            };
+            let upstream = match upstream {
+                Some(u) => u,
+                None => prompt::input("Enter the address to proxy to.")
+                    .placeholder("localhost:8080")
+                    .interact()?,
+            };
+
+            push_caddy_route(&domain, &upstream).await?;
+            show_url(&domain, args.qr)?;
+        }
+        Some(WebCommand::ImportLive { record }) => web_import_live(&args, record).await?,
+        Some(WebCommand::New {
+            name,
+            image,
+            preset,
+            secrets,
+            secret_storage,
+        }) => web_new(&args, name, image, preset, secrets, secret_storage).await?,
+        Some(WebCommand::RenameService { old, new }) => web_rename_service(&args, &old, &new)?,
+        Some(WebCommand::Remove { service }) => web_remove(&args, service).await?,
+        Some(WebCommand::Undo) => web_undo(&args)?,
+        Some(WebCommand::Format { map_labels }) => web_format(&args, map_labels)?,
+        Some(WebCommand::Adopt) => web_adopt(&args).await?,
+        Some(WebCommand::Templates) => web_templates()?,
+        Some(WebCommand::Inspect { container }) => web_inspect(&container)?,
+    }
+
+    if let Some(checkout) = checkout {
+        if checkout.push()? {
+            cliclack::log::success(format!(
+                "Wrote {} back to {}.",
+                checkout.file, checkout.remote.host
+            ))?;
+        }
+    }
+
+    cliclack::outro("Done!")?;
+
+    Ok(())
+}
+
+fn caddy_admin_url(config: &Config) -> &str {
+    match config.caddy_admin_url.as_str() {
+        "" => caddy::DEFAULT_ADMIN_URL,
+        url => url,
+    }
+}
+
+async fn push_caddy_route(domain: &str, upstream: &str) -> Result<()> {
+    let config = get_config().unwrap_or_default();
+    let url = caddy_admin_url(&config);
+
+    CaddyAdmin::new(url).add_route(domain, upstream).await?;
+    cliclack::log::success(format!("Added a route for {domain} to caddy at {url}."))?;
+
+    Ok(())
+}
+
+/// Points `args.path` at a compose project to work on when there isn't one
+/// where `web` was run. The projects below that directory are offered, or the
+/// ones under `projects_root` if there are none and no path was given.
+/// Watches the projects under `root` for services added without a proxy,
+/// announcing them and offering to proxy each. With `--yes` and `--domain`
+/// they are proxied without asking.
+async fn web_watch(root: &Path, args: &WebArgs) -> Result<()> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("{} doesn't exist.", root.display()))?;
+    let mut watcher = watch::ProjectWatcher::new(&root)?;
+    let config = get_config().unwrap_or_default();
+    let unattended = prompt::assume_yes() && !args.domains.is_empty();
+    cliclack::log::info(format!(
+        "Watching {} projects under {}, Ctrl-C stops.",
+        watcher.projects().count(),
+        root.display()
+    ))?;
+
+    let cancel = cancel::on_ctrl_c()?;
+    let metrics = metrics::Metrics::default();
+    if let Some(listen) = args.metrics {
+        metrics.serve(listen, cancel.clone()).await?;
+    }
+    loop {
+        metrics.set(
+            "eurus_watch_projects",
+            "Compose projects being watched.",
+            &[],
+            watcher.projects().count() as f64,
+        );
+        let found = tokio::select! {
+            _ = cancel.cancelled() => break,
+            found = watcher.next() => found,
+        };
+
+        for new in found {
+            metrics.inc(
+                "eurus_watch_services_found_total",
+                "Services found without a proxy.",
+                &[],
+            );
+            let message = format!(
+                "{} was added to {} without a proxy.",
+                new.service,
+                new.file.display()
+            );
+            cliclack::log::warning(&message)?;
+            config
+                .notify
+                .publish(
+                    "web",
+                    &message,
+                    &serde_json::json!({ "file": new.file, "service": new.service }),
+                )
+                .await;
+
+            if !unattended && !prompt::toggle(format!("Proxy {} now?", new.service)).interact()? {
+                continue;
+            }
+            match proxy_new_service(args, &new).await {
+                Ok(()) => {
+                    metrics.inc(
+                        "eurus_watch_services_proxied_total",
+                        "Services a proxy was added to.",
+                        &[],
+                    );
+                    metrics.succeeded();
+                }
+                Err(e) => {
+                    let message = format!("Could not proxy {}: {e}", new.service);
+                    cliclack::log::error(&message)?;
+                    metrics.inc(
+                        "eurus_watch_errors_total",
+                        "Services that couldn't be proxied.",
+                        &[],
+                    );
+                    metrics.failed(message);
+                }
+            }
+        }
+    }
+
+    cliclack::outro("Stopped watching.")?;
+    Ok(())
+}
+
+/// Runs the usual `web` flow for a service found by `--watch`. `{service}`
+/// and `{project}` in the domains are filled in for it.
+async fn proxy_new_service(args: &WebArgs, new: &watch::NewService) -> Result<()> {
+    let dir = new.file.parent().unwrap_or(Path::new("."));
+    LastRun::update_or_warn(dir, |last| last.service = Some(new.service.clone()));
+
+    let project = compose::project_name(&new.file).unwrap_or_default();
+    let proxied = add_proxy_to_service(&WebArgs {
+        path: Some(new.file.display().to_string()),
+        files: vec![],
+        domains: args
+            .domains
+            .iter()
+            .map(|d| {
+                d.replace("{service}", &new.service)
+                    .replace("{project}", &project)
+            })
+            .collect(),
+        watch: None,
+        ..args.clone()
+    })
+    .await?;
+    for proxied in proxied {
+        show_url(&proxied.domain, args.qr)?;
+    }
+
+    Ok(())
+}
+
+fn pick_project(args: &mut WebArgs) -> Result<()> {
+    if find_compose_file(args.path.clone()).is_ok() {
+        return Ok(());
+    }
+
+    let mut root = PathBuf::from(args.path.clone().unwrap_or(".".to_string()));
+    if !root.is_dir() {
+        return Ok(());
+    }
+    let mut projects = find_projects(&root);
+    if projects.is_empty() && args.path.is_none() {
+        if let Some(configured) = get_config().ok().and_then(|c| c.projects_root) {
+            projects = find_projects(&configured);
+            root = configured;
+        }
+    }
+
+    let file = match projects.as_slice() {
+        [] => return Ok(()),
+        [file] => file.clone(),
+        _ => {
+            let choices: Vec<_> = projects
+                .iter()
+                .map(|file| {
+                    let services = std::fs::read_to_string(file)
+                        .ok()
+                        .and_then(|c| compose::parse(&c).ok())
+                        .map(|c| c.services.0.into_keys().collect::<Vec<_>>().join(", "))
+                        .unwrap_or_else(|| "invalid compose file".to_string());
+                    let label = file.strip_prefix(&root).unwrap_or(file).display();
+                    (file.clone(), label.to_string(), services)
+                })
+                .collect();
+            prompt::select("Select a project")
+                .items(&choices)
+                .filter_mode()
+                .interact()?
+        }
+    };
+    cliclack::log::info(format!("Using {}.", file.display()))?;
+    args.path = Some(file.display().to_string());
+
+    Ok(())
+}
+
+/// Scaffolds a service with a preset applied, then offers to proxy it.
+async fn web_new(
+    args: &WebArgs,
+    name: Option<String>,
+    image: Option<String>,
+    preset: Option<String>,
+    secrets: Vec<String>,
+    secret_storage: Option<SecretStorage>,
+) -> Result<()> {
+    // A directory without a compose file gets a new one.
+    let (file, contents) = match find_compose_file(args.path.clone()) {
+        Ok(file) => {
+            let contents =
+                std::fs::read_to_string(&file).context("Could not read the file contents.")?;
+            (file, contents)
+        }
+        Err(_) => {
+            let dir = PathBuf::from(args.path.clone().unwrap_or(".".to_string()));
+            ensure!(dir.is_dir(), "The file provided should exist.");
+            (dir.join("compose.yaml"), String::new())
+        }
+    };
+    let mut compose: Compose = match contents.trim().is_empty() {
+        true => Compose::default(),
+        false => compose::parse(&contents).context("The compose yaml was invalid.")?,
+    };
+
+    let name = match name {
+        Some(name) => name,
+        None => prompt::input("Name of the new service").interact()?,
+    };
+    ensure!(
+        !compose.services.0.contains_key(&name),
+        "There is already a service called {name}."
+    );
+    let image = match image {
+        Some(image) => image,
+        None => prompt::input("Image").interact()?,
+    };
+
+    let config = get_config().unwrap_or_default();
+    let presets = presets::all(&config.presets);
+    let preset = match preset {
+        Some(preset) => presets
+            .get(&preset)
+            .with_context(|| format!("There is no preset called {preset}."))?
+            .clone(),
+        None => {
+            let items: Vec<_> = presets
+                .iter()
+                .map(|(name, p)| {
+                    let hint = [p.restart.clone(), p.memory.clone(), p.cpus.clone()]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    (name.clone(), name.clone(), hint)
+                })
+                .collect();
+            let picked = prompt::select("Preset")
+                .items(&items)
+                .initial_value("default".to_string())
+                .interact()?;
+            presets[&picked].clone()
+        }
+    };
+
+    let mut service = Service {
+        image: Some(image),
+        ..Default::default()
+    };
+    preset.apply(&mut service);
+
+    let mut secrets = secrets;
+    secrets.extend(preset.secrets.iter().cloned());
+    secrets.sort();
+    secrets.dedup();
+    let storage = secret_storage.unwrap_or(config.secret_storage);
+    let secret_files = secrets::add(
+        &mut compose,
+        file.parent().unwrap_or(Path::new(".")),
+        &name,
+        &mut service,
+        &secrets,
+        storage,
+    )?;
+    compose.services.0.insert(name.clone(), Some(service));
+
+    if !edits::confirm_unchanged(&file, &contents)? {
+        return Err(EurusError::Cancelled.into());
+    }
+    let payload = serde_json::json!({ "file": file, "service": name });
+    config.hooks.run(Event::PreWeb, &payload)?;
+    secrets::write(&secret_files)?;
+    edits::write(&file, &compose)?;
+    config.hooks.run(Event::PostWeb, &payload)?;
+    cliclack::log::success(format!("Added {name} to {}.", file.display()))?;
+    for secret in &secret_files {
+        cliclack::log::info(format!(
+            "Generated {}, keep it out of version control.",
+            secret.path.display()
+        ))?;
+    }
+
+    if prompt::toggle("Proxy it now?").interact()? {
+        LastRun::update_or_warn(file.parent().unwrap_or(Path::new(".")), |last| {
+            last.service = Some(name.clone());
+        });
+        let proxied = add_proxy_to_service(&WebArgs {
+            path: Some(file.display().to_string()),
+            ..args.clone()
+        })
+        .await?;
+        for proxied in proxied {
+            show_url(&proxied.domain, args.qr)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Shows the Caddyfile caddy-docker-proxy builds from a container's labels,
+/// for containers deployed from a compose file that isn't on this machine.
+fn web_inspect(container: &str) -> Result<()> {
+    let container = docker::inspect(container)?;
+    let caddyfile = proxy::caddyfile_from_labels(&container.labels, &container.addresses);
+
+    if json_output() {
+        let labels: BTreeMap<_, _> = container
+            .labels
+            .iter()
+            .filter(|(k, _)| proxy::is_caddy_label(k))
+            .collect();
+        return print_json(&serde_json::json!({
+            "container": container.name,
+            "service": container.service,
+            "config_files": container.config_files,
+            "addresses": container.addresses,
+            "labels": labels,
+            "caddyfile": caddyfile,
+        }));
+    }
+
+    if let Some(service) = &container.service {
+        let files: Vec<_> = container
+            .config_files
+            .iter()
+            .map(|f| match f.exists() {
+                true => f.display().to_string(),
+                false => format!("{} (not on this machine)", f.display()),
+            })
+            .collect();
+        cliclack::log::info(format!(
+            "{} is the {service} service from {}.",
+            container.name,
+            files.join(", ")
+        ))?;
+    }
+    match caddyfile {
+        Some(caddyfile) => cliclack::note(&container.name, caddyfile)?,
+        None => cliclack::log::warning(format!("{} has no caddy labels.", container.name))?,
+    }
+
+    Ok(())
+}
+
+/// Offers the backups of the compose file, newest first, and restores the
+/// picked one after showing what it would change.
+/// Sorts the labels and networks of every file in the project, turning list
+/// labels into mappings with `map_labels`.
+fn web_format(args: &WebArgs, map_labels: bool) -> Result<()> {
+    let config = get_config().unwrap_or_default();
+    let mut formatted = 0;
+    for file in compose::project_files(&args.files, args.path.clone())? {
+        let contents =
+            std::fs::read_to_string(&file).context("Could not read the file contents.")?;
+        let current: Compose = compose::parse(&contents)
+            .with_context(|| format!("{} is not valid compose yaml.", file.display()))?;
+        let mut compose = current.clone();
+        compose::canonicalize(&mut compose, map_labels);
+        // Compared as yaml, as the compose types ignore the order of mappings.
+        if serde_yml::to_string(&compose)? == serde_yml::to_string(&current)? {
+            continue;
+        }
+
+        if !edits::confirm_unchanged(&file, &contents)? {
+            return Err(EurusError::Cancelled.into());
+        }
+        let payload = serde_json::json!({ "file": file, "formatted": true });
+        config.hooks.run(Event::PreWeb, &payload)?;
+        edits::write(&file, &compose)?;
+        config.hooks.run(Event::PostWeb, &payload)?;
+        cliclack::log::success(format!("Formatted {}.", file.display()))?;
+        formatted += 1;
+    }
+    if formatted == 0 {
+        cliclack::log::info("The compose files are already formatted.")?;
+    }
+
+    Ok(())
+}
+
+fn web_templates() -> Result<()> {
+    let templates = templates::all()?;
+    if json_output() {
+        return print_json(&templates);
+    }
+
+    print_table(
+        &["Name", "Port", "Description"],
+        templates
+            .into_iter()
+            .map(|(name, t)| {
+                let port = t.port.map(|p| p.to_string()).unwrap_or_default();
+                vec![name, port, t.description]
+            })
+            .collect(),
+    );
+    cliclack::log::remark(format!(
+        "Add your own as <name>.toml or <name>.yaml in {}.",
+        templates::dir().display()
+    ))?;
+
+    Ok(())
+}
+
+fn web_undo(args: &WebArgs) -> Result<()> {
+    let file = find_compose_file(args.path.clone())?;
+    let contents = std::fs::read_to_string(&file).context("Could not read the file contents.")?;
+
+    let backups: Vec<_> = edits::backups(&file)
+        .into_iter()
+        .map(|backup| {
+            let modified = std::fs::metadata(&backup)
+                .and_then(|m| m.modified())
+                .map(|m| {
+                    chrono::DateTime::<chrono::Local>::from(m)
+                        .format("%Y-%m-%d %H:%M:%S")
+                        .to_string()
+                })
+                .unwrap_or_default();
+            let name = backup
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            (backup, modified, name)
+        })
+        .collect();
+    ensure!(
+        !backups.is_empty(),
+        "There are no backups of {}.",
+        file.display()
+    );
+    let backup = prompt::select("Select the backup to restore")
+        .items(&backups)
+        .interact()?;
+    let previous = std::fs::read_to_string(&backup).context("Could not read the backup.")?;
+
+    if previous == contents {
+        cliclack::log::info(format!("{} already matches this backup.", file.display()))?;
+        return Ok(());
+    }
+
+    cliclack::note(
+        format!("Restoring {}", backup.display()),
+        edits::diff(&contents, &previous),
+    )?;
+    if !prompt::confirm("Restore the backup?").interact()? {
+        return Err(EurusError::Cancelled.into());
+    }
+
+    let config = get_config().unwrap_or_default();
+    let payload = serde_json::json!({ "file": file, "restored": backup });
+    config.hooks.run(Event::PreWeb, &payload)?;
+    edits::restore(&file, &backup)?;
+    config.hooks.run(Event::PostWeb, &payload)?;
+    cliclack::log::success(format!(
+        "Restored {}. The replaced contents are the newest backup now.",
+        file.display()
+    ))?;
+
+    Ok(())
+}
+
+/// Undoes what `web` adds with caddy: the site labels and their directives,
+/// the caddy network, and then the records for the domains if wanted.
+async fn web_remove(args: &WebArgs, service: Option<String>) -> Result<()> {
+    let file = find_compose_file(args.path.clone())?;
+    let project_dir = file.parent().unwrap_or(Path::new("."));
+    let vars = env::load_variables(project_dir);
+    let contents = std::fs::read_to_string(&file).context("Could not read the file contents.")?;
+    let resolved: Compose = compose::parse(&env::interpolate(&contents, &vars)?)
+        .context("The compose yaml was invalid after variable substitution.")?;
+    let mut compose: Compose =
+        compose::parse(&contents).context("The compose yaml was invalid.")?;
+
+    let proxied: Vec<_> = compose
+        .services
+        .0
+        .iter()
+        .filter_map(|(name, service)| Some((name, service.as_ref()?)))
+        .filter(|(_, service)| {
+            proxy::metadata(service).is_some()
+                || proxy::service_labels(service)
+                    .iter()
+                    .any(|(key, _)| proxy::is_caddy_label(key))
+        })
+        .map(|(name, service)| {
+            (
+                name.clone(),
+                name.clone(),
+                proxy::domains(service).join(", "),
+            )
+        })
+        .collect();
+    ensure!(
+        !proxied.is_empty(),
+        "No service in {} has caddy labels.",
+        file.display()
+    );
+    let name = match service {
+        Some(name) => {
+            ensure!(
+                proxied.iter().any(|(p, _, _)| *p == name),
+                "{name} has no caddy labels."
+            );
+            name
+        }
+        None => prompt::select("Select the service to remove caddy from")
+            .items(&proxied)
+            .interact()?,
+    };
+
+    // What `web` recorded is removed exactly, otherwise every caddy label.
+    let (backend, domains) = match compose.services.0.get_mut(&name) {
+        Some(Some(service)) => proxy::edit_labels(service, false, |service| {
+            match proxy::remove_managed(service) {
+                Some(metadata) => (metadata.proxy, metadata.domains),
+                None => {
+                    proxy::remove_label(&mut service.labels, proxy::MANAGED_LABEL);
+                    (
+                        ProxyBackend::Caddy,
+                        proxy::remove_caddy_labels(&mut service.labels),
+                    )
+                }
+            }
+        }),
+        _ => (ProxyBackend::Caddy, vec![]),
+    };
+
+    let config = get_config().unwrap_or_default();
+    let network = env::interpolate(config.network(backend), &vars)?;
+    if !network.is_empty() && detach_network(&mut compose, &resolved, &name, &network) {
+        cliclack::log::info(format!("Detached {name} from {network}."))?;
+    }
+
+    if !edits::confirm_unchanged(&file, &contents)? {
+        return Err(EurusError::Cancelled.into());
+    }
+
+    let payload = serde_json::json!({ "file": file, "service": name, "removed": domains });
+    config.hooks.run(Event::PreWeb, &payload)?;
+    edits::write(&file, &compose)?;
+    config.hooks.run(Event::PostWeb, &payload)?;
+    cliclack::log::success(format!("Removed the {backend} configuration from {name}."))?;
+
+    for domain in &domains {
+        let Some(zone) = zone_for_name(&config, domain) else {
+            continue;
+        };
+        let provider = provider::for_zone(&config, zone)?;
+        let records: Vec<_> = provider
+            .list_records(&zone.id)
+            .await?
+            .into_iter()
+            .filter(|r| r.name == *domain)
+            .collect();
+        if records.is_empty()
+            || !prompt::confirm(format!(
+                "Delete the {} records for {}?",
+                records.len(),
+                idn::to_unicode(domain)
+            ))
+            .initial_value(false)
+            .interact()?
+        {
+            continue;
+        }
+
+        let payload = serde_json::json!({ "zone": zone, "deleted": records });
+        config.hooks.run(Event::PreDns, &payload)?;
+        if config.trash {
+            trash::keep(zone, &records)?;
+        }
+        for record in &records {
+            provider.delete_record(&zone.id, &record.id).await?;
+        }
+        config.hooks.run(Event::PostDns, &payload)?;
+        cliclack::log::success(format!("Deleted the records for {domain}."))?;
+    }
+
+    Ok(())
+}
+
+/// Marks the services of the project that were labelled for caddy without
+/// eurus as managed, then checks each of their domains has a DNS record.
+async fn web_adopt(args: &WebArgs) -> Result<()> {
+    let config = get_config().unwrap_or_default();
+    let mut adopted = vec![];
+    for file in compose::project_files(&args.files, args.path.clone())? {
+        let contents =
+            std::fs::read_to_string(&file).context("Could not read the file contents.")?;
+        let mut compose: Compose = compose::parse(&contents)
+            .with_context(|| format!("{} is not valid compose yaml.", file.display()))?;
+
+        let unmanaged: Vec<_> = compose
+            .services
+            .0
+            .iter()
+            .filter_map(|(name, service)| Some((name, service.as_ref()?)))
+            .filter(|(_, service)| !proxy::is_managed(service))
+            .filter(|(_, service)| {
+                proxy::label_pairs(&service.labels)
+                    .iter()
+                    .any(|(key, _)| proxy::is_caddy_site(key))
+            })
+            .map(|(name, service)| {
+                (
+                    name.clone(),
+                    name.clone(),
+                    proxy::domains(service).join(", "),
+                )
+            })
+            .collect();
+        if unmanaged.is_empty() {
+            continue;
+        }
+
+        let names: Vec<String> = unmanaged.iter().map(|(name, ..)| name.clone()).collect();
+        let picked: Vec<String> = prompt::multiselect(format!(
+            "Select the services in {} to adopt",
+            file.display()
+        ))
+        .items(&unmanaged)
+        .initial_values(names)
+        .required(false)
+        .interact()?;
+        if picked.is_empty() {
+            continue;
+        }
+
+        let mut services = vec![];
+        for name in &picked {
+            if let Some(Some(service)) = compose.services.0.get_mut(name) {
+                proxy::edit_labels(service, false, |service| {
+                    proxy::add_or_ignore_label(&mut service.labels, proxy::MANAGED_LABEL, "true")
+                });
+                services.push((name.clone(), proxy::domains(service)));
+            }
+        }
+
+        if !edits::confirm_unchanged(&file, &contents)? {
+            return Err(EurusError::Cancelled.into());
+        }
+        let payload = serde_json::json!({ "file": file, "adopted": picked });
+        config.hooks.run(Event::PreWeb, &payload)?;
+        edits::write(&file, &compose)?;
+        config.hooks.run(Event::PostWeb, &payload)?;
+
+        let file = std::fs::canonicalize(&file).unwrap_or(file);
+        for (service, domains) in services {
+            audit::record_or_warn(&audit::Change::Adopt {
+                file: file.clone(),
+                service: service.clone(),
+                domains: domains.clone(),
+            });
+            cliclack::log::success(format!("Adopted {service}."))?;
+            adopted.extend(domains);
+        }
+    }
+    if adopted.is_empty() {
+        cliclack::log::info("There are no services labelled for caddy that eurus doesn't manage.")?;
+        return Ok(());
+    }
+
+    // The zones are listed once each, however many domains are in them.
+    let mut listings: BTreeMap<String, Vec<provider::DnsListResponse>> = BTreeMap::new();
+    for domain in &adopted {
+        let Some(zone) = zone_for_name(&config, domain) else {
+            cliclack::log::warning(format!(
+                "{} isn't in any configured zone, so its DNS record wasn't checked.",
+                idn::to_unicode(domain)
+            ))?;
+            continue;
+        };
+        if !listings.contains_key(&zone.id) {
+            let records = provider::for_zone(&config, zone)?
+                .list_records(&zone.id)
+                .await?;
+            listings.insert(zone.id.clone(), records);
+        }
+        // A wildcard record covers the names below it.
+        let wildcard = domain
+            .split_once('.')
+            .map(|(_, parent)| format!("*.{parent}"));
+        let exists = listings[&zone.id].iter().any(|r| {
+            r.name.eq_ignore_ascii_case(domain)
+                || wildcard
+                    .as_ref()
+                    .is_some_and(|w| r.name.eq_ignore_ascii_case(w))
+        });
+        match exists {
+            true => cliclack::log::info(format!("{} has a DNS record.", idn::to_unicode(domain)))?,
+            false => cliclack::log::warning(format!(
+                "{} has no DNS record, create one with `eurus dns`.",
+                idn::to_unicode(domain)
+            ))?,
+        }
+    }
+
+    Ok(())
+}
+
+fn web_rename_service(args: &WebArgs, old: &str, new: &str) -> Result<()> {
+    let file = find_compose_file(args.path.clone())?;
+    let contents = std::fs::read_to_string(&file).context("Could not read the file contents.")?;
+    let mut compose: Compose =
+        compose::parse(&contents).context("The compose yaml was invalid.")?;
+
+    let updated = rename_service(&mut compose, old, new)?;
+
+    if !edits::confirm_unchanged(&file, &contents)? {
+        return Err(EurusError::Cancelled.into());
+    }
+
+    let config = get_config().unwrap_or_default();
+    let payload = serde_json::json!({ "file": file, "service": new, "renamed_from": old });
+    config.hooks.run(Event::PreWeb, &payload)?;
+    edits::write(&file, &compose)?;
+    config.hooks.run(Event::PostWeb, &payload)?;
+
+    let mut notes = Notes::load()?;
+    notes.rename_service(&file, old, new);
+    notes.save()?;
+    LastRun::update_or_warn(file.parent().unwrap_or(Path::new(".")), |last| {
+        if last.service.as_deref() == Some(old) {
+            last.service = Some(new.to_string());
+        }
+    });
+
+    cliclack::log::success(match updated.is_empty() {
+        true => format!("Renamed {old} to {new}."),
+        false => format!(
+            "Renamed {old} to {new} and updated the references in {}.",
+            updated.join(", ")
+        ),
+    })?;
+
+    Ok(())
+}
+
+/// Swaps the caddy labels of the service serving `domain` for a maintenance
+/// page, keeping the old ones to put back. With `duration` it waits that long
+/// and turns maintenance off again.
+async fn maintenance_on(
+    domain: &str,
+    path: Option<String>,
+    duration: Option<std::time::Duration>,
+    message: &str,
+    up: bool,
+) -> Result<()> {
+    cliclack::intro("eurus-maintenance")?;
+
+    let mut state = Maintenance::load()?;
+    ensure!(
+        !state.sites.contains_key(domain),
+        "{domain} is already in maintenance mode."
+    );
+
+    let file = std::fs::canonicalize(find_compose_file(path)?)?;
+    let contents = std::fs::read_to_string(&file).context("Could not read the file contents.")?;
+    let mut compose: Compose =
+        compose::parse(&contents).context("The compose yaml was invalid.")?;
+    let (service_name, prefix) = maintenance::find_site(&compose, domain)?;
+    let service = compose.services.0[&service_name]
+        .as_mut()
+        .context("The service is empty.")?;
+    let labels = maintenance::enable(service, &prefix, message);
+
+    if !edits::confirm_unchanged(&file, &contents)? {
+        return Err(EurusError::Cancelled.into());
+    }
+
+    let until = duration.map(|d| (chrono::Utc::now() + d).to_rfc3339());
+    let config = get_config().unwrap_or_default();
+    let payload = serde_json::json!({
+        "file": file,
+        "service": service_name,
+        "maintenance": true,
+        "until": until,
+    });
+    config.hooks.run(Event::PreWeb, &payload)?;
+    edits::write(&file, &compose)?;
+    config.hooks.run(Event::PostWeb, &payload)?;
+
+    state.sites.insert(
+        domain.to_string(),
+        maintenance::Site {
+            file: file.clone(),
+            service: service_name.clone(),
+            prefix,
+            labels,
+            until: until.clone(),
+        },
+    );
+    state.save()?;
+    audit::record_or_warn(&audit::Change::Maintenance {
+        domain: domain.to_string(),
+        enabled: true,
+        until,
+    });
+
+    recreate_services(&file, &[&service_name], up)?;
+
+    let Some(duration) = duration else {
+        cliclack::outro(format!(
+            "{domain} is in maintenance mode, `eurus maintenance {domain} off` brings it back."
+        ))?;
+        return Ok(());
+    };
+
+    let spinner = cliclack::spinner();
+    spinner.start(format!(
+        "Turning maintenance off in {}s, Ctrl-C leaves it on.",
+        duration.as_secs()
+    ));
+    tokio::time::sleep(duration).await;
+    spinner.stop("Time's up.");
+
+    maintenance_off(domain, true)?;
+    cliclack::outro(format!("{domain} is back."))?;
+
+    Ok(())
+}
+
+/// Puts back the labels the service had before maintenance mode.
+fn maintenance_off(domain: &str, up: bool) -> Result<()> {
+    let mut state = Maintenance::load()?;
+    let site = state
+        .sites
+        .remove(domain)
+        .ok_or_else(|| EurusError::NotFound(format!("{domain} isn't in maintenance mode.")))?;
+
+    let contents =
+        std::fs::read_to_string(&site.file).context("Could not read the file contents.")?;
+    let mut compose: Compose =
+        compose::parse(&contents).context("The compose yaml was invalid.")?;
+    let service = compose
+        .services
+        .0
+        .get_mut(&site.service)
+        .and_then(|s| s.as_mut())
+        .with_context(|| {
+            format!(
+                "{} no longer has a {} service.",
+                site.file.display(),
+                site.service
+            )
+        })?;
+    maintenance::disable(service, &site);
+
+    let config = get_config().unwrap_or_default();
+    let payload = serde_json::json!({
+        "file": site.file,
+        "service": site.service,
+        "maintenance": false,
+    });
+    config.hooks.run(Event::PreWeb, &payload)?;
+    edits::write(&site.file, &compose)?;
+    config.hooks.run(Event::PostWeb, &payload)?;
+
+    state.save()?;
+    audit::record_or_warn(&audit::Change::Maintenance {
+        domain: domain.to_string(),
+        enabled: false,
+        until: None,
+    });
+
+    recreate_services(&site.file, &[&site.service], up)
+}
+
+/// caddy-docker-proxy only sees new labels once the container is recreated.
+fn recreate_services(file: &Path, services: &[&str], up: bool) -> Result<()> {
+    let command = format!("docker compose up -d {}", services.join(" "));
+    if !up && !prompt::confirm(format!("Run `{command}` now?")).interact()? {
+        cliclack::log::info("The labels take effect once the container is recreated.")?;
+        return Ok(());
+    }
+
+    let status = std::process::Command::new("docker")
+        .args(["compose", "-f"])
+        .arg(file)
+        .args(["up", "-d"])
+        .args(services)
+        .status()
+        .context("Failed to run docker compose.")?;
+    ensure!(status.success(), "docker compose exited with {status}.");
+
+    Ok(())
+}
+
+/// Moves a domain between two services' caddy labels, or between two values
+/// of its DNS record, remembering the previous target for `--back`.
+async fn switch(
+    domain: &str,
+    target: Option<String>,
+    back: bool,
+    path: Option<String>,
+    up: bool,
+) -> Result<()> {
+    cliclack::intro("eurus-switch")?;
+
+    let mut switches = Switches::load()?;
+    let switch = match back {
+        true => switches
+            .domains
+            .get(domain)
+            .with_context(|| format!("{domain} hasn't been switched yet."))?
+            .clone()
+            .reversed(),
+        false => prompt_switch(domain, target, path).await?,
+    };
+
+    match &switch {
+        Switch::Labels { file, from, to } => {
+            let contents =
+                std::fs::read_to_string(file).context("Could not read the file contents.")?;
+            let mut compose: Compose =
+                compose::parse(&contents).context("The compose yaml was invalid.")?;
+            switch::move_site(&mut compose, domain, from, to)?;
+
+            if !edits::confirm_unchanged(file, &contents)? {
+                return Err(EurusError::Cancelled.into());
+            }
+
+            let config = get_config().unwrap_or_default();
+            let payload =
+                serde_json::json!({ "file": file, "domain": domain, "from": from, "to": to });
+            config.hooks.run(Event::PreWeb, &payload)?;
+            edits::write(file, &compose)?;
+            config.hooks.run(Event::PostWeb, &payload)?;
+
+            recreate_services(file, &[from, to], up)?;
+        }
+        Switch::Dns {
+            zone_id,
+            record_id,
+            from,
+            to,
+        } => {
+            let config = get_dns_config().await?;
+            let zone = config
+                .zones
+                .iter()
+                .find(|z| z.id == *zone_id)
+                .with_context(|| format!("The zone of {domain} is no longer in the config."))?;
+            let provider = provider::for_zone(&config, zone)?;
+            let current = provider
+                .list_records(zone_id)
+                .await?
+                .into_iter()
+                .find(|r| r.id == *record_id)
+                .with_context(|| format!("The record for {domain} no longer exists."))?;
+            if current.content != *from {
+                cliclack::log::warning(format!(
+                    "{domain} was changed to {} since it was switched.",
+                    current.content
+                ))?;
+            }
+
+            let mut body = current.to_update();
+            body.content = to.clone();
+            body.record_type = match to.parse::<std::net::IpAddr>() {
+                Ok(std::net::IpAddr::V4(_)) => "A",
+                Ok(std::net::IpAddr::V6(_)) => "AAAA",
+                Err(_) => "CNAME",
+            }
+            .to_string();
+
+            let payload = serde_json::json!({ "zone": zone, "record": body });
+            config.hooks.run(Event::PreDns, &payload)?;
+            provider.upsert_record(zone_id, &body).await?;
+            config.hooks.run(Event::PostDns, &payload)?;
+        }
+    }
+
+    let (from, to) = match &switch {
+        Switch::Labels { from, to, .. } | Switch::Dns { from, to, .. } => (from, to),
+    };
+    cliclack::outro(format!(
+        "{domain} now points at {to} instead of {from}, `eurus switch {domain} --back` to undo."
+    ))?;
+    switches.domains.insert(domain.to_string(), switch.clone());
+    switches.save()?;
+
+    Ok(())
+}
+
+/// Works out what to switch: between services when one in the compose file
+/// serves the domain through caddy, or the DNS record otherwise.
+async fn prompt_switch(
+    domain: &str,
+    target: Option<String>,
+    path: Option<String>,
+) -> Result<Switch> {
+    let local = find_compose_file(path).ok().and_then(|file| {
+        let compose: Compose = compose::parse(&std::fs::read_to_string(&file).ok()?).ok()?;
+        let (service, _) = maintenance::find_site(&compose, domain).ok()?;
+        Some((
+            std::fs::canonicalize(&file).unwrap_or(file),
+            compose,
+            service,
+        ))
+    });
+
+    if let Some((file, compose, from)) = local {
+        let to = match target {
+            Some(target) => target,
+            None => {
+                let choices: Vec<_> = compose
+                    .services
+                    .0
+                    .keys()
+                    .filter(|s| **s != from)
+                    .map(|s| (s.clone(), s, ""))
+                    .collect();
+                ensure!(
+                    !choices.is_empty(),
+                    "There is no other service to switch to."
+                );
+                prompt::select(format!("Switch {domain} from {from} to"))
+                    .items(&choices)
+                    .interact()?
+            }
+        };
+        return Ok(Switch::Labels { file, from, to });
+    }
+
+    let config = get_dns_config().await?;
+    let zone = zone_for_name(&config, domain).ok_or_else(|| {
+        EurusError::NotFound(format!(
+            "No service proxies {domain} and it isn't in any zone."
+        ))
+    })?;
+    let records: Vec<_> = provider::for_zone(&config, zone)?
+        .list_records(&zone.id)
+        .await?
+        .into_iter()
+        .filter(|r| r.name == domain && matches!(r.record_type.as_str(), "A" | "AAAA" | "CNAME"))
+        .collect();
+    let record = match records.as_slice() {
+        [record] => record,
+        [] => bail!(EurusError::NotFound(format!(
+            "{domain} has no A, AAAA or CNAME record to switch."
+        ))),
+        _ => bail!("{domain} has several records, switching only works with one."),
+    };
+
+    let to = match target {
+        Some(target) => target,
+        None => prompt::input(format!("Switch {domain} from {} to", record.content)).interact()?,
+    };
+    Ok(Switch::Dns {
+        zone_id: zone.id.clone(),
+        record_id: record.id.clone(),
+        from: record.content.clone(),
+        to,
+    })
+}
+
+/// A domain served by the running caddy, and the compose service behind it
+/// when it could be found.
+struct LiveService {
+    domain: String,
+    upstream: String,
+    port: String,
+    service: Option<(PathBuf, String)>,
+}
+
+/// Maps the routes of the running caddy back to compose services, by the
+/// address of the container they proxy to, and optionally labels those
+/// services so eurus knows about them.
+async fn web_import_live(args: &WebArgs, record: bool) -> Result<()> {
+    let config = get_config().unwrap_or_default();
+    let url = caddy_admin_url(&config);
+
+    let spinner = cliclack::spinner();
+    spinner.start(format!("Reading the config of caddy at {url}..."));
+    let routes = CaddyAdmin::new(url).routes().await;
+    spinner.stop("Read the caddy config.");
+    let routes = routes?;
+
+    let containers = docker::running().unwrap_or_else(|e| {
+        let _ = cliclack::log::warning(format!(
+            "Could not list containers, only matching by service name: {e}"
+        ));
+        vec![]
+    });
+    // Upstreams that use a service name rather than a container address can
+    // still be matched against the compose file in the given directory.
+    let local = find_compose_file(args.path.clone()).ok().and_then(|file| {
+        let compose: Compose = compose::parse(&std::fs::read_to_string(&file).ok()?).ok()?;
+        Some((file, compose))
+    });
+
+    let mut live = vec![];
+    for route in routes {
+        let Some(upstream) = route.upstreams.first() else {
+            continue;
+        };
+        let (host, port) = upstream.rsplit_once(':').unwrap_or((upstream, "80"));
+
+        let service = containers
+            .iter()
+            .find(|c| c.addresses.iter().any(|a| a == host) || c.name == host)
+            .and_then(|c| Some((c.config_files.first()?.clone(), c.service.clone()?)))
+            .or_else(|| {
+                let (file, compose) = local.as_ref()?;
+                compose
+                    .services
+                    .0
+                    .contains_key(host)
+                    .then(|| (file.clone(), host.to_string()))
+            });
+
+        for domain in &route.hosts {
+            live.push(LiveService {
+                domain: domain.clone(),
+                upstream: upstream.clone(),
+                port: port.to_string(),
+                service: service.clone(),
+            });
+        }
+    }
+
+    if live.is_empty() {
+        cliclack::log::info("Caddy has no reverse proxy routes.")?;
+        return Ok(());
+    }
+
+    let mut composes: BTreeMap<PathBuf, Option<Compose>> = BTreeMap::new();
+    for file in live.iter().filter_map(|l| l.service.as_ref().map(|s| &s.0)) {
+        composes
+            .entry(file.clone())
+            .or_insert_with(|| compose::parse(&std::fs::read_to_string(file).ok()?).ok());
+    }
+    let labelled = |l: &LiveService| -> bool {
+        l.service.as_ref().is_some_and(|(file, name)| {
+            composes
+                .get(file)
+                .cloned()
+                .flatten()
+                .and_then(|c| c.services.0.get(name).cloned().flatten())
+                .is_some_and(|s| proxy::domains(&s).contains(&l.domain))
+        })
+    };
+
+    print_table(
+        &["DOMAIN", "UPSTREAM", "SERVICE", "COMPOSE FILE", "LABELLED"],
+        live.iter()
+            .map(|l| {
+                let (file, service) = match &l.service {
+                    Some((file, service)) => (file.display().to_string(), service.clone()),
+                    None => ("-".to_string(), "-".to_string()),
+                };
+                let state = match (&l.service, labelled(l)) {
+                    (None, _) => "-",
+                    (Some(_), true) => "yes",
+                    (Some(_), false) => "no",
+                };
+                vec![
+                    l.domain.clone(),
+                    l.upstream.clone(),
+                    service,
+                    file,
+                    state.to_string(),
+                ]
+            })
+            .collect(),
+    );
+
+    if !record {
+        return Ok(());
+    }
+
+    let mut by_file: BTreeMap<PathBuf, Vec<&LiveService>> = BTreeMap::new();
+    for l in live.iter().filter(|l| !labelled(l)) {
+        if let Some((file, _)) = &l.service {
+            by_file.entry(file.clone()).or_default().push(l);
+        }
+    }
+
+    for (file, services) in by_file {
+        let contents = std::fs::read_to_string(&file)
+            .with_context(|| format!("Could not read {}.", file.display()))?;
+        let mut compose: Compose = compose::parse(&contents)
+            .with_context(|| format!("{} is not valid compose yaml.", file.display()))?;
+
+        let mut names = vec![];
+        for l in services {
+            let (_, name) = l.service.as_ref().unwrap();
+            let Some(mut service) = compose.services.0.get(name).cloned().flatten() else {
+                continue;
+            };
+            // A service routing other domains gets the next indexed site.
+            ProxyBackend::Caddy.apply(
+                &mut service,
+                name,
+                std::slice::from_ref(&l.domain),
+                &l.port,
+                "",
+            );
+            compose.services.0.insert(name.clone(), Some(service));
+            names.push(name.clone());
+        }
+
+        if names.is_empty() || !edits::confirm_unchanged(&file, &contents)? {
+            continue;
+        }
+
+        let payload = serde_json::json!({ "file": file, "services": names });
+        config.hooks.run(Event::PreWeb, &payload)?;
+        edits::write(&file, &compose)?;
+        config.hooks.run(Event::PostWeb, &payload)?;
+        cliclack::log::success(format!(
+            "Labelled {} in {}.",
+            names.join(", "),
+            file.display()
+        ))?;
+    }
+
+    Ok(())
+}
+
+async fn deploy(mut args: WebArgs, record: RecordArgs, up: bool) -> Result<()> {
+    cliclack::intro("eurus-deploy")?;
+    ensure!(
+        args.host.is_none(),
+        "deploy starts the service with docker here, use `web --host` for remote files."
+    );
+    ensure!(
+        !args.stdout && args.out_file.is_none(),
+        "deploy starts the edited service, so it can't use --stdout or --out-file."
+    );
+
+    pick_project(&mut args)?;
+
+    let proxied = add_proxy_to_service(&args).await?;
+    for proxied in &proxied {
+        cliclack::log::success(format!(
+            "Added {} to {} in {}.",
+            proxied.backend,
+            proxied.service,
+            proxied.file.display()
+        ))?;
+    }
+
+    for proxied in &proxied {
+        let saved = update_record(
+            Some(&proxied.domain),
+            &record,
+            Some((&proxied.file, &proxied.service)),
+        )
+        .await?;
+        cliclack::log::success(format!("Updated the DNS record for {}.", proxied.domain))?;
+        if let Some(timeout) = record.wait {
+            wait_for_propagation(&saved, timeout).await?;
+        }
+        if record.explain {
+            explain_record(&saved).await?;
+        }
+    }
+
+    if up || prompt::confirm("Run `docker compose up -d` now?").interact()? {
+        let status = std::process::Command::new("docker")
+            .args(["compose", "-f"])
+            .arg(&proxied[0].file)
+            .args(["up", "-d"])
+            .status()
+            .context("Failed to run docker compose.")?;
+        ensure!(status.success(), "docker compose exited with {status}.");
+    }
+
+    for proxied in &proxied {
+        show_url(&proxied.domain, args.qr)?;
+    }
+
+    let domains: Vec<_> = proxied.iter().map(|p| p.domain.as_str()).collect();
+    cliclack::outro(match domains.as_slice() {
+        [domain] => format!("{domain} is deployed."),
+        domains => format!("{} are deployed.", domains.join(", ")),
+    })?;
+
+    Ok(())
+}
+
+/// Prints the URL the service will be reachable at, optionally as a QR code.
+fn show_url(domain: &str, qr: bool) -> Result<()> {
+    let url = format!("https://{domain}");
+
+    if qr {
+        let code = qrcode::QrCode::new(&url)?;
+        let rendered = code
+            .render::<qrcode::render::unicode::Dense1x2>()
+            .quiet_zone(true)
+            .build();
+        cliclack::note(&url, rendered)?;
+    } else {
+        cliclack::note("Your service will be available at", &url)?;
+    }
+
+    Ok(())
+}
+
+/// Prints a Caddyfile site block for `domain`, or appends it to the configured
+/// Caddyfile.
+fn write_caddyfile_block(
+    config: &Config,
+    domain: &str,
+    upstream: &str,
+    options: &proxy::CaddyOptions,
+) -> Result<()> {
+    let prefix = proxy::label_prefix();
+    let mut labels = BTreeMap::from([
+        (prefix.to_string(), domain.to_string()),
+        (format!("{prefix}.reverse_proxy"), upstream.to_string()),
+    ]);
+    labels.extend(options.labels(prefix));
+    let block = proxy::caddyfile_from_labels(&labels, &[]).unwrap_or_default();
+
+    match &config.caddyfile_path {
+        Some(path) => {
+            let existing = std::fs::read_to_string(path).unwrap_or_default();
+            let separator = match existing.is_empty() || existing.ends_with("\n\n") {
+                true => "",
+                false if existing.ends_with('\n') => "\n",
+                false => "\n\n",
+            };
+            std::fs::write(path, format!("{existing}{separator}{block}"))
+                .with_context(|| format!("Could not write to {}.", path.display()))?;
+            cliclack::log::success(format!("Added {domain} to {}.", path.display()))?;
+        }
+        None => print!("{block}"),
+    }
+
+    Ok(())
+}
+
+/// Records what `web` set up in the service's `x-eurus` block: the labels
+/// added since `before` on top of the ones recorded already, or every proxy
+/// label when eurus managed the service before it kept the block.
+fn record_metadata(
+    config: &Config,
+    service: &mut Service,
+    backend: ProxyBackend,
+    domains: &[String],
+    port: &str,
+    before: &[(String, String)],
+    adopted: bool,
+) {
+    let mut metadata = proxy::metadata(service).unwrap_or_default();
+    for (key, value) in proxy::service_labels(service) {
+        let added =
+            !before.contains(&(key.clone(), value)) || (adopted && proxy::is_proxy_label(&key));
+        if added && !metadata.labels.contains(&key) {
+            metadata.labels.push(key);
+        }
+    }
+    if backend == ProxyBackend::NginxProxy {
+        for (key, _) in backend.environment(domains, port) {
+            if !metadata.environment.contains(&key) {
+                metadata.environment.push(key);
+            }
+        }
+    }
+    metadata.domains = domains.to_vec();
+    metadata.port = port.to_string();
+    metadata.zone = zone_for_name(config, &domains[0]).map(|z| z.name.clone());
+    metadata.proxy = backend;
+    proxy::set_metadata(service, &metadata);
+}
+
+/// The TLS, basic auth, header and websocket directives for a service served
+/// by caddy, from the flags, or asked for when none are given.
+fn caddy_options(
+    args: &WebArgs,
+    name: &str,
+    template: Option<&Template>,
+) -> Result<proxy::CaddyOptions> {
+    let mut options = proxy::CaddyOptions {
+        tls: args.tls.clone(),
+        headers: args.headers.clone(),
+        websocket: args.websocket,
+        ..Default::default()
+    };
+    for user in &args.basic_auth {
+        let (user, hash) = user
+            .split_once(':')
+            .with_context(|| format!("--basic-auth takes USER:HASH, not {user}."))?;
+        options
+            .basic_auth
+            .push((user.to_string(), hash.to_string()));
+    }
+    if let Some(template) = template {
+        return Ok(template.caddy_options(options));
+    }
+    if !options.is_empty() {
+        return Ok(options);
+    }
+
+    let picked: Vec<&str> =
+        prompt::multiselect(format!("Select anything else caddy should do for {name}"))
+            .items(&[
+                (
+                    "tls",
+                    "TLS",
+                    "caddy's internal CA or a certificate of your own",
+                ),
+                ("auth", "Basic auth", "ask for a user and password"),
+                ("headers", "Headers", "set or remove response headers"),
+                (
+                    "websocket",
+                    "Websockets",
+                    "keep streams open when caddy reloads",
+                ),
+            ])
+            .required(false)
+            .interact()?;
+
+    if picked.contains(&"tls") {
+        let internal = prompt::select("Which certificate should caddy use?")
+            .item(true, "Internal", "caddy's own CA, for local domains")
+            .item(false, "Custom", "a certificate and key caddy can read")
+            .interact()?;
+        options.tls = Some(match internal {
+            true => "internal".to_string(),
+            false => {
+                let cert: String =
+                    prompt::input("Enter the path of the certificate, as caddy sees it.")
+                        .interact()?;
+                let key: String = prompt::input("Enter the path of its key.").interact()?;
+                format!("{} {}", cert.trim(), key.trim())
+            }
+        });
+    }
+
+    if picked.contains(&"auth") {
+        loop {
+            let user: String = prompt::input("Enter a user, or nothing to stop.")
+                .required(false)
+                .interact()?;
+            let user = user.trim();
+            if user.is_empty() {
+                break;
+            }
+            let password: String =
+                prompt::password(format!("Enter the password for {user}.")).interact()?;
+            let hash = match caddy::hash_password(&password) {
+                Ok(hash) => hash,
+                Err(e) => {
+                    cliclack::log::warning(e)?;
+                    prompt::input("Enter a bcrypt hash of it instead, e.g. from htpasswd -nB.")
+                        .validate(|hash: &String| match hash.starts_with("$2") {
+                            true => Ok(()),
+                            false => Err("That isn't a bcrypt hash."),
+                        })
+                        .interact()?
+                }
+            };
+            options.basic_auth.push((user.to_string(), hash));
+        }
+    }
+
+    if picked.contains(&"headers") {
+        loop {
+            let header: String = prompt::input(
+                "Enter a header like `X-Frame-Options DENY`, `-Server` to remove one, or nothing \
+                 to stop.",
+            )
+            .required(false)
+            .interact()?;
+            if header.trim().is_empty() {
+                break;
+            }
+            options.headers.push(header.trim().to_string());
+        }
+    }
+
+    options.websocket = picked.contains(&"websocket");
+    Ok(options)
+}
+
+/// Asks which of the other services (databases, caches, ...) the proxied one
+/// needs, and what state to wait for them to reach, adding them to its
+/// `depends_on`.
+fn prompt_depends_on(resolved: &Compose, name: &str, service: &mut Service) -> Result<()> {
+    let existing: Vec<String> = match &service.depends_on {
+        DependsOnOptions::Simple(services) => services.clone(),
+        DependsOnOptions::Conditional(services) => services.keys().cloned().collect(),
+    };
+    let others: Vec<_> = resolved
+        .services
+        .0
+        .iter()
+        .filter(|(key, _)| *key != name && !existing.contains(key))
+        .map(|(key, s)| {
+            let image = s.as_ref().and_then(|s| s.image.clone()).unwrap_or_default();
+            (key.clone(), key.clone(), image)
+        })
+        .collect();
+    if others.is_empty() {
+        return Ok(());
+    }
+
+    let picked = prompt::multiselect(format!("Select the services {name} depends on"))
+        .items(&others)
+        .required(false)
+        .interact()?;
+    if picked.is_empty() {
+        return Ok(());
+    }
+
+    // Conditions need the long form, which the short form converts to as
+    // waiting for the service to start.
+    let mut conditions: IndexMap<String, DependsCondition> = match &service.depends_on {
+        DependsOnOptions::Simple(services) => services
+            .iter()
+            .map(|s| {
+                let condition = DependsCondition {
+                    condition: "service_started".to_string(),
+                };
+                (s.clone(), condition)
+            })
+            .collect(),
+        DependsOnOptions::Conditional(services) => services.clone(),
+    };
+    for dependency in picked {
+        let healthcheck = resolved
+            .services
+            .0
+            .get(&dependency)
+            .cloned()
+            .flatten()
+            .is_some_and(|s| s.healthcheck.is_some());
+        let condition = prompt::select(format!("Wait for {dependency} to be"))
+            .item("service_started", "started", "")
+            .item(
+                "service_healthy",
+                "healthy",
+                if healthcheck {
+                    ""
+                } else {
+                    "it has no healthcheck"
+                },
+            )
+            .item(
+                "service_completed_successfully",
+                "completed",
+                "for one-off setup jobs",
+            )
+            .initial_value(if healthcheck {
+                "service_healthy"
+            } else {
+                "service_started"
+            })
+            .interact()?;
+        conditions.insert(
+            dependency,
+            DependsCondition {
+                condition: condition.to_string(),
+            },
+        );
+    }
+    service.depends_on = DependsOnOptions::Conditional(conditions);
+
+    Ok(())
+}
+
+/// One of the files of a compose project, as [`add_proxy_to_service`] edits it.
+struct Source {
+    file: PathBuf,
+    contents: String,
+    /// Only used for validation and display, edits are made to `compose` so
+    /// `${VAR}` references are written back untouched.
+    resolved: Compose,
+    compose: Compose,
+    changed: bool,
+}
+
+impl Source {
+    fn load(file: &Path, vars: &env::Variables) -> Result<Self> {
+        let contents = std::fs::read_to_string(file)
+            .with_context(|| format!("Could not read {}.", file.display()))?;
+        let resolved = compose::parse(&env::interpolate(&contents, vars)?).with_context(|| {
+            format!(
+                "The compose yaml in {} was invalid after variable substitution.",
+                file.display()
+            )
+        })?;
+        let compose = compose::parse(&contents)
+            .with_context(|| format!("The compose yaml in {} was invalid.", file.display()))?;
+
+        Ok(Self {
+            file: file.to_path_buf(),
+            contents,
+            resolved,
+            compose,
+            changed: false,
+        })
+    }
+
+    fn service(&self, name: &str) -> Option<&Service> {
+        self.resolved.services.0.get(name).and_then(Option::as_ref)
+    }
+}
+
+/// Which file the labels for `name` go in: the last one already proxying it,
+/// or else the first one that defines it.
+fn target_source(sources: &[Source], name: &str) -> Option<usize> {
+    sources
+        .iter()
+        .rposition(|s| {
+            s.service(name)
+                .is_some_and(|s| !proxy::domains(s).is_empty())
+        })
+        .or_else(|| sources.iter().position(|s| s.service(name).is_some()))
+}
+
+/// Offers to create the external network the proxy is on when docker doesn't
+/// have it yet, as `docker compose up` fails without it.
+fn ensure_network(network: &str) -> Result<()> {
+    let command = format!("docker network create {network}");
+    match docker::network_exists(network) {
+        Ok(true) => (),
+        Ok(false) => {
+            if prompt::confirm(format!(
+                "The network {network} doesn't exist yet, create it?"
+            ))
+            .initial_value(true)
+            .interact()?
+            {
+                docker::create_network(network)?;
+                cliclack::log::success(format!("Created the network {network}."))?;
+            } else {
+                cliclack::log::remark(format!(
+                    "Create it with `{command}` before starting the stack."
+                ))?;
+            }
+        }
+        Err(e) => {
+            tracing::debug!("Could not list the docker networks: {e}");
+            cliclack::log::remark(format!(
+                "Could not check that the network {network} exists. If it doesn't, create it with \
+                 `{command}`."
+            ))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Points out where the running containers differ from the compose files,
+/// which usually means they weren't recreated after the files changed.
+fn warn_diverged(
+    sources: &[Source],
+    merged: &Compose,
+    containers: &[docker::Container],
+    network: &str,
+) -> Result<()> {
+    for container in containers {
+        if let Some(name) = container
+            .service
+            .as_ref()
+            .filter(|s| !merged.services.0.contains_key(*s))
+        {
+            cliclack::log::warning(format!(
+                "{name} is running, but isn't in the compose files anymore."
+            ))?;
+        }
+    }
+
+    for (name, service) in merged
+        .services
+        .0
+        .iter()
+        .filter_map(|(name, s)| Some((name, s.as_ref()?)))
+    {
+        let Some(container) = containers.iter().find(|c| c.service.as_ref() == Some(name)) else {
+            cliclack::log::warning(format!(
+                "{name} is in the compose files, but isn't running."
+            ))?;
+            continue;
+        };
+
+        let mut differences = vec![];
+        let labels: Vec<_> = proxy::label_pairs(&service.labels)
+            .into_iter()
+            .filter(|(key, value)| container.labels.get(key) != Some(value))
+            .map(|(key, _)| key)
+            .collect();
+        if !labels.is_empty() {
+            differences.push(format!("the labels {}", labels.join(", ")));
+        }
+        let ports: Vec<_> = compose::container_ports(service)
+            .into_iter()
+            .filter(|p| !container.ports.contains(p))
+            .map(|p| p.to_string())
+            .collect();
+        if !ports.is_empty() {
+            differences.push(format!("the ports {}", ports.join(", ")));
+        }
+        let on_network = sources.iter().any(|source| {
+            source
+                .service(name)
+                .is_some_and(|s| compose::on_network(&source.resolved, s, network))
+        });
+        if on_network && !container.networks.iter().any(|n| n == network) {
+            differences.push(format!("the network {network}"));
+        }
+
+        if !differences.is_empty() {
+            cliclack::log::warning(format!(
+                "{name} is running without {} from the compose files, `docker compose up -d` \
+                 recreates it.",
+                differences.join(" and ")
+            ))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Offers to connect more services to the proxy's network without routing to
+/// them, e.g. a websocket sidecar of a proxied app.
+fn prompt_join_network(
+    sources: &mut [Source],
+    merged: &Compose,
+    chosen: &[(String, Vec<String>, String)],
+    network: &str,
+) -> Result<()> {
+    let others: Vec<_> = merged
+        .services
+        .0
+        .iter()
+        .filter(|(key, s)| s.is_some() && !chosen.iter().any(|(name, ..)| name == *key))
+        .filter(|(key, _)| {
+            !sources.iter().any(|source| {
+                source
+                    .service(key)
+                    .is_some_and(|s| compose::on_network(&source.resolved, s, network))
+            })
+        })
+        .map(|(key, s)| {
+            let image = s.as_ref().and_then(|s| s.image.clone()).unwrap_or_default();
+            (key.clone(), key.clone(), image)
+        })
+        .collect();
+    if others.is_empty() {
+        return Ok(());
+    }
+
+    let picked: Vec<String> =
+        prompt::multiselect(format!("Select other services to connect to {network}"))
+            .items(&others)
+            .required(false)
+            .interact()?;
+    for name in picked {
+        let target = target_source(sources, &name)
+            .with_context(|| format!("There is no service called {name}."))?;
+        let source = &mut sources[target];
+        let mut service = source
+            .compose
+            .services
+            .0
+            .get(&name)
+            .cloned()
+            .flatten()
+            .with_context(|| format!("There is no service called {name}."))?;
+        attach_network(&mut source.compose, &source.resolved, &mut service, network);
+        source.compose.services.0.insert(name, Some(service));
+        source.changed = true;
+    }
+
+    Ok(())
+}
+
+/// Offers to add caddy-docker-proxy to the main compose file when the project
+/// doesn't have it and docker isn't running it already, so a small stack
+/// doesn't need a second project just for the proxy.
+fn prompt_add_caddy(sources: &mut [Source], merged: &Compose, network: &str) -> Result<()> {
+    let is_caddy = |image: &str| image.contains("caddy-docker-proxy");
+    if merged.services.0.iter().any(|(key, s)| {
+        key == "caddy"
+            || s.as_ref()
+                .and_then(|s| s.image.as_deref())
+                .is_some_and(is_caddy)
+    }) {
+        return Ok(());
+    }
+    match docker::running() {
+        Ok(containers) if containers.iter().any(|c| is_caddy(&c.image)) => return Ok(()),
+        Ok(_) => (),
+        // Without docker there's no telling whether the proxy runs elsewhere.
+        Err(e) => {
+            tracing::debug!("Could not list the running containers: {e}");
+            return Ok(());
+        }
+    }
+
+    let source = &mut sources[0];
+    if !prompt::confirm(format!(
+        "caddy-docker-proxy isn't running, add it to {}?",
+        source.file.display()
+    ))
+    .interact()?
+    {
+        return Ok(());
+    }
+
+    let stack = compose::parse(&compose::caddy_stack(network, proxy::label_prefix()))?;
+    let mut service = stack
+        .services
+        .0
+        .get("caddy")
+        .cloned()
+        .flatten()
+        .context("The caddy stack has no caddy service.")?;
+    // Attached like the proxied services, so a parameterized network is
+    // referenced by its key.
+    service.networks = Default::default();
+    attach_network(&mut source.compose, &source.resolved, &mut service, network);
+    for (name, volume) in stack.volumes.0 {
+        source.compose.volumes.0.entry(name).or_insert(volume);
+    }
+    source
+        .compose
+        .services
+        .0
+        .insert("caddy".to_string(), Some(service));
+    source.changed = true;
+
+    Ok(())
+}
+
+async fn add_proxy_to_service(args: &WebArgs) -> Result<Vec<ProxiedService>> {
+    let files = compose::project_files(&args.files, args.path.clone())?;
+    let project_dir = files[0].parent().unwrap_or(Path::new(".")).to_path_buf();
+    let project_dir = project_dir.as_path();
+    let dotenv = env::load_dotenv(project_dir);
+
+    let mut config = get_config().unwrap_or_default();
+    let backend = args.proxy.unwrap_or(config.proxy);
+    ensure!(
+        backend == ProxyBackend::Caddy
+            || args.emit == WebOutput::Caddyfile
+            || (args.tls.is_none()
+                && args.basic_auth.is_empty()
+                && args.headers.is_empty()
+                && !args.websocket),
+        "--tls, --basic-auth, --header and --websocket are only for caddy."
+    );
+    ensure!(
+        args.emit == WebOutput::Compose || (!args.stdout && args.out_file.is_none()),
+        "--stdout and --out-file are only for compose edits."
+    );
+    let template = match &args.template {
+        Some(name) => Some(templates::all()?.remove(name).with_context(|| {
+            format!("There is no template called {name}, see `eurus web templates`.")
+        })?),
+        None => None,
+    };
+    if backend != ProxyBackend::Caddy
+        && args.emit == WebOutput::Compose
+        && template
+            .as_ref()
+            .is_some_and(|t| !t.caddy_options(Default::default()).is_empty())
+    {
+        cliclack::log::warning(format!(
+            "The template's caddy directives are left out for {backend}."
+        ))?;
+    }
+    let configured_network = config.proxy_network(backend);
+    if configured_network.is_empty() && args.emit == WebOutput::Compose {
+        let mut input = prompt::input(format!("Enter the network that {backend} is on."));
+        if let Some(network) = env::default_network(&dotenv) {
+            input = input.default_input(network);
+        }
+        *configured_network = input.interact()?;
+        save_config(&config)?;
+    }
+    let configured_network = config.proxy_network(backend).clone();
+
+    let vars = env::load_variables(project_dir);
+    let mut sources = files
+        .iter()
+        .map(|file| Source::load(file, &vars))
+        .collect::<Result<Vec<_>>>()?;
+    if sources.len() > 1 {
+        cliclack::log::info(format!(
+            "Using {}.",
+            files
+                .iter()
+                .map(|f| f.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))?;
+    }
+    // Every service of the project, merged across the files, for listing and
+    // for the dependencies to offer.
+    let merged = compose::merge_services(&sources.iter().map(|s| &s.resolved).collect::<Vec<_>>());
+    // The network may be declared under a different key with a parameterized
+    // `name: ${CADDY_NETWORK}`, in which case that key is the one to reference.
+    let caddy_network = env::interpolate(&configured_network, &vars)?;
+
+    let containers = match args.live {
+        true => {
+            let containers = docker::project_containers(&files)?;
+            ensure!(
+                !containers.is_empty(),
+                "None of the project's services are running."
+            );
+            warn_diverged(&sources, &merged, &containers, &caddy_network)?;
+            containers
+        }
+        false => vec![],
+    };
+    let container = |name: &str| {
+        containers
+            .iter()
+            .find(|c| c.service.as_deref() == Some(name))
+    };
+
+    let notes = Notes::load()?;
+    let services: Vec<_> = merged
+        .services
+        .0
+        .iter()
+        .filter(|e| e.1.is_some())
+        .filter(|(key, _)| !args.live || container(key).is_some())
+        .map(|(key, _)| {
+            // Ports from every file, as docker compose adds them up.
+            let hint = match container(key) {
+                Some(c) => format!(
+                    "{} on {}",
+                    c.ports
+                        .iter()
+                        .map(u16::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    c.networks.join(", ")
+                ),
+                None => sources
+                    .iter()
+                    .filter_map(|s| s.service(key))
+                    .flat_map(|s| match &s.ports {
+                        Ports::Short(p) => p.clone(),
+                        Ports::Long(p) => p.iter().map(|p| p.target.to_string()).collect(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            };
+            let note =
+                target_source(&sources, key).and_then(|i| notes.service(&sources[i].file, key));
+            let hint = match note {
+                Some(note) if hint.is_empty() => note.clone(),
+                Some(note) => format!("{hint} - {note}"),
+                None => hint,
+            };
+            (key.clone(), key, hint)
+        })
+        .collect();
+    let last = LastRun::load(project_dir);
+    let mut session = Session::start("web", project_dir)?;
+    let previous: Vec<String> = match session.get("services") {
+        Some(services) => services.split(',').map(String::from).collect(),
+        None => last.service.iter().cloned().collect(),
+    };
+    let selected: Vec<String> = prompt::multiselect(format!(
+        "Select the services to add {backend} to (space to pick)"
+    ))
+    .items(&services)
+    .initial_values(previous)
+    .required(true)
+    .interact()?;
+    session.answer("services", selected.join(","))?;
+
+    let mut chosen = vec![];
+    let mut caddy_directives = BTreeMap::new();
+    for name in &selected {
+        if selected.len() > 1 {
+            cliclack::log::step(name)?;
+        }
+        // The domain and port are only worth offering again for the same
+        // service.
+        let last = match last.service.as_ref() == Some(name) {
+            true => last.clone(),
+            false => LastRun::default(),
+        };
+        // What an earlier run recorded in the service's `x-eurus` block.
+        let recorded = sources
+            .iter()
+            .filter_map(|s| s.service(name))
+            .find_map(proxy::metadata);
+        let recorded_domains = recorded.as_ref().map(|m| m.domains.join(", "));
+
+        let domains = match args.domains.is_empty() {
+            true => {
+                let mut input = prompt::input(format!(
+                    "Enter the domain for {name}, separate several with commas."
+                ));
+                if let Some(domain) = session
+                    .get(&format!("domain {name}"))
+                    .or(recorded_domains.as_deref())
+                    .or(last.domain.as_deref())
+                {
+                    input = input.default_input(domain);
+                }
+                input.interact::<String>()?
+            }
+            false => args.domains.join(","),
+        };
+        let domains = domains
+            .split(',')
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+            .map(idn::to_ascii)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        ensure!(!domains.is_empty(), "{name} needs a domain.");
+        session.answer(&format!("domain {name}"), domains.join(", "))?;
+        if backend == ProxyBackend::Caddy || args.emit == WebOutput::Caddyfile {
+            caddy_directives.insert(name.clone(), caddy_options(args, name, template.as_ref())?);
+        }
+        // The ports the running container exposes, then the container side
+        // of the ports in the compose files.
+        let compose_ports: Vec<u16> = container(name)
+            .into_iter()
+            .flat_map(|c| c.ports.clone())
+            .chain(
+                sources
+                    .iter()
+                    .filter_map(|s| s.service(name))
+                    .flat_map(compose::container_ports),
+            )
+            .fold(vec![], |mut ports, port| {
+                if !ports.contains(&port) {
+                    ports.push(port);
+                }
+                ports
+            });
+        if let Some(port) = template.as_ref().and_then(|t| t.port) {
+            cliclack::log::info(format!("Using port {port} for {name} from the template."))?;
+            session.answer(&format!("port {name}"), port.to_string())?;
+            chosen.push((name.clone(), domains, port.to_string()));
+            continue;
+        }
+        if args.auto_port {
+            if let Some(port) = compose_ports.first() {
+                cliclack::log::info(format!("Using port {port} for {name}."))?;
+                session.answer(&format!("port {name}"), port.to_string())?;
+                chosen.push((name.clone(), domains, port.to_string()));
+                continue;
+            }
+            cliclack::log::warning(format!("{name} has no ports or expose to take one from."))?;
+        }
+
+        // Kept as entered so a `${VAR}` port stays parameterized in the label.
+        let default_port = session
+            .get(&format!("port {name}"))
+            .map(String::from)
+            .or(recorded.map(|m| m.port))
+            .or(last.port.clone())
+            .or_else(|| compose_ports.first().map(u16::to_string))
+            .or_else(|| env::default_port(&dotenv, name));
+        let port: String = loop {
+            let mut input = prompt::input("Enter the port this application exposes");
+            if let Some(port) = &default_port {
+                input = input.default_input(port);
+            }
+            let text: String = input.interact()?;
+
+            if env::resolve_port(&text, &vars).is_ok() {
+                break text.trim().to_string();
+            }
+        };
+        session.answer(&format!("port {name}"), &port)?;
+
+        chosen.push((name.clone(), domains, port));
+    }
+
+    if let Some((service, domains, port)) = chosen.last() {
+        LastRun::update_or_warn(project_dir, |last| {
+            last.service = Some(service.clone());
+            last.domain = Some(domains.join(", "));
+            last.port = Some(port.clone());
+        });
+    }
+
+    if args.emit == WebOutput::Caddyfile {
+        for (service, domains, port) in &chosen {
+            let upstream = format!("{service}:{}", env::resolve_port(port, &vars)?);
+            write_caddyfile_block(
+                &config,
+                &domains.join(", "),
+                &upstream,
+                &caddy_directives.get(service).cloned().unwrap_or_default(),
+            )?;
+        }
+        session.finish()?;
+
+        return Ok(chosen
+            .into_iter()
+            .flat_map(|(service, domains, _)| {
+                let file = target_source(&sources, &service)
+                    .map_or(files[0].clone(), |i| files[i].clone());
+                domains.into_iter().map(move |domain| ProxiedService {
+                    backend: ProxyBackend::Caddy,
+                    file: file.clone(),
+                    service: service.clone(),
+                    domain,
+                })
+            })
+            .collect());
+    }
+
+    let mut payloads = vec![];
+    let mut proxied = vec![];
+    let swarm_node = std::cell::OnceCell::new();
+    for (name, domains, port) in &chosen {
+        let target = target_source(&sources, name)
+            .with_context(|| format!("There is no service called {name}."))?;
+        let source = &mut sources[target];
+        let mut service = source
+            .compose
+            .services
+            .0
+            .get(name)
+            .cloned()
+            .flatten()
+            .with_context(|| format!("There is no service called {name}."))?;
+
+        // Swarm only reads labels under `deploy:`, which a service on a swarm
+        // node usually has, while plain compose ignores them.
+        let swarm = args.swarm
+            || (args.host.is_none()
+                && service.deploy.is_some()
+                && *swarm_node.get_or_init(docker::swarm_active));
+        let before = proxy::service_labels(&service);
+        let adopted = proxy::metadata(&service).is_none() && proxy::is_managed(&service);
+        proxy::edit_labels(&mut service, swarm, |service| {
+            backend.apply(service, name, domains, port, &caddy_network);
+            proxy::add_or_ignore_label(&mut service.labels, proxy::MANAGED_LABEL, "true");
+            for (key, value) in template.iter().flat_map(|t| &t.labels) {
+                proxy::add_or_ignore_label(&mut service.labels, key, value);
+            }
+            if let Some(options) = caddy_directives.get(name) {
+                let sites = proxy::caddy_sites(&service.labels, domains);
+                options.apply(service, &sites);
+            }
+        });
+
+        record_metadata(
+            &config,
+            &mut service,
+            backend,
+            domains,
+            port,
+            &before,
+            adopted,
+        );
+
+        attach_network(
+            &mut source.compose,
+            &source.resolved,
+            &mut service,
+            &caddy_network,
+        );
+        prompt_depends_on(&merged, name, &mut service)?;
+        let has_healthcheck = service.healthcheck.is_some()
+            || merged
+                .services
+                .0
+                .get(name)
+                .cloned()
+                .flatten()
+                .is_some_and(|s| s.healthcheck.is_some());
+        if !has_healthcheck && prompt::toggle(format!("Add a healthcheck to {name}?")).interact()? {
+            let image = merged
+                .services
+                .0
+                .get(name)
+                .cloned()
+                .flatten()
+                .and_then(|s| s.image)
+                .unwrap_or_default();
+            let port = env::resolve_port(port, &vars)?.to_string();
+            let command: String = prompt::input("Healthcheck command")
+                .default_input(&healthcheck::command(&image, &port, &config.healthchecks))
+                .interact()?;
+            service.healthcheck = Some(healthcheck::healthcheck(&command));
+        }
+
+        let source = &mut sources[target];
+        source
+            .compose
+            .services
+            .0
+            .insert(name.clone(), Some(service));
+        source.changed = true;
+        payloads.push(serde_json::json!({
+            "file": source.file,
+            "service": name,
+            "domain": domains[0],
+            "domains": domains,
+            "port": port,
+            "proxy": backend,
+        }));
+        proxied.extend(domains.iter().map(|domain| ProxiedService {
+            backend,
+            file: source.file.clone(),
+            service: name.clone(),
+            domain: domain.clone(),
+        }));
+    }
+
+    prompt_join_network(&mut sources, &merged, &chosen, &caddy_network)?;
+    // Docker here says nothing about the proxy on a remote host.
+    if backend == ProxyBackend::Caddy && args.host.is_none() {
+        prompt_add_caddy(&mut sources, &merged, &caddy_network)?;
+    }
+    if args.canonical || config.canonical_compose {
+        for source in sources.iter_mut().filter(|s| s.changed) {
+            compose::canonicalize(&mut source.compose, false);
+        }
+    }
+
+    let in_place = !args.stdout && args.out_file.is_none();
+    ensure!(
+        in_place || sources.iter().filter(|s| s.changed).count() == 1,
+        "--stdout and --out-file need the edits to be in a single compose file."
+    );
+    for source in sources.iter().filter(|s| s.changed && in_place) {
+        if !edits::confirm_unchanged(&source.file, &source.contents)? {
+            return Err(EurusError::Cancelled.into());
+        }
+    }
+
+    for payload in &payloads {
+        config.hooks.run(Event::PreWeb, payload)?;
+    }
+
+    for source in sources.iter().filter(|s| s.changed) {
+        match &args.out_file {
+            _ if in_place => edits::write(&source.file, &source.compose)?,
+            Some(out) => {
+                std::fs::write(out, edits::render(&source.contents, &source.compose)?)
+                    .with_context(|| format!("Could not write {}.", out.display()))?;
+                cliclack::log::success(format!(
+                    "Wrote the edited {} to {}.",
+                    source.file.display(),
+                    out.display()
+                ))?;
+            }
+            None => print!("{}", edits::render(&source.contents, &source.compose)?),
+        }
+    }
+    session.finish()?;
+    match &args.host {
+        Some(host) => cliclack::log::remark(format!(
+            "The network {caddy_network} has to exist on {host}, create it there with `docker \
+             network create {caddy_network}` if it doesn't."
+        ))?,
+        None => ensure_network(&caddy_network)?,
+    }
+
+    for payload in &payloads {
+        config.hooks.run(Event::PostWeb, payload)?;
+    }
+
+    if args.caddy_api {
+        for (service, domains, port) in &chosen {
+            let upstream = format!("{service}:{}", env::resolve_port(port, &vars)?);
+            for domain in domains {
+                push_caddy_route(domain, &upstream).await?;
+            }
+        }
+    }
+
+    Ok(proxied)
+}
+
+async fn account(command: AccountCommand) -> Result<()> {
+    let config = get_api_key_config()?;
+    let cloudflare = Cloudflare::from_config(&config)?;
+
+    match command {
+        AccountCommand::List => {
+            let (accounts, zones) =
+                futures::try_join!(cloudflare.list_accounts(), cloudflare.list_zones())?;
+            let count = |account: &cloudflare::Account, configured: bool| {
+                zones
+                    .iter()
+                    .filter(|z| z.account.as_ref().is_some_and(|a| a.id == account.id))
+                    .filter(|z| !configured || config.zones.iter().any(|c| c.id == z.id))
+                    .count()
+            };
+
+            if json_output() {
+                let accounts: Vec<_> = accounts
+                    .iter()
+                    .map(|a| {
+                        serde_json::json!({
+                            "account": a,
+                            "zones": count(a, false),
+                            "configured": count(a, true),
+                        })
+                    })
+                    .collect();
+                return print_json(&accounts);
+            }
+            print_table(
+                &["NAME", "ID", "ZONES", "CONFIGURED"],
+                accounts
+                    .iter()
+                    .map(|a| {
+                        vec![
+                            a.name.clone(),
+                            a.id.clone(),
+                            count(a, false).to_string(),
+                            count(a, true).to_string(),
+                        ]
+                    })
+                    .collect(),
+            );
+        }
+        AccountCommand::Zones { account } => {
+            let account = match account {
+                Some(account) => cloudflare
+                    .list_accounts()
+                    .await?
+                    .into_iter()
+                    .find(|a| a.id == account || a.name.eq_ignore_ascii_case(&account))
+                    .with_context(|| format!("The api key can't access an account {account}."))?,
+                None => select_account(&cloudflare, &config.zones, "Select the account").await?,
+            };
+            let mut zones = cloudflare.list_zones_in(&account.id).await?;
+            zones.sort_by(|a, b| a.name.cmp(&b.name));
+            let configured =
+                |zone: &provider::ZoneDetailsResponse| config.zones.iter().any(|c| c.id == zone.id);
+
+            if json_output() {
+                let zones: Vec<_> = zones
+                    .iter()
+                    .map(|z| {
+                        serde_json::json!({
+                            "name": z.name,
+                            "id": z.id,
+                            "configured": configured(z),
+                        })
+                    })
+                    .collect();
+                return print_json(&zones);
+            }
+            print_table(
+                &["NAME", "ID", "CONFIGURED"],
+                zones
+                    .iter()
+                    .map(|z| {
+                        vec![
+                            idn::to_unicode(&z.name),
+                            z.id.clone(),
+                            if configured(z) { "yes" } else { "no" }.to_string(),
+                        ]
+                    })
+                    .collect(),
+            );
+            if zones.iter().any(|z| !configured(z)) {
+                cliclack::log::remark("Add them with `eurus zone import`.")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn zone(command: ZoneCommand) -> Result<()> {
+    match command {
+        ZoneCommand::Create { domain } => zone_create(&domain).await,
+        ZoneCommand::List => zone_list().await,
+        ZoneCommand::Add {
+            zone,
+            provider,
+            no_proxy,
+        } => zone_add(&zone, provider, no_proxy).await,
+        ZoneCommand::Import => zone_import().await,
+        ZoneCommand::Redirect { zone } => zone_redirect(zone.as_deref()).await,
+        ZoneCommand::Settings {
+            zone,
+            ssl,
+            always_https,
+            https_rewrites,
+            min_tls,
+        } => {
+            let wanted = [
+                ("ssl", ssl),
+                ("always_use_https", always_https),
+                ("automatic_https_rewrites", https_rewrites),
+                ("min_tls_version", min_tls),
+            ];
+            zone_settings(zone.as_deref(), &wanted).await
+        }
+        ZoneCommand::Remove { name, dangerous } => zone_remove(name.as_deref(), dangerous).await,
+    }
+}
+
+/// Sets up the redirect between www and the apex: a proxied record for the
+/// host being redirected, so requests reach Cloudflare, and a redirect rule.
+async fn zone_redirect(name: Option<&str>) -> Result<()> {
+    cliclack::intro("eurus-redirect")?;
+
+    let mut config = get_dns_config().await?;
+    let zone = select_zone(&mut config, name).await?;
+    ensure!(
+        zone.provider == Provider::Cloudflare,
+        "Redirect rules need the zone to be on Cloudflare, {} uses {}.",
+        zone.name,
+        zone.provider
+    );
+
+    let apex = zone.name.clone();
+    let www = format!("www.{apex}");
+    let to_apex = prompt::select("Which way should it redirect?")
+        .item(true, format!("{www} -> {apex}"), "")
+        .item(false, format!("{apex} -> {www}"), "")
+        .interact()?;
+    let (from, to) = match to_apex {
+        true => (www, apex),
+        false => (apex, www),
+    };
+
+    let cloudflare = Cloudflare::from_config(&config)?;
+    let provider = provider::for_zone(&config, &zone)?;
+    let records = provider.list_records(&zone.id).await?;
+    let is_address =
+        |r: &&provider::DnsListResponse| matches!(r.record_type.as_str(), "A" | "AAAA" | "CNAME");
+
+    if !records.iter().filter(is_address).any(|r| r.name == to) {
+        cliclack::log::warning(format!(
+            "{to} has no record yet, the redirect will lead nowhere until it does."
+        ))?;
+    }
+
+    let placeholder = match to_apex {
+        true => ("CNAME", to.as_str()),
+        false => ("AAAA", "100::"),
+    };
+    proxy_redirect_source(
+        &config,
+        &zone,
+        provider.as_ref(),
+        &records,
+        &from,
+        placeholder,
+    )
+    .await?;
+
+    // A rule for either direction on this zone is replaced, so switching
+    // directions doesn't leave a redirect loop behind.
+    let rule = cloudflare::Rule::redirect_pattern(&from, &to, 301);
+    let reverse = cloudflare::Rule::redirect_pattern(&to, &from, 301);
+    let mut rules = cloudflare.redirect_rules(&zone.id).await?;
+    rules.retain(|r| r.expression != rule.expression && r.expression != reverse.expression);
+    rules.push(rule);
+    cloudflare.set_redirect_rules(&zone.id, &rules).await?;
+
+    cliclack::outro(format!("https://{from} now redirects to https://{to}."))?;
+
+    Ok(())
+}
+
+/// Purges the URLs from Cloudflare's cache, grouped by the zone each is in,
+/// or everything cached for the zone when there are none.
+async fn cache_purge(zone: Option<&str>, urls: &[String]) -> Result<()> {
+    cliclack::intro("eurus-purge")?;
+
+    let mut config = get_dns_config().await?;
+    let cloudflare = Cloudflare::from_config(&config)?;
+    let ensure_cloudflare = |zone: &ZoneInfo| {
+        ensure!(
+            zone.provider == Provider::Cloudflare,
+            "Only Cloudflare caches, {} uses {}.",
+            zone.name,
+            zone.provider
+        );
+        Ok(())
+    };
+
+    if urls.is_empty() {
+        let zone = select_zone(&mut config, zone).await?;
+        ensure_cloudflare(&zone)?;
+        if !prompt::confirm(format!(
+            "Purge everything Cloudflare has cached for {}?",
+            zone.name
+        ))
+        .initial_value(false)
+        .interact()?
+        {
+            return Err(EurusError::Cancelled.into());
+        }
+
+        cloudflare.purge_everything(&zone.id).await?;
+        cliclack::outro(format!("Purged everything cached for {}.", zone.name))?;
+        return Ok(());
+    }
+
+    let given =
+        match zone {
+            Some(name) => Some(zone_for_name(&config, name).cloned().ok_or_else(|| {
+                EurusError::NotFound(format!("{name} is not a configured zone."))
+            })?),
+            None => None,
+        };
+    let mut by_zone: BTreeMap<String, (ZoneInfo, Vec<String>)> = BTreeMap::new();
+    for url in urls {
+        // Cloudflare matches the URL as visitors request it, so a bare host
+        // and path means https.
+        let url = match url.contains("://") {
+            true => url.clone(),
+            false => format!("https://{url}"),
+        };
+        let host = reqwest::Url::parse(&url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .ok_or_else(|| EurusError::ValidationError(format!("{url} is not a valid URL.")))?;
+        let zone = match &given {
+            Some(zone) => zone.clone(),
+            None => zone_for_name(&config, &host).cloned().ok_or_else(|| {
+                EurusError::NotFound(format!("{host} isn't in any configured zone."))
+            })?,
+        };
+        ensure_cloudflare(&zone)?;
+        by_zone
+            .entry(zone.id.clone())
+            .or_insert_with(|| (zone, vec![]))
+            .1
+            .push(url);
+    }
+
+    for (zone, urls) in by_zone.values() {
+        cloudflare.purge_urls(&zone.id, urls).await?;
+        cliclack::log::success(format!("Purged {} URLs from {}.", urls.len(), zone.name))?;
+    }
+    cliclack::outro("Cloudflare fetches them from the origin on the next request.")?;
+
+    Ok(())
+}
+
+/// Writes an Ingress routing the domains to a Kubernetes Service, or an
+/// HTTPRoute with `--gateway`, and with `--dns` points the domains at the
+/// ingress controller.
+async fn k8s(args: K8sArgs) -> Result<()> {
+    cliclack::intro("eurus-k8s")?;
+    let service = match args.service {
+        Some(service) => service,
+        None => prompt::input("Service name").interact()?,
+    };
+    let domains = match args.domains.is_empty() {
+        false => args.domains,
+        true => {
+            let domains: String = prompt::input("Domains, separated by commas").interact()?;
+            domains
+                .split(',')
+                .map(str::trim)
+                .filter(|d| !d.is_empty())
+                .map(idn::to_ascii)
+                .collect::<std::result::Result<_, _>>()?
+        }
+    };
+    let port = match args.port {
+        Some(port) => port,
+        None => prompt::input("Service port")
+            .default_input("80")
+            .interact()?,
+    };
+    let route = k8s::Route {
+        service,
+        namespace: args.namespace,
+        domains,
+        port,
+        class: args.class,
+        issuer: args.issuer,
+    };
+
+    let (kind, manifest) = match &args.gateway {
+        Some(gateway) => ("HTTPRoute", route.http_route(gateway)?),
+        None => ("Ingress", route.ingress()),
+    };
+    let manifest = k8s::to_yaml(&manifest)?;
+    match &args.out {
+        Some(path) => {
+            std::fs::write(path, &manifest)
+                .with_context(|| format!("Could not write to {}.", path.display()))?;
+            cliclack::log::success(format!("Wrote the {kind} to {}.", path.display()))?;
+        }
+        None => print!("{manifest}"),
+    }
+
+    if args.dns {
+        let config = get_dns_config().await?;
+        let address = match args.ingress.or(config.k8s_ingress.clone()) {
+            Some(address) => address,
+            None => ingress_address()?,
+        };
+        for domain in &route.domains {
+            point_at_ingress(&config, domain, &address).await?;
+        }
+    }
+
+    cliclack::outro(match &args.out {
+        Some(path) => format!("Apply it with `kubectl apply -f {}`.", path.display()),
+        None => "Pipe it to `kubectl apply -f -` to apply it.".to_string(),
+    })?;
+
+    Ok(())
+}
+
+/// The address of the cluster's ingress controller, from its LoadBalancer
+/// service.
+fn ingress_address() -> Result<String> {
+    let balancers = k8s::load_balancers()
+        .context("Couldn't find the ingress controller, pass its address with --ingress.")?;
+    match balancers.as_slice() {
+        [] => bail!(EurusError::NotFound(
+            "No LoadBalancer service has an address, pass the ingress controller's with --ingress."
+                .to_string()
+        )),
+        [(_, address)] => Ok(address.clone()),
+        [(_, first), ..] => {
+            let mut select =
+                prompt::select("Which is the ingress controller?").initial_value(first.clone());
+            for (name, address) in &balancers {
+                select = select.item(address.clone(), address, name);
+            }
+            select.interact()
+        }
+    }
+}
+
+/// Points the domain's record at the ingress controller, creating it if
+/// there is none and asking before replacing a different target.
+async fn point_at_ingress(config: &Config, domain: &str, address: &str) -> Result<()> {
+    let zone = zone_for_name(config, domain)
+        .cloned()
+        .ok_or_else(|| EurusError::NotFound(format!("{domain} isn't in any configured zone.")))?;
+    let record_type = match address.parse::<std::net::IpAddr>() {
+        Ok(std::net::IpAddr::V4(_)) => "A",
+        Ok(std::net::IpAddr::V6(_)) => "AAAA",
+        Err(_) => "CNAME",
+    };
+
+    let provider = provider::for_zone(config, &zone)?;
+    let existing = provider
+        .list_records(&zone.id)
+        .await?
+        .into_iter()
+        .filter(|r| matches!(r.record_type.as_str(), "A" | "AAAA" | "CNAME"))
+        .find(|r| r.name == domain);
+    let body = match existing {
+        Some(r) if r.record_type == record_type && r.content == address => {
+            cliclack::log::info(format!("{domain} already points at {address}."))?;
+            return Ok(());
+        }
+        Some(r) => {
+            if !prompt::confirm(format!(
+                "{domain} points at {}, point it at {address} instead?",
+                r.content
+            ))
+            .interact()?
+            {
+                return Ok(());
+            }
+            let mut body = r.to_update();
+            body.record_type = record_type.to_string();
+            body.content = address.to_string();
+            body.comment = config.record_comment(None, None).or(body.comment);
+            body
+        }
+        None => DnsCreateUpdate {
+            name: domain.to_string(),
+            record_type: record_type.to_string(),
+            content: address.to_string(),
+            proxied: zone.proxied,
+            ttl: match zone.proxied {
+                true => AUTO_TTL,
+                false => zone.default_ttl(),
+            },
+            comment: config.record_comment(None, None),
+            ..Default::default()
+        },
+    };
+
+    let payload = serde_json::json!({ "zone": zone, "record": body });
+    config.hooks.run(Event::PreDns, &payload)?;
+    provider.upsert_record(&zone.id, &body).await?;
+    config.hooks.run(Event::PostDns, &payload)?;
+    cliclack::log::success(format!("Pointed {domain} at {address}."))?;
+
+    Ok(())
+}
+
+/// The values offered for a zone setting, with a hint for each.
+fn zone_setting_choices(id: &str) -> Vec<(&'static str, &'static str)> {
+    match id {
+        "ssl" => vec![
+            ("off", "no HTTPS at all"),
+            ("flexible", "plain HTTP to the origin"),
+            ("full", "HTTPS to the origin, with any certificate"),
+            ("strict", "HTTPS to the origin, with a valid certificate"),
+        ],
+        "min_tls_version" => vec![
+            ("1.0", ""),
+            ("1.1", ""),
+            ("1.2", "recommended"),
+            ("1.3", ""),
+        ],
+        _ => vec![("on", ""), ("off", "")],
+    }
+}
+
+/// Shows the zone's SSL and HTTPS settings at Cloudflare and changes the
+/// ones given, or offers to change them when none are.
+async fn zone_settings(name: Option<&str>, wanted: &[(&str, Option<String>)]) -> Result<()> {
+    let mut config = get_dns_config().await?;
+    let zone = select_zone(&mut config, name).await?;
+    ensure!(
+        zone.provider == Provider::Cloudflare,
+        "Zone settings are only managed on Cloudflare, {} uses {}.",
+        zone.name,
+        zone.provider
+    );
+
+    let cloudflare = Cloudflare::from_config(&config)?;
+    let settings = cloudflare.zone_settings(&zone.id).await?;
+    let label = |id: &str| {
+        cloudflare::ZONE_SETTINGS
+            .iter()
+            .find(|(i, _)| *i == id)
+            .map_or(id.to_string(), |(_, label)| label.to_string())
+    };
+
+    let mut changes: Vec<_> = settings
+        .iter()
+        .filter_map(|setting| {
+            let (_, value) = wanted.iter().find(|(id, _)| *id == setting.id)?;
+            let value = value.clone()?;
+            (value != setting.display_value()).then_some((setting, value))
+        })
+        .collect();
+
+    if wanted.iter().all(|(_, value)| value.is_none()) {
+        if json_output() {
+            return print_json(&settings);
+        }
+        print_table(
+            &["SETTING", "VALUE"],
+            settings
+                .iter()
+                .map(|s| vec![label(&s.id), s.display_value()])
+                .collect(),
+        );
+        if settings
+            .iter()
+            .any(|s| s.id == "ssl" && s.display_value() == "flexible")
+        {
+            cliclack::log::warning(
+                "Flexible SSL reaches the origin over plain HTTP, which caddy redirects to HTTPS, \
+                 so proxied sites end up in a redirect loop. Full or strict avoids that.",
+            )?;
+        }
+        if !prompt::toggle("Change any of them?").interact()? {
+            return Ok(());
+        }
+
+        for setting in settings.iter().filter(|s| s.editable) {
+            let current = setting.display_value();
+            let choices: Vec<_> = zone_setting_choices(&setting.id)
+                .into_iter()
+                .map(|(value, hint)| (value.to_string(), value, hint))
+                .collect();
+            let value = prompt::select(label(&setting.id))
+                .items(&choices)
+                .initial_value(current.clone())
+                .interact()?;
+            if value != current {
+                changes.push((setting, value));
+            }
+        }
+    }
+
+    if changes.is_empty() {
+        cliclack::outro(format!("{} already has these settings.", zone.name))?;
+        return Ok(());
+    }
+
+    // Checked first, so a setting that can't change doesn't leave the others
+    // half applied.
+    if let Some((setting, _)) = changes.iter().find(|(s, _)| !s.editable) {
+        bail!(
+            "{} can't be changed on {}'s plan.",
+            label(&setting.id),
+            zone.name
+        );
+    }
+
+    let mut updated = vec![];
+    for (setting, value) in changes {
+        updated.push(
+            cloudflare
+                .set_zone_setting(&zone.id, &setting.id, &value)
+                .await?,
+        );
+        cliclack::log::success(format!(
+            "{}: {} -> {value}",
+            label(&setting.id),
+            setting.display_value()
+        ))?;
+    }
+
+    if json_output() {
+        print_json(&updated)?;
+    }
+    cliclack::outro(format!(
+        "Updated {} settings of {}.",
+        updated.len(),
+        zone.name
+    ))?;
+
+    Ok(())
+}
+
+/// Makes sure `from` has a proxied record, as redirect rules only apply to
+/// traffic through Cloudflare. A missing one is created as `placeholder`.
+async fn proxy_redirect_source(
+    config: &Config,
+    zone: &ZoneInfo,
+    provider: &dyn provider::DnsProvider,
+    records: &[provider::DnsListResponse],
+    from: &str,
+    placeholder: (&str, &str),
+) -> Result<()> {
+    let existing: Vec<_> = records
+        .iter()
+        .filter(|r| matches!(r.record_type.as_str(), "A" | "AAAA" | "CNAME"))
+        .filter(|r| r.name == from)
+        .collect();
+    let changes: Vec<DnsCreateUpdate> = if existing.is_empty() {
+        // The redirect happens at the edge, so the record only has to exist.
+        // 100:: is the discard prefix Cloudflare suggests for this.
+        vec![DnsCreateUpdate {
+            name: from.to_string(),
+            record_type: placeholder.0.to_string(),
+            content: placeholder.1.to_string(),
+            proxied: true,
+            ttl: AUTO_TTL,
+            comment: config.record_comment(None, None),
+            ..Default::default()
+        }]
+    } else if existing.iter().all(|r| r.proxied) {
+        vec![]
+    } else if prompt::toggle(format!(
+        "{from} isn't proxied, so the redirect wouldn't apply. Proxy it?"
+    ))
+    .interact()?
+    {
+        existing
+            .iter()
+            .filter(|r| !r.proxied)
+            .map(|r| DnsCreateUpdate {
+                id: Some(r.id.clone()),
+                name: r.name.clone(),
+                record_type: r.record_type.clone(),
+                content: r.content.clone(),
+                proxied: true,
+                ttl: r.ttl,
+                comment: config.record_comment(None, None).or(r.comment.clone()),
+                ..Default::default()
+            })
+            .collect()
+    } else {
+        return Err(EurusError::Cancelled.into());
+    };
+
+    for body in &changes {
+        let payload = serde_json::json!({ "zone": zone, "record": body });
+        config.hooks.run(Event::PreDns, &payload)?;
+        provider.upsert_record(&zone.id, body).await?;
+        config.hooks.run(Event::PostDns, &payload)?;
+    }
+
+    Ok(())
+}
+
+async fn zone_list() -> Result<()> {
+    let mut config = get_config()?;
+    ensure!(!config.zones.is_empty(), "There are no zones configured.");
+
+    let spinner = cliclack::spinner();
+    spinner.start("Checking zones...");
+    let stale = stale_zones(&config).await;
+    spinner.stop("Checked zones.");
+
+    if json_output() {
+        let zones: Vec<_> = config
+            .zones
+            .iter()
+            .zip(&stale)
+            .map(|(z, stale)| serde_json::json!({ "zone": z, "stale": stale }))
+            .collect();
+        return print_json(&zones);
+    }
+
+    print_table(
+        &["NAME", "ID", "PROVIDER", "PROXIED", "STATUS"],
+        config
+            .zones
+            .iter()
+            .zip(&stale)
+            .map(|(z, stale)| {
+                vec![
+                    idn::to_unicode(&z.name),
+                    z.id.clone(),
+                    z.provider.to_string(),
+                    if z.proxied { "yes" } else { "no" }.to_string(),
+                    if *stale { "not found" } else { "ok" }.to_string(),
+                ]
+            })
+            .collect(),
+    );
+
+    let stale: Vec<_> = config
+        .zones
+        .iter()
+        .zip(stale)
+        .filter(|(_, stale)| *stale)
+        .map(|(z, _)| z.clone())
+        .collect();
+    let mut removed = false;
+    for zone in stale {
+        if prompt::toggle(format!(
+            "{} is no longer accessible. Remove it from the config?",
+            zone.name
+        ))
+        .interact()?
+        {
+            config.zones.retain(|z| *z != zone);
+            removed = true;
+        }
+    }
+    if removed {
+        save_config(&config)?;
+    }
+
+    Ok(())
+}
+
+async fn zone_add(zone: &str, provider: Provider, no_proxy: bool) -> Result<()> {
+    cliclack::intro("eurus-zone")?;
+
+    let mut config = match provider {
+        Provider::Cloudflare => get_api_key_config()?,
+        _ => get_config().unwrap_or_default(),
+    };
+
+    // Cloudflare zone ids are opaque, so a name is looked up among the zones
+    // the token can access. Other providers are asked for the id directly.
+    let details = match provider {
+        Provider::Cloudflare if zone.contains('.') => {
+            let name = idn::to_ascii(zone)?;
+            Cloudflare::from_config(&config)?
+                .list_zones()
+                .await?
+                .into_iter()
+                .find(|z| z.name == name)
+                .with_context(|| format!("The Cloudflare token can't access {zone}."))?
+        }
+        _ => {
+            let placeholder = ZoneInfo {
+                id: zone.to_string(),
+                provider,
+                ..Default::default()
+            };
+            provider::for_zone(&config, &placeholder)?
+                .get_zone(zone)
+                .await?
+        }
+    };
+
+    ensure!(
+        !config.zones.iter().any(|z| z.id == details.id),
+        "{} is already configured.",
+        details.name
+    );
+
+    let name = details.name.clone();
+    config.zones.push(ZoneInfo {
+        id: details.id,
+        name: details.name,
+        provider,
+        proxied: !no_proxy,
+        account: details.account.map(|a| a.id),
+        ..Default::default()
+    });
+    save_config(&config)?;
+
+    cliclack::outro(format!("Added {}.", idn::to_unicode(&name)))?;
+
+    Ok(())
+}
+
+async fn zone_import() -> Result<()> {
+    cliclack::intro("eurus-zone")?;
+
+    let mut config = get_api_key_config()?;
+    let zones = pick_zones(&Cloudflare::from_config(&config)?, &config.zones).await?;
+    if zones.is_empty() {
+        cliclack::outro("There are no new zones to add.")?;
+        return Ok(());
+    }
+
+    let names: Vec<_> = zones.iter().map(|z| z.name.clone()).collect();
+    config.zones.extend(zones);
+    save_config(&config)?;
+
+    cliclack::outro(format!("Added {}.", names.join(", ")))?;
+
+    Ok(())
+}
+
+async fn zone_remove(name: Option<&str>, dangerous: bool) -> Result<()> {
+    cliclack::intro("eurus-zone")?;
+
+    let mut config = get_config()?;
+    let zone = match name {
+        Some(name) => config
+            .zones
+            .iter()
+            .find(|z| z.name == name || z.id == name)
+            .ok_or_else(|| EurusError::NotFound(format!("{name} is not a configured zone.")))?
+            .clone(),
+        None => select_zone(&mut config, None).await?,
+    };
+
+    #[derive(Clone, PartialEq, Eq)]
+    enum Removal {
+        Forget,
+        Delete,
+    }
+
+    let removal = if dangerous && zone.provider == Provider::Cloudflare {
+        prompt::select(format!("How should {} be removed?", zone.name))
+            .item(
+                Removal::Forget,
+                "Forget locally",
+                "keeps the zone at Cloudflare",
+            )
+            .item(
+                Removal::Delete,
+                "Delete at Cloudflare",
+                "deletes the zone and all of its records",
+            )
+            .interact()?
+    } else {
+        Removal::Forget
+    };
+
+    match removal {
+        Removal::Forget => {
+            if !prompt::confirm(format!("Forget {} locally?", zone.name)).interact()? {
+                cliclack::outro_cancel("Nothing was changed.")?;
+                return Ok(());
+            }
+        }
+        Removal::Delete => {
+            let expected = zone.name.clone();
+            prompt::input(format!(
+                "This permanently deletes {} and its records. Type the zone name to confirm.",
+                zone.name
+            ))
+            .validate(move |input: &String| {
+                if *input == expected {
+                    Ok(())
+                } else {
+                    Err("The name does not match.")
+                }
+            })
+            .interact::<String>()?;
+
+            Cloudflare::from_config(&config)?
+                .delete_zone(&zone.id)
+                .await?;
+            cliclack::log::warning(format!("Deleted {} at Cloudflare.", zone.name))?;
+        }
+    }
+
+    config.zones.retain(|z| *z != zone);
+    save_config(&config)?;
+
+    cliclack::outro(format!("Removed {} from the config.", zone.name))?;
+
+    Ok(())
+}
+
+async fn zone_create(domain: &str) -> Result<()> {
+    cliclack::intro("eurus-zone")?;
+
+    let mut config = get_api_key_config()?;
+    let cloudflare = Cloudflare::from_config(&config)?;
+
+    let account = select_account(
+        &cloudflare,
+        &config.zones,
+        "Select the account to add the zone to",
+    )
+    .await?;
+
+    let created = cloudflare.create_zone(domain, &account.id).await?;
+    cliclack::note(
+        "Nameservers",
+        format!(
+            "Point {} at these nameservers at your registrar:\n{}",
+            created.name,
+            created.name_servers.join("\n")
+        ),
+    )?;
+
+    let zone = ZoneInfo {
+        id: created.id,
+        name: created.name,
+        provider: Provider::Cloudflare,
+        proxied: true,
+        account: Some(account.id.clone()),
+        ..Default::default()
+    };
+
+    if prompt::toggle("Import existing records from public DNS?").interact()? {
+        import_public_records(&cloudflare, &zone, domain, &resolve::COMMON_SUBDOMAINS).await?;
+    }
+
+    config.zones.push(zone);
+    save_config(&config)?;
+
+    cliclack::outro(format!("Added {domain}."))?;
+
+    Ok(())
+}
+
+async fn status(probe: bool) -> Result<()> {
+    let config = get_config().unwrap_or_default();
+    let domains = scan::managed_domains(&config).await?;
+
+    if probe {
+        probe_domains(domains.into_keys().collect()).await?;
+    } else if json_output() {
+        print_json(&domains)?;
+    } else {
+        print_table(
+            &["DOMAIN", "SOURCE"],
+            domains
+                .into_iter()
+                .map(|(d, sources)| vec![idn::to_unicode(&d), sources.join(", ")])
+                .collect(),
+        );
+    }
+
+    report_certs(&unproxied_domains(&config).await?, config.cert_warning_days).await?;
+
+    Ok(())
+}
+
+/// Compares the proxied services in the compose files under `path` with the
+/// DNS records and the config: domains without a record, records that point
+/// somewhere other than this host, records pointing at this host that no
+/// service serves, and services that aren't on their proxy's network.
+async fn status_drift(path: &Path) -> Result<()> {
+    let config = get_config()?;
+    let files = match path.is_dir() {
+        true => compose::find_projects(path),
+        false => vec![path.to_path_buf()],
+    };
+    ensure!(
+        !files.is_empty(),
+        "There are no compose files in {}.",
+        path.display()
+    );
+
+    let spinner = cliclack::spinner();
+    spinner.start("Reading the zones...");
+    let listings = try_join_all(config.zones.iter().map(|zone| async {
+        provider::for_zone(&config, zone)?
+            .list_records(&zone.id)
+            .await
+    }))
+    .await;
+    // Without the address, records just can't be compared with this host.
+    let ip = ddns::agreed_ipv4(&config.ipv4_resolvers(), config.ddns_consensus).await;
+    spinner.stop("Read the zones.");
+    let records: Vec<_> = listings?.into_iter().flatten().collect();
+    let ip = match ip {
+        Ok(ip) => Some(ip.to_string()),
+        Err(e) => {
+            cliclack::log::warning(format!(
+                "Could not find this host's public IP, so records aren't compared with it: {e}"
+            ))?;
+            None
+        }
+    };
+
+    let mut problems = vec![];
+    let mut served = std::collections::BTreeSet::new();
+    for file in &files {
+        let contents = std::fs::read_to_string(file)
+            .with_context(|| format!("Could not read {}.", file.display()))?;
+        let compose = compose::parse(&contents)
+            .with_context(|| format!("{} is not a valid compose file.", file.display()))?;
+        for (name, service) in &compose.services.0 {
+            let Some(service) = service else {
+                continue;
+            };
+            let domains = proxy::domains(service);
+            let metadata = proxy::metadata(service);
+            if domains.is_empty() && metadata.is_none() {
+                continue;
+            }
+            let mut problem = |domain: &str, message: String| {
+                problems.push((
+                    Some((file.clone(), name.clone())),
+                    domain.to_string(),
+                    message,
+                ));
+            };
+
+            // The `x-eurus` block says what `web` set up, so anything of it
+            // that's gone was changed by hand since.
+            if let Some(metadata) = &metadata {
+                let labels = proxy::service_labels(service);
+                for key in &metadata.labels {
+                    if !labels.iter().any(|(k, _)| k == key) {
+                        problem(
+                            "-",
+                            format!("lost the label {key}, `eurus web` puts it back"),
+                        );
+                    }
+                }
+                for domain in metadata.domains.iter().filter(|d| !domains.contains(d)) {
+                    problem(domain, "is in x-eurus, but no label serves it".to_string());
+                }
+            }
+
+            if let Some(backend) = proxy::backend(service) {
+                let network = config.network(backend);
+                if !network.is_empty() && !compose::on_network(&compose, service, network) {
+                    problem("-", format!("isn't on the {backend} network {network}"));
+                }
+            }
+
+            for domain in domains {
+                served.insert(domain.clone());
+                if domain.contains('{') || domain.starts_with(':') {
+                    continue;
+                }
+                if zone_for_name(&config, &domain).is_none() {
+                    problem(&domain, "isn't in a configured zone".to_string());
+                    continue;
+                }
+                let wildcard = domain.split_once('.').map(|(_, p)| format!("*.{p}"));
+                let record = records
+                    .iter()
+                    .filter(|r| matches!(r.record_type.as_str(), "A" | "AAAA" | "CNAME"))
+                    .find(|r| r.name == domain)
+                    .or_else(|| {
+                        records.iter().find(|r| {
+                            Some(&r.name) == wildcard.as_ref()
+                                && matches!(r.record_type.as_str(), "A" | "AAAA" | "CNAME")
+                        })
+                    });
+                let Some(record) = record else {
+                    problem(&domain, "has no DNS record".to_string());
+                    continue;
+                };
+                let Some(ip) = &ip else {
+                    continue;
+                };
+                if let Some(points_at) = record_address(record, &records).await {
+                    if points_at != *ip {
+                        problem(
+                            &domain,
+                            format!("points at {points_at}, but this host is {ip}"),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(ip) = &ip {
+        for record in records
+            .iter()
+            .filter(|r| r.record_type == "A" && r.content == *ip && !r.name.starts_with('*'))
+            .filter(|r| !served.contains(&r.name))
+        {
+            problems.push((
+                None,
+                record.name.clone(),
+                "points at this host, but no service here serves it".to_string(),
+            ));
+        }
+    }
+
+    if json_output() {
+        let problems: Vec<_> = problems
+            .iter()
+            .map(|(service, domain, problem)| {
+                serde_json::json!({
+                    "file": service.as_ref().map(|(f, _)| f),
+                    "service": service.as_ref().map(|(_, s)| s),
+                    "domain": domain,
+                    "problem": problem,
+                })
+            })
+            .collect();
+        return print_json(&problems);
+    }
+    if problems.is_empty() {
+        cliclack::log::success(format!(
+            "The {} compose files agree with DNS and the config.",
+            files.len()
+        ))?;
+        return Ok(());
+    }
+    print_table(
+        &["SERVICE", "DOMAIN", "PROBLEM"],
+        problems
+            .into_iter()
+            .map(|(service, domain, problem)| {
+                vec![
+                    match service {
+                        Some((file, name)) => format!(
+                            "{}/{name}",
+                            compose::project_name(&file).unwrap_or_default()
+                        ),
+                        None => "-".to_string(),
+                    },
+                    idn::to_unicode(&domain),
+                    problem,
+                ]
+            })
+            .collect(),
+    );
+
+    Ok(())
+}
+
+/// The IPv4 address an address record ends up at, following CNAMEs through
+/// the zones' records and then public DNS. Nothing for proxied records past
+/// the zones, AAAA records and tunnels, which can't be compared with this host.
+async fn record_address(
+    record: &provider::DnsListResponse,
+    records: &[provider::DnsListResponse],
+) -> Option<String> {
+    match record.record_type.as_str() {
+        "A" => return Some(record.content.clone()),
+        "CNAME" => {}
+        _ => return None,
+    }
+    if cloudflare::SpecialTarget::detect(&record.content).is_some() {
+        return None;
+    }
+
+    let chain = provider::cname_chain(records, &record.name, &record.content).ok()?;
+    let end = chain.last()?;
+    if let Some(a) = records
+        .iter()
+        .find(|r| r.record_type == "A" && r.name == *end)
+    {
+        return Some(a.content.clone());
+    }
+    if records.iter().any(|r| r.name == *end) {
+        return None;
+    }
+
+    resolve::lookup(end, "A")
+        .await
+        .ok()?
+        .into_iter()
+        .find(|a| a.record_type == 1)
+        .map(|a| a.data)
+}
+
+async fn probe_domains(domains: Vec<String>) -> Result<()> {
+    let spinner = cliclack::spinner();
+    spinner.start(format!("Probing {} domains...", domains.len()));
+    let probes = probe::probe_all(&domains).await?;
+    spinner.stop(format!("Probed {} domains.", probes.len()));
+
+    if json_output() {
+        let probes: Vec<_> = probes
+            .iter()
+            .map(|p| {
+                serde_json::json!({
+                    "domain": p.domain,
+                    "status": p.status,
+                    "latency_ms": p.latency.as_millis(),
+                    "cert_expiry": p.cert_expiry.map(|e| e.to_rfc3339()),
+                    "cloudflare": p.cloudflare,
+                    "error": p.error,
+                })
+            })
+            .collect();
+        return print_json(&probes);
+    }
+
+    print_table(
+        &["DOMAIN", "STATUS", "LATENCY", "CERT EXPIRES", "CLOUDFLARE"],
+        probes
+            .into_iter()
+            .map(|p| {
+                vec![
+                    p.domain,
+                    match (p.status, p.error) {
+                        (Some(status), _) => status.to_string(),
+                        (None, Some(e)) => format!("error: {e}"),
+                        (None, None) => "-".to_string(),
+                    },
+                    format!("{}ms", p.latency.as_millis()),
+                    p.cert_expiry
+                        .map(|e| e.format("%Y-%m-%d").to_string())
+                        .unwrap_or("-".to_string()),
+                    if p.cloudflare { "yes" } else { "no" }.to_string(),
+                ]
+            })
+            .collect(),
+    );
+
+    Ok(())
+}
+
+/// Address records that aren't proxied, so serve the origin's own certificate.
+async fn unproxied_domains(config: &Config) -> Result<Vec<String>> {
+    let listings = try_join_all(config.zones.iter().map(|zone| async {
+        provider::for_zone(config, zone)?
+            .list_records(&zone.id)
+            .await
+    }))
+    .await?;
+    let mut domains: Vec<_> = listings
+        .into_iter()
+        .flatten()
+        .filter(|r| {
+            !r.proxied
+                && matches!(r.record_type.as_str(), "A" | "AAAA" | "CNAME")
+                && !r.name.starts_with('*')
+        })
+        .map(|r| r.name)
+        .collect();
+    domains.sort();
+    domains.dedup();
+
+    Ok(domains)
+}
+
+/// Warns about each domain whose certificate expires within `days` or can't
+/// be checked. Returns how many were warned about.
+async fn report_certs(domains: &[String], days: u32) -> Result<usize> {
+    let now = chrono::Utc::now();
+    let mut warnings = 0;
+
+    for (domain, expiry) in domains.iter().zip(probe::cert_expiries(domains).await) {
+        match expiry {
+            Ok(expiry) if expiry - now > chrono::Duration::days(days.into()) => (),
+            Ok(expiry) if expiry < now => {
+                warnings += 1;
+                cliclack::log::error(format!(
+                    "The certificate for {domain} expired on {}.",
+                    expiry.format("%Y-%m-%d")
+                ))?;
+            }
+            Ok(expiry) => {
+                warnings += 1;
+                cliclack::log::warning(format!(
+                    "The certificate for {domain} expires in {} days.",
+                    (expiry - now).num_days()
+                ))?;
+            }
+            Err(e) => {
+                warnings += 1;
+                cliclack::log::warning(format!("Could not check {domain}: {e}"))?;
+            }
+        }
+    }
+
+    Ok(warnings)
+}
+
+async fn cert_check(domains: Vec<String>, days: Option<u32>) -> Result<()> {
+    let config = get_config().unwrap_or_default();
+    let domains = match domains.is_empty() {
+        true => unproxied_domains(&config).await?,
+        false => domains,
+    };
+    let days = days.unwrap_or(config.cert_warning_days);
+
+    if report_certs(&domains, days).await? == 0 {
+        cliclack::log::success(format!(
+            "All {} certificates are valid for at least {days} days.",
+            domains.len()
+        ))?;
+    }
+
+    Ok(())
+}
+
+async fn reconcile(file: &Path, apply: bool, host: Option<String>) -> Result<()> {
+    cliclack::intro("eurus-reconcile")?;
+
+    let spec = reconcile::load(file)?;
+    let host = host.or_else(reconcile::hostname);
+    if !spec.hosts.is_empty() {
+        match &host {
+            Some(host) if spec.hosts.contains_key(host) => {
+                cliclack::log::info(format!("Using the section for {host}."))?
+            }
+            Some(host) => cliclack::log::warning(format!(
+                "There is no section for {host}, only the shared records and services apply."
+            ))?,
+            None => cliclack::log::warning(
+                "Could not tell this machine's hostname, pass --host to pick a section.",
+            )?,
+        }
+    }
+    let spec = spec.for_host(host.as_deref());
+    let config = if spec.records.is_empty() {
+        get_config().unwrap_or_default()
+    } else {
+        get_dns_config().await?
+    };
+
+    let cancel = cancel::on_ctrl_c()?;
+    let changes = reconcile::plan(&config, &spec, &cancel).await?;
+    if changes.is_empty() {
+        cliclack::outro("Everything matches, nothing to do.")?;
+        return Ok(());
+    }
+
+    cliclack::note(
+        "Plan",
+        changes
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )?;
+
+    if !apply {
+        cliclack::outro("Run again with --apply to make these changes.")?;
+        return Ok(());
+    }
+
+    if !prompt::confirm(format!("Apply {} changes?", changes.len())).interact()? {
+        cliclack::outro_cancel("Nothing was changed.")?;
+        return Ok(());
+    }
+
+    let count = changes.len();
+    reconcile::apply(&config, changes, &cancel).await?;
+    cliclack::outro(format!("Applied {count} changes."))?;
+
+    Ok(())
+}
+
+/// Asks which account to use, skipping the prompt when there is only one.
+/// The account most of the `configured` zones are in is offered first.
+async fn select_account(
+    cloudflare: &Cloudflare,
+    configured: &[ZoneInfo],
+    prompt: &str,
+) -> Result<cloudflare::Account> {
+    let accounts = cloudflare.list_accounts().await?;
+    let account = match accounts.as_slice() {
+        [] => bail!("The api key does not have access to any accounts."),
+        [account] => account,
+        accounts => {
+            let usual = accounts.iter().max_by_key(|a| {
+                configured
+                    .iter()
+                    .filter(|z| z.account.as_ref() == Some(&a.id))
+                    .count()
+            });
+            let choices: Vec<_> = accounts.iter().map(|a| (a, &a.name, &a.id)).collect();
+            let mut select = prompt::select(prompt).items(&choices);
+            if let Some(usual) = usual {
+                select = select.initial_value(usual);
+            }
+            select.interact()?
+        }
+    };
+
+    Ok(account.clone())
+}
+
+async fn tunnel(path: Option<String>) -> Result<()> {
+    cliclack::intro("eurus-tunnel")?;
+
+    let config = get_dns_config().await?;
+    let cloudflare = Cloudflare::from_config(&config)?;
+    let account = select_account(
+        &cloudflare,
+        &config.zones,
+        "Select the account the tunnel is in",
+    )
+    .await?;
+
+    let tunnels = cloudflare.list_tunnels(&account.id).await?;
+    ensure!(
+        !tunnels.is_empty(),
+        "There are no tunnels in {}, create one with `cloudflared tunnel create` first.",
+        account.name
+    );
+    let choices: Vec<_> = tunnels.iter().map(|t| (t, &t.name, &t.status)).collect();
+    let tunnel = prompt::select("Select a tunnel")
+        .items(&choices)
+        .interact()?;
+
+    // Offer the services of a nearby compose file, but a tunnel can route to
+    // anything cloudflared can reach so fall back to asking for a host.
+    let services: Vec<String> = find_compose_file(path)
+        .ok()
+        .and_then(|f| std::fs::read_to_string(f).ok())
+        .and_then(|c| compose::parse(&c).ok())
+        .map(|c| c.services.0.into_keys().collect())
+        .unwrap_or_default();
+    let host: String = if services.is_empty() {
+        prompt::input("Enter the host cloudflared should forward to.")
+            .default_input("localhost")
+            .interact()?
+    } else {
+        let choices: Vec<_> = services.iter().map(|s| (s.clone(), s, "")).collect();
+        prompt::select("Select the service to expose")
+            .items(&choices)
+            .interact()?
+    };
+    let port: u16 = prompt::input("Enter the port this application exposes").interact()?;
+    let hostname: String = prompt::input("Enter the domain for this service.").interact()?;
+
+    let zone = zone_for_name(&config, &hostname).ok_or_else(|| {
+        EurusError::NotFound(format!("{hostname} is not in any configured zone."))
+    })?;
+    ensure!(
+        zone.provider == Provider::Cloudflare,
+        "Tunnels need the zone to be on Cloudflare, {} uses {:?}.",
+        zone.name,
+        zone.provider
+    );
+
+    let mut tunnel_config = cloudflare.tunnel_config(&account.id, &tunnel.id).await?;
+    tunnel_config.set_ingress(&hostname, &format!("http://{host}:{port}"));
+    cloudflare
+        .set_tunnel_config(&account.id, &tunnel.id, &tunnel_config)
+        .await?;
+
+    let provider = provider::for_zone(&config, zone)?;
+    let existing = provider
+        .list_records(&zone.id)
+        .await?
+        .into_iter()
+        .find(|r| r.name == hostname);
+    let body = DnsCreateUpdate {
+        name: hostname.clone(),
+        id: existing.map(|r| r.id),
+        proxied: true,
+        record_type: "CNAME".to_string(),
+        content: tunnel.target(),
+        ttl: AUTO_TTL,
+        comment: config.record_comment(None, None),
+        ..Default::default()
+    };
+
+    let payload = serde_json::json!({ "zone": zone, "record": body });
+    config.hooks.run(Event::PreDns, &payload)?;
+    provider.upsert_record(&zone.id, &body).await?;
+    config.hooks.run(Event::PostDns, &payload)?;
+
+    cliclack::outro(format!(
+        "{hostname} now routes through {} to http://{host}:{port}.",
+        tunnel.name
+    ))?;
+
+    Ok(())
+}
+
+/// Scans public DNS for `domain` and creates the records the user picks in `zone`.
+async fn import_public_records(
+    provider: &dyn DnsProvider,
+    zone: &ZoneInfo,
+    domain: &str,
+    subdomains: &[&str],
+) -> Result<()> {
+    let spinner = cliclack::spinner();
+    spinner.start("Scanning public DNS...");
+    let found = resolve::scan(domain, subdomains).await?;
+    spinner.stop(format!("Found {} records.", found.len()));
+
+    if found.is_empty() {
+        return Ok(());
+    }
+
+    let choices: Vec<_> = found
+        .iter()
+        .map(|r| {
+            (
+                r.clone(),
+                &r.name,
+                format!("{} {}", r.record_type, r.content),
+            )
+        })
+        .collect();
+    let selected = prompt::multiselect("Select the records to create")
+        .items(&choices)
+        .initial_values(found.clone())
+        .required(false)
+        .interact()?;
+
+    let records: Vec<_> = selected
+        .into_iter()
+        .map(|record| DnsCreateUpdate {
+            // Discovering a different domain than the zone copies the records over.
+            name: match record.name.strip_suffix(domain) {
+                Some(prefix) => format!("{prefix}{}", zone.name),
+                None => record.name,
+            },
+            id: None,
+            record_type: record.record_type,
+            proxied: false,
+            content: record.content,
+            ttl: AUTO_TTL,
+            ..Default::default()
+        })
+        .collect();
+    provider.batch(&zone.id, &records, &[]).await?;
+
+    Ok(())
+}
+
+async fn api(method: &str, path: &str, data: Option<String>, zone: Option<String>) -> Result<()> {
+    let method: reqwest::Method = method
+        .to_uppercase()
+        .parse()
+        .with_context(|| format!("{method} is not a valid HTTP method."))?;
+    let body = data
+        .map(|d| serde_json::from_str(&d))
+        .transpose()
+        .context("--data is not valid JSON.")?;
+
+    let mut config = if path.contains(":zone") {
+        get_dns_config().await?
+    } else {
+        get_api_key_config()?
+    };
+
+    let path = if path.contains(":zone") {
+        let zone = match zone {
+            Some(z) => config
+                .zones
+                .iter()
+                .find(|c| c.name == z || c.id == z)
+                .ok_or_else(|| EurusError::NotFound(format!("{z} is not a configured zone.")))?
+                .clone(),
+            None => select_zone(&mut config, None).await?,
+        };
+        path.replace(":zone", &zone.id)
+    } else {
+        path.to_string()
+    };
+
+    let (status, response) = Cloudflare::from_config(&config)?
+        .raw(method, &path, body)
+        .await?;
+    println!("{}", serde_json::to_string_pretty(&response)?);
+
+    ensure!(status.is_success(), "Cloudflare api returned {status}.");
+
+    Ok(())
+}
+
+async fn note(command: NoteCommand) -> Result<()> {
+    cliclack::intro("eurus-note")?;
+
+    let mut notes = Notes::load()?;
+
+    match command {
+        NoteCommand::Record => {
+            let mut config = get_dns_config().await?;
+            let zone = select_zone(&mut config, None).await?;
+            let records = provider::for_zone(&config, &zone)?
+                .list_records(&zone.id)
+                .await?;
+
+            let choices: Vec<_> = records
+                .iter()
+                .map(|r| {
+                    let hint = format!("{} {}", r.record_type, r.content);
+                    (r.id.clone(), &r.name, hint)
+                })
+                .collect();
+            let record_id = prompt::select("Select a record")
+                .items(&choices)
+                .filter_mode()
+                .interact()?;
+
+            let note = prompt_note(notes.record(&zone.id, &record_id))?;
+            notes.set_record(&zone.id, &record_id, note);
+        }
+        NoteCommand::Service { path } => {
+            let file = find_compose_file(path)?;
+            let compose: Compose = compose::parse(&std::fs::read_to_string(&file)?)
+                .context("The compose yaml was invalid.")?;
+
+            let choices: Vec<_> = compose.services.0.keys().map(|k| (k, k, "")).collect();
+            let service = prompt::select("Select a service")
+                .items(&choices)
+                .interact()?;
+
+            let note = prompt_note(notes.service(&file, service))?;
+            notes.set_service(&file, service, note);
+        }
+        NoteCommand::List => {
+            for (key, note) in notes.records.iter().chain(&notes.services) {
+                cliclack::log::info(format!("{key}\n{note}"))?;
+            }
+        }
+    }
+
+    notes.save()?;
+
+    cliclack::outro("Done!")?;
+
+    Ok(())
+}
+
+async fn tui(path: Option<String>) -> Result<()> {
+    let config = get_dns_config().await?;
+    let file = match path {
+        Some(path) => Some(find_compose_file(Some(path))?),
+        None => find_compose_file(None).ok(),
+    };
+
+    tui::run(config, file).await
+}
+
+async fn self_update(check: bool) -> Result<()> {
+    cliclack::intro("eurus-self-update")?;
+
+    let spinner = cliclack::spinner();
+    spinner.start("Checking for a new release...");
+    let release = update::latest().await;
+    spinner.stop("Checked for a new release.");
+    let release = release?;
+
+    let current = env!("CARGO_PKG_VERSION");
+    if !update::is_newer(release.version(), current) {
+        cliclack::outro(format!("eurus {current} is the latest version."))?;
+        return Ok(());
+    }
+    cliclack::log::info(format!(
+        "eurus {} is available, this is {current}.",
+        release.version()
+    ))?;
+    if check {
+        cliclack::outro("Run `eurus self-update` to install it.")?;
+        return Ok(());
+    }
+    if !prompt::confirm(format!("Update to {}?", release.version())).interact()? {
+        return Err(EurusError::Cancelled.into());
+    }
+
+    let spinner = cliclack::spinner();
+    spinner.start(format!("Downloading {}...", update::asset_name()));
+    let binary = update::fetch_binary(&release).await;
+    spinner.stop("Downloaded and checked the release.");
+    let path = update::replace_current(&binary?)?;
+    cliclack::outro(format!(
+        "Updated {} to {}.",
+        path.display(),
+        release.version()
+    ))?;
+
+    Ok(())
+}
+
+fn man(dir: Option<PathBuf>) -> Result<()> {
+    let Some(dir) = dir else {
+        return Ok(clap_mangen::Man::new(Cli::command()).render(&mut std::io::stdout())?);
+    };
+
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("Could not create {}.", dir.display()))?;
+    clap_mangen::generate_to(Cli::command(), &dir)
+        .with_context(|| format!("Could not write the man pages to {}.", dir.display()))?;
+    eprintln!("Wrote the man pages to {}.", dir.display());
+
+    Ok(())
+}
+
+/// Prints the systemd units for `daemon` as system units running as the
+/// current user, or installs them as user units.
+fn generate_systemd(daemon: SystemdDaemon, install: bool) -> Result<()> {
+    let absolute = |path: PathBuf| path.canonicalize().or_else(|_| std::path::absolute(&path));
+    let daemon = match daemon {
+        SystemdDaemon::Ddns { interval } => systemd::Daemon::Ddns { interval },
+        SystemdDaemon::Watch { dir, args } => systemd::Daemon::Watch {
+            dir: absolute(dir)?,
+            args,
+        },
+    };
+    let context = systemd::Context {
+        exe: std::env::current_exe().context("Could not find the eurus binary.")?,
+        config: absolute(config::config_path())?,
+        data_dir: DATA_DIR.to_path_buf(),
+        profile: PROFILE.get().cloned(),
+        user: match install {
+            true => None,
+            false => Some(std::env::var("USER").unwrap_or("root".to_string())),
+        },
+    };
+    let units = daemon.units(&context);
+
+    if !install {
+        let units: Vec<_> = units
+            .iter()
+            .map(|(name, contents)| format!("# /etc/systemd/system/{name}\n{contents}"))
+            .collect();
+        print!("{}", units.join("\n"));
+        return Ok(());
+    }
+
+    let dir = directories::BaseDirs::new()
+        .context("Could not find the home directory.")?
+        .config_dir()
+        .join("systemd/user");
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("Could not create {}.", dir.display()))?;
+    for (name, contents) in &units {
+        let path = dir.join(name);
+        std::fs::write(&path, contents)
+            .with_context(|| format!("Could not write to {}.", path.display()))?;
+        eprintln!("Wrote {}.", path.display());
+    }
+    let (enable, _) = units.last().context("There are no units.")?;
+    eprintln!(
+        "Start it with `systemctl --user daemon-reload && systemctl --user enable --now {enable}`."
+    );
+
+    Ok(())
+}
+
+async fn serve(
+    listen: std::net::SocketAddr,
+    spec: Option<PathBuf>,
+    token: Option<String>,
+    force_unlock: bool,
+) -> Result<()> {
+    cliclack::intro("eurus-serve")?;
+    let _lock = lock::acquire("serve", force_unlock)?;
+
+    let config = get_config().context("The dashboard needs a config, run `eurus dns` first.")?;
+    let token = match token {
+        Some(token) => token,
+        None => secrets::generate()?,
+    };
+    if !listen.ip().is_loopback() {
+        cliclack::log::warning(format!(
+            "{listen} is reachable from other machines, anyone with the token can apply plans."
+        ))?;
+    }
+    // The token goes in the fragment, which browsers never send to the server.
+    cliclack::log::info(format!(
+        "Dashboard at http://{listen}/#token={token}, Ctrl-C to stop."
+    ))?;
+
+    dashboard::serve(config, listen, token, spec, cancel::on_ctrl_c()?).await
+}
+
+/// Writes the caddy-docker-proxy stack and saves its network as the one
+/// `web` attaches services to.
+fn init(path: Option<PathBuf>, network: Option<String>, force: bool) -> Result<()> {
+    cliclack::intro("eurus-init")?;
+
+    let dir = path.unwrap_or_else(|| PathBuf::from("."));
+    let file = dir.join("compose.yaml");
+    if let Ok(existing) = compose::find_compose_file(Some(dir.display().to_string())) {
+        ensure!(
+            force,
+            "{} already exists, pass --force to replace it.",
+            existing.display()
+        );
+    }
+
+    let mut config = get_config().unwrap_or_default();
+    let network = match network {
+        Some(network) => network,
+        None => prompt::input("Enter the network caddy should proxy on.")
+            .default_input(match config.caddy_network.as_str() {
+                "" => "caddy",
+                network => network,
+            })
+            .validate(|n: &String| match docker::valid_network_name(n) {
+                true => Ok(()),
+                false => Err("Use letters, digits, `_`, `.` and `-`."),
+            })
+            .interact()?,
+    };
+    ensure!(
+        docker::valid_network_name(&network),
+        "{network} is not a valid network name."
+    );
+
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("Could not create {}.", dir.display()))?;
+    std::fs::write(&file, compose::caddy_stack(&network, proxy::label_prefix()))
+        .with_context(|| format!("Could not write {}.", file.display()))?;
+    cliclack::log::success(format!("Wrote {}.", file.display()))?;
+
+    if config.caddy_network != network {
+        config.caddy_network = network.clone();
+        save_config(&config)?;
+        cliclack::log::info(format!("Saved {network} as the caddy network."))?;
+    }
+    if config.proxy != ProxyBackend::Caddy {
+        cliclack::log::remark(format!(
+            "The default proxy is {}, pass --proxy caddy to `web` or run `eurus config set proxy \
+             caddy`.",
+            config.proxy
+        ))?;
+    }
+    ensure_network(&network)?;
+
+    cliclack::outro(format!(
+        "Start it with `docker compose up -d` in {}.",
+        dir.display()
+    ))?;
+
+    Ok(())
+}
+
+fn quota() -> Result<()> {
+    let mut usage = quota::Usage::load();
+    let window = usage.in_window();
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "window": window,
+            "limit": quota::LIMIT,
+            "days": usage.days,
+        }));
+    }
+
+    println!(
+        "{window} of {} calls used in the last 5 minutes.\n",
+        quota::LIMIT
+    );
+    print_table(
+        &["DAY", "CALLS"],
+        usage
+            .days
+            .iter()
+            .rev()
+            .map(|(day, calls)| vec![day.clone(), calls.to_string()])
+            .collect(),
+    );
+
+    Ok(())
+}
+
+async fn verify() -> Result<()> {
+    cliclack::intro("eurus-verify")?;
+
+    let config = get_config()?;
+    let mut failed = 0;
+    let mut checks = vec![];
+    let mut check = |ok: bool, message: String| -> Result<()> {
+        checks.push(serde_json::json!({ "ok": ok, "message": message }));
+        if ok {
+            cliclack::log::success(message)?;
+        } else {
+            failed += 1;
+            cliclack::log::error(message)?;
+        }
+        Ok(())
+    };
+
+    let uses_cloudflare = config
+        .zones
+        .iter()
+        .any(|z| z.provider == Provider::Cloudflare);
+    if uses_cloudflare || config.has_cloudflare_key() {
+        match Cloudflare::from_config(&config) {
+            Err(e) => check(
+                false,
+                format!("The Cloudflare key could not be loaded: {e}"),
+            )?,
+            Ok(cloudflare) => match config.cloudflare_auth {
+                AuthMethod::Token => match cloudflare.verify_token().await {
+                    Ok(token) => check(
+                        token.status == "active",
+                        format!(
+                            "The api token is {}{}.",
+                            token.status,
+                            token
+                                .expires_on
+                                .map(|e| format!(" and expires on {e}"))
+                                .unwrap_or_default()
+                        ),
+                    )?,
+                    Err(e) => check(false, format!("The api token could not be verified: {e}"))?,
+                },
+                AuthMethod::GlobalKey => match cloudflare.user().await {
+                    Ok(user) => check(
+                        true,
+                        format!("The Global API Key belongs to {}.", user.email),
+                    )?,
+                    Err(e) => check(false, format!("The Global API Key was rejected: {e}"))?,
+                },
+            },
+        }
+    }
+
+    let reads = join_all(config.zones.iter().map(|zone| async {
+        provider::for_zone(&config, zone)?
+            .list_records(&zone.id)
+            .await
+    }))
+    .await;
+    for (zone, read) in config.zones.iter().zip(reads) {
+        let name = idn::to_unicode(&zone.name);
+        if let Err(e) = read {
+            check(false, format!("{name}: can't read records: {e}"))?;
+            continue;
+        }
+
+        // Only Cloudflare says what the credentials may do without trying it.
+        if zone.provider != Provider::Cloudflare {
+            check(true, format!("{name}: can read records."))?;
+            continue;
+        }
+        match Cloudflare::from_config(&config)?
+            .zone_permissions(&zone.id)
+            .await
+        {
+            Ok(permissions) if permissions.is_empty() => check(
+                true,
+                format!("{name}: can read records, edit access is unknown."),
+            )?,
+            Ok(permissions) => {
+                let edit = permissions.iter().any(|p| p == "#dns_records:edit");
+                let message = match edit {
+                    true => format!("{name}: can read and edit records."),
+                    false => format!("{name}: can read records, but not edit them."),
+                };
+                check(edit, message)?
+            }
+            Err(e) => check(false, format!("{name}: can't read zone permissions: {e}"))?,
+        }
+    }
+
+    if json_output() {
+        print_json(&checks)?;
+    }
+    ensure!(failed == 0, "{failed} checks failed.");
+    cliclack::outro("Everything checks out.")?;
+
+    Ok(())
+}
+
+/// Follows a domain from its record through public DNS to an HTTPS request,
+/// like a visitor would.
+async fn check_domain(domain: &str) -> Result<()> {
+    cliclack::intro("eurus-check")?;
+
+    let config = get_config()?;
+    let zone = zone_for_name(&config, domain).ok_or_else(|| {
+        EurusError::NotFound(format!("{domain} isn't in any of the configured zones."))
+    })?;
+    let mut failed = 0;
+    let mut checks = vec![];
+    let mut check = |ok: bool, message: String| -> Result<()> {
+        checks.push(serde_json::json!({ "ok": ok, "message": message }));
+        if ok {
+            cliclack::log::success(message)?;
+        } else {
+            failed += 1;
+            cliclack::log::error(message)?;
+        }
+        Ok(())
+    };
+
+    let records = provider::for_zone(&config, zone)?
+        .list_records(&zone.id)
+        .await?;
+    // A wildcard covers the names that don't have records of their own.
+    let wildcard = domain
+        .split_once('.')
+        .map(|(_, parent)| format!("*.{parent}"));
+    let address = |name: &str| {
+        records
+            .iter()
+            .find(|r| r.name == name && matches!(r.record_type.as_str(), "A" | "AAAA" | "CNAME"))
+    };
+    let record = address(domain).or_else(|| wildcard.as_deref().and_then(address));
+    match record {
+        Some(r) => check(
+            true,
+            format!(
+                "{} has a {} record for {}.",
+                r.name, r.record_type, r.content
+            ),
+        )?,
+        None => check(
+            false,
+            format!("{} has no A, AAAA or CNAME record for {domain}.", zone.name),
+        )?,
+    }
+
+    if let Some(record) = record {
+        if zone.provider == Provider::Cloudflare {
+            let expected = zone.proxied
+                || cloudflare::SpecialTarget::detect(&record.content)
+                    == Some(cloudflare::SpecialTarget::Tunnel);
+            let state = |proxied: bool| match proxied {
+                true => "proxied",
+                false => "DNS only",
+            };
+            check(
+                record.proxied == expected,
+                format!(
+                    "The record is {}, {} {} for {}.",
+                    state(record.proxied),
+                    match record.proxied == expected {
+                        true => "as configured",
+                        false => "but configured",
+                    },
+                    state(expected),
+                    zone.name
+                ),
+            )?;
+        }
+
+        let (proxied, lookup_type) = live_lookup(record);
+        let edge = match proxied {
+            true => cloudflare::edge_ips(&config).await.unwrap_or_else(|e| {
+                tracing::debug!("Could not get Cloudflare's addresses: {e}");
+                Default::default()
+            }),
+            false => Default::default(),
+        };
+        let expected = match proxied {
+            true => "Cloudflare's addresses".to_string(),
+            false => record.value(),
+        };
+        for (address, url) in resolve::PUBLIC_RESOLVERS {
+            match resolve::lookup_with(url, domain, lookup_type).await {
+                Ok(answers) if answers_show(record, &answers, &edge) => {
+                    check(true, format!("{address} returns {expected}."))?
+                }
+                Ok(answers) => check(
+                    false,
+                    format!(
+                        "{address} returns {} instead of {expected}.",
+                        match answers.is_empty() {
+                            true => "nothing".to_string(),
+                            false => answers
+                                .iter()
+                                .map(|a| a.data.trim_end_matches('.'))
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                        }
+                    ),
+                )?,
+                Err(e) => check(
+                    false,
+                    format!("Could not look {domain} up on {address}: {e}"),
+                )?,
+            }
+        }
+    }
+
+    let probe = probe::probe_all(&[domain.to_string()]).await?.remove(0);
+    match probe.status {
+        Some(status) => {
+            let via = match (probe.cloudflare, &probe.server) {
+                (true, _) => " through Cloudflare".to_string(),
+                (false, Some(server)) => format!(" from {server}"),
+                (false, None) => String::new(),
+            };
+            let hint = status_hint(status)
+                .map(|h| format!(" {h}"))
+                .unwrap_or_default();
+            check(
+                status < 500,
+                format!(
+                    "https://{domain}/ answered {status}{via} in {}ms.{hint}",
+                    probe.latency.as_millis()
+                ),
+            )?;
+            if record.is_some_and(|r| r.proxied) && !probe.cloudflare {
+                check(
+                    false,
+                    "The record is proxied, but the response didn't come through Cloudflare."
+                        .to_string(),
+                )?;
+            }
+        }
+        None => check(
+            false,
+            format!(
+                "https://{domain}/ didn't answer: {}",
+                probe.error.unwrap_or_default()
+            ),
+        )?,
+    }
+    if let (Some(expiry), Some(issuer)) = (probe.cert_expiry, &probe.cert_issuer) {
+        check(
+            expiry > chrono::Utc::now(),
+            format!(
+                "The certificate is issued by {issuer} and expires on {}.",
+                expiry.format("%Y-%m-%d")
+            ),
+        )?;
+    }
+
+    if json_output() {
+        print_json(&checks)?;
+    }
+    ensure!(failed == 0, "{failed} checks failed.");
+    cliclack::outro(format!("{domain} works end to end."))?;
+
+    Ok(())
+}
+
+/// What an error status usually means for a proxied service.
+fn status_hint(status: u16) -> Option<&'static str> {
+    Some(match status {
+        502 | 504 => {
+            "The proxy couldn't reach the service, is it running and on the proxy's network?"
+        }
+        521 => "Cloudflare couldn't connect to the origin, is the proxy running?",
+        522 => "Cloudflare timed out connecting to the origin, is a firewall blocking it?",
+        523 => "Cloudflare couldn't reach the origin's address.",
+        525 | 526 => "The origin's certificate doesn't work with the zone's SSL mode.",
+        _ => return None,
+    })
+}
+
+async fn config(command: ConfigCommand) -> Result<()> {
+    match command {
+        ConfigCommand::SetKey { file } => config_set_key(file),
+        ConfigCommand::Path => {
+            println!("{}", config::config_path().display());
+            Ok(())
+        }
+        ConfigCommand::Show => {
+            let mut raw = config::read_raw()?;
+            config::redact(&mut raw);
+            if json_output() {
+                return print_json(&raw);
+            }
+            let path = config::config_path();
+            cliclack::note(path.display(), config::serialize(&path, &raw, None)?)?;
+            Ok(())
+        }
+        ConfigCommand::Get { key } => config_get(key.as_deref()),
+        ConfigCommand::Set { key, value } => config_set(&key, &value),
+        ConfigCommand::Edit => config_edit(),
+        ConfigCommand::Lint { fix } => config_lint(fix).await,
+    }
+}
+
+async fn config_lint(fix: bool) -> Result<()> {
+    cliclack::intro("eurus-lint")?;
+
+    let path = config::config_path();
+    let mut raw = config::read_raw()?;
+    let mut problems = lint::check(&raw);
+    let permissions = lint::check_permissions(&path, &raw);
+    problems.extend(permissions.clone());
+    // The zones can only be tried once the config loads with its keys.
+    if let Some(config) = get_config().ok().filter(|c| !c.zones.is_empty()) {
+        let spinner = cliclack::spinner();
+        spinner.start("Checking the zones can be reached...");
+        problems.extend(lint::check_reachable(&config).await);
+        spinner.stop("Checked the zones.");
+    }
+
+    if fix {
+        let fixed = lint::fix(&mut raw);
+        if !fixed.is_empty() {
+            config::write_raw(&raw)?;
+        }
+        for message in fixed {
+            cliclack::log::success(message)?;
+        }
+        if !permissions.is_empty() {
+            lint::fix_permissions(&path)?;
+            cliclack::log::success(format!(
+                "Made {} readable by its owner only.",
+                path.display()
+            ))?;
+        }
+        problems.retain(|p| !p.fixable);
+    }
+
+    if json_output() {
+        print_json(&problems)?;
+    }
+    for problem in &problems {
+        match problem.fixable {
+            true => cliclack::log::warning(format!("{} (--fix fixes it)", problem.message))?,
+            false => cliclack::log::warning(&problem.message)?,
+        }
+    }
+    ensure!(problems.is_empty(), "Found {} problems.", problems.len());
+    cliclack::outro("The config looks good.")?;
+
+    Ok(())
+}
+
+/// Prints a setting, strings as they are unless JSON was asked for. Keys are
+/// only shown when asked for by name.
+fn config_get(key: Option<&str>) -> Result<()> {
+    let mut raw = config::read_raw()?;
+    let value = match key {
+        Some(key) => config::get_path(&raw, key)
+            .with_context(|| format!("There is no setting `{key}`."))?
+            .clone(),
+        None => {
+            config::redact(&mut raw);
+            raw
+        }
+    };
+
+    match value {
+        serde_json::Value::String(s) if !json_output() => println!("{s}"),
+        value => println!("{}", serde_json::to_string_pretty(&value)?),
+    }
+
+    Ok(())
+}
+
+fn config_set(key: &str, value: &str) -> Result<()> {
+    let raw = config::read_raw()?;
+    let parsed = serde_json::from_str(value).unwrap_or(serde_json::json!(value));
+
+    let mut updated = raw.clone();
+    config::set_path(&mut updated, key, parsed.clone())?;
+    // `eurus config set caddy_network 1234` means the string, so a value that
+    // only parsed as some other JSON type gets another try as one.
+    if config::validate(&updated).is_err() && !parsed.is_string() {
+        let mut as_string = raw.clone();
+        config::set_path(&mut as_string, key, serde_json::json!(value))?;
+        if config::validate(&as_string).is_ok() {
+            updated = as_string;
+        }
+    }
+    config::write_raw(&updated)?;
+
+    println!(
+        "{key} = {}",
+        serde_json::to_string(config::get_path(&updated, key).unwrap_or(&serde_json::Value::Null))?
+    );
+
+    Ok(())
+}
+
+/// Opens a pretty printed copy of the config in the editor, and only writes it
+/// back once it loads, offering to fix it otherwise.
+fn config_edit() -> Result<()> {
+    cliclack::intro("eurus-config")?;
+
+    config::migrate()?;
+    let path = config::config_path();
+    let original = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).context("Could not read the config."),
+    };
+    // JSON is pretty printed to be editable, TOML is edited as it is so its
+    // comments stay.
+    let mut contents = match config::parse(&path, &original) {
+        _ if original.is_empty() => config::serialize(&path, &config::read_raw()?, None)?,
+        Ok(value) if path.extension().is_some_and(|e| e == "json") => {
+            serde_json::to_string_pretty(&value)?
+        }
+        _ => original.clone(),
+    };
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or("vi".to_string());
+    let temp = path.with_extension(format!(
+        "edit.{}",
+        path.extension().and_then(|e| e.to_str()).unwrap_or("toml")
+    ));
+    loop {
+        std::fs::write(&temp, &contents)?;
+        // Through the shell, since editors are often set with arguments like
+        // `code --wait`.
+        let status = std::process::Command::new("sh")
+            .args(["-c", &format!("{editor} \"$1\""), "sh"])
+            .arg(&temp)
+            .status()
+            .with_context(|| format!("Failed to run {editor}."))?;
+        ensure!(status.success(), "{editor} exited with {status}.");
+        contents = std::fs::read_to_string(&temp)?;
+
+        let checked = config::parse(&path, &contents).and_then(|value| config::validate(&value));
+        match checked {
+            Ok(()) => break,
+            Err(e) => {
+                cliclack::log::error(format!("{e:#}"))?;
+                if !prompt::confirm("Edit it again? Otherwise the changes are discarded.")
+                    .initial_value(true)
+                    .interact()?
+                {
+                    std::fs::remove_file(&temp)?;
+                    return Err(EurusError::Cancelled.into());
+                }
+            }
+        }
+    }
+    std::fs::remove_file(&temp)?;
+
+    if contents == original {
+        cliclack::outro("Nothing changed.")?;
+        return Ok(());
+    }
+    tracing::info!("Writing {}", path.display());
+    std::fs::write(&path, &contents)?;
+    cliclack::outro(format!("Saved {}.", path.display()))?;
+
+    Ok(())
+}
+
+fn config_set_key(file: bool) -> Result<()> {
+    cliclack::intro("eurus-config")?;
+
+    // Only the key is changed, a config that doesn't load is left alone.
+    let mut raw = config::read_raw()?;
+    let key: String = prompt::password("Enter your Cloudflare api key.").interact()?;
+
+    let path = match PROFILE.get() {
+        Some(profile) => format!("profiles.{profile}.cloudflare_key"),
+        None => "cloudflare_key".to_string(),
+    };
+    // A key moved to the keyring is cleared from the file.
+    let stored = match file {
+        true => key,
+        false => {
+            credentials::set_cloudflare_key(&key)?;
+            String::new()
+        }
+    };
+    config::set_path(&mut raw, &path, serde_json::json!(stored))?;
+    config::write_raw(&raw)?;
+
+    cliclack::outro(match file {
+        true => "Saved the key to the config file.",
+        false => "Saved the key to the system keyring.",
+    })?;
+
+    Ok(())
+}
+
+fn prompt_note(current: Option<&String>) -> Result<String> {
+    let mut input = prompt::input("Note (leave empty to remove it)").required(false);
+    if let Some(current) = current {
+        input = input.default_input(current);
+    }
+    input.interact()
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    CompleteEnv::with_factory(Cli::command)
+        .var(completions::VAR)
+        .complete();
+    color_eyre::install()?;
+
+    let args = Cli::try_parse().unwrap_or_else(|e| {
+        let _ = e.print();
+        std::process::exit(match e.use_stderr() {
+            true => exit::USAGE,
+            false => exit::SUCCESS,
+        })
+    });
+    if let Some(profile) = args.profile {
+        PROFILE.get_or_init(|| profile);
+    }
+    if let Some(config) = args.config {
+        CONFIG_PATH.get_or_init(|| config);
+    }
+    if let Some(prefix) = args.label_prefix.filter(|p| !p.is_empty()) {
+        LABEL_PREFIX.get_or_init(|| prefix);
+    }
+    REFRESH.get_or_init(|| args.refresh);
+    ASSUME_YES.get_or_init(|| args.yes);
+    logging::init(args.verbose);
+
+    let mut command = args.command;
+    let output = match (args.output, &mut command) {
+        (
+            legacy @ (OutputFormat::Compose | OutputFormat::Caddyfile),
+            Command::Web { args: web, .. },
+        ) => {
+            cliclack::log::warning("`web --output` is deprecated, use `web --emit` instead.")?;
+            web.emit = match legacy {
+                OutputFormat::Caddyfile => WebOutput::Caddyfile,
+                _ => WebOutput::Compose,
+            };
+            OutputFormat::Text
+        }
+        (OutputFormat::Compose | OutputFormat::Caddyfile, _) => {
+            let _ = Cli::command()
+                .error(
+                    clap::error::ErrorKind::InvalidValue,
+                    "--output takes text or json, compose and caddyfile are for `web --emit`.",
+                )
+                .print();
+            std::process::exit(exit::USAGE);
+        }
+        (output, _) => output,
+    };
+    OUTPUT.get_or_init(|| output);
+
+    let result = run(command).await;
+    if let Err(e) = quota::save() {
+        cliclack::log::warning(format!("Could not save api usage: {e}"))?;
+    }
+
+    if let Err(report) = result.map_err(explain_error) {
+        eprintln!("Error: {report:?}");
+        std::process::exit(exit_code(&report));
+    }
+
+    Ok(())
+}
+
+/// Runs the subcommand.
+async fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Dns { record, command } => dns(record, command).await,
+        Command::Web { args, command } => web(args, command).await,
+        Command::Deploy { args, record, up } => deploy(args, record, up).await,
+        Command::Init {
+            path,
+            network,
+            force,
+        } => init(path, network, force),
+        Command::Account { command } => account(command).await,
+        Command::Zone { command } => zone(command).await,
+        Command::Cache {
+            command: CacheCommand::Purge { zone, urls },
+        } => cache_purge(zone.as_deref(), &urls).await,
+        Command::K8s { args } => k8s(args).await,
+        Command::Status {
+            path: Some(path), ..
+        } => status_drift(&path).await,
+        Command::Status { probe, .. } => status(probe).await,
+        Command::Cert {
+            command: CertCommand::Check { domains, days },
+        } => cert_check(domains, days).await,
+        Command::Reconcile { file, apply, host } => reconcile(&file, apply, host).await,
+        Command::Tunnel { path } => tunnel(path).await,
+        Command::Api {
+            method,
+            path,
+            data,
+            zone,
+        } => api(&method, &path, data, zone).await,
+        Command::Note { command } => note(command).await,
+        Command::Config { command } => config(command).await,
+        Command::Verify => verify().await,
+        Command::Check { domain } => check_domain(&domain).await,
+        Command::Maintenance {
+            domain,
+            state,
+            path,
+            duration,
+            message,
+            up,
+        } => match state {
+            Toggle::On => maintenance_on(&domain, path, duration, &message, up).await,
+            Toggle::Off => {
+                cliclack::intro("eurus-maintenance")?;
+                maintenance_off(&domain, up)?;
+                cliclack::outro(format!("{domain} is back."))?;
+                Ok(())
+            }
+        },
+        Command::Switch {
+            domain,
+            target,
+            back,
+            path,
+            up,
+        } => switch(&domain, target, back, path, up).await,
+        Command::Quota => quota(),
+        Command::Serve {
+            listen,
+            spec,
+            token,
+            force_unlock,
+        } => serve(listen, spec, token, force_unlock).await,
+        Command::Tui { path } => tui(path).await,
+        Command::SelfUpdate { check } => self_update(check).await,
+        Command::Man { dir } => man(dir),
+        Command::Generate {
+            command: GenerateCommand::Systemd { daemon, install },
+        } => generate_systemd(daemon, install),
+        Command::Completions { shell } => {
+            completions::write_registration(&shell, &mut std::io::stdout())
+        }
+    }
+}
+
+/// The exit code for the first error in the report eurus knows the kind of.
+fn exit_code(report: &Report) -> i32 {
+    let interrupted = |e: &std::io::Error| e.kind() == std::io::ErrorKind::Interrupted;
+    if let Some(e) = report.downcast_ref::<EurusError>() {
+        return e.exit_code();
+    }
+    report
+        .chain()
+        .find_map(|e| match e.downcast_ref::<std::io::Error>() {
+            Some(e) if interrupted(e) => Some(exit::CANCELLED),
+            _ => e.downcast_ref::<EurusError>().map(EurusError::exit_code),
+        })
+        .unwrap_or(exit::FAILURE)
+}
+
+/// Adds what the Cloudflare error codes in the report mean below it.
+fn explain_error(report: Report) -> Report {
+    let codes = match report.chain().find_map(|e| e.downcast_ref::<EurusError>()) {
+        Some(EurusError::ApiError { codes, .. }) => codes.clone(),
+        _ => return report,
+    };
+
+    codes
+        .into_iter()
+        .filter_map(explain::explain)
+        .fold(report, |report, explanation| {
+            let report = report.note(explanation.meaning);
+            match explanation.fix {
+                Some(fix) => report.suggestion(fix),
+                None => report,
+            }
+        })
 }
@@ -0,0 +1,112 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use color_eyre::eyre::{Context, ContextCompat, Result};
+use docker_compose_types::Service;
+use serde::{Deserialize, Serialize};
+
+use crate::{proxy, DATA_DIR};
+
+/// The sites currently in maintenance mode, with the labels they had before
+/// so they can be put back.
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct Maintenance {
+    #[serde(default)]
+    pub sites: BTreeMap<String, Site>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Site {
+    pub file: PathBuf,
+    pub service: String,
+    /// The `caddy` or `caddy_<n>` label the site is declared with.
+    pub prefix: String,
+    /// The site's directive labels from before maintenance mode.
+    pub labels: Vec<(String, String)>,
+    /// When `maintenance on --for` reverts it, in RFC 3339.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+}
+
+impl Maintenance {
+    pub fn load() -> Result<Self> {
+        match std::fs::read_to_string(DATA_DIR.join("maintenance.json")) {
+            Ok(s) => serde_json::from_str(&s).context("Maintenance file is malformed."),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .create(*DATA_DIR)
+            .context("Failed to create data directory")?;
+        std::fs::write(
+            DATA_DIR.join("maintenance.json"),
+            serde_json::to_string(self)?,
+        )?;
+
+        Ok(())
+    }
+}
+
+/// The caddy label that declares `domain` on the service.
+pub fn site_prefix(service: &Service, domain: &str) -> Option<String> {
+    proxy::label_pairs(&service.labels)
+        .into_iter()
+        .find(|(key, value)| {
+            proxy::is_caddy_site(key)
+                && value
+                    .split([' ', ','])
+                    .any(|d| d.trim_start_matches("https://") == domain)
+        })
+        .map(|(key, _)| key)
+}
+
+/// Replaces the site's directives with a 503 `respond` of `message`,
+/// returning the labels that were replaced.
+pub fn enable(service: &mut Service, prefix: &str, message: &str) -> Vec<(String, String)> {
+    let saved = remove_directives(service, prefix);
+    proxy::set_label(
+        &mut service.labels,
+        &format!("{prefix}.respond"),
+        &format!("\"{}\" 503", message.replace('"', "\\\"")),
+    );
+
+    saved
+}
+
+/// Puts the labels saved by [`enable`] back in place of the maintenance page.
+pub fn disable(service: &mut Service, site: &Site) {
+    remove_directives(service, &site.prefix);
+    for (key, value) in &site.labels {
+        proxy::set_label(&mut service.labels, key, value);
+    }
+}
+
+fn remove_directives(service: &mut Service, prefix: &str) -> Vec<(String, String)> {
+    let directives: Vec<_> = proxy::label_pairs(&service.labels)
+        .into_iter()
+        .filter(|(key, _)| key.starts_with(&format!("{prefix}.")))
+        .collect();
+    for (key, _) in &directives {
+        proxy::remove_label(&mut service.labels, key);
+    }
+
+    directives
+}
+
+/// Finds the service in `compose` that serves `domain` through caddy.
+pub fn find_site(
+    compose: &docker_compose_types::Compose,
+    domain: &str,
+) -> Result<(String, String)> {
+    compose
+        .services
+        .0
+        .iter()
+        .find_map(|(name, service)| {
+            let prefix = site_prefix(service.as_ref()?, domain)?;
+            Some((name.clone(), prefix))
+        })
+        .with_context(|| format!("No service in the compose file has caddy labels for {domain}."))
+}
@@ -0,0 +1,159 @@
+//! Prometheus metrics for the long running modes, `dns ddns --watch` and
+//! `web --watch`, served with a plain text health check.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use axum::{extract::State, http::StatusCode, routing::get, Router};
+use color_eyre::eyre::{Context, Result};
+
+use crate::cancel::CancellationToken;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Counter,
+    Gauge,
+}
+
+#[derive(Debug)]
+struct Metric {
+    help: &'static str,
+    kind: Kind,
+    /// The value of each series, by its rendered labels.
+    values: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    metrics: BTreeMap<&'static str, Metric>,
+    /// What went wrong in the last check, cleared once one succeeds.
+    failure: Option<String>,
+}
+
+/// Metrics shared between a daemon and the endpoint serving them. Clones
+/// share the same values.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics(Arc<Mutex<Registry>>);
+
+impl Metrics {
+    /// Adds one to a counter.
+    pub fn inc(&self, name: &'static str, help: &'static str, labels: &[(&str, &str)]) {
+        self.update(name, help, Kind::Counter, labels, |v| *v += 1.0);
+    }
+
+    /// Sets a gauge.
+    pub fn set(&self, name: &'static str, help: &'static str, labels: &[(&str, &str)], value: f64) {
+        self.update(name, help, Kind::Gauge, labels, |v| *v = value);
+    }
+
+    /// Sets an info gauge to 1 for `labels`, dropping the series it had for
+    /// other labels, like the current public IP.
+    pub fn info(&self, name: &'static str, help: &'static str, labels: &[(&str, &str)]) {
+        if let Some(metric) = self.0.lock().unwrap().metrics.get_mut(name) {
+            metric.values.clear();
+        }
+        self.set(name, help, labels, 1.0);
+    }
+
+    /// Sets a gauge to the current unix time.
+    pub fn now(&self, name: &'static str, help: &'static str) {
+        self.set(name, help, &[], chrono::Utc::now().timestamp() as f64);
+    }
+
+    /// Marks the daemon as healthy for the health check.
+    pub fn succeeded(&self) {
+        self.0.lock().unwrap().failure = None;
+    }
+
+    /// Marks the daemon as failing for the health check, with why.
+    pub fn failed(&self, reason: impl Into<String>) {
+        self.0.lock().unwrap().failure = Some(reason.into());
+    }
+
+    fn update(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        kind: Kind,
+        labels: &[(&str, &str)],
+        change: impl FnOnce(&mut f64),
+    ) {
+        let labels = labels
+            .iter()
+            .map(|(k, v)| format!("{k}=\"{}\"", escape(v)))
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut registry = self.0.lock().unwrap();
+        let metric = registry.metrics.entry(name).or_insert_with(|| Metric {
+            help,
+            kind,
+            values: BTreeMap::new(),
+        });
+        change(metric.values.entry(labels).or_default());
+    }
+
+    /// The metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let registry = self.0.lock().unwrap();
+        let mut out = String::new();
+        for (name, metric) in &registry.metrics {
+            let kind = match metric.kind {
+                Kind::Counter => "counter",
+                Kind::Gauge => "gauge",
+            };
+            let _ = writeln!(out, "# HELP {name} {}", metric.help);
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            for (labels, value) in &metric.values {
+                let _ = match labels.is_empty() {
+                    true => writeln!(out, "{name} {value}"),
+                    false => writeln!(out, "{name}{{{labels}}} {value}"),
+                };
+            }
+        }
+        out
+    }
+
+    /// Serves `/metrics` and `/health` on `listen` in the background until
+    /// `cancel` is cancelled. Fails right away if the address can't be used.
+    pub async fn serve(&self, listen: SocketAddr, cancel: CancellationToken) -> Result<()> {
+        let app = Router::new()
+            .route(
+                "/metrics",
+                get(|State(m): State<Metrics>| async move { m.render() }),
+            )
+            .route("/health", get(health))
+            .with_state(self.clone());
+        let listener = tokio::net::TcpListener::bind(listen)
+            .await
+            .with_context(|| format!("Could not listen on {listen}."))?;
+
+        tokio::spawn(async move {
+            let served = axum::serve(listener, app)
+                .with_graceful_shutdown(cancel.cancelled_owned())
+                .await;
+            if let Err(e) = served {
+                tracing::warn!("The metrics endpoint stopped: {e}");
+            }
+        });
+
+        Ok(())
+    }
+}
+
+async fn health(State(metrics): State<Metrics>) -> (StatusCode, String) {
+    match &metrics.0.lock().unwrap().failure {
+        None => (StatusCode::OK, "ok\n".to_string()),
+        Some(reason) => (StatusCode::SERVICE_UNAVAILABLE, format!("{reason}\n")),
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
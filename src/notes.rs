@@ -0,0 +1,77 @@
+use std::{collections::BTreeMap, path::Path};
+
+use color_eyre::eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::DATA_DIR;
+
+/// Free-form notes attached to records and compose services. These are only
+/// ever stored locally, never sent to the provider.
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct Notes {
+    #[serde(default)]
+    pub records: BTreeMap<String, String>,
+    #[serde(default)]
+    pub services: BTreeMap<String, String>,
+}
+
+impl Notes {
+    pub fn load() -> Result<Self> {
+        match std::fs::read_to_string(DATA_DIR.join("notes.json")) {
+            Ok(s) => serde_json::from_str(&s).context("Notes file is malformed."),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .create(*DATA_DIR)
+            .context("Failed to create data directory")?;
+        std::fs::write(DATA_DIR.join("notes.json"), serde_json::to_string(self)?)?;
+
+        Ok(())
+    }
+
+    pub fn record(&self, zone_id: &str, record_id: &str) -> Option<&String> {
+        self.records.get(&record_key(zone_id, record_id))
+    }
+
+    pub fn service(&self, file: &Path, service: &str) -> Option<&String> {
+        self.services.get(&service_key(file, service))
+    }
+
+    /// Sets the note for a record, removing it when `note` is empty.
+    pub fn set_record(&mut self, zone_id: &str, record_id: &str, note: String) {
+        set(&mut self.records, record_key(zone_id, record_id), note);
+    }
+
+    /// Moves the note of a service that was renamed.
+    pub fn rename_service(&mut self, file: &Path, old: &str, new: &str) {
+        if let Some(note) = self.services.remove(&service_key(file, old)) {
+            self.services.insert(service_key(file, new), note);
+        }
+    }
+
+    /// Sets the note for a service, removing it when `note` is empty.
+    pub fn set_service(&mut self, file: &Path, service: &str, note: String) {
+        set(&mut self.services, service_key(file, service), note);
+    }
+}
+
+fn set(map: &mut BTreeMap<String, String>, key: String, note: String) {
+    if note.trim().is_empty() {
+        map.remove(&key);
+    } else {
+        map.insert(key, note);
+    }
+}
+
+fn record_key(zone_id: &str, record_id: &str) -> String {
+    format!("{zone_id}/{record_id}")
+}
+
+fn service_key(file: &Path, service: &str) -> String {
+    let file = std::fs::canonicalize(file).unwrap_or(file.to_path_buf());
+    format!("{}#{service}", file.display())
+}
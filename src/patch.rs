@@ -0,0 +1,535 @@
+//! Edits a YAML file in place: only the parts of the document that changed
+//! are rewritten, so comments, anchors and the order of everything else are
+//! kept.
+
+use serde_yml::Value;
+
+/// Rewrites the parts of `text` that differ between `old` and `new`, where
+/// `old` is what `text` parses to. Only block style mappings and sequences
+/// are edited into, anything else that changed is replaced as a whole.
+/// Returns nothing when the text's layout isn't understood.
+pub fn patch(text: &str, old: &Value, new: &Value) -> Option<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    // A leading document marker is kept as it is.
+    let start = lines
+        .iter()
+        .position(|l| is_content(l))
+        .filter(|&i| lines[i].trim_end() == "---")
+        .map_or(0, |i| i + 1);
+
+    let mut edits = vec![];
+    diff(&lines, start, lines.len(), old, new, &mut edits)?;
+
+    // Applied from the end so the line numbers of earlier edits stay valid.
+    // Insertions at the same line go in the order they were made, nested
+    // ones first, so those are applied last.
+    edits.reverse();
+    edits.sort_by_key(|e| std::cmp::Reverse(e.start));
+    let mut lines: Vec<String> = lines.into_iter().map(String::from).collect();
+    for edit in edits {
+        lines.splice(edit.start..edit.end, edit.lines);
+    }
+
+    let mut patched = lines.join("\n");
+    if text.ends_with('\n') || text.is_empty() {
+        patched.push('\n');
+    }
+    Some(patched)
+}
+
+/// Replaces the lines in `start..end` with `lines`.
+struct Edit {
+    start: usize,
+    end: usize,
+    lines: Vec<String>,
+}
+
+/// A key of a block mapping and the lines its value spans.
+struct Entry {
+    key: Value,
+    line: usize,
+    end: usize,
+    /// What follows the colon on the key's line, without a comment.
+    inline: String,
+}
+
+/// An item of a block sequence and the lines it spans.
+struct Item {
+    line: usize,
+    end: usize,
+}
+
+/// Whether the value is on the following lines, i.e. nothing but maybe an
+/// anchor follows the colon.
+fn is_block(inline: &str) -> bool {
+    inline.is_empty() || (inline.starts_with('&') && !inline.contains(' '))
+}
+
+fn is_content(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    !trimmed.is_empty() && !trimmed.starts_with('#')
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn is_item(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed == "-" || trimmed.starts_with("- ")
+}
+
+/// The first line in `start..end` with something on it.
+fn first_content(lines: &[&str], start: usize, end: usize) -> Option<usize> {
+    (start..end).find(|&i| is_content(lines[i]))
+}
+
+/// Splits `key: value` into the parsed key and what follows the colon.
+fn split_key(line: &str) -> Option<(Value, String)> {
+    let line = line.trim_start();
+    let end = match line.chars().next()? {
+        quote @ ('"' | '\'') => line[1..].find(quote)? + 2,
+        _ => line
+            .find(": ")
+            .or_else(|| line.strip_suffix(':').map(str::len))?,
+    };
+    let rest = line[end..].strip_prefix(':')?;
+    let key = serde_yml::from_str(&line[..end]).ok()?;
+
+    let rest = rest.trim();
+    let rest = match rest.starts_with('#') {
+        true => "",
+        false => rest.split(" #").next().unwrap_or(rest).trim_end(),
+    };
+    Some((key, rest.to_string()))
+}
+
+/// The entries of the block mapping in `start..end`.
+fn entries(lines: &[&str], start: usize, end: usize) -> Option<Vec<Entry>> {
+    let first = first_content(lines, start, end)?;
+    let mapping_indent = indent(lines[first]);
+
+    let mut entries: Vec<Entry> = vec![];
+    for (i, line) in lines.iter().enumerate().take(end).skip(first) {
+        if !is_content(line) {
+            continue;
+        }
+        let line_indent = indent(line);
+        // Deeper lines, and a sequence written level with its key, belong to
+        // the entry before.
+        if line_indent > mapping_indent
+            || (line_indent == mapping_indent
+                && is_item(line)
+                && entries.last().is_some_and(|e| is_block(&e.inline)))
+        {
+            entries.last_mut()?.end = i + 1;
+            continue;
+        }
+        if line_indent < mapping_indent || is_item(line) {
+            return None;
+        }
+
+        let (key, inline) = split_key(line)?;
+        entries.push(Entry {
+            key,
+            line: i,
+            end: i + 1,
+            inline,
+        });
+    }
+
+    Some(entries)
+}
+
+/// The items of the block sequence in `start..end`.
+fn items(lines: &[&str], start: usize, end: usize) -> Option<Vec<Item>> {
+    let first = first_content(lines, start, end)?;
+    let sequence_indent = indent(lines[first]);
+
+    let mut items: Vec<Item> = vec![];
+    for (i, line) in lines.iter().enumerate().take(end).skip(first) {
+        if !is_content(line) {
+            continue;
+        }
+        match indent(line) {
+            n if n > sequence_indent => items.last_mut()?.end = i + 1,
+            n if n == sequence_indent && is_item(line) => items.push(Item {
+                line: i,
+                end: i + 1,
+            }),
+            _ => return None,
+        }
+    }
+
+    Some(items)
+}
+
+/// `value` as block YAML, with every line indented by `indent` spaces.
+fn render(value: &impl serde::Serialize, indent: usize) -> Option<Vec<String>> {
+    let yaml = serde_yml::to_string(value).ok()?;
+    Some(
+        yaml.lines()
+            .map(|l| format!("{}{l}", " ".repeat(indent)))
+            .collect(),
+    )
+}
+
+fn render_entry(key: &Value, value: &Value, indent: usize) -> Option<Vec<String>> {
+    let mut entry = serde_yml::Mapping::new();
+    entry.insert(key.clone(), value.clone());
+    render(&entry, indent)
+}
+
+/// Collects the edits that turn the `old` node in `start..end` into `new`.
+fn diff(
+    lines: &[&str],
+    start: usize,
+    end: usize,
+    old: &Value,
+    new: &Value,
+    edits: &mut Vec<Edit>,
+) -> Option<()> {
+    match (old, new) {
+        (Value::Mapping(old), Value::Mapping(new)) => {
+            diff_mapping(lines, start, end, old, new, edits)
+        }
+        (Value::Sequence(old), Value::Sequence(new)) => {
+            diff_sequence(lines, start, end, old, new, edits)
+        }
+        _ => None,
+    }
+}
+
+fn diff_mapping(
+    lines: &[&str],
+    start: usize,
+    end: usize,
+    old: &serde_yml::Mapping,
+    new: &serde_yml::Mapping,
+    edits: &mut Vec<Edit>,
+) -> Option<()> {
+    // Keys that were reordered, e.g. sorted, mean rewriting the mapping whole.
+    if shared_keys(old, new) != shared_keys(new, old) {
+        return None;
+    }
+
+    let entries = entries(lines, start, end)?;
+    let mapping_indent = indent(lines[entries.first()?.line]);
+    for (key, old_value) in old {
+        let new_value = new.get(key);
+        if new_value.is_some_and(|new| identical(old_value, new)) {
+            continue;
+        }
+        // A key that isn't in the text came from elsewhere, e.g. merged in
+        // with `<<`, and can't be edited here.
+        let entry = entries.iter().find(|e| e.key == *key)?;
+        let Some(new_value) = new_value else {
+            edits.push(Edit {
+                start: entry.line,
+                end: entry.end,
+                lines: vec![],
+            });
+            continue;
+        };
+
+        let mut nested = vec![];
+        match is_block(&entry.inline)
+            && entry.end > entry.line + 1
+            && diff(
+                lines,
+                entry.line + 1,
+                entry.end,
+                old_value,
+                new_value,
+                &mut nested,
+            )
+            .is_some()
+        {
+            true => edits.extend(nested),
+            false => edits.push(Edit {
+                start: entry.line,
+                end: entry.end,
+                lines: render_entry(key, new_value, mapping_indent)?,
+            }),
+        }
+    }
+
+    let added: Vec<String> = new
+        .iter()
+        .filter(|(key, _)| !old.contains_key(*key))
+        .map(|(key, value)| render_entry(key, value, mapping_indent))
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+    if !added.is_empty() {
+        let after = entries.last()?.end;
+        edits.push(Edit {
+            start: after,
+            end: after,
+            lines: added,
+        });
+    }
+
+    Some(())
+}
+
+/// Whether the values are equal with their keys in the same order, which
+/// `==` doesn't look at.
+fn identical(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Mapping(a), Value::Mapping(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|((ka, va), (kb, vb))| ka == kb && identical(va, vb))
+        }
+        (Value::Sequence(a), Value::Sequence(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| identical(a, b))
+        }
+        _ => a == b,
+    }
+}
+
+/// The keys of `a` that are also in `b`, in the order of `a`.
+fn shared_keys<'a>(a: &'a serde_yml::Mapping, b: &serde_yml::Mapping) -> Vec<&'a Value> {
+    a.keys().filter(|k| b.contains_key(*k)).collect()
+}
+
+/// Sequences are edited item by item when only items were removed or
+/// appended, which covers labels, networks and ports being added or dropped.
+fn diff_sequence(
+    lines: &[&str],
+    start: usize,
+    end: usize,
+    old: &[Value],
+    new: &[Value],
+    edits: &mut Vec<Edit>,
+) -> Option<()> {
+    let items = items(lines, start, end)?;
+    let sequence_indent = indent(lines[items.first()?.line]);
+    if items.len() != old.len() {
+        return None;
+    }
+
+    // The old items that are kept have to be in the same order in the new
+    // sequence, anything after them is appended.
+    let mut kept = 0;
+    for (item, old_value) in items.iter().zip(old) {
+        match new.get(kept) {
+            Some(new_value) if identical(old_value, new_value) => kept += 1,
+            _ if new[kept..].iter().any(|n| identical(old_value, n)) => return None,
+            _ => edits.push(Edit {
+                start: item.line,
+                end: item.end,
+                lines: vec![],
+            }),
+        }
+    }
+
+    if kept < new.len() {
+        let after = items.last()?.end;
+        edits.push(Edit {
+            start: after,
+            end: after,
+            lines: render(&new[kept..].to_vec(), sequence_indent)?,
+        });
+    }
+
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Patches `text` with the change made to what it parses to.
+    fn patched(text: &str, change: impl FnOnce(&mut Value)) -> Option<String> {
+        let old: Value = serde_yml::from_str(text).unwrap();
+        let mut new = old.clone();
+        change(&mut new);
+        patch(text, &old, &new)
+    }
+
+    fn labels(value: &mut Value) -> &mut Vec<Value> {
+        value["services"]["app"]["labels"]
+            .as_sequence_mut()
+            .unwrap()
+    }
+
+    #[test]
+    fn keeps_comments() {
+        let text = "\
+# The stack
+services:
+  app: # the web app
+    image: nginx # pinned below
+    labels:
+      - a=1 # first
+";
+        let patched = patched(text, |v| labels(v).push("b=2".into())).unwrap();
+
+        assert_eq!(
+            patched,
+            "\
+# The stack
+services:
+  app: # the web app
+    image: nginx # pinned below
+    labels:
+      - a=1 # first
+      - b=2
+"
+        );
+    }
+
+    #[test]
+    fn keeps_anchors_and_aliases() {
+        let text = "\
+x-common: &common
+  restart: always
+services:
+  app:
+    <<: *common
+    image: nginx
+";
+        let patched = patched(text, |v| {
+            v["services"]["app"]["image"] = "caddy".into();
+        })
+        .unwrap();
+
+        assert_eq!(
+            patched,
+            "\
+x-common: &common
+  restart: always
+services:
+  app:
+    <<: *common
+    image: caddy
+"
+        );
+    }
+
+    #[test]
+    fn appends_and_removes_items() {
+        let text = "\
+services:
+  app:
+    labels:
+      - a=1
+      - b=2
+      - c=3
+";
+        let patched = patched(text, |v| {
+            labels(v).remove(1);
+            labels(v).push("d=4".into());
+        })
+        .unwrap();
+
+        assert_eq!(
+            patched,
+            "\
+services:
+  app:
+    labels:
+      - a=1
+      - c=3
+      - d=4
+"
+        );
+    }
+
+    #[test]
+    fn adds_keys_to_nested_mappings() {
+        let text = "\
+services:
+  app:
+    image: nginx
+
+  db:
+    image: postgres
+";
+        let patched = patched(text, |v| {
+            v["services"]["app"]
+                .as_mapping_mut()
+                .unwrap()
+                .insert("networks".into(), vec![Value::from("caddy")].into());
+        })
+        .unwrap();
+
+        assert_eq!(
+            patched,
+            "\
+services:
+  app:
+    image: nginx
+    networks:
+    - caddy
+
+  db:
+    image: postgres
+"
+        );
+    }
+
+    #[test]
+    fn replaces_values_it_cant_edit_into() {
+        let text = "\
+services:
+  app:
+    labels: [a=1]
+    image: nginx
+";
+        let patched = patched(text, |v| labels(v).push("b=2".into())).unwrap();
+
+        assert_eq!(
+            patched,
+            "\
+services:
+  app:
+    labels:
+    - a=1
+    - b=2
+    image: nginx
+"
+        );
+    }
+
+    #[test]
+    fn rewrites_reordered_mappings_whole() {
+        let text = "\
+# The stack
+services:
+  b: # second
+    image: nginx
+  a:
+    image: caddy
+";
+        let patched = patched(text, |v| {
+            let services = v["services"].as_mapping_mut().unwrap();
+            let b = services.remove("b").unwrap();
+            services.insert("b".into(), b);
+        })
+        .unwrap();
+
+        assert_eq!(
+            patched,
+            "\
+# The stack
+services:
+  a:
+    image: caddy
+  b:
+    image: nginx
+"
+        );
+    }
+
+    #[test]
+    fn gives_up_on_flow_style_documents() {
+        // `edits::write` then writes the file out whole.
+        let flow = "{services: {app: {image: nginx}}}\n";
+        assert!(patched(flow, |v| v["services"]["app"]["image"] = "caddy".into()).is_none());
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use docker_compose_types::{Deploy, Limits, LoggingParameters, Resources, Service, SingleValue};
+use serde::{Deserialize, Serialize};
+
+/// Hardening applied to services created by `web new`.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    #[serde(default)]
+    pub restart: Option<String>,
+    /// A compose memory limit, e.g. `512m`.
+    #[serde(default)]
+    pub memory: Option<String>,
+    #[serde(default)]
+    pub cpus: Option<String>,
+    #[serde(default)]
+    pub logging_driver: Option<String>,
+    #[serde(default)]
+    pub logging_options: BTreeMap<String, String>,
+    /// Variables the image needs secrets for, which are generated rather than
+    /// asked for.
+    #[serde(default)]
+    pub secrets: Vec<String>,
+}
+
+impl Preset {
+    pub fn apply(&self, service: &mut Service) {
+        if let Some(restart) = &self.restart {
+            service.restart = Some(restart.clone());
+        }
+
+        if self.memory.is_some() || self.cpus.is_some() {
+            let deploy = service.deploy.get_or_insert_with(Deploy::default);
+            let resources = deploy.resources.get_or_insert_with(Resources::default);
+            // The field isn't skipped when empty, and compose rejects a null.
+            resources.reservations.get_or_insert_with(Limits::default);
+            let limits = resources.limits.get_or_insert_with(Limits::default);
+            limits.memory = self.memory.clone().or(limits.memory.take());
+            limits.cpus = self.cpus.clone().or(limits.cpus.take());
+        }
+
+        if self.logging_driver.is_some() || !self.logging_options.is_empty() {
+            service.logging = Some(LoggingParameters {
+                driver: self.logging_driver.clone(),
+                options: (!self.logging_options.is_empty()).then(|| {
+                    self.logging_options
+                        .iter()
+                        .map(|(k, v)| (k.clone(), SingleValue::String(v.clone())))
+                        .collect()
+                }),
+            });
+        }
+    }
+}
+
+/// The built in presets, with the ones from the config added or replacing
+/// them by name.
+pub fn all(configured: &BTreeMap<String, Preset>) -> BTreeMap<String, Preset> {
+    let rotated_logs = BTreeMap::from([
+        ("max-size".to_string(), "10m".to_string()),
+        ("max-file".to_string(), "3".to_string()),
+    ]);
+
+    let mut presets = BTreeMap::from([
+        (
+            "small".to_string(),
+            Preset {
+                restart: Some("unless-stopped".to_string()),
+                memory: Some("256m".to_string()),
+                cpus: Some("0.5".to_string()),
+                logging_driver: Some("json-file".to_string()),
+                logging_options: rotated_logs.clone(),
+                ..Default::default()
+            },
+        ),
+        (
+            "default".to_string(),
+            Preset {
+                restart: Some("unless-stopped".to_string()),
+                memory: Some("512m".to_string()),
+                cpus: Some("1".to_string()),
+                logging_driver: Some("json-file".to_string()),
+                logging_options: rotated_logs.clone(),
+                ..Default::default()
+            },
+        ),
+        (
+            "db".to_string(),
+            Preset {
+                restart: Some("always".to_string()),
+                memory: Some("1g".to_string()),
+                cpus: Some("2".to_string()),
+                logging_driver: Some("json-file".to_string()),
+                logging_options: rotated_logs,
+                ..Default::default()
+            },
+        ),
+    ]);
+    presets.extend(configured.clone());
+
+    presets
+}
@@ -0,0 +1,134 @@
+use std::{
+    net::TcpStream,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Context, ContextCompat, Result};
+use futures::future::join_all;
+use reqwest::Client;
+
+use crate::logging;
+
+static TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The result of requesting `https://<domain>/`.
+#[derive(Debug, Clone)]
+pub struct Probe {
+    pub domain: String,
+    pub status: Option<u16>,
+    pub latency: Duration,
+    pub cert_expiry: Option<DateTime<Utc>>,
+    /// The organization, or else the name, of the certificate's issuer.
+    pub cert_issuer: Option<String>,
+    /// Whether the response was served by Cloudflare's edge.
+    pub cloudflare: bool,
+    /// The `server` header of the response.
+    pub server: Option<String>,
+    pub error: Option<String>,
+}
+
+/// What [`cert_info`] reads from a certificate.
+#[derive(Debug, Clone)]
+pub struct CertInfo {
+    pub expiry: DateTime<Utc>,
+    pub issuer: String,
+}
+
+/// Probes all the domains at once, returning the results in the same order.
+pub async fn probe_all(domains: &[String]) -> Result<Vec<Probe>> {
+    let client = Client::builder().timeout(TIMEOUT).build()?;
+
+    Ok(join_all(domains.iter().map(|d| probe(&client, d))).await)
+}
+
+pub async fn probe(client: &Client, domain: &str) -> Probe {
+    let start = Instant::now();
+    let response = logging::send(client.get(format!("https://{domain}/"))).await;
+    let latency = start.elapsed();
+
+    let (status, cloudflare, server, error) = match response {
+        Ok(r) => (
+            Some(r.status().as_u16()),
+            r.headers().contains_key("cf-ray"),
+            r.headers()
+                .get("server")
+                .and_then(|s| s.to_str().ok())
+                .map(str::to_string),
+            None,
+        ),
+        Err(e) => (None, false, None, Some(e.to_string())),
+    };
+    let cert = cert_info_blocking(domain).await.ok();
+
+    Probe {
+        domain: domain.to_string(),
+        status,
+        latency,
+        cert_expiry: cert.as_ref().map(|c| c.expiry),
+        cert_issuer: cert.map(|c| c.issuer),
+        cloudflare,
+        server,
+        error,
+    }
+}
+
+/// Looks up the certificate expiry of all the domains at once, returning the
+/// results in the same order.
+pub async fn cert_expiries(domains: &[String]) -> Vec<Result<DateTime<Utc>>> {
+    join_all(
+        domains
+            .iter()
+            .map(|d| async { cert_info_blocking(d).await.map(|c| c.expiry) }),
+    )
+    .await
+}
+
+/// Runs [`cert_info`] on port 443 off the async threads, since native-tls
+/// only has a blocking api.
+async fn cert_info_blocking(host: &str) -> Result<CertInfo> {
+    let host = host.to_string();
+    tokio::task::spawn_blocking(move || cert_info(&host, 443))
+        .await
+        .unwrap_or_else(|_| Err(color_eyre::eyre::eyre!("The check panicked.")))
+}
+
+/// When the certificate served for `host` expires.
+pub fn cert_expiry(host: &str, port: u16) -> Result<DateTime<Utc>> {
+    cert_info(host, port).map(|c| c.expiry)
+}
+
+/// The expiry and issuer of the certificate served for `host`. The
+/// certificate isn't verified, so this also works for ones that are already
+/// invalid.
+pub fn cert_info(host: &str, port: u16) -> Result<CertInfo> {
+    let connector = native_tls::TlsConnector::builder()
+        .danger_accept_invalid_certs(true)
+        .build()?;
+
+    let stream = TcpStream::connect((host, port))
+        .with_context(|| format!("Could not connect to {host}:{port}."))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let tls = connector
+        .connect(host, stream)
+        .with_context(|| format!("TLS handshake with {host} failed."))?;
+    let der = tls
+        .peer_certificate()?
+        .context("The server did not send a certificate.")?
+        .to_der()?;
+
+    let (_, cert) = x509_parser::parse_x509_certificate(&der)
+        .map_err(|e| color_eyre::eyre::eyre!("Could not parse the certificate: {e}"))?;
+    let expiry = DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0)
+        .context("The certificate has an invalid expiry date.")?;
+    let issuer = cert.issuer();
+    let issuer = issuer
+        .iter_organization()
+        .chain(issuer.iter_common_name())
+        .find_map(|a| a.as_str().ok())
+        .map_or(issuer.to_string(), str::to_string);
+
+    Ok(CertInfo { expiry, issuer })
+}
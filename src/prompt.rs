@@ -0,0 +1,311 @@
+//! Prompts that `--yes` answers without asking. They wrap the cliclack ones
+//! and take the same builder calls. Confirmations are answered with yes and
+//! other prompts with their default, and a prompt without a default fails
+//! since there is nothing sane to pick for the user.
+
+use std::{fmt::Display, rc::Rc, str::FromStr};
+
+use cliclack::Validate;
+use color_eyre::eyre::{eyre, Result};
+
+use crate::{error::EurusError, ASSUME_YES};
+
+/// Whether `--yes` was passed.
+pub fn assume_yes() -> bool {
+    ASSUME_YES.get().copied().unwrap_or_default()
+}
+
+/// Shows what `--yes` answered, so the output still reads like a session.
+fn answered(prompt: &str, answer: impl Display) -> Result<()> {
+    Ok(cliclack::log::remark(format!("{prompt} {answer}"))?)
+}
+
+fn no_default(prompt: &str) -> color_eyre::Report {
+    eyre!(EurusError::ValidationError(format!(
+        "\"{prompt}\" has no default for --yes to take, pass the answer as a flag instead."
+    )))
+}
+
+/// Asks whether to go ahead, which `--yes` answers with yes.
+pub fn confirm(prompt: impl Display) -> Confirm {
+    Confirm::new(prompt, true)
+}
+
+/// Asks a yes or no question, which `--yes` answers with the initial value.
+pub fn toggle(prompt: impl Display) -> Confirm {
+    Confirm::new(prompt, false)
+}
+
+pub struct Confirm {
+    inner: cliclack::Confirm,
+    prompt: String,
+    initial: bool,
+    go_ahead: bool,
+}
+
+impl Confirm {
+    fn new(prompt: impl Display, go_ahead: bool) -> Self {
+        let prompt = prompt.to_string();
+        Self {
+            inner: cliclack::confirm(&prompt),
+            prompt,
+            initial: false,
+            go_ahead,
+        }
+    }
+
+    pub fn initial_value(mut self, value: bool) -> Self {
+        self.inner = self.inner.initial_value(value);
+        self.initial = value;
+        self
+    }
+
+    pub fn interact(&mut self) -> Result<bool> {
+        if !assume_yes() {
+            return Ok(self.inner.interact()?);
+        }
+
+        let answer = self.go_ahead || self.initial;
+        answered(&self.prompt, if answer { "Yes" } else { "No" })?;
+        Ok(answer)
+    }
+}
+
+pub fn select<T: Clone + Eq>(prompt: impl Display) -> Select<T> {
+    let prompt = prompt.to_string();
+    Select {
+        inner: cliclack::select(&prompt),
+        prompt,
+        labels: vec![],
+        initial: None,
+    }
+}
+
+pub struct Select<T> {
+    inner: cliclack::Select<T>,
+    prompt: String,
+    labels: Vec<(T, String)>,
+    initial: Option<T>,
+}
+
+impl<T: Clone + Eq> Select<T> {
+    pub fn item(mut self, value: T, label: impl Display, hint: impl Display) -> Self {
+        self.labels.push((value.clone(), label.to_string()));
+        self.inner = self.inner.item(value, label, hint);
+        self
+    }
+
+    pub fn items(self, items: &[(T, impl Display, impl Display)]) -> Self {
+        items.iter().fold(self, |select, (value, label, hint)| {
+            select.item(value.clone(), label, hint)
+        })
+    }
+
+    pub fn initial_value(mut self, value: T) -> Self {
+        self.inner = self.inner.initial_value(value.clone());
+        self.initial = Some(value);
+        self
+    }
+
+    pub fn filter_mode(mut self) -> Self {
+        self.inner = self.inner.filter_mode();
+        self
+    }
+
+    pub fn interact(&mut self) -> Result<T> {
+        if !assume_yes() {
+            return Ok(self.inner.interact()?);
+        }
+
+        let value = self
+            .initial
+            .clone()
+            .ok_or_else(|| no_default(&self.prompt))?;
+        let label = self.labels.iter().find(|(v, _)| *v == value);
+        answered(&self.prompt, label.map_or("", |(_, l)| l))?;
+        Ok(value)
+    }
+}
+
+pub fn multiselect<T: Clone + Eq>(prompt: impl Display) -> MultiSelect<T> {
+    let prompt = prompt.to_string();
+    MultiSelect {
+        inner: cliclack::multiselect(&prompt),
+        prompt,
+        labels: vec![],
+        initial: None,
+        required: true,
+    }
+}
+
+pub struct MultiSelect<T> {
+    inner: cliclack::MultiSelect<T>,
+    prompt: String,
+    labels: Vec<(T, String)>,
+    initial: Option<Vec<T>>,
+    required: bool,
+}
+
+impl<T: Clone + Eq> MultiSelect<T> {
+    pub fn item(mut self, value: T, label: impl Display, hint: impl Display) -> Self {
+        self.labels.push((value.clone(), label.to_string()));
+        self.inner = self.inner.item(value, label, hint);
+        self
+    }
+
+    pub fn items(self, items: &[(T, impl Display, impl Display)]) -> Self {
+        items.iter().fold(self, |select, (value, label, hint)| {
+            select.item(value.clone(), label, hint)
+        })
+    }
+
+    pub fn initial_values(mut self, values: Vec<T>) -> Self {
+        self.inner = self.inner.initial_values(values.clone());
+        self.initial = Some(values);
+        self
+    }
+
+    pub fn required(mut self, required: bool) -> Self {
+        self.inner = self.inner.required(required);
+        self.required = required;
+        self
+    }
+
+    pub fn filter_mode(mut self) -> Self {
+        self.inner = self.inner.filter_mode();
+        self
+    }
+
+    pub fn interact(&mut self) -> Result<Vec<T>> {
+        if !assume_yes() {
+            return Ok(self.inner.interact()?);
+        }
+
+        let values = match (&self.initial, self.required) {
+            (Some(values), _) => values.clone(),
+            (None, false) => vec![],
+            (None, true) => return Err(no_default(&self.prompt)),
+        };
+        let labels: Vec<_> = self
+            .labels
+            .iter()
+            .filter(|(v, _)| values.contains(v))
+            .map(|(_, l)| l.as_str())
+            .collect();
+        answered(&self.prompt, labels.join(", "))?;
+        Ok(values)
+    }
+}
+
+pub fn input(prompt: impl Display) -> Input {
+    let prompt = prompt.to_string();
+    Input {
+        inner: cliclack::input(&prompt),
+        prompt,
+        default: None,
+        validate: None,
+    }
+}
+
+type Validator = Rc<dyn Fn(&String) -> Result<(), String>>;
+
+pub struct Input {
+    inner: cliclack::Input,
+    prompt: String,
+    default: Option<String>,
+    validate: Option<Validator>,
+}
+
+impl Input {
+    pub fn placeholder(mut self, placeholder: &str) -> Self {
+        self.inner = self.inner.placeholder(placeholder);
+        self
+    }
+
+    pub fn default_input(mut self, value: &str) -> Self {
+        self.inner = self.inner.default_input(value);
+        self.default = Some(value.to_string());
+        self
+    }
+
+    pub fn required(mut self, required: bool) -> Self {
+        self.inner = self.inner.required(required);
+        if !required {
+            self.default.get_or_insert_with(String::new);
+        }
+        self
+    }
+
+    pub fn multiline(mut self) -> Self {
+        self.inner = self.inner.multiline();
+        self
+    }
+
+    pub fn validate<V>(mut self, validator: V) -> Self
+    where
+        V: Validate<String> + 'static,
+        V::Err: ToString,
+    {
+        let validator: Validator =
+            Rc::new(move |input: &String| validator.validate(input).map_err(|e| e.to_string()));
+        let inner = validator.clone();
+        self.inner = self.inner.validate(move |input: &String| inner(input));
+        self.validate = Some(validator);
+        self
+    }
+
+    pub fn interact<T: FromStr>(&mut self) -> Result<T> {
+        if !assume_yes() {
+            return Ok(self.inner.interact()?);
+        }
+
+        let value = self
+            .default
+            .clone()
+            .ok_or_else(|| no_default(&self.prompt))?;
+        if let Some(validate) = &self.validate {
+            validate(&value).map_err(|e| {
+                eyre!(EurusError::ValidationError(format!(
+                    "The default for \"{}\" isn't valid: {e}",
+                    self.prompt
+                )))
+            })?;
+        }
+        let parsed = value.parse().map_err(|_| {
+            eyre!(EurusError::ValidationError(format!(
+                "The default for \"{}\" isn't valid.",
+                self.prompt
+            )))
+        })?;
+        answered(&self.prompt, &value)?;
+        Ok(parsed)
+    }
+}
+
+/// Asks for a secret, which `--yes` can't answer.
+pub fn password(prompt: impl Display) -> Password {
+    let prompt = prompt.to_string();
+    Password {
+        inner: cliclack::password(&prompt),
+        prompt,
+    }
+}
+
+pub struct Password {
+    inner: cliclack::Password,
+    prompt: String,
+}
+
+impl Password {
+    pub fn mask(mut self, mask: char) -> Self {
+        self.inner = self.inner.mask(mask);
+        self
+    }
+
+    pub fn interact(&mut self) -> Result<String> {
+        match assume_yes() {
+            true => Err(no_default(&self.prompt)),
+            false => Ok(self.inner.interact()?),
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use async_trait::async_trait;
+
+use super::{
+    batch_individually, DnsCreateUpdate, DnsListResponse, DnsProvider, ZoneDetailsResponse,
+};
+use crate::{
+    audit::{self, Change},
+    error::Result,
+    logging,
+};
+
+/// Records every change made through the provider in the audit log. Records
+/// listed beforehand are remembered, so updates and deletes can log what they
+/// replaced.
+pub struct Audited {
+    inner: Box<dyn DnsProvider>,
+    zone: String,
+    listed: Mutex<BTreeMap<String, DnsListResponse>>,
+}
+
+impl Audited {
+    pub fn new(inner: Box<dyn DnsProvider>, zone: &str) -> Self {
+        Self {
+            inner,
+            zone: zone.to_string(),
+            listed: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn old(&self, id: &str) -> Option<serde_json::Value> {
+        let listed = self.listed.lock().unwrap();
+        listed.get(id).and_then(|r| serde_json::to_value(r).ok())
+    }
+
+    /// Lists the zone first when a record about to change wasn't listed, so
+    /// the log has what it was before.
+    async fn remember<'a>(&self, zone_id: &str, mut ids: impl Iterator<Item = &'a String>) {
+        let unknown = {
+            let listed = self.listed.lock().unwrap();
+            ids.any(|id| !listed.contains_key(id))
+        };
+        if unknown {
+            let _ = self.list_records(zone_id).await;
+        }
+    }
+
+    fn record_upsert(&self, record: &DnsCreateUpdate, new: serde_json::Value, ray: Option<String>) {
+        let zone = self.zone.clone();
+        audit::record_or_warn(&match &record.id {
+            Some(id) => Change::DnsUpdate {
+                zone,
+                old: self.old(id),
+                new,
+                ray,
+            },
+            None => Change::DnsCreate { zone, new, ray },
+        });
+    }
+
+    fn record_delete(&self, id: &str, ray: Option<String>) {
+        audit::record_or_warn(&Change::DnsDelete {
+            zone: self.zone.clone(),
+            id: id.to_string(),
+            old: self.old(id),
+            ray,
+        });
+        self.listed.lock().unwrap().remove(id);
+    }
+}
+
+#[async_trait]
+impl DnsProvider for Audited {
+    async fn get_zone(&self, zone_id: &str) -> Result<ZoneDetailsResponse> {
+        self.inner.get_zone(zone_id).await
+    }
+
+    async fn list_records(&self, zone_id: &str) -> Result<Vec<DnsListResponse>> {
+        let records = self.inner.list_records(zone_id).await?;
+        self.listed
+            .lock()
+            .unwrap()
+            .extend(records.iter().map(|r| (r.id.clone(), r.clone())));
+
+        Ok(records)
+    }
+
+    async fn upsert_record(
+        &self,
+        zone_id: &str,
+        record: &DnsCreateUpdate,
+    ) -> Result<DnsListResponse> {
+        self.remember(zone_id, record.id.iter()).await;
+        let (result, ray) = logging::with_ray(self.inner.upsert_record(zone_id, record)).await;
+        let result = result?;
+        self.record_upsert(
+            record,
+            serde_json::to_value(&result).unwrap_or_default(),
+            ray,
+        );
+        self.listed
+            .lock()
+            .unwrap()
+            .insert(result.id.clone(), result.clone());
+
+        Ok(result)
+    }
+
+    async fn delete_record(&self, zone_id: &str, record_id: &str) -> Result<()> {
+        self.remember(zone_id, [record_id.to_string()].iter()).await;
+        let (result, ray) = logging::with_ray(self.inner.delete_record(zone_id, record_id)).await;
+        result?;
+        self.record_delete(record_id, ray);
+
+        Ok(())
+    }
+
+    fn batches_atomically(&self) -> bool {
+        self.inner.batches_atomically()
+    }
+
+    async fn batch(
+        &self,
+        zone_id: &str,
+        upserts: &[DnsCreateUpdate],
+        deletes: &[String],
+    ) -> Result<()> {
+        self.remember(
+            zone_id,
+            deletes
+                .iter()
+                .chain(upserts.iter().filter_map(|r| r.id.as_ref())),
+        )
+        .await;
+        // Going through this provider for each call logs the ones that
+        // succeeded even when others fail.
+        if !self.inner.batches_atomically() {
+            return batch_individually(self, zone_id, upserts, deletes).await;
+        }
+        let (result, ray) = logging::with_ray(self.inner.batch(zone_id, upserts, deletes)).await;
+        result?;
+        for id in deletes {
+            self.record_delete(id, ray.clone());
+        }
+        for record in upserts {
+            self.record_upsert(
+                record,
+                serde_json::to_value(record).unwrap_or_default(),
+                ray.clone(),
+            );
+        }
+
+        Ok(())
+    }
+}
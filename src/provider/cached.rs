@@ -0,0 +1,153 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::{DnsCreateUpdate, DnsListResponse, DnsProvider, ZoneDetailsResponse};
+use crate::{error::Result, DATA_DIR, REFRESH};
+
+/// Keeps the zone's details and records in the data dir for `ttl` seconds,
+/// so commands run one after another don't list large zones again. Any
+/// change made through eurus drops the zone's cache.
+pub struct Cached {
+    inner: Box<dyn DnsProvider>,
+    ttl: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default)]
+struct Cache {
+    #[serde(default)]
+    zone: Option<Entry<ZoneDetailsResponse>>,
+    #[serde(default)]
+    records: Option<Entry<Vec<DnsListResponse>>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Entry<T> {
+    /// In unix seconds.
+    fetched: i64,
+    value: T,
+}
+
+impl<T> Entry<T> {
+    fn fresh(&self, ttl: u64) -> bool {
+        chrono::Utc::now().timestamp() - self.fetched < ttl as i64
+    }
+}
+
+impl Cached {
+    pub fn new(inner: Box<dyn DnsProvider>, ttl: u64) -> Self {
+        Self { inner, ttl }
+    }
+
+    fn path(zone_id: &str) -> PathBuf {
+        DATA_DIR
+            .join("cache")
+            .join(format!("{}.json", zone_id.replace(['/', '\\'], "_")))
+    }
+
+    /// The cache of the zone, empty when it is off, missing or unreadable, or
+    /// `--refresh` was passed.
+    fn load(&self, zone_id: &str) -> Cache {
+        if self.ttl == 0 || REFRESH.get() == Some(&true) {
+            return Cache::default();
+        }
+
+        std::fs::read_to_string(Self::path(zone_id))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, zone_id: &str, change: impl FnOnce(&mut Cache)) {
+        if self.ttl == 0 {
+            return;
+        }
+
+        let mut cache = std::fs::read_to_string(Self::path(zone_id))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        change(&mut cache);
+        let written = std::fs::create_dir_all(DATA_DIR.join("cache")).and_then(|_| {
+            std::fs::write(
+                Self::path(zone_id),
+                serde_json::to_string(&cache).unwrap_or_default(),
+            )
+        });
+        if let Err(e) = written {
+            tracing::debug!("Could not write the cache of {zone_id}: {e}");
+        }
+    }
+
+    fn invalidate(zone_id: &str) {
+        _ = std::fs::remove_file(Self::path(zone_id));
+    }
+}
+
+#[async_trait]
+impl DnsProvider for Cached {
+    async fn get_zone(&self, zone_id: &str) -> Result<ZoneDetailsResponse> {
+        if let Some(entry) = self.load(zone_id).zone.filter(|e| e.fresh(self.ttl)) {
+            return Ok(entry.value);
+        }
+
+        let zone = self.inner.get_zone(zone_id).await?;
+        self.save(zone_id, |cache| {
+            cache.zone = Some(Entry {
+                fetched: chrono::Utc::now().timestamp(),
+                value: zone.clone(),
+            })
+        });
+
+        Ok(zone)
+    }
+
+    async fn list_records(&self, zone_id: &str) -> Result<Vec<DnsListResponse>> {
+        if let Some(entry) = self.load(zone_id).records.filter(|e| e.fresh(self.ttl)) {
+            tracing::debug!("Using the cached records of {zone_id}");
+            return Ok(entry.value);
+        }
+
+        let records = self.inner.list_records(zone_id).await?;
+        self.save(zone_id, |cache| {
+            cache.records = Some(Entry {
+                fetched: chrono::Utc::now().timestamp(),
+                value: records.clone(),
+            })
+        });
+
+        Ok(records)
+    }
+
+    async fn upsert_record(
+        &self,
+        zone_id: &str,
+        record: &DnsCreateUpdate,
+    ) -> Result<DnsListResponse> {
+        let result = self.inner.upsert_record(zone_id, record).await;
+        Self::invalidate(zone_id);
+        result
+    }
+
+    async fn delete_record(&self, zone_id: &str, record_id: &str) -> Result<()> {
+        let result = self.inner.delete_record(zone_id, record_id).await;
+        Self::invalidate(zone_id);
+        result
+    }
+
+    fn batches_atomically(&self) -> bool {
+        self.inner.batches_atomically()
+    }
+
+    async fn batch(
+        &self,
+        zone_id: &str,
+        upserts: &[DnsCreateUpdate],
+        deletes: &[String],
+    ) -> Result<()> {
+        let result = self.inner.batch(zone_id, upserts, deletes).await;
+        Self::invalidate(zone_id);
+        result
+    }
+}
@@ -0,0 +1,230 @@
+use async_trait::async_trait;
+use reqwest::{RequestBuilder, Response};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{
+    absolute_name, relative_name, ttl_or, DnsCreateUpdate, DnsListResponse, DnsProvider,
+    ZoneDetailsResponse,
+};
+use crate::{
+    error::{EurusError, Result},
+    retry, CLIENT,
+};
+
+static BASE_URL: &str = "https://api.digitalocean.com/v2";
+static DEFAULT_TTL: u32 = 1800;
+
+#[derive(Debug, Deserialize)]
+struct DomainResponse {
+    domain: Domain,
+}
+
+#[derive(Debug, Deserialize)]
+struct Domain {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecordsResponse {
+    domain_records: Vec<DomainRecord>,
+    #[serde(default)]
+    links: Links,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct Links {
+    #[serde(default)]
+    pages: Pages,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct Pages {
+    next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecordResponse {
+    domain_record: DomainRecord,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct DomainRecord {
+    #[serde(skip_serializing)]
+    id: u64,
+    #[serde(rename = "type")]
+    record_type: String,
+    name: String,
+    data: String,
+    ttl: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    weight: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    flags: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
+
+impl DomainRecord {
+    fn to_record(&self, domain: &str) -> DnsListResponse {
+        let value = self.data.trim_end_matches('.').to_string();
+        // The fields kept next to `data` go back into the structured data, so
+        // an update made from the listing keeps them.
+        let (content, data) = match self.record_type.as_str() {
+            "SRV" => (
+                format!(
+                    "{} {} {value}",
+                    self.weight.unwrap_or_default(),
+                    self.port.unwrap_or_default()
+                ),
+                Some(serde_json::json!({
+                    "priority": self.priority.unwrap_or_default(),
+                    "weight": self.weight.unwrap_or_default(),
+                    "port": self.port.unwrap_or_default(),
+                    "target": value,
+                })),
+            ),
+            "CAA" => {
+                let flags = self.flags.unwrap_or_default();
+                let tag = self.tag.as_deref().unwrap_or_default();
+                (
+                    format!("{flags} {tag} \"{value}\""),
+                    Some(serde_json::json!({ "flags": flags, "tag": tag, "value": value })),
+                )
+            }
+            _ => (value, None),
+        };
+
+        DnsListResponse {
+            name: absolute_name(&self.name, domain),
+            id: self.id.to_string(),
+            record_type: self.record_type.clone(),
+            content,
+            proxied: false,
+            ttl: self.ttl,
+            priority: self.priority,
+            data: data.and_then(|d| d.as_object().cloned()),
+            comment: None,
+            tags: vec![],
+        }
+    }
+}
+
+/// DigitalOcean zones are identified by the domain name, which is what
+/// should be stored as the zone id in the config.
+pub struct DigitalOcean {
+    token: String,
+}
+
+impl DigitalOcean {
+    pub fn new(token: &str) -> Self {
+        Self {
+            token: token.to_string(),
+        }
+    }
+
+    async fn execute(&self, request: RequestBuilder) -> Result<Response> {
+        let response = retry::send(request.bearer_auth(&self.token)).await?;
+        let status = response.status();
+
+        if !status.is_success() {
+            return Err(
+                EurusError::api(match response.json::<ErrorResponse>().await {
+                    Ok(e) => format!("DigitalOcean api returned an error: {}", e.message),
+                    Err(_) => format!("DigitalOcean api returned {status}."),
+                })
+                .with_status(status),
+            );
+        }
+
+        Ok(response)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        Ok(self.execute(request).await?.json().await?)
+    }
+}
+
+#[async_trait]
+impl DnsProvider for DigitalOcean {
+    async fn get_zone(&self, zone_id: &str) -> Result<ZoneDetailsResponse> {
+        let res: DomainResponse = self
+            .send((*CLIENT).get(format!("{BASE_URL}/domains/{zone_id}")))
+            .await?;
+
+        Ok(ZoneDetailsResponse {
+            id: res.domain.name.clone(),
+            name: res.domain.name,
+            account: None,
+        })
+    }
+
+    async fn list_records(&self, zone_id: &str) -> Result<Vec<DnsListResponse>> {
+        let mut records = vec![];
+        let mut url = Some(format!("{BASE_URL}/domains/{zone_id}/records?per_page=200"));
+
+        while let Some(next) = url {
+            let page: RecordsResponse = self.send((*CLIENT).get(next)).await?;
+            records.extend(page.domain_records.iter().map(|r| r.to_record(zone_id)));
+            url = page.links.pages.next;
+        }
+
+        Ok(records)
+    }
+
+    async fn upsert_record(
+        &self,
+        zone_id: &str,
+        record: &DnsCreateUpdate,
+    ) -> Result<DnsListResponse> {
+        record.validate()?;
+        // SRV and CAA records keep their extra fields next to `data`, which is
+        // only the target or value.
+        let field = |key: &str| record.data.as_ref().and_then(|d| d.get(key));
+        let number = |key: &str| field(key).and_then(|v| v.as_u64()).map(|n| n as u16);
+        let content = field("target")
+            .or(field("value"))
+            .and_then(|v| v.as_str())
+            .map_or(record.content.clone(), String::from);
+
+        let data = match record.record_type.as_str() {
+            "CNAME" | "MX" | "NS" | "SRV" if !content.ends_with('.') => format!("{content}."),
+            _ => content,
+        };
+        let body = DomainRecord {
+            id: 0,
+            record_type: record.record_type.clone(),
+            name: relative_name(&record.name, zone_id),
+            data,
+            ttl: ttl_or(record.ttl, DEFAULT_TTL),
+            priority: record.priority,
+            port: number("port"),
+            weight: number("weight"),
+            flags: number("flags").map(|f| f as u8),
+            tag: field("tag").and_then(|v| v.as_str()).map(String::from),
+        };
+
+        let request = match &record.id {
+            Some(id) => (*CLIENT).put(format!("{BASE_URL}/domains/{zone_id}/records/{id}")),
+            None => (*CLIENT).post(format!("{BASE_URL}/domains/{zone_id}/records")),
+        };
+        let res: RecordResponse = self.send(request.json(&body)).await?;
+
+        Ok(res.domain_record.to_record(zone_id))
+    }
+
+    async fn delete_record(&self, zone_id: &str, record_id: &str) -> Result<()> {
+        self.execute((*CLIENT).delete(format!("{BASE_URL}/domains/{zone_id}/records/{record_id}")))
+            .await?;
+
+        Ok(())
+    }
+}
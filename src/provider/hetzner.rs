@@ -0,0 +1,200 @@
+use async_trait::async_trait;
+use reqwest::{RequestBuilder, Response};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{
+    absolute_name, relative_name, split_priority, ttl_or, DnsCreateUpdate, DnsListResponse,
+    DnsProvider, ZoneDetailsResponse, AUTO_TTL,
+};
+use crate::{
+    error::{EurusError, Result},
+    retry, CLIENT,
+};
+
+static BASE_URL: &str = "https://dns.hetzner.com/api/v1";
+static DEFAULT_TTL: u32 = 3600;
+
+#[derive(Debug, Deserialize)]
+struct ZoneResponse {
+    zone: Zone,
+}
+
+#[derive(Debug, Deserialize)]
+struct Zone {
+    id: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecordsResponse {
+    records: Vec<Record>,
+    meta: Option<Meta>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Meta {
+    pagination: Pagination,
+}
+
+#[derive(Debug, Deserialize)]
+struct Pagination {
+    page: u32,
+    last_page: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecordResponse {
+    record: Record,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Record {
+    #[serde(skip_serializing)]
+    id: String,
+    zone_id: String,
+    #[serde(rename = "type")]
+    record_type: String,
+    name: String,
+    value: String,
+    #[serde(default)]
+    ttl: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: HetznerError,
+}
+
+#[derive(Debug, Deserialize)]
+struct HetznerError {
+    message: String,
+    code: u16,
+}
+
+impl Record {
+    fn to_record(&self, domain: &str) -> DnsListResponse {
+        let (priority, content) = split_priority(&self.record_type, &self.value);
+        DnsListResponse {
+            name: absolute_name(&self.name, domain),
+            id: self.id.clone(),
+            record_type: self.record_type.clone(),
+            content,
+            proxied: false,
+            ttl: self.ttl.unwrap_or(AUTO_TTL),
+            priority,
+            data: None,
+            comment: None,
+            tags: vec![],
+        }
+    }
+}
+
+pub struct Hetzner {
+    token: String,
+}
+
+impl Hetzner {
+    pub fn new(token: &str) -> Self {
+        Self {
+            token: token.to_string(),
+        }
+    }
+
+    async fn execute(&self, request: RequestBuilder) -> Result<Response> {
+        let response = retry::send(request.header("Auth-API-Token", &self.token)).await?;
+        let status = response.status();
+
+        if !status.is_success() {
+            return Err(match response.json::<ErrorResponse>().await {
+                Ok(e) => EurusError::ApiError {
+                    status: None,
+                    codes: vec![e.error.code.into()],
+                    message: format!("Hetzner api returned an error: {}", e.error.message),
+                    ray: None,
+                },
+                Err(_) => EurusError::api(format!("Hetzner api returned {status}.")),
+            }
+            .with_status(status));
+        }
+
+        Ok(response)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        Ok(self.execute(request).await?.json().await?)
+    }
+}
+
+#[async_trait]
+impl DnsProvider for Hetzner {
+    async fn get_zone(&self, zone_id: &str) -> Result<ZoneDetailsResponse> {
+        let res: ZoneResponse = self
+            .send((*CLIENT).get(format!("{BASE_URL}/zones/{zone_id}")))
+            .await?;
+
+        Ok(ZoneDetailsResponse {
+            id: res.zone.id,
+            name: res.zone.name,
+            account: None,
+        })
+    }
+
+    async fn list_records(&self, zone_id: &str) -> Result<Vec<DnsListResponse>> {
+        let zone = self.get_zone(zone_id).await?;
+        let mut records = vec![];
+        let mut page = 1;
+
+        loop {
+            let res: RecordsResponse = self
+                .send((*CLIENT).get(format!(
+                    "{BASE_URL}/records?zone_id={zone_id}&page={page}&per_page=100"
+                )))
+                .await?;
+            records.extend(res.records.iter().map(|r| r.to_record(&zone.name)));
+
+            match res.meta {
+                Some(m) if m.pagination.page < m.pagination.last_page => page += 1,
+                _ => break,
+            }
+        }
+
+        Ok(records)
+    }
+
+    async fn upsert_record(
+        &self,
+        zone_id: &str,
+        record: &DnsCreateUpdate,
+    ) -> Result<DnsListResponse> {
+        record.validate()?;
+        let zone = self.get_zone(zone_id).await?;
+        let content = record.zone_file_content();
+        let value = match record.record_type.as_str() {
+            "CNAME" | "MX" | "NS" | "SRV" if !content.ends_with('.') => format!("{content}."),
+            _ => content,
+        };
+        let body = Record {
+            id: String::new(),
+            zone_id: zone_id.to_string(),
+            record_type: record.record_type.clone(),
+            name: relative_name(&record.name, &zone.name),
+            value,
+            ttl: Some(ttl_or(record.ttl, DEFAULT_TTL)),
+        };
+
+        let request = match &record.id {
+            Some(id) => (*CLIENT).put(format!("{BASE_URL}/records/{id}")),
+            None => (*CLIENT).post(format!("{BASE_URL}/records")),
+        };
+        let res: RecordResponse = self.send(request.json(&body)).await?;
+
+        Ok(res.record.to_record(&zone.name))
+    }
+
+    async fn delete_record(&self, _zone_id: &str, record_id: &str) -> Result<()> {
+        self.execute((*CLIENT).delete(format!("{BASE_URL}/records/{record_id}")))
+            .await?;
+
+        Ok(())
+    }
+}
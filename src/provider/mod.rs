@@ -0,0 +1,448 @@
+mod audited;
+mod cached;
+mod digitalocean;
+mod hetzner;
+mod route53;
+
+use std::{
+    fmt::Display,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use async_trait::async_trait;
+use clap::ValueEnum;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use self::{
+    audited::Audited, cached::Cached, digitalocean::DigitalOcean, hetzner::Hetzner,
+    route53::Route53,
+};
+use crate::{
+    cloudflare::Cloudflare,
+    config::{Config, ZoneInfo},
+    error::{EurusError, Result},
+};
+
+/// The TTL that leaves it up to the provider, as Cloudflare calls it.
+pub static AUTO_TTL: u32 = 1;
+
+/// The DNS backends a zone can be managed through.
+#[derive(Debug, Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+#[value(rename_all = "lowercase")]
+pub enum Provider {
+    #[default]
+    Cloudflare,
+    Route53,
+    DigitalOcean,
+    Hetzner,
+}
+
+impl Display for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Provider::Cloudflare => write!(f, "cloudflare"),
+            Provider::Route53 => write!(f, "route53"),
+            Provider::DigitalOcean => write!(f, "digitalocean"),
+            Provider::Hetzner => write!(f, "hetzner"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ZoneDetailsResponse {
+    pub name: String,
+    pub id: String,
+    /// The Cloudflare account the zone is in, other providers have none.
+    #[serde(default)]
+    pub account: Option<crate::cloudflare::Account>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DnsListResponse {
+    pub name: String,
+    pub id: String,
+    #[serde(rename = "type")]
+    pub record_type: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub proxied: bool,
+    #[serde(default = "auto_ttl")]
+    pub ttl: u32,
+    /// Set for MX, SRV and URI records.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u16>,
+    /// The structured fields of record types like SRV, CAA and TLSA, which
+    /// are also summarized in `content`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Map<String, serde_json::Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl DnsListResponse {
+    /// The record's value as it should be shown, with the priority in front
+    /// where it has one.
+    pub fn value(&self) -> String {
+        let content = match (&self.data, self.content.is_empty()) {
+            (Some(data), true) => data
+                .iter()
+                .map(|(k, v)| match v {
+                    serde_json::Value::String(s) => format!("{k}={s}"),
+                    v => format!("{k}={v}"),
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+            _ => self.content.clone(),
+        };
+
+        match self.priority {
+            Some(priority) => format!("{priority} {content}"),
+            None => content,
+        }
+    }
+
+    /// An update that leaves the record as it is, to change fields on.
+    pub fn to_update(&self) -> DnsCreateUpdate {
+        DnsCreateUpdate {
+            name: self.name.clone(),
+            id: Some(self.id.clone()),
+            record_type: self.record_type.clone(),
+            proxied: self.proxied,
+            content: self.content.clone(),
+            ttl: self.ttl,
+            priority: self.priority,
+            data: self.data.clone().map(serde_json::Value::Object),
+            comment: self.comment.clone(),
+            tags: Some(self.tags.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct DnsCreateUpdate {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub proxied: bool,
+    pub content: String,
+    /// In seconds, or [`AUTO_TTL`].
+    pub ttl: u32,
+    /// For MX and SRV records.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u16>,
+    /// The structured fields of SRV (`weight`, `port`, `target`) and CAA
+    /// (`flags`, `tag`, `value`) records. `content` holds the same values in
+    /// zone file form for providers that only take a string.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    /// Only kept by Cloudflare, other providers ignore these.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Updates leave the record's tags as they are when this is `None`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+}
+
+impl DnsCreateUpdate {
+    /// Checks the content with [`check_content`]. Records built from
+    /// structured `data` are left to the provider.
+    pub fn validate(&self) -> Result<()> {
+        match self.data {
+            Some(_) => Ok(()),
+            None => check_content(&self.record_type, &self.name, &self.content),
+        }
+    }
+
+    /// The content with the priority in front, the way zone files write it.
+    fn zone_file_content(&self) -> String {
+        match self.priority {
+            Some(priority) => format!("{priority} {}", self.content),
+            None => self.content.clone(),
+        }
+    }
+}
+
+/// The longest TXT content Cloudflare takes, split into 255 character strings
+/// on its side.
+pub const TXT_MAX_LENGTH: usize = 2048;
+
+/// Checks that `content` fits a record of `record_type` named `name`, so a
+/// typo is caught with a clear message instead of the provider's error code.
+pub fn check_content(record_type: &str, name: &str, content: &str) -> Result<()> {
+    let invalid = |message: String| Err(EurusError::ValidationError(message));
+    let trimmed = |name: &str| name.trim_end_matches('.').to_lowercase();
+
+    match record_type {
+        "A" if content.parse::<Ipv4Addr>().is_err() => invalid(format!(
+            "{content} isn't an IPv4 address, which A records point at."
+        )),
+        "AAAA" if content.parse::<Ipv6Addr>().is_err() => invalid(format!(
+            "{content} isn't an IPv6 address, which AAAA records point at."
+        )),
+        "CNAME" if trimmed(content) == trimmed(name) => {
+            invalid(format!("{name} can't be a CNAME to itself."))
+        }
+        "CNAME" | "MX" | "NS" | "PTR" if content.parse::<IpAddr>().is_ok() => invalid(format!(
+            "{record_type} records point at a hostname, not an address like {content}.{}",
+            match record_type {
+                "CNAME" => " Use an A or AAAA record for it instead.",
+                _ => "",
+            }
+        )),
+        // `.` is the null MX of a domain that takes no mail.
+        "MX" if content == "." => Ok(()),
+        "CNAME" | "MX" | "NS" | "PTR" if !is_hostname(content) => {
+            invalid(format!("{content} isn't a valid hostname."))
+        }
+        "TXT" if content.chars().count() > TXT_MAX_LENGTH => invalid(format!(
+            "TXT records hold at most {TXT_MAX_LENGTH} characters, this one has {}.",
+            content.chars().count()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Whether `name` looks like a hostname: dot separated labels of letters,
+/// digits, hyphens and underscores, or `@` for the zone.
+fn is_hostname(name: &str) -> bool {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    name == "@"
+        || (!name.is_empty()
+            && name.len() <= 253
+            && name.split('.').all(|label| {
+                (1..=63).contains(&label.len())
+                    && !label.starts_with('-')
+                    && !label.ends_with('-')
+                    && label
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+            }))
+}
+
+fn auto_ttl() -> u32 {
+    AUTO_TTL
+}
+
+/// Shows a TTL the way the Cloudflare dashboard does.
+pub fn display_ttl(ttl: u32) -> String {
+    match ttl {
+        t if t == AUTO_TTL => "auto".to_string(),
+        t => format!("{t}s"),
+    }
+}
+
+/// Follows a CNAME from `name` to `target` through the other records in the
+/// zone, returning every name passed through. The chain ends where it leaves
+/// the zone or reaches a record that isn't a CNAME, and it is an error for it
+/// to lead back to a name already passed.
+pub fn cname_chain(records: &[DnsListResponse], name: &str, target: &str) -> Result<Vec<String>> {
+    let normalize = |n: &str| n.trim_end_matches('.').to_lowercase();
+
+    let mut chain = vec![normalize(name)];
+    let mut next = normalize(target);
+    loop {
+        if chain.contains(&next) {
+            chain.push(next);
+            return Err(EurusError::ValidationError(format!(
+                "The CNAME would create a loop: {}",
+                chain.join(" -> ")
+            )));
+        }
+        chain.push(next.clone());
+
+        match records
+            .iter()
+            .find(|r| r.record_type == "CNAME" && normalize(&r.name) == next)
+        {
+            Some(r) => next = normalize(&r.content),
+            None => return Ok(chain),
+        }
+    }
+}
+
+/// Providers without an automatic TTL use their own default instead.
+fn ttl_or(ttl: u32, default: u32) -> u32 {
+    match ttl {
+        t if t == AUTO_TTL => default,
+        t => t,
+    }
+}
+
+/// A backend that can manage the records of a zone.
+#[async_trait]
+pub trait DnsProvider: Send + Sync {
+    async fn get_zone(&self, zone_id: &str) -> Result<ZoneDetailsResponse>;
+
+    async fn list_records(&self, zone_id: &str) -> Result<Vec<DnsListResponse>>;
+
+    /// Creates the record, or updates it in place when `record.id` is set.
+    async fn upsert_record(
+        &self,
+        zone_id: &str,
+        record: &DnsCreateUpdate,
+    ) -> Result<DnsListResponse>;
+
+    async fn delete_record(&self, zone_id: &str, record_id: &str) -> Result<()>;
+
+    /// Whether [`batch`](Self::batch) applies several changes in one call
+    /// rather than one call per record.
+    fn batches_atomically(&self) -> bool {
+        false
+    }
+
+    /// Deletes, then creates or updates several records. Providers that can
+    /// do this in a single atomic call override it, by default each record is
+    /// its own call.
+    async fn batch(
+        &self,
+        zone_id: &str,
+        upserts: &[DnsCreateUpdate],
+        deletes: &[String],
+    ) -> Result<()> {
+        batch_individually(self, zone_id, upserts, deletes).await
+    }
+}
+
+/// How many calls [`batch_individually`] makes at once.
+pub const BATCH_CONCURRENCY: usize = 8;
+
+/// Applies a batch with one call per record, [`BATCH_CONCURRENCY`] at a time
+/// behind a progress bar. Deletes go first, so a record can be replaced by one
+/// of another type. Failed calls don't stop the rest, they're logged and
+/// counted in the error at the end.
+pub async fn batch_individually<P: DnsProvider + ?Sized>(
+    provider: &P,
+    zone_id: &str,
+    upserts: &[DnsCreateUpdate],
+    deletes: &[String],
+) -> Result<()> {
+    let total = upserts.len() + deletes.len();
+    let progress = (total > 1).then(|| {
+        let progress = cliclack::progress_bar(total as u64);
+        progress.start("Applying changes...");
+        progress
+    });
+    let done = || {
+        if let Some(progress) = &progress {
+            progress.inc(1);
+        }
+    };
+
+    // Collected before they're streamed, as async_trait can't prove a stream
+    // still holding the closures is Send.
+    let deletions: Vec<_> = deletes
+        .iter()
+        .map(|id| async move {
+            let result = provider.delete_record(zone_id, id).await;
+            done();
+            result.map_err(|e| format!("Could not delete {id}: {e}"))
+        })
+        .collect();
+    let deleted: Vec<_> = futures::stream::iter(deletions)
+        .buffer_unordered(BATCH_CONCURRENCY)
+        .collect()
+        .await;
+    let saves: Vec<_> = upserts
+        .iter()
+        .map(|r| async move {
+            let result = provider.upsert_record(zone_id, r).await;
+            done();
+            result.map_err(|e| format!("Could not save {} {}: {e}", r.record_type, r.name))
+        })
+        .collect();
+    let upserted: Vec<_> = futures::stream::iter(saves)
+        .buffer_unordered(BATCH_CONCURRENCY)
+        .collect()
+        .await;
+
+    let failures: Vec<_> = deleted
+        .into_iter()
+        .filter_map(|r| r.err())
+        .chain(upserted.into_iter().filter_map(|r| r.err()))
+        .collect();
+    if let Some(progress) = &progress {
+        let message = format!("Applied {} of {total} changes.", total - failures.len());
+        match failures.is_empty() {
+            true => progress.stop(message),
+            false => progress.error(message),
+        }
+    }
+    for failure in &failures {
+        let _ = cliclack::log::error(failure);
+    }
+    match failures.as_slice() {
+        [] => Ok(()),
+        [_] if total == 1 => Err(EurusError::api(failures[0].clone())),
+        _ => Err(EurusError::api(format!(
+            "{} of {total} changes failed.",
+            failures.len()
+        ))),
+    }
+}
+
+/// Some providers name records relative to the zone, with `@` for the apex.
+fn relative_name(name: &str, domain: &str) -> String {
+    if name == domain {
+        "@".to_string()
+    } else {
+        name.strip_suffix(&format!(".{domain}"))
+            .unwrap_or(name)
+            .to_string()
+    }
+}
+
+fn absolute_name(name: &str, domain: &str) -> String {
+    match name {
+        "@" => domain.to_string(),
+        _ => format!("{name}.{domain}"),
+    }
+}
+
+/// Splits the preference off the zone file form of MX and SRV values, like
+/// `10 mail.example.com`, for providers that keep it in the value.
+fn split_priority(record_type: &str, value: &str) -> (Option<u16>, String) {
+    let value = value.trim_end_matches('.');
+    match record_type {
+        "MX" | "SRV" => match value.split_once(' ').map(|(p, rest)| (p.parse(), rest)) {
+            Some((Ok(priority), rest)) => (Some(priority), rest.trim().to_string()),
+            _ => (None, value.to_string()),
+        },
+        _ => (None, value.to_string()),
+    }
+}
+
+/// Builds the provider configured for the given zone, with every change it
+/// makes recorded in the audit log.
+pub fn for_zone(config: &Config, zone: &ZoneInfo) -> Result<Box<dyn DnsProvider>> {
+    let provider: Box<dyn DnsProvider> = match zone.provider {
+        Provider::Cloudflare => Box::new(Cloudflare::from_config(config)?),
+        Provider::Route53 => Box::new(Route53::new()?),
+        Provider::DigitalOcean => {
+            if config.digitalocean_key.is_empty() {
+                return Err(EurusError::ConfigError(
+                    "Set `digitalocean_key` in the config to use DigitalOcean zones.".to_string(),
+                ));
+            }
+            Box::new(DigitalOcean::new(&config.digitalocean_key))
+        }
+        Provider::Hetzner => {
+            if config.hetzner_key.is_empty() {
+                return Err(EurusError::ConfigError(
+                    "Set `hetzner_key` in the config to use Hetzner zones.".to_string(),
+                ));
+            }
+            Box::new(Hetzner::new(&config.hetzner_key))
+        }
+    };
+
+    let provider = Box::new(Cached::new(provider, config.cache_ttl));
+
+    Ok(Box::new(Audited::new(provider, &zone.name)))
+}
@@ -0,0 +1,623 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use directories::BaseDirs;
+use hmac::{Hmac, Mac};
+use reqwest::{Method, RequestBuilder};
+use serde::{
+    de::{DeserializeOwned, IgnoredAny},
+    Deserialize,
+};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+use super::{
+    split_priority, ttl_or, DnsCreateUpdate, DnsListResponse, DnsProvider, ZoneDetailsResponse,
+    AUTO_TTL,
+};
+use crate::{
+    error::{EurusError, Result},
+    retry, CLIENT,
+};
+
+static HOST: &str = "route53.amazonaws.com";
+// Route53 is a global service, but requests are always signed for us-east-1.
+static REGION: &str = "us-east-1";
+static DEFAULT_TTL: u32 = 300;
+
+/// AWS credentials, resolved the same way the AWS cli does for the common
+/// cases: environment variables first, then the shared credentials file.
+struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+impl Credentials {
+    fn resolve() -> Result<Self> {
+        if let (Ok(access_key_id), Ok(secret_access_key)) = (
+            std::env::var("AWS_ACCESS_KEY_ID"),
+            std::env::var("AWS_SECRET_ACCESS_KEY"),
+        ) {
+            return Ok(Self {
+                access_key_id,
+                secret_access_key,
+                session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+            });
+        }
+
+        let path = match std::env::var("AWS_SHARED_CREDENTIALS_FILE") {
+            Ok(p) => p.into(),
+            Err(_) => BaseDirs::new()
+                .ok_or_else(|| config_error("Could not find the home directory."))?
+                .home_dir()
+                .join(".aws/credentials"),
+        };
+        let profile = std::env::var("AWS_PROFILE").unwrap_or("default".to_string());
+
+        let contents = std::fs::read_to_string(&path).map_err(|_| {
+            config_error(format!(
+                "Could not read AWS credentials from {}.",
+                path.display()
+            ))
+        })?;
+        let mut section = parse_profile(&contents, &profile)
+            .ok_or_else(|| config_error(format!("AWS profile `{profile}` was not found.")))?;
+
+        Ok(Self {
+            access_key_id: section
+                .remove("aws_access_key_id")
+                .ok_or_else(|| config_error("AWS profile is missing aws_access_key_id."))?,
+            secret_access_key: section
+                .remove("aws_secret_access_key")
+                .ok_or_else(|| config_error("AWS profile is missing aws_secret_access_key."))?,
+            session_token: section.remove("aws_session_token"),
+        })
+    }
+}
+
+fn config_error(message: impl Into<String>) -> EurusError {
+    EurusError::ConfigError(message.into())
+}
+
+/// Reads the keys of a single `[profile]` section from an ini-style file.
+fn parse_profile(contents: &str, profile: &str) -> Option<HashMap<String, String>> {
+    let mut found = None;
+
+    for line in contents.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            if found.is_some() {
+                break;
+            }
+            if name.trim() == profile {
+                found = Some(HashMap::new());
+            }
+        } else if let (Some(section), Some((key, value))) = (&mut found, line.split_once('=')) {
+            section.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+
+    found
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GetHostedZoneResponse {
+    hosted_zone: HostedZone,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HostedZone {
+    id: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListResourceRecordSetsResponse {
+    resource_record_sets: ResourceRecordSets,
+    is_truncated: bool,
+    next_record_name: Option<String>,
+    next_record_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResourceRecordSets {
+    #[serde(rename = "ResourceRecordSet", default)]
+    sets: Vec<ResourceRecordSet>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+struct ResourceRecordSet {
+    name: String,
+    #[serde(rename = "Type")]
+    record_type: String,
+    #[serde(rename = "TTL")]
+    ttl: Option<u32>,
+    resource_records: Option<ResourceRecords>,
+    alias_target: Option<AliasTarget>,
+}
+
+/// Points the set at another AWS resource, in place of its own values.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+struct AliasTarget {
+    hosted_zone_id: String,
+    #[serde(rename = "DNSName")]
+    dns_name: String,
+    evaluate_target_health: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct ResourceRecords {
+    #[serde(rename = "ResourceRecord", default)]
+    records: Vec<ResourceRecord>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+struct ResourceRecord {
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ErrorResponse {
+    error: AwsError,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AwsError {
+    code: String,
+    message: String,
+}
+
+impl ResourceRecordSet {
+    fn values(&self) -> Vec<String> {
+        self.resource_records
+            .iter()
+            .flat_map(|r| &r.records)
+            .map(|r| r.value.clone())
+            .collect()
+    }
+
+    /// The same set holding `values` instead.
+    fn with_values(&self, values: Vec<String>) -> Self {
+        Self {
+            resource_records: Some(ResourceRecords {
+                records: values
+                    .into_iter()
+                    .map(|value| ResourceRecord { value })
+                    .collect(),
+            }),
+            alias_target: None,
+            ..self.clone()
+        }
+    }
+
+    /// Each value of the set as a record of its own, or the set as a single
+    /// record when it is an alias.
+    fn to_records(&self) -> Vec<DnsListResponse> {
+        match &self.alias_target {
+            Some(alias) => {
+                let mut data = serde_json::Map::new();
+                data.insert("hosted_zone_id".into(), alias.hosted_zone_id.clone().into());
+                data.insert(
+                    "evaluate_target_health".into(),
+                    alias.evaluate_target_health.into(),
+                );
+
+                vec![DnsListResponse {
+                    id: set_id(&self.name, &self.record_type),
+                    content: alias.dns_name.trim_end_matches('.').to_string(),
+                    data: Some(data),
+                    ..self.to_record("")
+                }]
+            }
+            None => self.values().iter().map(|v| self.to_record(v)).collect(),
+        }
+    }
+
+    fn to_record(&self, value: &str) -> DnsListResponse {
+        let name = self.name.trim_end_matches('.').to_string();
+        let (priority, content) = split_priority(&self.record_type, value);
+
+        DnsListResponse {
+            id: format!("{}|{value}", set_id(&name, &self.record_type)),
+            name,
+            record_type: self.record_type.clone(),
+            content,
+            proxied: false,
+            ttl: self.ttl.unwrap_or(AUTO_TTL),
+            priority,
+            data: None,
+            comment: None,
+            tags: vec![],
+        }
+    }
+}
+
+/// Route53 has no record ids, a record set is identified by its name and type.
+/// The records listed from it add their value to that, aliases have none.
+fn set_id(name: &str, record_type: &str) -> String {
+    format!(
+        "{}|{record_type}",
+        name.trim_end_matches('.').to_lowercase()
+    )
+}
+
+/// Splits a record id back into its set's id and the value, if it has one.
+fn parse_id(id: &str) -> (String, Option<&str>) {
+    let mut parts = id.splitn(3, '|');
+    let name = parts.next().unwrap_or_default();
+    let record_type = parts.next().unwrap_or_default();
+
+    (set_id(name, record_type), parts.next())
+}
+
+pub struct Route53 {
+    credentials: Credentials,
+    /// Held while a set is read and written back, so changes to values of
+    /// the same set don't overwrite each other.
+    changing: Mutex<()>,
+}
+
+impl Route53 {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            credentials: Credentials::resolve()?,
+            changing: Mutex::new(()),
+        })
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: String,
+    ) -> Result<T> {
+        let request = self.sign(method, path, query, body)?;
+        let response = retry::send(request).await?;
+        let status = response.status();
+        let text = response.text().await?;
+
+        if !status.is_success() {
+            return Err(
+                EurusError::api(match quick_xml::de::from_str::<ErrorResponse>(&text) {
+                    Ok(e) => format!(
+                        "Route53 returned an error: {} ({})",
+                        e.error.message, e.error.code
+                    ),
+                    Err(_) => format!("Route53 returned {status}: {text}"),
+                })
+                .with_status(status),
+            );
+        }
+
+        quick_xml::de::from_str(&text)
+            .map_err(|_| EurusError::api("Route53 returned an unexpected response."))
+    }
+
+    /// Builds a request signed with AWS Signature Version 4.
+    fn sign(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: String,
+    ) -> Result<RequestBuilder> {
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let scope = format!("{date}/{REGION}/route53/aws4_request");
+
+        let mut query: Vec<_> = query
+            .iter()
+            .map(|(k, v)| (uri_encode(k), uri_encode(v)))
+            .collect();
+        query.sort();
+        let query = query
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let mut headers = vec![("host", HOST.to_string()), ("x-amz-date", amz_date.clone())];
+        if let Some(token) = &self.credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let signed_headers = headers
+            .iter()
+            .map(|(k, _)| *k)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_headers: String = headers.iter().map(|(k, v)| format!("{k}:{v}\n")).collect();
+
+        let canonical_request = format!(
+            "{method}\n{path}\n{query}\n{canonical_headers}\n{signed_headers}\n{}",
+            hex(&Sha256::digest(body.as_bytes()))
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = [date.as_str(), REGION, "route53", "aws4_request"]
+            .iter()
+            .fold(
+                format!("AWS4{}", self.credentials.secret_access_key).into_bytes(),
+                |key, part| hmac(&key, part.as_bytes()),
+            );
+        let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+
+        let mut url = format!("https://{HOST}{path}");
+        if !query.is_empty() {
+            url = format!("{url}?{query}");
+        }
+
+        let mut request = (*CLIENT)
+            .request(method, url)
+            .header(
+                "authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+                    self.credentials.access_key_id
+                ),
+            )
+            .body(body);
+        for (k, v) in headers.into_iter().filter(|(k, _)| *k != "host") {
+            request = request.header(k, v);
+        }
+
+        Ok(request)
+    }
+
+    async fn record_sets(&self, zone_id: &str) -> Result<Vec<ResourceRecordSet>> {
+        let path = format!("/2013-04-01/hostedzone/{zone_id}/rrset");
+        let mut sets = vec![];
+        let mut next: Option<(String, String)> = None;
+
+        loop {
+            let query: Vec<_> = match &next {
+                Some((name, record_type)) => {
+                    vec![("name", name.as_str()), ("type", record_type.as_str())]
+                }
+                None => vec![],
+            };
+            let page: ListResourceRecordSetsResponse =
+                self.send(Method::GET, &path, &query, String::new()).await?;
+            sets.extend(page.resource_record_sets.sets);
+
+            match (
+                page.is_truncated,
+                page.next_record_name,
+                page.next_record_type,
+            ) {
+                (true, Some(name), Some(record_type)) => next = Some((name, record_type)),
+                _ => break,
+            }
+        }
+
+        Ok(sets)
+    }
+
+    /// The set with the name and type of `id`, if there is one.
+    async fn record_set(&self, zone_id: &str, id: &str) -> Result<Option<ResourceRecordSet>> {
+        let (id, _) = parse_id(id);
+        let (name, record_type) = id.split_once('|').unwrap_or_default();
+        let page: ListResourceRecordSetsResponse = self
+            .send(
+                Method::GET,
+                &format!("/2013-04-01/hostedzone/{zone_id}/rrset"),
+                &[("name", name), ("type", record_type), ("maxitems", "1")],
+                String::new(),
+            )
+            .await?;
+
+        // The list starts at the name and type, which may not exist.
+        Ok(page
+            .resource_record_sets
+            .sets
+            .into_iter()
+            .find(|s| set_id(&s.name, &s.record_type) == id))
+    }
+
+    /// Takes the value of `record_id` out of its set, deleting the set once
+    /// it has none left. Ids without a value, like aliases, are the whole set.
+    async fn remove(&self, zone_id: &str, record_id: &str) -> Result<()> {
+        let not_found = || EurusError::api("The record to delete was not found.");
+        let set = self
+            .record_set(zone_id, record_id)
+            .await?
+            .ok_or_else(not_found)?;
+
+        let remaining: Vec<_> = match (parse_id(record_id).1, &set.alias_target) {
+            (Some(value), None) => {
+                let values = set.values();
+                if !values.iter().any(|v| v == value) {
+                    return Err(not_found());
+                }
+                values.into_iter().filter(|v| v != value).collect()
+            }
+            _ => vec![],
+        };
+
+        // Deletions have to match the current record set exactly.
+        match remaining.is_empty() {
+            true => self.change(zone_id, "DELETE", &set).await,
+            false => {
+                self.change(zone_id, "UPSERT", &set.with_values(remaining))
+                    .await
+            }
+        }
+    }
+
+    async fn change(&self, zone_id: &str, action: &str, set: &ResourceRecordSet) -> Result<()> {
+        let records: String = set
+            .values()
+            .iter()
+            .map(|v| {
+                format!(
+                    "<ResourceRecord><Value>{}</Value></ResourceRecord>",
+                    xml_escape(v)
+                )
+            })
+            .collect();
+        // Aliases have neither a TTL nor values of their own.
+        let target = match &set.alias_target {
+            Some(alias) => format!(
+                "<AliasTarget><HostedZoneId>{}</HostedZoneId><DNSName>{}</DNSName>\
+                 <EvaluateTargetHealth>{}</EvaluateTargetHealth></AliasTarget>",
+                xml_escape(&alias.hosted_zone_id),
+                xml_escape(&alias.dns_name),
+                alias.evaluate_target_health,
+            ),
+            None => format!(
+                "<TTL>{}</TTL><ResourceRecords>{records}</ResourceRecords>",
+                set.ttl.unwrap_or(DEFAULT_TTL)
+            ),
+        };
+        let body = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<ChangeResourceRecordSetsRequest xmlns="https://route53.amazonaws.com/doc/2013-04-01/">
+<ChangeBatch><Changes><Change><Action>{action}</Action><ResourceRecordSet>
+<Name>{}</Name><Type>{}</Type>{target}
+</ResourceRecordSet></Change></Changes></ChangeBatch>
+</ChangeResourceRecordSetsRequest>"#,
+            xml_escape(&set.name),
+            set.record_type,
+        );
+
+        self.send::<IgnoredAny>(
+            Method::POST,
+            &format!("/2013-04-01/hostedzone/{zone_id}/rrset/"),
+            &[],
+            body,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DnsProvider for Route53 {
+    async fn get_zone(&self, zone_id: &str) -> Result<ZoneDetailsResponse> {
+        let res: GetHostedZoneResponse = self
+            .send(
+                Method::GET,
+                &format!("/2013-04-01/hostedzone/{zone_id}"),
+                &[],
+                String::new(),
+            )
+            .await?;
+
+        Ok(ZoneDetailsResponse {
+            id: res
+                .hosted_zone
+                .id
+                .trim_start_matches("/hostedzone/")
+                .to_string(),
+            name: res.hosted_zone.name.trim_end_matches('.').to_string(),
+            account: None,
+        })
+    }
+
+    async fn list_records(&self, zone_id: &str) -> Result<Vec<DnsListResponse>> {
+        Ok(self
+            .record_sets(zone_id)
+            .await?
+            .iter()
+            .flat_map(ResourceRecordSet::to_records)
+            .collect())
+    }
+
+    async fn upsert_record(
+        &self,
+        zone_id: &str,
+        record: &DnsCreateUpdate,
+    ) -> Result<DnsListResponse> {
+        record.validate()?;
+        let value = match record.record_type.as_str() {
+            "TXT" if !record.content.starts_with('"') => format!("\"{}\"", record.content),
+            _ => record.zone_file_content(),
+        };
+        let id = set_id(&record.name, &record.record_type);
+        let _changing = self.changing.lock().await;
+
+        // The value joins the others in the set, replacing the one it was
+        // before when that was in this set too. Aliases and the types that
+        // hold a single value are replaced whole.
+        let (previous_set, previous) = match record.id.as_deref().map(parse_id) {
+            Some((set, previous)) => (Some(set), previous),
+            None => (None, None),
+        };
+        let mut values = match self.record_set(zone_id, &id).await? {
+            Some(set) if set.alias_target.is_none() => set.values(),
+            _ => vec![],
+        };
+        match (previous_set == Some(id.clone()), previous) {
+            _ if matches!(record.record_type.as_str(), "CNAME" | "SOA") => values.clear(),
+            (true, Some(previous)) => values.retain(|v| v != previous),
+            (true, None) => values.clear(),
+            (false, _) => {}
+        }
+        if !values.contains(&value) {
+            values.push(value.clone());
+        }
+
+        let set = ResourceRecordSet {
+            name: record.name.clone(),
+            record_type: record.record_type.clone(),
+            ttl: Some(ttl_or(record.ttl, DEFAULT_TTL)),
+            resource_records: None,
+            alias_target: None,
+        }
+        .with_values(values);
+        self.change(zone_id, "UPSERT", &set).await?;
+
+        // Moved to another name or type, so it goes from the set it was in.
+        if let Some(previous_id) = record.id.as_deref().filter(|_| previous_set != Some(id)) {
+            self.remove(zone_id, previous_id).await?;
+        }
+
+        Ok(set.to_record(&value))
+    }
+
+    async fn delete_record(&self, zone_id: &str, record_id: &str) -> Result<()> {
+        let _changing = self.changing.lock().await;
+        self.remove(zone_id, record_id).await
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn uri_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,716 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use clap::ValueEnum;
+use docker_compose_types::{Environment, Extension, Labels, Service, SingleValue};
+use serde::{Deserialize, Serialize};
+
+use crate::LABEL_PREFIX;
+
+/// The reverse proxy that `web` writes configuration for.
+#[derive(Debug, Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProxyBackend {
+    /// caddy-docker-proxy labels.
+    #[default]
+    Caddy,
+    /// Traefik v2 labels.
+    Traefik,
+    /// nginx-proxy (and acme-companion) environment variables.
+    NginxProxy,
+}
+
+impl Display for ProxyBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyBackend::Caddy => write!(f, "caddy"),
+            ProxyBackend::Traefik => write!(f, "traefik"),
+            ProxyBackend::NginxProxy => write!(f, "nginx-proxy"),
+        }
+    }
+}
+
+impl ProxyBackend {
+    /// Configures `service` so the proxy routes `domains` to `port`. Existing
+    /// values are left alone, and caddy sites for other domains are kept.
+    pub fn apply(
+        &self,
+        service: &mut Service,
+        name: &str,
+        domains: &[String],
+        port: &str,
+        network: &str,
+    ) {
+        let existing = service.labels.clone();
+        for (key, value) in self.labels(&existing, name, domains, port, network) {
+            add_or_ignore_label(&mut service.labels, &key, &value);
+        }
+        for (key, value) in self.environment(domains, port) {
+            add_or_ignore_env(&mut service.environment, &key, &value);
+        }
+    }
+
+    /// Like [`ProxyBackend::apply`], but overwrites any existing values.
+    pub fn set(
+        &self,
+        service: &mut Service,
+        name: &str,
+        domains: &[String],
+        port: &str,
+        network: &str,
+    ) {
+        for (key, value) in self.labels(&Labels::default(), name, domains, port, network) {
+            set_label(&mut service.labels, &key, &value);
+        }
+        for (key, value) in self.environment(domains, port) {
+            set_env(&mut service.environment, &key, &value);
+        }
+    }
+
+    /// The labels that route `domains` to `port` on `service`, next to the
+    /// `existing` ones.
+    pub fn labels(
+        &self,
+        existing: &Labels,
+        service: &str,
+        domains: &[String],
+        port: &str,
+        network: &str,
+    ) -> Vec<(String, String)> {
+        match self {
+            ProxyBackend::Caddy => caddy_sites(existing, domains)
+                .into_iter()
+                .zip(domains)
+                .flat_map(|(site, domain)| {
+                    let upstream = (
+                        format!("{site}.reverse_proxy"),
+                        format!("{{{{ upstreams {port} }}}}"),
+                    );
+                    [(site, domain.clone()), upstream]
+                })
+                .collect(),
+            ProxyBackend::Traefik => {
+                let router = router_name(service);
+                let rule = domains
+                    .iter()
+                    .map(|d| format!("Host(`{d}`)"))
+                    .collect::<Vec<_>>()
+                    .join(" || ");
+                vec![
+                    ("traefik.enable".to_string(), "true".to_string()),
+                    (format!("traefik.http.routers.{router}.rule"), rule),
+                    (
+                        format!("traefik.http.services.{router}.loadbalancer.server.port"),
+                        port.to_string(),
+                    ),
+                    ("traefik.docker.network".to_string(), network.to_string()),
+                ]
+            }
+            ProxyBackend::NginxProxy => vec![],
+        }
+    }
+
+    /// The environment variables that route `domains` to `port`.
+    pub fn environment(&self, domains: &[String], port: &str) -> Vec<(String, String)> {
+        match self {
+            ProxyBackend::NginxProxy => vec![
+                ("VIRTUAL_HOST".to_string(), domains.join(",")),
+                ("VIRTUAL_PORT".to_string(), port.to_string()),
+                ("LETSENCRYPT_HOST".to_string(), domains.join(",")),
+            ],
+            _ => vec![],
+        }
+    }
+}
+
+/// Directives for a caddy site beyond the reverse proxy, as caddy-docker-proxy
+/// labels.
+#[derive(Debug, Clone, Default)]
+pub struct CaddyOptions {
+    /// `internal` for caddy's own CA, or the paths of a certificate and key.
+    pub tls: Option<String>,
+    /// Users and their bcrypt password hashes.
+    pub basic_auth: Vec<(String, String)>,
+    /// Response headers like `X-Frame-Options DENY`, or `-Server` to remove one.
+    pub headers: Vec<String>,
+    /// Keeps websockets and other streams open when caddy reloads, which
+    /// caddy-docker-proxy does whenever a container changes.
+    pub websocket: bool,
+    /// Any other directives, by their path below the site, e.g. `encode`.
+    pub directives: Vec<(String, String)>,
+}
+
+impl CaddyOptions {
+    pub fn is_empty(&self) -> bool {
+        self.tls.is_none()
+            && self.basic_auth.is_empty()
+            && self.headers.is_empty()
+            && !self.websocket
+            && self.directives.is_empty()
+    }
+
+    /// The labels for the caddy site `site`, e.g. `caddy` or `caddy_1`.
+    pub fn labels(&self, site: &str) -> Vec<(String, String)> {
+        let mut labels = vec![];
+        if let Some(tls) = &self.tls {
+            labels.push((format!("{site}.tls"), tls.clone()));
+        }
+        for (user, hash) in &self.basic_auth {
+            labels.push((format!("{site}.basic_auth.{user}"), hash.clone()));
+        }
+        for header in &self.headers {
+            let (name, value) = header.trim().split_once(' ').unwrap_or((header.trim(), ""));
+            labels.push((format!("{site}.header.{name}"), value.trim().to_string()));
+        }
+        if self.websocket {
+            labels.push((
+                format!("{site}.reverse_proxy.flush_interval"),
+                "-1".to_string(),
+            ));
+            labels.push((
+                format!("{site}.reverse_proxy.stream_close_delay"),
+                "5m".to_string(),
+            ));
+        }
+        for (path, value) in &self.directives {
+            labels.push((format!("{site}.{path}"), value.clone()));
+        }
+        labels
+    }
+
+    /// Adds the labels to each of the `sites` of `service`, leaving existing
+    /// ones alone. `$` is escaped, as compose would substitute the `$`s of
+    /// password hashes.
+    pub fn apply(&self, service: &mut Service, sites: &[String]) {
+        for site in sites {
+            for (key, value) in self.labels(site) {
+                add_or_ignore_label(&mut service.labels, &key, &value.replace('$', "$$"));
+            }
+        }
+    }
+}
+
+/// The service's labels and those under `deploy:`, which proxies read on
+/// Docker Swarm.
+pub fn service_labels(service: &Service) -> Vec<(String, String)> {
+    let mut labels = label_pairs(&service.labels);
+    labels.extend(
+        service
+            .deploy
+            .iter()
+            .flat_map(|d| &d.labels)
+            .filter_map(|l| l.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string())),
+    );
+
+    labels
+}
+
+/// Whether the label is one eurus writes for a proxy.
+pub fn is_proxy_label(key: &str) -> bool {
+    is_caddy_label(key) || key.starts_with("traefik.") || key == MANAGED_LABEL
+}
+
+/// Whether the service's proxy labels belong under `deploy.labels`, as on
+/// Docker Swarm: when `swarm` says so, or they are there already.
+pub fn uses_deploy_labels(service: &Service, swarm: bool) -> bool {
+    swarm
+        || service.deploy.as_ref().is_some_and(|d| {
+            d.labels
+                .iter()
+                .any(|l| is_proxy_label(l.split('=').next().unwrap_or(l)))
+        })
+}
+
+/// Runs `edit` on the service's labels. When they belong under
+/// `deploy.labels` (see [`uses_deploy_labels`]) those are among them during
+/// the edit, and the proxy labels are moved there afterwards.
+pub fn edit_labels<T>(
+    service: &mut Service,
+    swarm: bool,
+    edit: impl FnOnce(&mut Service) -> T,
+) -> T {
+    if !uses_deploy_labels(service, swarm) {
+        return edit(service);
+    }
+
+    let lifted = lift_deploy_labels(service);
+    let result = edit(service);
+    lower_deploy_labels(service, &lifted);
+
+    result
+}
+
+/// Moves `deploy.labels` into the service's labels, so they are edited like
+/// any others, returning their keys for [`lower_deploy_labels`].
+fn lift_deploy_labels(service: &mut Service) -> Vec<String> {
+    let lifted = service
+        .deploy
+        .as_mut()
+        .map(|d| std::mem::take(&mut d.labels))
+        .unwrap_or_default();
+
+    lifted
+        .iter()
+        .filter_map(|l| l.split_once('='))
+        .map(|(key, value)| {
+            add_or_ignore_label(&mut service.labels, key, value);
+            key.to_string()
+        })
+        .collect()
+}
+
+/// Moves the proxy labels, and the ones [`lift_deploy_labels`] took, to
+/// `deploy.labels` where Docker Swarm reads them.
+fn lower_deploy_labels(service: &mut Service, lifted: &[String]) {
+    let lowered: Vec<_> = label_pairs(&service.labels)
+        .into_iter()
+        .filter(|(key, _)| is_proxy_label(key) || lifted.contains(key))
+        .collect();
+    if lowered.is_empty() {
+        return;
+    }
+
+    let deploy = service.deploy.get_or_insert_with(Default::default);
+    for (key, value) in lowered {
+        remove_label(&mut service.labels, &key);
+        deploy.labels.push(format!("{key}={value}"));
+    }
+}
+
+/// The domains any of the supported proxies would route to this service.
+pub fn domains(service: &Service) -> Vec<String> {
+    let labels = service_labels(service);
+    let environment: Vec<(String, String)> = match &service.environment {
+        Environment::List(l) => l
+            .iter()
+            .filter_map(|e| e.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        Environment::KvPair(m) => m
+            .iter()
+            .filter_map(|(k, v)| match v {
+                Some(SingleValue::String(v)) => Some((k.clone(), v.clone())),
+                _ => None,
+            })
+            .collect(),
+    };
+
+    let mut domains = vec![];
+    for (key, value) in labels {
+        if is_caddy_site(&key) {
+            domains.extend(site_domains(&value));
+        } else if key.starts_with("traefik.http.routers.") && key.ends_with(".rule") {
+            domains.extend(
+                value
+                    .split("Host(`")
+                    .skip(1)
+                    .filter_map(|h| h.split('`').next())
+                    .map(String::from),
+            );
+        }
+    }
+    for (key, value) in environment {
+        if key == "VIRTUAL_HOST" {
+            domains.extend(value.split(',').map(|d| d.trim().to_string()));
+        }
+    }
+
+    domains
+}
+
+/// The proxy the service is labelled for, if any.
+pub fn backend(service: &Service) -> Option<ProxyBackend> {
+    let labels = service_labels(service);
+    if labels.iter().any(|(k, _)| is_caddy_site(k)) {
+        return Some(ProxyBackend::Caddy);
+    }
+    if labels
+        .iter()
+        .any(|(k, _)| k.starts_with("traefik.http.routers."))
+    {
+        return Some(ProxyBackend::Traefik);
+    }
+    let virtual_host = match &service.environment {
+        Environment::List(l) => l.iter().any(|e| e.starts_with("VIRTUAL_HOST=")),
+        Environment::KvPair(m) => m.contains_key("VIRTUAL_HOST"),
+    };
+
+    virtual_host.then_some(ProxyBackend::NginxProxy)
+}
+
+pub fn add_or_ignore_label(labels: &mut Labels, key: &str, value: &str) {
+    match labels {
+        Labels::List(l) => {
+            let label = format!("{}={}", key, value);
+            if !l.contains(&label) {
+                l.push(label);
+            }
+        }
+        Labels::Map(m) => {
+            if !m.contains_key(key) {
+                m.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+}
+
+pub fn add_or_ignore_env(environment: &mut Environment, key: &str, value: &str) {
+    match environment {
+        Environment::List(l) => {
+            if !l.iter().any(|e| e.split('=').next() == Some(key)) {
+                l.push(format!("{key}={value}"));
+            }
+        }
+        Environment::KvPair(m) => {
+            if !m.contains_key(key) {
+                m.insert(
+                    key.to_string(),
+                    Some(SingleValue::String(value.to_string())),
+                );
+            }
+        }
+    }
+}
+
+pub fn set_label(labels: &mut Labels, key: &str, value: &str) {
+    match labels {
+        Labels::List(l) => {
+            let label = format!("{key}={value}");
+            match l.iter_mut().find(|e| e.split('=').next() == Some(key)) {
+                Some(existing) => *existing = label,
+                None => l.push(label),
+            }
+        }
+        Labels::Map(m) => {
+            m.insert(key.to_string(), value.to_string());
+        }
+    }
+}
+
+/// The labels as key value pairs, however they are written in the file.
+pub fn label_pairs(labels: &Labels) -> Vec<(String, String)> {
+    match labels {
+        Labels::List(l) => l
+            .iter()
+            .filter_map(|l| l.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        Labels::Map(m) => m.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+    }
+}
+
+pub fn remove_label(labels: &mut Labels, key: &str) {
+    match labels {
+        Labels::List(l) => l.retain(|e| e.split('=').next() != Some(key)),
+        Labels::Map(m) => {
+            m.shift_remove(key);
+        }
+    }
+}
+
+pub fn set_env(environment: &mut Environment, key: &str, value: &str) {
+    match environment {
+        Environment::List(l) => {
+            let entry = format!("{key}={value}");
+            match l.iter_mut().find(|e| e.split('=').next() == Some(key)) {
+                Some(existing) => *existing = entry,
+                None => l.push(entry),
+            }
+        }
+        Environment::KvPair(m) => {
+            m.insert(
+                key.to_string(),
+                Some(SingleValue::String(value.to_string())),
+            );
+        }
+    }
+}
+
+pub fn remove_env(environment: &mut Environment, key: &str) {
+    match environment {
+        Environment::List(l) => l.retain(|e| e.split('=').next() != Some(key)),
+        Environment::KvPair(m) => {
+            m.shift_remove(key);
+        }
+    }
+}
+
+/// Marks a service whose proxy labels eurus manages, whether it added them or
+/// took them over with `web adopt`.
+pub const MANAGED_LABEL: &str = "eurus.managed";
+
+/// Whether eurus manages the service's proxy labels.
+pub fn is_managed(service: &Service) -> bool {
+    metadata(service).is_some()
+        || service_labels(service)
+            .iter()
+            .any(|(key, value)| key == MANAGED_LABEL && value == "true")
+}
+
+/// The compose extension `web` records what it set up for a service in.
+pub const EXTENSION: &str = "x-eurus";
+
+/// What `web` set up for a service, kept in its [`EXTENSION`] block so later
+/// runs, `web remove` and `status` know which labels are eurus' own.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub domains: Vec<String>,
+    pub port: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zone: Option<String>,
+    #[serde(default)]
+    pub proxy: ProxyBackend,
+    /// The labels eurus added, wherever they are, including `deploy.labels`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    /// The environment variables eurus added, for nginx-proxy.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub environment: Vec<String>,
+}
+
+fn extension() -> Extension {
+    EXTENSION.parse().expect("starts with x-")
+}
+
+/// The service's [`EXTENSION`] block, if it has a valid one.
+pub fn metadata(service: &Service) -> Option<Metadata> {
+    serde_yml::from_value(service.extensions.get(&extension())?.clone()).ok()
+}
+
+pub fn set_metadata(service: &mut Service, metadata: &Metadata) {
+    if let Ok(value) = serde_yml::to_value(metadata) {
+        service.extensions.insert(extension(), value);
+    }
+}
+
+/// Removes the labels and environment variables the service's
+/// [`EXTENSION`] block lists, and the block itself. Labels under
+/// `deploy.labels` are only seen within [`edit_labels`].
+pub fn remove_managed(service: &mut Service) -> Option<Metadata> {
+    let metadata = metadata(service)?;
+    for key in &metadata.labels {
+        remove_label(&mut service.labels, key);
+    }
+    for key in &metadata.environment {
+        remove_env(&mut service.environment, key);
+    }
+    service.extensions.shift_remove(&extension());
+
+    Some(metadata)
+}
+
+/// The prefix caddy-docker-proxy reads labels with, changed with its
+/// `CADDY_DOCKER_LABEL_PREFIX`. Picked with `--label-prefix` or the config's
+/// `label_prefix`, and `caddy` otherwise.
+pub fn label_prefix() -> &'static str {
+    LABEL_PREFIX.get_or_init(|| {
+        crate::config::read_raw()
+            .ok()
+            .and_then(|c| c["label_prefix"].as_str().map(String::from))
+            .filter(|p| !p.is_empty())
+            .unwrap_or_else(|| "caddy".to_string())
+    })
+}
+
+/// caddy-docker-proxy allows `caddy_0`, `caddy_1`, ... for multiple sites.
+pub fn is_caddy_site(key: &str) -> bool {
+    let prefix = label_prefix();
+    key == prefix
+        || key
+            .strip_prefix(prefix)
+            .and_then(|k| k.strip_prefix('_'))
+            .is_some_and(|n| n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// The caddy site each of `domains` goes in: the one that already serves it,
+/// or else a new one. A single domain on a service without sites gets
+/// `caddy`, anything else the next free `caddy_N` (with the
+/// [`label_prefix`]).
+pub fn caddy_sites(existing: &Labels, domains: &[String]) -> Vec<String> {
+    let sites: Vec<(String, String)> = label_pairs(existing)
+        .into_iter()
+        .filter(|(key, _)| is_caddy_site(key))
+        .collect();
+    let prefix = label_prefix();
+    if sites.is_empty() && domains.len() == 1 {
+        return vec![prefix.to_string()];
+    }
+
+    let mut taken: Vec<String> = label_pairs(existing)
+        .into_iter()
+        .filter_map(|(key, _)| Some(key.split('.').next()?.to_string()))
+        .collect();
+    let mut next = 0;
+    domains
+        .iter()
+        .map(|domain| {
+            let serving = sites
+                .iter()
+                .find(|(_, value)| site_domains(value).any(|d| d.eq_ignore_ascii_case(domain)));
+            if let Some((site, _)) = serving {
+                return site.clone();
+            }
+            while taken.contains(&format!("{prefix}_{next}")) {
+                next += 1;
+            }
+            let site = format!("{prefix}_{next}");
+            taken.push(site.clone());
+            site
+        })
+        .collect()
+}
+
+/// The domains in the address of a caddy site label.
+fn site_domains(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split([' ', ','])
+        .filter(|d| !d.is_empty())
+        .map(|d| d.trim_start_matches("https://").to_string())
+}
+
+/// Whether the label configures caddy-docker-proxy, as a site or a directive
+/// of one, e.g. `caddy_1.reverse_proxy`.
+pub fn is_caddy_label(key: &str) -> bool {
+    is_caddy_site(key.split('.').next().unwrap_or(key))
+}
+
+/// Removes every caddy-docker-proxy label, returning the domains the removed
+/// sites were for.
+pub fn remove_caddy_labels(labels: &mut Labels) -> Vec<String> {
+    let mut domains = vec![];
+    for (key, value) in label_pairs(labels) {
+        if is_caddy_site(&key) {
+            domains.extend(site_domains(&value));
+        }
+        if is_caddy_label(&key) {
+            remove_label(labels, &key);
+        }
+    }
+
+    domains
+}
+
+/// A directive built from caddy-docker-proxy labels, with the labels nested
+/// under it as its block.
+#[derive(Default)]
+struct Directive {
+    args: String,
+    block: BTreeMap<String, Directive>,
+}
+
+/// Renders the Caddyfile caddy-docker-proxy generates from a container's
+/// labels, with `{{upstreams}}` filled in from its addresses. Returns nothing
+/// when there are no caddy labels.
+pub fn caddyfile_from_labels(
+    labels: &BTreeMap<String, String>,
+    addresses: &[String],
+) -> Option<String> {
+    let mut sites: BTreeMap<&str, Directive> = BTreeMap::new();
+    for (key, value) in labels {
+        let mut path = key.split('.');
+        let Some(site) = path.next().filter(|s| is_caddy_site(s)) else {
+            continue;
+        };
+        let mut directive = sites.entry(site).or_default();
+        for name in path {
+            directive = directive.block.entry(name.to_string()).or_default();
+        }
+        directive.args = expand_upstreams(value, addresses);
+    }
+    if sites.is_empty() {
+        return None;
+    }
+
+    let mut caddyfile = String::new();
+    for site in sites.values() {
+        match site.args.is_empty() {
+            true => caddyfile.push_str("{\n"),
+            false => caddyfile.push_str(&format!("{} {{\n", site.args)),
+        }
+        render_block(&site.block, 1, &mut caddyfile);
+        caddyfile.push_str("}\n");
+    }
+
+    Some(caddyfile)
+}
+
+fn render_block(block: &BTreeMap<String, Directive>, depth: usize, out: &mut String) {
+    let indent = "\t".repeat(depth);
+    for (name, directive) in block {
+        // `1_handle` orders directives and `handle_1` tells repeated ones apart,
+        // neither is part of the name.
+        let name = name
+            .split_once('_')
+            .filter(|(n, _)| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+            .map_or(name.as_str(), |(_, rest)| rest);
+        let name = name
+            .rsplit_once('_')
+            .filter(|(_, n)| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+            .map_or(name, |(rest, _)| rest);
+
+        let line = [name, directive.args.as_str()]
+            .into_iter()
+            .filter(|p| !p.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if directive.block.is_empty() {
+            out.push_str(&format!("{indent}{line}\n"));
+        } else {
+            out.push_str(&format!("{indent}{line} {{\n"));
+            render_block(&directive.block, depth + 1, out);
+            out.push_str(&format!("{indent}}}\n"));
+        }
+    }
+}
+
+/// Replaces `{{upstreams}}`, `{{upstreams 8080}}` and `{{upstreams https 8443}}`
+/// with the container's addresses.
+fn expand_upstreams(value: &str, addresses: &[String]) -> String {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("{{upstreams") {
+        let Some(end) = rest[start..].find("}}").map(|e| start + e) else {
+            break;
+        };
+        expanded.push_str(&rest[..start]);
+
+        let args: Vec<_> = rest[start + "{{upstreams".len()..end]
+            .split_whitespace()
+            .collect();
+        let port = args.iter().find(|a| a.parse::<u16>().is_ok());
+        let scheme = args.iter().find(|a| a.parse::<u16>().is_err());
+        let upstreams: Vec<_> = addresses
+            .iter()
+            .map(|address| {
+                let mut upstream = address.clone();
+                if let Some(scheme) = scheme {
+                    upstream = format!("{scheme}://{upstream}");
+                }
+                if let Some(port) = port {
+                    upstream = format!("{upstream}:{port}");
+                }
+                upstream
+            })
+            .collect();
+        expanded.push_str(&upstreams.join(" "));
+
+        rest = &rest[end + 2..];
+    }
+    expanded.push_str(rest);
+
+    expanded
+}
+
+/// Traefik router and service names can't contain dots.
+fn router_name(service: &str) -> String {
+    service
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,176 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        LazyLock, Mutex,
+    },
+    time::Duration,
+};
+
+use color_eyre::eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::DATA_DIR;
+
+/// Cloudflare blocks a token for a while after this many calls in [`WINDOW`].
+pub static LIMIT: usize = 1200;
+static WINDOW: Duration = Duration::from_secs(5 * 60);
+/// Calls past this are warned about.
+static WARN_AT: usize = 900;
+/// Calls past this wait for older ones to leave the window, leaving some room
+/// for anything else using the token.
+static THROTTLE_AT: usize = 1000;
+/// Daily counts older than this are dropped.
+static KEEP_DAYS: u64 = 90;
+/// How often calls are written to `quota.json` while a run is making them,
+/// so long running commands like `serve` keep it current.
+static SAVE_EVERY: Duration = Duration::from_secs(30);
+
+static USAGE: LazyLock<Mutex<Usage>> = LazyLock::new(|| Mutex::new(Usage::load()));
+static RUN: AtomicU64 = AtomicU64::new(0);
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// The Cloudflare api calls made on this machine, kept in `quota.json` in the
+/// data dir so the window carries over between runs.
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct Usage {
+    /// Calls per local day, as `YYYY-MM-DD`.
+    #[serde(default)]
+    pub days: BTreeMap<String, u64>,
+    /// When the calls in the last [`WINDOW`] were made, in unix milliseconds.
+    #[serde(default)]
+    recent: VecDeque<i64>,
+    /// The calls counted since the last save, added to the file on disk then
+    /// so runs at the same time don't overwrite each other's calls.
+    #[serde(skip)]
+    unsaved: Vec<i64>,
+    #[serde(skip)]
+    saved_at: i64,
+}
+
+impl Usage {
+    pub fn load() -> Self {
+        let usage: Self = std::fs::read_to_string(DATA_DIR.join("quota.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+
+        Self {
+            saved_at: chrono::Utc::now().timestamp_millis(),
+            ..usage
+        }
+    }
+
+    /// Adds the unsaved calls to what is on disk now and writes that back,
+    /// keeping it as the usage from here on.
+    fn save(&mut self) -> Result<()> {
+        let mut usage = Self::load();
+        for call in self.unsaved.drain(..) {
+            usage.recent.push_back(call);
+            let day = chrono::DateTime::from_timestamp_millis(call)
+                .unwrap_or_default()
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d")
+                .to_string();
+            *usage.days.entry(day).or_default() += 1;
+        }
+        usage.recent.make_contiguous().sort();
+        usage.prune(usage.saved_at);
+
+        let cutoff = (chrono::Local::now() - chrono::Days::new(KEEP_DAYS))
+            .format("%Y-%m-%d")
+            .to_string();
+        usage.days.retain(|day, _| *day >= cutoff);
+        *self = usage;
+
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .create(*DATA_DIR)
+            .context("Failed to create data directory")?;
+        std::fs::write(DATA_DIR.join("quota.json"), serde_json::to_string(self)?)?;
+
+        Ok(())
+    }
+
+    fn prune(&mut self, now: i64) {
+        let start = now - WINDOW.as_millis() as i64;
+        while self.recent.front().is_some_and(|t| *t <= start) {
+            self.recent.pop_front();
+        }
+    }
+
+    /// The calls made in the last five minutes.
+    pub fn in_window(&mut self) -> usize {
+        self.prune(chrono::Utc::now().timestamp_millis());
+        self.recent.len()
+    }
+}
+
+/// Counts a call about to be made, first waiting while the calls in the
+/// window are close to [`LIMIT`]. Calls made concurrently all wait here, so
+/// bulk operations slow down to what the limit allows.
+pub async fn acquire() {
+    loop {
+        let wait = {
+            let mut usage = USAGE.lock().unwrap();
+            let now = chrono::Utc::now().timestamp_millis();
+            usage.prune(now);
+
+            let count = usage.recent.len();
+            if count >= WARN_AT && !WARNED.swap(true, Ordering::Relaxed) {
+                let _ = cliclack::log::warning(format!(
+                    "{count} Cloudflare api calls in the last 5 minutes, close to the limit of \
+                     {LIMIT}. Slowing down."
+                ));
+            }
+            match count < THROTTLE_AT {
+                true => {
+                    usage.recent.push_back(now);
+                    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+                    *usage.days.entry(today).or_default() += 1;
+                    usage.unsaved.push(now);
+                    RUN.fetch_add(1, Ordering::Relaxed);
+                    if now - usage.saved_at >= SAVE_EVERY.as_millis() as i64 {
+                        if let Err(e) = usage.save() {
+                            tracing::warn!("Could not save the api quota: {e:#}");
+                        }
+                    }
+                    None
+                }
+                false => {
+                    let oldest = *usage.recent.front().unwrap();
+                    Some(Duration::from_millis(
+                        (oldest + WINDOW.as_millis() as i64 - now).max(1) as u64,
+                    ))
+                }
+            }
+        };
+
+        match wait {
+            Some(delay) => {
+                tracing::debug!("Waiting {delay:?} for the api quota");
+                tokio::time::sleep(delay).await;
+            }
+            None => return,
+        }
+    }
+}
+
+/// The calls made by this run.
+pub fn run_calls() -> u64 {
+    RUN.load(Ordering::Relaxed)
+}
+
+/// Writes the calls not saved yet back, when this run made any.
+pub fn save() -> Result<()> {
+    if run_calls() == 0 {
+        return Ok(());
+    }
+    tracing::info!(calls = run_calls(), "Cloudflare api calls this run");
+
+    let mut usage = USAGE.lock().unwrap();
+    match usage.unsaved.is_empty() {
+        true => Ok(()),
+        false => usage.save(),
+    }
+}
@@ -0,0 +1,581 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::{bail, ensure, Context, ContextCompat, Result};
+use docker_compose_types::Compose;
+use futures::future::try_join_all;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{
+    cancel::{self, or_cancelled, CancellationToken},
+    cloudflare::SpecialTarget,
+    compose::{self, attach_network, find_compose_file},
+    config::{zone_for_name, Config, ZoneInfo},
+    edits, env,
+    hooks::Event,
+    provider::{self, DnsCreateUpdate, DnsListResponse, Provider, AUTO_TTL},
+    proxy::{self, ProxyBackend},
+    trash,
+};
+
+/// The desired state of records and proxied services, read from a yaml file.
+/// Anything not mentioned in it is left alone.
+#[derive(Debug, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Spec {
+    #[serde(default)]
+    pub records: Vec<RecordSpec>,
+    #[serde(default)]
+    pub services: Vec<ServiceSpec>,
+    /// Records and services for one machine only, keyed by its hostname, so
+    /// the same file can be used across several.
+    #[serde(default)]
+    pub hosts: BTreeMap<String, HostSpec>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct HostSpec {
+    #[serde(default)]
+    pub records: Vec<RecordSpec>,
+    #[serde(default)]
+    pub services: Vec<ServiceSpec>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RecordSpec {
+    pub name: String,
+    #[serde(rename = "type", default = "default_record_type")]
+    pub record_type: String,
+    pub content: String,
+    /// Defaults to the zone's `proxied` setting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxied: Option<bool>,
+    /// Left as it is when not given, or automatic for new records.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+    /// For MX and SRV records, left as it is when not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u16>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceSpec {
+    /// The compose file, or the directory it is in.
+    pub compose: String,
+    pub service: String,
+    pub domain: String,
+    #[serde(deserialize_with = "string_or_number")]
+    pub port: String,
+    #[serde(default)]
+    pub proxy: Option<ProxyBackend>,
+}
+
+impl Spec {
+    /// The shared records and services together with those of `host`, which
+    /// replace shared ones for the same record or service. `{host}` in names,
+    /// contents and domains is replaced with the hostname.
+    pub fn for_host(mut self, host: Option<&str>) -> Self {
+        let section = host.and_then(|h| self.hosts.remove(h)).unwrap_or_default();
+        self.hosts.clear();
+
+        for record in section.records {
+            self.records.retain(|r| {
+                r.name != record.name || !r.record_type.eq_ignore_ascii_case(&record.record_type)
+            });
+            self.records.push(record);
+        }
+        for service in section.services {
+            self.services
+                .retain(|s| s.compose != service.compose || s.service != service.service);
+            self.services.push(service);
+        }
+
+        let fill = |text: &mut String| {
+            if let Some(host) = host {
+                *text = text.replace("{host}", host);
+            }
+        };
+        for record in &mut self.records {
+            fill(&mut record.name);
+            fill(&mut record.content);
+        }
+        for service in &mut self.services {
+            fill(&mut service.compose);
+            fill(&mut service.domain);
+        }
+
+        self
+    }
+}
+
+/// This machine's hostname without its domain, which picks its section of a
+/// spec.
+pub fn hostname() -> Option<String> {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .find_map(|p| std::fs::read_to_string(p).ok())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .map(|h| {
+            h.trim()
+                .split('.')
+                .next()
+                .unwrap_or_default()
+                .to_lowercase()
+        })
+        .filter(|h| !h.is_empty())
+}
+
+fn default_record_type() -> String {
+    "CNAME".to_string()
+}
+
+/// Ports are usually written as numbers, but may also be `${VAR}` references.
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Port {
+        Number(u16),
+        Text(String),
+    }
+
+    Ok(match Port::deserialize(deserializer)? {
+        Port::Number(n) => n.to_string(),
+        Port::Text(t) => t,
+    })
+}
+
+/// A single step needed to get from the current state to the spec.
+#[derive(Debug)]
+pub enum Change {
+    CreateRecord {
+        zone: ZoneInfo,
+        record: DnsCreateUpdate,
+    },
+    UpdateRecord {
+        zone: ZoneInfo,
+        record: DnsCreateUpdate,
+        current: String,
+    },
+    DeleteRecord {
+        zone: ZoneInfo,
+        record: DnsListResponse,
+    },
+    EditCompose {
+        file: PathBuf,
+        services: Vec<String>,
+        compose: Box<Compose>,
+    },
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::CreateRecord { record, .. } => write!(
+                f,
+                "+ {} {} -> {}{}",
+                record.record_type,
+                record.name,
+                record.content,
+                if record.proxied { " (proxied)" } else { "" }
+            ),
+            Change::UpdateRecord {
+                record, current, ..
+            } => write!(
+                f,
+                "~ {} {}: {current} -> {}{}",
+                record.record_type,
+                record.name,
+                record.content,
+                if record.proxied { " (proxied)" } else { "" }
+            ),
+            Change::DeleteRecord { record, .. } => write!(
+                f,
+                "- {} {} -> {}",
+                record.record_type,
+                record.name,
+                record.value()
+            ),
+            Change::EditCompose { file, services, .. } => {
+                write!(f, "~ {}: {}", file.display(), services.join(", "))
+            }
+        }
+    }
+}
+
+/// The complete records of zones for `dns sync`, keyed by the zone's name.
+/// Names are relative to the zone, with `@` for the zone itself, or full
+/// names.
+#[derive(Debug, Deserialize, Serialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ZonesSpec {
+    pub zones: BTreeMap<String, Vec<RecordSpec>>,
+}
+
+impl ZonesSpec {
+    /// A spec with the zone's current records, as `dns export` writes it.
+    pub fn from_records(zone: &ZoneInfo, records: &[DnsListResponse]) -> Self {
+        let mut specs: Vec<_> = records
+            .iter()
+            .map(|r| RecordSpec {
+                name: match r.name.strip_suffix(&zone.name) {
+                    Some("") => "@".to_string(),
+                    Some(prefix) if prefix.ends_with('.') => prefix.trim_end_matches('.').into(),
+                    _ => r.name.clone(),
+                },
+                record_type: r.record_type.clone(),
+                content: r.content.clone(),
+                proxied: Some(r.proxied),
+                ttl: Some(r.ttl),
+                priority: r.priority,
+            })
+            .collect();
+        specs.sort_by(|a, b| (&a.name, &a.record_type).cmp(&(&b.name, &b.record_type)));
+
+        Self {
+            zones: BTreeMap::from([(zone.name.clone(), specs)]),
+        }
+    }
+}
+
+pub fn load_zones(path: &Path) -> Result<ZonesSpec> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read {}.", path.display()))?;
+
+    serde_yml::from_str(&contents).with_context(|| format!("{} is malformed.", path.display()))
+}
+
+/// What has to change for the zones to have exactly the records in `spec`.
+/// Records missing from it are only deleted with `prune`.
+pub async fn plan_sync(
+    config: &Config,
+    spec: &ZonesSpec,
+    prune: bool,
+    cancel: &CancellationToken,
+) -> Result<Vec<Change>> {
+    let mut zones = vec![];
+    for (name, records) in &spec.zones {
+        let zone = config
+            .zones
+            .iter()
+            .find(|z| z.name == *name || z.id == *name)
+            .with_context(|| format!("{name} is not a configured zone."))?;
+        let records: Vec<RecordSpec> = records
+            .iter()
+            .map(|r| RecordSpec {
+                name: match r.name.as_str() {
+                    "@" => zone.name.clone(),
+                    n if n == zone.name || n.ends_with(&format!(".{}", zone.name)) => n.into(),
+                    n => format!("{n}.{}", zone.name),
+                },
+                ..r.clone()
+            })
+            .collect();
+        zones.push((zone, records));
+    }
+
+    let listings = or_cancelled(
+        cancel,
+        try_join_all(zones.iter().map(|(zone, _)| async move {
+            provider::for_zone(config, zone)?
+                .list_records(&zone.id)
+                .await
+        })),
+    )
+    .await?;
+
+    let mut changes = vec![];
+    for ((zone, records), existing) in zones.iter().zip(listings) {
+        let records: Vec<_> = records.iter().collect();
+        changes.extend(plan_zone(config, zone, &records, &existing, prune)?);
+    }
+
+    Ok(changes)
+}
+
+pub fn load(path: &Path) -> Result<Spec> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read {}.", path.display()))?;
+
+    serde_yml::from_str(&contents).with_context(|| format!("{} is malformed.", path.display()))
+}
+
+/// Works out what has to change for the zones and compose files to match the
+/// spec, without changing anything. Stops listing zones once `cancel` is
+/// cancelled.
+pub async fn plan(config: &Config, spec: &Spec, cancel: &CancellationToken) -> Result<Vec<Change>> {
+    let mut changes = vec![];
+
+    let mut by_zone: BTreeMap<&str, (&ZoneInfo, Vec<&RecordSpec>)> = BTreeMap::new();
+    for record in &spec.records {
+        let zone = zone_for_name(config, &record.name)
+            .with_context(|| format!("{} is not in any configured zone.", record.name))?;
+        by_zone
+            .entry(&zone.id)
+            .or_insert((zone, vec![]))
+            .1
+            .push(record);
+    }
+
+    // The zones are listed concurrently, the comparison doesn't need an order.
+    let zones: Vec<_> = by_zone.into_values().collect();
+    let listings = or_cancelled(
+        cancel,
+        try_join_all(zones.iter().map(|(zone, _)| async move {
+            provider::for_zone(config, zone)?
+                .list_records(&zone.id)
+                .await
+        })),
+    )
+    .await?;
+
+    for ((zone, records), existing) in zones.into_iter().zip(listings) {
+        changes.extend(plan_zone(config, zone, &records, &existing, false)?);
+    }
+
+    let mut by_file: BTreeMap<PathBuf, Vec<&ServiceSpec>> = BTreeMap::new();
+    for service in &spec.services {
+        let file = find_compose_file(Some(service.compose.clone()))?;
+        let file = file.canonicalize().unwrap_or(file);
+        by_file.entry(file).or_default().push(service);
+    }
+
+    for (file, services) in by_file {
+        if let Some(change) = plan_compose(config, &file, &services)? {
+            changes.push(change);
+        }
+    }
+
+    Ok(changes)
+}
+
+fn plan_compose(config: &Config, file: &Path, services: &[&ServiceSpec]) -> Result<Option<Change>> {
+    let project_dir = file.parent().unwrap_or(Path::new("."));
+    let vars = env::load_variables(project_dir);
+    let contents = std::fs::read_to_string(file)
+        .with_context(|| format!("Could not read {}.", file.display()))?;
+    let resolved: Compose = compose::parse(&env::interpolate(&contents, &vars)?)
+        .with_context(|| format!("{} is invalid after variable substitution.", file.display()))?;
+    let current: Compose = compose::parse(&contents)
+        .with_context(|| format!("{} is not valid compose yaml.", file.display()))?;
+
+    let mut compose = current.clone();
+    for spec in services {
+        let backend = spec.proxy.unwrap_or(config.proxy);
+        let network = config.network(backend);
+        ensure!(
+            !network.is_empty(),
+            "No network is configured for {backend}, run `eurus web` once to set it."
+        );
+        let network = env::interpolate(network, &vars)?;
+        env::resolve_port(&spec.port, &vars)
+            .with_context(|| format!("{} has an invalid port.", spec.service))?;
+
+        let mut service = compose
+            .services
+            .0
+            .get(&spec.service)
+            .cloned()
+            .flatten()
+            .with_context(|| format!("{} has no service {}.", file.display(), spec.service))?;
+
+        backend.set(
+            &mut service,
+            &spec.service,
+            std::slice::from_ref(&spec.domain),
+            &spec.port,
+            &network,
+        );
+        proxy::add_or_ignore_label(&mut service.labels, proxy::MANAGED_LABEL, "true");
+        attach_network(&mut compose, &resolved, &mut service, &network);
+
+        compose
+            .services
+            .0
+            .insert(spec.service.clone(), Some(service));
+    }
+
+    if config.canonical_compose {
+        compose::canonicalize(&mut compose, false);
+    }
+    if serde_yml::to_string(&compose)? == serde_yml::to_string(&current)? {
+        return Ok(None);
+    }
+
+    Ok(Some(Change::EditCompose {
+        file: file.to_path_buf(),
+        services: services.iter().map(|s| s.service.clone()).collect(),
+        compose: Box::new(compose),
+    }))
+}
+
+/// What has to change for the records of `zone` to match `records`, given
+/// the ones it has. With `prune`, records that aren't in `records` are
+/// deleted, apart from the zone's own NS records.
+pub fn plan_zone(
+    config: &Config,
+    zone: &ZoneInfo,
+    records: &[&RecordSpec],
+    existing: &[DnsListResponse],
+    prune: bool,
+) -> Result<Vec<Change>> {
+    let mut changes = vec![];
+    // Only Cloudflare knows about proxying, elsewhere it is always off.
+    let proxies = zone.provider == Provider::Cloudflare;
+    let mut matched = vec![];
+
+    for record in records {
+        let record_type = record.record_type.to_uppercase();
+        let mut special = None;
+        if record_type == "CNAME" {
+            provider::cname_chain(existing, &record.name, &record.content)?;
+            special = SpecialTarget::detect(&record.content);
+        }
+        let matching: Vec<_> = existing
+            .iter()
+            .filter(|r| r.name == record.name && r.record_type == record_type)
+            .filter(|r| !matched.contains(&&r.id))
+            .collect();
+        let current = matching
+            .iter()
+            .find(|r| r.content == record.content)
+            .or(matching.first());
+        if let Some(current) = current {
+            matched.push(&current.id);
+        }
+
+        let body = DnsCreateUpdate {
+            name: record.name.clone(),
+            id: current.map(|r| r.id.clone()),
+            record_type,
+            proxied: record.proxied.unwrap_or(zone.proxied || special.is_some()) && proxies,
+            content: record.content.clone(),
+            ttl: record.ttl.or(current.map(|r| r.ttl)).unwrap_or(AUTO_TTL),
+            priority: record.priority.or(current.and_then(|r| r.priority)),
+            comment: config.record_comment(None, None),
+            ..Default::default()
+        };
+
+        if let Some(problem) = special.and_then(|s| s.problem(body.proxied)) {
+            bail!("{}: {problem}", record.name);
+        }
+
+        match current {
+            None => changes.push(Change::CreateRecord {
+                zone: zone.clone(),
+                record: body,
+            }),
+            Some(c)
+                if c.content == body.content
+                    && c.proxied == body.proxied
+                    && c.ttl == body.ttl
+                    && c.priority == body.priority => {}
+            Some(c) => changes.push(Change::UpdateRecord {
+                zone: zone.clone(),
+                record: body,
+                current: c.content.clone(),
+            }),
+        }
+    }
+
+    if prune {
+        changes.extend(
+            existing
+                .iter()
+                .filter(|r| !matched.contains(&&r.id))
+                // The provider manages the zone's SOA and apex NS records.
+                .filter(|r| r.record_type != "SOA")
+                .filter(|r| !(r.record_type == "NS" && r.name == zone.name))
+                .map(|r| Change::DeleteRecord {
+                    zone: zone.clone(),
+                    record: r.clone(),
+                }),
+        );
+    }
+
+    Ok(changes)
+}
+
+/// Makes the planned changes, running the same hooks as `dns` and `web`.
+/// Once `cancel` is cancelled, the zone or file being changed is finished and
+/// the rest are left alone.
+pub async fn apply(
+    config: &Config,
+    changes: Vec<Change>,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    // Record changes are batched per zone, so each zone changes atomically
+    // where the provider allows it.
+    type ZoneChanges = (ZoneInfo, Vec<DnsCreateUpdate>, Vec<DnsListResponse>);
+    let mut by_zone: BTreeMap<String, ZoneChanges> = BTreeMap::new();
+    let mut compose_changes = vec![];
+    for change in changes {
+        match change {
+            Change::CreateRecord { zone, record } | Change::UpdateRecord { zone, record, .. } => {
+                by_zone
+                    .entry(zone.id.clone())
+                    .or_insert((zone, vec![], vec![]))
+                    .1
+                    .push(record);
+            }
+            Change::DeleteRecord { zone, record } => {
+                by_zone
+                    .entry(zone.id.clone())
+                    .or_insert((zone, vec![], vec![]))
+                    .2
+                    .push(record);
+            }
+            Change::EditCompose {
+                file,
+                services,
+                compose,
+            } => compose_changes.push((file, services, compose)),
+        }
+    }
+
+    for (zone, records, deleted) in by_zone.into_values() {
+        cancel::check(cancel)?;
+        let mut payloads: Vec<_> = records
+            .iter()
+            .map(|r| serde_json::json!({ "zone": zone, "record": r }))
+            .collect();
+        if !deleted.is_empty() {
+            payloads.push(serde_json::json!({ "zone": zone, "deleted": deleted }));
+        }
+        for payload in &payloads {
+            config.hooks.run(Event::PreDns, payload)?;
+        }
+        if config.trash && !deleted.is_empty() {
+            trash::keep(&zone, &deleted)?;
+        }
+        let deletes: Vec<_> = deleted.iter().map(|r| r.id.clone()).collect();
+        provider::for_zone(config, &zone)?
+            .batch(&zone.id, &records, &deletes)
+            .await?;
+        for payload in &payloads {
+            config.hooks.run(Event::PostDns, payload)?;
+        }
+    }
+
+    for (file, services, compose) in compose_changes {
+        cancel::check(cancel)?;
+        let current = std::fs::read_to_string(&file)?;
+        if !edits::confirm_unchanged(&file, &current)? {
+            cliclack::log::info(format!("Skipped {}.", file.display()))?;
+            continue;
+        }
+
+        let payload = serde_json::json!({ "file": file, "services": services });
+        config.hooks.run(Event::PreWeb, &payload)?;
+        edits::write(&file, &compose)?;
+        config.hooks.run(Event::PostWeb, &payload)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,230 @@
+//! Compose files on other machines. They are copied into a local folder to
+//! be edited like any other, and written back afterwards, all through the
+//! `ssh` cli so the user's ssh config and agent apply.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use color_eyre::eyre::{bail, ensure, Context, Result};
+
+use crate::{edits, DATA_DIR};
+
+/// The names docker compose looks for, in its order.
+static COMPOSE_NAMES: [&str; 4] = [
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yaml",
+    "docker-compose.yml",
+];
+
+/// A compose file or project directory on another machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remote {
+    /// Anything `ssh` takes as a destination, e.g. `user@server` or
+    /// `ssh://user@server:2222`.
+    pub host: String,
+    /// Relative to the login directory unless absolute.
+    pub path: String,
+}
+
+impl Remote {
+    /// The remote from `--host` and the path, or from a path like
+    /// `ssh://user@server/srv/app`. Nothing when the path is local.
+    pub fn parse(host: Option<&str>, path: Option<&str>) -> Option<Self> {
+        if let Some(host) = host {
+            return Some(Self {
+                host: host.to_string(),
+                path: path.unwrap_or(".").to_string(),
+            });
+        }
+
+        let rest = path?.strip_prefix("ssh://")?;
+        let (host, path) = match rest.split_once('/') {
+            // `ssh://server/~/app` is relative to the login directory like
+            // with git, anything else is absolute.
+            Some((host, path)) => match path.strip_prefix("~/") {
+                Some(relative) => (host, relative.to_string()),
+                None => (host, format!("/{path}")),
+            },
+            None => (rest, ".".to_string()),
+        };
+        Some(Self {
+            host: format!("ssh://{host}"),
+            path,
+        })
+    }
+
+    /// Copies the compose file, and the `.env` next to it, into a local
+    /// folder kept for this remote.
+    pub fn fetch(&self) -> Result<Checkout> {
+        let file = match self.path.ends_with(".yml") || self.path.ends_with(".yaml") {
+            true => self.path.clone(),
+            false => {
+                let script = format!(
+                    "cd {} && for f in {}; do [ -f \"$f\" ] && echo \"$f\" && exit; done",
+                    quote(&self.path),
+                    COMPOSE_NAMES.join(" ")
+                );
+                let found = self.run(&script, None)?;
+                let name = found.trim();
+                ensure!(
+                    !name.is_empty(),
+                    "There is no compose file in {} on {}.",
+                    self.path,
+                    self.host
+                );
+                format!("{}/{name}", self.path.trim_end_matches('/'))
+            }
+        };
+        let file = self.run(&format!("realpath {}", quote(&file)), None)?;
+        let file = file.trim().to_string();
+        let contents = self.run(&format!("cat {}", quote(&file)), None)?;
+        let remote_dir = Path::new(&file).parent().unwrap_or(Path::new("/"));
+        let dotenv = self.run(
+            &format!(
+                "cat {} 2>/dev/null || true",
+                quote(&remote_dir.join(".env").display().to_string())
+            ),
+            None,
+        )?;
+
+        let dir = DATA_DIR
+            .join("remote")
+            .join(sanitize(&self.host))
+            .join(sanitize(&remote_dir.display().to_string()));
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Could not create {}.", dir.display()))?;
+        let local = dir.join(Path::new(&file).file_name().unwrap_or_default());
+        std::fs::write(&local, &contents)?;
+        match dotenv.is_empty() {
+            true => _ = std::fs::remove_file(dir.join(".env")),
+            false => std::fs::write(dir.join(".env"), &dotenv)?,
+        }
+
+        Ok(Checkout {
+            remote: self.clone(),
+            file,
+            local,
+            original: contents,
+        })
+    }
+
+    /// Runs `script` with the remote's shell, returning what it printed.
+    fn run(&self, script: &str, input: Option<&str>) -> Result<String> {
+        let mut child = Command::new("ssh")
+            .arg(&self.host)
+            .arg(script)
+            .stdin(match input {
+                Some(_) => Stdio::piped(),
+                None => Stdio::null(),
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to run ssh.")?;
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            stdin.write_all(input.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        ensure!(
+            output.status.success(),
+            "ssh {} failed: {}",
+            self.host,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// A local copy of a remote compose file.
+#[derive(Debug, Clone)]
+pub struct Checkout {
+    pub remote: Remote,
+    /// The absolute path on the remote.
+    pub file: String,
+    pub local: PathBuf,
+    original: String,
+}
+
+impl Checkout {
+    /// Writes the local copy back when it was changed, returning whether it
+    /// was. The remote file is backed up first into `.eurus-backups` next to
+    /// it, like local edits, and it isn't overwritten when it was changed on
+    /// the remote in the meantime.
+    pub fn push(&self) -> Result<bool> {
+        let contents = std::fs::read_to_string(&self.local)
+            .with_context(|| format!("Could not read {}.", self.local.display()))?;
+        if contents == self.original {
+            return Ok(false);
+        }
+
+        let current = self
+            .remote
+            .run(&format!("cat {}", quote(&self.file)), None)?;
+        if current != self.original {
+            bail!(
+                "{} changed on {} while it was being edited, so it was left alone. The edited \
+                 copy is in {}.",
+                self.file,
+                self.remote.host,
+                self.local.display()
+            );
+        }
+
+        let file = Path::new(&self.file);
+        let dir = file
+            .parent()
+            .unwrap_or(Path::new("/"))
+            .display()
+            .to_string();
+        let name = file
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let keep = crate::config::read_raw()
+            .ok()
+            .and_then(|c| c["backups"].as_u64())
+            .map_or(edits::DEFAULT_BACKUPS, |n| n as usize)
+            .max(1);
+        let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S%.3f");
+        // The new contents go to a temporary file first, so a dropped
+        // connection can't leave half a compose file.
+        let script = format!(
+            "set -e; cd {dir}; mkdir -p .eurus-backups; \
+             cp -p {name} .eurus-backups/{name}.{stamp}.bak; \
+             ls -1 .eurus-backups | grep -F {prefix} | grep '\\.bak$' | sort -r | \
+             tail -n +{next} | while read -r old; do rm -f \".eurus-backups/$old\"; done; \
+             cat > {name}.eurus-tmp; mv {name}.eurus-tmp {name}",
+            dir = quote(&dir),
+            name = quote(&name),
+            prefix = quote(&format!("{name}.")),
+            next = keep + 1,
+        );
+        self.remote.run(&script, Some(&contents))?;
+
+        Ok(true)
+    }
+}
+
+/// `value` quoted for a POSIX shell.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// A name that is safe as a single path component.
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '@') {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect()
+}
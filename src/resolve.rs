@@ -0,0 +1,153 @@
+use color_eyre::eyre::{bail, Result};
+use serde::Deserialize;
+
+use crate::{logging, CLIENT};
+
+/// DNS-over-HTTPS endpoint used for public lookups.
+static DOH_URL: &str = "https://cloudflare-dns.com/dns-query";
+
+/// Public resolvers a change is checked against to see it's live, by their
+/// address and DNS-over-HTTPS endpoint. Both take the same JSON queries.
+pub static PUBLIC_RESOLVERS: [(&str, &str); 2] = [
+    ("1.1.1.1", DOH_URL),
+    ("8.8.8.8", "https://dns.google/resolve"),
+];
+
+/// Subdomains that are commonly in use, checked when scanning a domain.
+pub static COMMON_SUBDOMAINS: [&str; 24] = [
+    "www", "mail", "smtp", "imap", "pop", "webmail", "ftp", "vpn", "api", "app", "admin", "blog",
+    "shop", "dev", "staging", "test", "cdn", "static", "git", "status", "docs", "portal", "cloud",
+    "home",
+];
+
+#[derive(Debug, Deserialize)]
+struct DohResponse {
+    #[serde(rename = "Status")]
+    status: u32,
+    #[serde(rename = "Answer", default)]
+    answer: Vec<Answer>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Answer {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub record_type: u16,
+    #[serde(rename = "TTL")]
+    pub ttl: u32,
+    pub data: String,
+}
+
+/// A record found in public DNS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredRecord {
+    pub name: String,
+    pub record_type: String,
+    pub content: String,
+}
+
+pub fn type_code(record_type: &str) -> u16 {
+    match record_type {
+        "A" => 1,
+        "NS" => 2,
+        "CNAME" => 5,
+        "SOA" => 6,
+        "MX" => 15,
+        "TXT" => 16,
+        "AAAA" => 28,
+        "SRV" => 33,
+        "CAA" => 257,
+        _ => 0,
+    }
+}
+
+/// The name of a record type code, the other way around from [`type_code`].
+pub fn type_name(code: u16) -> String {
+    match code {
+        1 => "A".to_string(),
+        2 => "NS".to_string(),
+        5 => "CNAME".to_string(),
+        6 => "SOA".to_string(),
+        15 => "MX".to_string(),
+        16 => "TXT".to_string(),
+        28 => "AAAA".to_string(),
+        33 => "SRV".to_string(),
+        257 => "CAA".to_string(),
+        code => format!("TYPE{code}"),
+    }
+}
+
+/// Looks up `name`, returning every answer in the response (including any
+/// CNAMEs that were followed along the way).
+pub async fn lookup(name: &str, record_type: &str) -> Result<Vec<Answer>> {
+    lookup_with(DOH_URL, name, record_type).await
+}
+
+/// Like [`lookup`], but asks the resolver at the DNS-over-HTTPS `url`.
+pub async fn lookup_with(url: &str, name: &str, record_type: &str) -> Result<Vec<Answer>> {
+    let request = (*CLIENT)
+        .get(url)
+        .query(&[("name", name), ("type", record_type)])
+        .header("accept", "application/dns-json");
+    let res: DohResponse = logging::send(request).await?.json().await?;
+
+    // 3 is NXDOMAIN, which just means there is nothing there.
+    match res.status {
+        0 | 3 => Ok(res.answer),
+        status => bail!("DNS lookup for {name} failed with rcode {status}."),
+    }
+}
+
+/// Records that are directly on `name`, ignoring anything reached by
+/// following a CNAME.
+async fn records_at(name: &str, record_type: &str) -> Result<Vec<DiscoveredRecord>> {
+    let code = type_code(record_type);
+
+    Ok(lookup(name, record_type)
+        .await?
+        .into_iter()
+        .filter(|a| a.record_type == code && a.name.trim_end_matches('.') == name)
+        .map(|a| DiscoveredRecord {
+            name: name.to_string(),
+            record_type: record_type.to_string(),
+            content: a.data.trim_end_matches('.').trim_matches('"').to_string(),
+        })
+        .collect())
+}
+
+/// Scans public DNS for the apex of `domain` and the given subdomains.
+/// Subdomains that only exist because of a wildcard record are skipped.
+pub async fn scan(domain: &str, subdomains: &[&str]) -> Result<Vec<DiscoveredRecord>> {
+    let wildcard = records_at(&format!("eurus-wildcard-probe.{domain}"), "A")
+        .await?
+        .into_iter()
+        .chain(records_at(&format!("eurus-wildcard-probe.{domain}"), "CNAME").await?)
+        .map(|r| r.content)
+        .collect::<Vec<_>>();
+
+    let mut found = vec![];
+
+    for record_type in ["A", "AAAA", "TXT"] {
+        found.extend(records_at(domain, record_type).await?);
+    }
+
+    for sub in subdomains {
+        let name = format!("{sub}.{domain}");
+
+        let cname = records_at(&name, "CNAME").await?;
+        let records = if cname.is_empty() {
+            let mut records = records_at(&name, "A").await?;
+            records.extend(records_at(&name, "AAAA").await?);
+            records
+        } else {
+            cname
+        };
+
+        if records.iter().all(|r| wildcard.contains(&r.content)) && !wildcard.is_empty() {
+            continue;
+        }
+        found.extend(records);
+    }
+
+    Ok(found)
+}
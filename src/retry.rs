@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use reqwest::{header::RETRY_AFTER, Method, RequestBuilder, Response, StatusCode};
+
+use crate::logging;
+
+static ATTEMPTS: u32 = 5;
+static BASE_DELAY: Duration = Duration::from_millis(500);
+/// Longer waits than this are left to the caller to report.
+static MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Sends the request, retrying connection failures and 5xx responses with
+/// exponential backoff, and 429s after the `Retry-After` the api asks for.
+/// Requests with a streamed body can't be cloned and are only sent once.
+/// POSTs may have been carried out when they time out or fail with a 5xx, so
+/// they're only sent again when they couldn't connect or were rate limited.
+pub async fn send(request: RequestBuilder) -> reqwest::Result<Response> {
+    let mut attempt = 0;
+    let idempotent = request
+        .try_clone()
+        .and_then(|r| r.build().ok())
+        .is_some_and(|r| r.method() != Method::POST);
+
+    loop {
+        let Some(retry) = request.try_clone().filter(|_| attempt + 1 < ATTEMPTS) else {
+            return logging::send(request).await;
+        };
+        let backoff = BASE_DELAY * 2u32.pow(attempt);
+        attempt += 1;
+
+        let delay = match logging::send(retry).await {
+            Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                match retry_after(&response).unwrap_or(backoff) {
+                    delay if delay > MAX_DELAY => return Ok(response),
+                    delay => delay,
+                }
+            }
+            Ok(response) if idempotent && response.status().is_server_error() => backoff,
+            Err(e) if e.is_connect() => backoff,
+            Err(e) if idempotent && (e.is_timeout() || e.is_request()) => backoff,
+            result => return result,
+        };
+
+        tracing::debug!("Retrying in {delay:?}");
+        tokio::time::sleep(delay).await;
+    }
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
@@ -0,0 +1,152 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use color_eyre::eyre::{Context, Result};
+use docker_compose_types::Compose;
+use futures::future::{join_all, try_join_all};
+use serde::{Deserialize, Serialize};
+
+use crate::{compose, config::Config, edits, provider, proxy, DATA_DIR};
+
+/// The domains each service of a compose file is proxied for, by service.
+pub type Domains = BTreeMap<String, Vec<String>>;
+
+/// What scanning each compose file found, kept in `scan_cache.json` in the
+/// data dir so files that didn't change aren't parsed again.
+#[derive(Debug, Deserialize, Serialize, Default)]
+struct Cache {
+    #[serde(default)]
+    files: BTreeMap<PathBuf, Scanned>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct Scanned {
+    /// The file's modification time in unix nanoseconds when it was parsed.
+    modified: u128,
+    domains: Domains,
+}
+
+impl Cache {
+    fn load() -> Self {
+        std::fs::read_to_string(DATA_DIR.join("scan_cache.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<()> {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .create(*DATA_DIR)
+            .context("Failed to create data directory")?;
+        std::fs::write(
+            DATA_DIR.join("scan_cache.json"),
+            serde_json::to_string(self)?,
+        )?;
+
+        Ok(())
+    }
+}
+
+fn modified(file: &Path) -> Option<u128> {
+    let modified = std::fs::metadata(file).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+}
+
+fn parse(file: &Path) -> Option<Domains> {
+    let compose: Compose = compose::parse(&std::fs::read_to_string(file).ok()?).ok()?;
+
+    Some(
+        compose
+            .services
+            .0
+            .iter()
+            .filter_map(|(name, service)| Some((name.clone(), proxy::domains(service.as_ref()?))))
+            .filter(|(_, domains)| !domains.is_empty())
+            .collect(),
+    )
+}
+
+/// The proxied domains in each of the compose files, leaving out those that
+/// can't be read or parsed. Files are parsed in parallel, and only when they
+/// changed since the last scan.
+pub async fn domains(files: impl IntoIterator<Item = PathBuf>) -> BTreeMap<PathBuf, Domains> {
+    let mut cache = Cache::load();
+
+    let scans = files.into_iter().map(|file| {
+        let cached = cache.files.get(&file).cloned();
+        tokio::task::spawn_blocking(move || {
+            let modified = modified(&file)?;
+            let scanned = match cached {
+                Some(cached) if cached.modified == modified => cached,
+                _ => {
+                    tracing::debug!("Parsing {}", file.display());
+                    Scanned {
+                        modified,
+                        domains: parse(&file)?,
+                    }
+                }
+            };
+            Some((file, scanned))
+        })
+    });
+    let scanned: Vec<_> = join_all(scans)
+        .await
+        .into_iter()
+        .filter_map(|s| s.ok().flatten())
+        .collect();
+
+    let mut changed = false;
+    for (file, scanned) in &scanned {
+        if cache.files.get(file).map(|c| c.modified) != Some(scanned.modified) {
+            cache.files.insert(file.clone(), scanned.clone());
+            changed = true;
+        }
+    }
+    if changed {
+        if let Err(e) = cache.save() {
+            tracing::debug!("Could not save the scan cache: {e}");
+        }
+    }
+
+    scanned
+        .into_iter()
+        .map(|(file, scanned)| (file, scanned.domains))
+        .collect()
+}
+
+/// Every domain eurus manages, with where it was found: the routed domains in
+/// compose files eurus has edited, and the address records in configured zones.
+pub async fn managed_domains(config: &Config) -> Result<BTreeMap<String, Vec<&'static str>>> {
+    let scanned = domains(edits::Edits::load()?.files.into_keys().map(PathBuf::from)).await;
+    let mut domains: BTreeMap<String, Vec<&'static str>> = BTreeMap::new();
+    for domain in scanned
+        .into_values()
+        .flat_map(|s| s.into_values().flatten())
+    {
+        domains.entry(domain).or_default().push("compose");
+    }
+
+    let listings = try_join_all(config.zones.iter().map(|zone| async {
+        provider::for_zone(config, zone)?
+            .list_records(&zone.id)
+            .await
+    }))
+    .await?;
+    for record in listings.into_iter().flatten() {
+        if matches!(record.record_type.as_str(), "A" | "AAAA" | "CNAME")
+            && !record.name.starts_with('*')
+        {
+            domains.entry(record.name).or_default().push("dns");
+        }
+    }
+
+    for sources in domains.values_mut() {
+        sources.dedup();
+    }
+
+    Ok(domains)
+}
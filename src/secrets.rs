@@ -0,0 +1,199 @@
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use color_eyre::eyre::{eyre, Context, Result};
+use docker_compose_types::{
+    Compose, ComposeSecret, ComposeSecrets, EnvFile, Environment, Secrets, Service, SingleValue,
+};
+use serde::{Deserialize, Serialize};
+
+/// Where secrets generated for new services are kept. Either way the values
+/// never end up in the compose file itself.
+#[derive(Debug, Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum SecretStorage {
+    /// A `<service>.env` file next to the compose file, loaded with `env_file`.
+    #[default]
+    EnvFile,
+    /// One file per secret under `secrets/`, mounted as a docker secret and
+    /// pointed at by a `<NAME>_FILE` variable.
+    DockerSecret,
+}
+
+/// A file holding generated secrets, written once the edit is confirmed.
+pub struct SecretFile {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+/// A random 32 character secret, safe to use unquoted in env files and urls.
+pub fn generate() -> Result<String> {
+    static ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+    let mut secret = String::with_capacity(32);
+    let mut buf = [0u8; 64];
+    while secret.len() < 32 {
+        getrandom::getrandom(&mut buf).map_err(|e| eyre!("Could not generate a secret: {e}"))?;
+        // Rejecting bytes past the last full multiple keeps every character
+        // equally likely.
+        let limit = 256 - 256 % ALPHABET.len();
+        secret.extend(
+            buf.iter()
+                .filter(|b| (**b as usize) < limit)
+                .map(|b| ALPHABET[*b as usize % ALPHABET.len()] as char)
+                .take(32 - secret.len()),
+        );
+    }
+
+    Ok(secret)
+}
+
+/// Generates a value for each of `names` and references them from `service`,
+/// adding top level secrets to `compose` where needed. The files are returned
+/// rather than written so nothing lands on disk if the edit is cancelled.
+/// Secrets that already exist are reused, and nothing is added without any.
+pub fn add(
+    compose: &mut Compose,
+    dir: &Path,
+    service_name: &str,
+    service: &mut Service,
+    names: &[String],
+    storage: SecretStorage,
+) -> Result<Vec<SecretFile>> {
+    if names.is_empty() {
+        return Ok(vec![]);
+    }
+    match storage {
+        SecretStorage::EnvFile => add_env_file(dir, service_name, service, names),
+        SecretStorage::DockerSecret => {
+            add_docker_secrets(compose, dir, service_name, service, names)
+        }
+    }
+}
+
+fn add_env_file(
+    dir: &Path,
+    service_name: &str,
+    service: &mut Service,
+    names: &[String],
+) -> Result<Vec<SecretFile>> {
+    let file_name = format!("{service_name}.env");
+    let path = dir.join(&file_name);
+    let mut contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("Could not read {}.", path.display())),
+    };
+
+    for name in names {
+        let present = contents.lines().any(|l| {
+            l.trim_start()
+                .strip_prefix(name.as_str())
+                .is_some_and(|r| r.starts_with('='))
+        });
+        if present {
+            continue;
+        }
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        contents.push_str(&format!("{name}={}\n", generate()?));
+    }
+
+    let entry = format!("./{file_name}");
+    service.env_file = Some(match service.env_file.take() {
+        None => EnvFile::Simple(entry),
+        Some(EnvFile::Simple(f)) if f == entry => EnvFile::Simple(f),
+        Some(EnvFile::Simple(f)) => EnvFile::List(vec![f, entry]),
+        Some(EnvFile::List(mut files)) => {
+            if !files.contains(&entry) {
+                files.push(entry);
+            }
+            EnvFile::List(files)
+        }
+    });
+
+    Ok(vec![SecretFile { path, contents }])
+}
+
+fn add_docker_secrets(
+    compose: &mut Compose,
+    dir: &Path,
+    service_name: &str,
+    service: &mut Service,
+    names: &[String],
+) -> Result<Vec<SecretFile>> {
+    let mut files = vec![];
+
+    for name in names {
+        let secret = format!("{service_name}_{}", name.to_lowercase());
+        let relative = format!("./secrets/{secret}");
+        let path = dir.join("secrets").join(&secret);
+        if !path.exists() {
+            files.push(SecretFile {
+                path,
+                contents: generate()?,
+            });
+        }
+
+        compose
+            .secrets
+            .get_or_insert_with(ComposeSecrets::default)
+            .0
+            .insert(secret.clone(), Some(ComposeSecret::File(relative)));
+
+        match service.secrets.get_or_insert_with(Secrets::default) {
+            Secrets::Simple(secrets) if !secrets.contains(&secret) => secrets.push(secret.clone()),
+            Secrets::Advanced(secrets) if !secrets.iter().any(|s| s.source == secret) => secrets
+                .push(docker_compose_types::AdvancedSecrets {
+                    source: secret.clone(),
+                    ..Default::default()
+                }),
+            _ => (),
+        }
+
+        // Most images that take secrets read them from the path in `<NAME>_FILE`.
+        let variable = format!("{name}_FILE");
+        let value = format!("/run/secrets/{secret}");
+        match &mut service.environment {
+            Environment::List(vars) => {
+                if !vars
+                    .iter()
+                    .any(|v| v.split('=').next() == Some(variable.as_str()))
+                {
+                    vars.push(format!("{variable}={value}"));
+                }
+            }
+            Environment::KvPair(vars) => {
+                vars.entry(variable)
+                    .or_insert(Some(SingleValue::String(value)));
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Writes the generated files, readable only by the owner where that's
+/// supported.
+pub fn write(files: &[SecretFile]) -> Result<()> {
+    for file in files {
+        // Only ever the path, the contents are the secrets.
+        tracing::info!("Writing {}", file.path.display());
+        if let Some(parent) = file.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let mut handle = options
+            .open(&file.path)
+            .with_context(|| format!("Could not write {}.", file.path.display()))?;
+        std::io::Write::write_all(&mut handle, file.contents.as_bytes())?;
+    }
+
+    Ok(())
+}
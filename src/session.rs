@@ -0,0 +1,109 @@
+use std::{collections::BTreeMap, path::Path};
+
+use color_eyre::eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{last::project_key, prompt, DATA_DIR};
+
+/// Flows that were interrupted before they finished, by flow and project
+/// directory.
+#[derive(Debug, Deserialize, Serialize, Default)]
+struct Sessions {
+    #[serde(default)]
+    sessions: BTreeMap<String, Saved>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+struct Saved {
+    /// When the flow was started, in local time.
+    started: String,
+    answers: BTreeMap<String, String>,
+}
+
+/// The answers given so far in an interactive flow. Each is written to
+/// `sessions.json` in the data dir as soon as it's given, so when the flow
+/// fails or is cancelled halfway, running it again can offer them as the
+/// defaults.
+pub struct Session {
+    key: String,
+    saved: Saved,
+}
+
+impl Session {
+    /// Starts `flow` in `dir`, asking whether to resume when an earlier run
+    /// of it there didn't finish.
+    pub fn start(flow: &str, dir: &Path) -> Result<Self> {
+        let key = format!("{flow} {}", project_key(dir));
+        let interrupted = read()
+            .ok()
+            .and_then(|mut s| s.sessions.remove(&key))
+            .filter(|s| !s.answers.is_empty());
+        let mut session = Self {
+            key,
+            saved: Saved {
+                started: chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+                answers: BTreeMap::new(),
+            },
+        };
+
+        if let Some(saved) = interrupted {
+            let resume = prompt::toggle(format!(
+                "The last `{flow}` here, started {}, didn't finish. Resume with its answers?",
+                saved.started
+            ))
+            .initial_value(true)
+            .interact()?;
+            match resume {
+                true => session.saved = saved,
+                false => session.save(false)?,
+            }
+        }
+
+        Ok(session)
+    }
+
+    /// The answer given to `question` before the flow was interrupted.
+    pub fn get(&self, question: &str) -> Option<&str> {
+        self.saved.answers.get(question).map(String::as_str)
+    }
+
+    pub fn answer(&mut self, question: &str, value: impl ToString) -> Result<()> {
+        self.saved
+            .answers
+            .insert(question.to_string(), value.to_string());
+        self.save(true)
+    }
+
+    /// Forgets the answers once the flow is done.
+    pub fn finish(self) -> Result<()> {
+        self.save(false)
+    }
+
+    fn save(&self, keep: bool) -> Result<()> {
+        let mut sessions = read()?;
+        match keep {
+            true => sessions
+                .sessions
+                .insert(self.key.clone(), self.saved.clone()),
+            false => sessions.sessions.remove(&self.key),
+        };
+
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .create(*DATA_DIR)
+            .context("Failed to create data directory")?;
+        std::fs::write(
+            DATA_DIR.join("sessions.json"),
+            serde_json::to_string(&sessions)?,
+        )?;
+
+        Ok(())
+    }
+}
+
+fn read() -> Result<Sessions> {
+    match std::fs::read_to_string(DATA_DIR.join("sessions.json")) {
+        Ok(s) => serde_json::from_str(&s).context("Sessions file is malformed."),
+        Err(_) => Ok(Sessions::default()),
+    }
+}
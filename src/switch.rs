@@ -0,0 +1,147 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use color_eyre::eyre::{ensure, Context, ContextCompat, Result};
+use docker_compose_types::{Compose, ComposeNetwork, MapOrEmpty, Networks};
+use serde::{Deserialize, Serialize};
+
+use crate::{compose::attach_network, maintenance, proxy, DATA_DIR};
+
+/// The domains switched between two targets, so `switch --back` can return
+/// to the previous one.
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct Switches {
+    #[serde(default)]
+    pub domains: BTreeMap<String, Switch>,
+}
+
+/// What a domain was last switched between. Switching back swaps `from` and
+/// `to`, so it can go back and forth.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Switch {
+    /// Between the caddy labels of two services in a compose file.
+    Labels {
+        file: PathBuf,
+        from: String,
+        to: String,
+    },
+    /// Between two values of the domain's DNS record.
+    Dns {
+        zone_id: String,
+        record_id: String,
+        from: String,
+        to: String,
+    },
+}
+
+impl Switch {
+    pub fn reversed(self) -> Self {
+        match self {
+            Switch::Labels { file, from, to } => Switch::Labels {
+                file,
+                from: to,
+                to: from,
+            },
+            Switch::Dns {
+                zone_id,
+                record_id,
+                from,
+                to,
+            } => Switch::Dns {
+                zone_id,
+                record_id,
+                from: to,
+                to: from,
+            },
+        }
+    }
+}
+
+impl Switches {
+    pub fn load() -> Result<Self> {
+        match std::fs::read_to_string(DATA_DIR.join("switches.json")) {
+            Ok(s) => serde_json::from_str(&s).context("Switches file is malformed."),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .create(*DATA_DIR)
+            .context("Failed to create data directory")?;
+        std::fs::write(DATA_DIR.join("switches.json"), serde_json::to_string(self)?)?;
+
+        Ok(())
+    }
+}
+
+/// Moves the caddy labels for `domain` from the `from` service to `to` in
+/// one edit, so the domain is never served by both or neither. `to` joins the
+/// external networks `from` is on, which is how caddy reaches it.
+pub fn move_site(compose: &mut Compose, domain: &str, from: &str, to: &str) -> Result<()> {
+    ensure!(from != to, "{domain} is already served by {to}.");
+
+    let source = compose
+        .services
+        .0
+        .get_mut(from)
+        .and_then(|s| s.as_mut())
+        .with_context(|| format!("There is no service called {from}."))?;
+    let prefix = maintenance::site_prefix(source, domain)
+        .with_context(|| format!("{from} has no caddy labels for {domain}."))?;
+    let labels: Vec<_> = proxy::label_pairs(&source.labels)
+        .into_iter()
+        .filter(|(key, _)| *key == prefix || key.starts_with(&format!("{prefix}.")))
+        .collect();
+    for (key, _) in &labels {
+        proxy::remove_label(&mut source.labels, key);
+    }
+    let networks = match &source.networks {
+        Networks::Simple(n) => n.clone(),
+        Networks::Advanced(n) => n.0.keys().cloned().collect(),
+    };
+
+    let external: Vec<_> = networks
+        .into_iter()
+        .filter(|n| {
+            matches!(
+                compose.networks.0.get(n),
+                Some(MapOrEmpty::Map(settings)) if matches!(settings.external, Some(ComposeNetwork::Bool(true)))
+            )
+        })
+        .collect();
+    let resolved = compose.clone();
+    let mut target = compose
+        .services
+        .0
+        .get(to)
+        .cloned()
+        .flatten()
+        .with_context(|| format!("There is no service called {to}."))?;
+
+    // The target may already declare a site under the same label, in which
+    // case the moved one gets the next free `caddy_<n>`.
+    let taken: Vec<_> = proxy::label_pairs(&target.labels)
+        .into_iter()
+        .map(|(key, _)| key)
+        .filter(|key| proxy::is_caddy_site(key))
+        .collect();
+    let new_prefix = match taken.contains(&prefix) {
+        false => prefix.clone(),
+        true => (0..)
+            .map(|n| format!("{}_{n}", proxy::label_prefix()))
+            .find(|p| !taken.contains(p))
+            .unwrap(),
+    };
+    for (key, value) in &labels {
+        let key = format!("{new_prefix}{}", &key[prefix.len()..]);
+        proxy::set_label(&mut target.labels, &key, value);
+    }
+    for network in &external {
+        attach_network(compose, &resolved, &mut target, network);
+    }
+    compose.services.0.insert(to.to_string(), Some(target));
+
+    Ok(())
+}
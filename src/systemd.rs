@@ -0,0 +1,142 @@
+//! systemd units that run eurus' long running modes, `dns ddns` on a timer
+//! and `web --watch` as a service.
+
+use std::{path::PathBuf, time::Duration};
+
+/// What the units run.
+#[derive(Debug, Clone)]
+pub enum Daemon {
+    /// `dns ddns`, started by a timer every `interval`.
+    Ddns { interval: Duration },
+    /// `web --watch` on `dir`, with extra `web` arguments like `--domain`.
+    Watch { dir: PathBuf, args: Vec<String> },
+}
+
+/// Where the eurus binary and its files are, and who the units run as.
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub exe: PathBuf,
+    pub config: PathBuf,
+    pub data_dir: PathBuf,
+    pub profile: Option<String>,
+    /// The user system units run as, `None` for user units.
+    pub user: Option<String>,
+}
+
+impl Daemon {
+    fn name(&self) -> &'static str {
+        match self {
+            Daemon::Ddns { .. } => "eurus-ddns",
+            Daemon::Watch { .. } => "eurus-watch",
+        }
+    }
+
+    /// The unit files, as file name and contents.
+    pub fn units(&self, context: &Context) -> Vec<(String, String)> {
+        let name = self.name();
+        let mut units = vec![(format!("{name}.service"), self.service(context))];
+        if let Daemon::Ddns { interval } = self {
+            units.push((format!("{name}.timer"), timer(*interval)));
+        }
+        units
+    }
+
+    fn service(&self, context: &Context) -> String {
+        let mut command = vec![
+            context.exe.display().to_string(),
+            "--yes".to_string(),
+            "--config".to_string(),
+            context.config.display().to_string(),
+        ];
+        if let Some(profile) = &context.profile {
+            command.extend(["--profile".to_string(), profile.clone()]);
+        }
+        let mut writable = vec![
+            context.data_dir.clone(),
+            context
+                .config
+                .parent()
+                .map(PathBuf::from)
+                .unwrap_or_default(),
+        ];
+
+        let (description, kind, restart) = match self {
+            Daemon::Ddns { .. } => {
+                command.extend(["dns".to_string(), "ddns".to_string()]);
+                ("Update dynamic DNS records with eurus", "oneshot", None)
+            }
+            Daemon::Watch { dir, args } => {
+                command.extend([
+                    "web".to_string(),
+                    "--watch".to_string(),
+                    dir.display().to_string(),
+                ]);
+                command.extend(args.iter().cloned());
+                writable.push(dir.clone());
+                (
+                    "Proxy new compose services with eurus",
+                    "simple",
+                    Some("on-failure"),
+                )
+            }
+        };
+
+        let mut unit = format!(
+            "[Unit]\nDescription={description}\nWants=network-online.target\n\
+             After=network-online.target\n\n[Service]\nType={kind}\nExecStart={}\n",
+            command
+                .iter()
+                .map(|arg| quote(arg))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        if let Some(user) = &context.user {
+            unit.push_str(&format!("User={user}\n"));
+        }
+        if let Some(restart) = restart {
+            unit.push_str(&format!("Restart={restart}\nRestartSec=10\n"));
+        }
+        unit.push_str(&format!(
+            "NoNewPrivileges=yes\nPrivateTmp=yes\nProtectSystem=strict\n\
+             ProtectHome=read-only\nReadWritePaths={}\nProtectKernelTunables=yes\n\
+             ProtectKernelModules=yes\nProtectControlGroups=yes\nRestrictSUIDSGID=yes\n\
+             RestrictNamespaces=yes\nLockPersonality=yes\n\
+             RestrictAddressFamilies=AF_UNIX AF_INET AF_INET6 AF_NETLINK\n",
+            writable
+                .iter()
+                .filter(|p| !p.as_os_str().is_empty())
+                .map(|p| quote(&format!("-{}", p.display())))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ));
+
+        // Timers start the ddns service, so only the watcher is enabled.
+        if let Daemon::Watch { .. } = self {
+            let target = match context.user {
+                Some(_) => "multi-user.target",
+                None => "default.target",
+            };
+            unit.push_str(&format!("\n[Install]\nWantedBy={target}\n"));
+        }
+        unit
+    }
+}
+
+fn timer(interval: Duration) -> String {
+    format!(
+        "[Unit]\nDescription=Update dynamic DNS records with eurus every {0}s\n\n\
+         [Timer]\nOnBootSec=1min\nOnUnitActiveSec={0}s\n\n\
+         [Install]\nWantedBy=timers.target\n",
+        interval.as_secs()
+    )
+}
+
+/// Quotes an argument for `ExecStart`, where `%` starts a specifier and
+/// whitespace splits arguments.
+fn quote(arg: &str) -> String {
+    let arg = arg.replace('%', "%%");
+    match arg.contains(char::is_whitespace) || arg.contains('"') {
+        true => format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\"")),
+        false => arg,
+    }
+}
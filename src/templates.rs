@@ -0,0 +1,135 @@
+//! Templates for proxying common self-hosted apps: the port they listen on
+//! and the caddy directives they need, so `web --template` only asks for the
+//! domain.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use color_eyre::eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{proxy::CaddyOptions, CONFIG_DIR};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Template {
+    #[serde(default)]
+    pub description: String,
+    /// The port the app listens on inside its container.
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub websocket: bool,
+    /// Response headers like `X-Frame-Options DENY`.
+    #[serde(default)]
+    pub headers: Vec<String>,
+    #[serde(default)]
+    pub tls: Option<String>,
+    /// Caddy directives below the site, e.g. `encode = "gzip"` or
+    /// `"request_body.max_size" = "10G"`.
+    #[serde(default)]
+    pub directives: BTreeMap<String, String>,
+    /// Other labels added to the service as they are.
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl Template {
+    /// The caddy directives of the template, with `options` (from flags or
+    /// prompts) added on top.
+    pub fn caddy_options(&self, options: CaddyOptions) -> CaddyOptions {
+        let mut headers = self.headers.clone();
+        headers.extend(options.headers);
+        CaddyOptions {
+            tls: options.tls.or(self.tls.clone()),
+            basic_auth: options.basic_auth,
+            headers,
+            websocket: options.websocket || self.websocket,
+            directives: self
+                .directives
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .chain(options.directives)
+                .collect(),
+        }
+    }
+}
+
+/// Where templates of your own are kept, one `<name>.toml` or `<name>.yaml`
+/// each.
+pub fn dir() -> PathBuf {
+    CONFIG_DIR.join("templates")
+}
+
+/// The built in templates, with the ones in [`dir`] added or replacing them
+/// by name.
+pub fn all() -> Result<BTreeMap<String, Template>> {
+    let mut templates = BTreeMap::from([
+        (
+            "websocket".to_string(),
+            Template {
+                description: "An app that keeps websockets open".to_string(),
+                websocket: true,
+                ..Default::default()
+            },
+        ),
+        (
+            "uptime-kuma".to_string(),
+            Template {
+                description: "Uptime Kuma".to_string(),
+                port: Some(3001),
+                websocket: true,
+                ..Default::default()
+            },
+        ),
+        (
+            "nextcloud".to_string(),
+            Template {
+                description: "Nextcloud (the apache image)".to_string(),
+                port: Some(80),
+                headers: vec!["Strict-Transport-Security max-age=15552000;".to_string()],
+                directives: BTreeMap::from([
+                    (
+                        "redir_0".to_string(),
+                        "/.well-known/carddav /remote.php/dav 301".to_string(),
+                    ),
+                    (
+                        "redir_1".to_string(),
+                        "/.well-known/caldav /remote.php/dav 301".to_string(),
+                    ),
+                    ("request_body.max_size".to_string(), "10G".to_string()),
+                ]),
+                ..Default::default()
+            },
+        ),
+        (
+            "vaultwarden".to_string(),
+            Template {
+                description: "Vaultwarden".to_string(),
+                port: Some(80),
+                websocket: true,
+                ..Default::default()
+            },
+        ),
+    ]);
+
+    for entry in std::fs::read_dir(dir()).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let Some(name) = path.file_stem().map(|n| n.to_string_lossy().to_string()) else {
+            continue;
+        };
+        let contents = || {
+            std::fs::read_to_string(&path)
+                .with_context(|| format!("Could not read {}.", path.display()))
+        };
+        let template: Template = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str(&contents()?)
+                .with_context(|| format!("{} is malformed.", path.display()))?,
+            Some("yaml" | "yml") => serde_yml::from_str(&contents()?)
+                .with_context(|| format!("{} is malformed.", path.display()))?,
+            _ => continue,
+        };
+        templates.insert(name, template);
+    }
+
+    Ok(templates)
+}
@@ -0,0 +1,53 @@
+use color_eyre::eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::ZoneInfo, provider::DnsListResponse, DATA_DIR};
+
+/// Records eurus deleted while `trash` is on in the config, kept in
+/// `trash.json` in the data dir so they can be recreated.
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct Trash {
+    #[serde(default)]
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Entry {
+    /// In RFC 3339.
+    pub deleted_at: String,
+    pub zone: ZoneInfo,
+    pub record: DnsListResponse,
+}
+
+impl Trash {
+    pub fn load() -> Result<Self> {
+        match std::fs::read_to_string(DATA_DIR.join("trash.json")) {
+            Ok(s) => serde_json::from_str(&s).context("Trash file is malformed."),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .create(*DATA_DIR)
+            .context("Failed to create data directory")?;
+        std::fs::write(DATA_DIR.join("trash.json"), serde_json::to_string(self)?)?;
+
+        Ok(())
+    }
+}
+
+/// Puts the records in the trash. Done before they are deleted, so a record
+/// is never gone without a copy.
+pub fn keep(zone: &ZoneInfo, records: &[DnsListResponse]) -> Result<()> {
+    let mut trash = Trash::load()?;
+    let deleted_at = chrono::Utc::now().to_rfc3339();
+    trash.entries.extend(records.iter().map(|r| Entry {
+        deleted_at: deleted_at.clone(),
+        zone: zone.clone(),
+        record: r.clone(),
+    }));
+
+    trash.save()
+}
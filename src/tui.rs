@@ -0,0 +1,571 @@
+//! `eurus tui`, a full-screen view of the zones, their records and the
+//! services of a compose file, for when stepping through prompts gets slow.
+
+use std::{path::PathBuf, time::Duration};
+
+use color_eyre::eyre::{Context, ContextCompat, Result};
+use docker_compose_types::{Compose, Extension, Service};
+use ratatui::{
+    crossterm::event::{self, Event as TermEvent, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::Line,
+    widgets::{Block, List, ListItem, ListState, Paragraph, Row, Table, TableState},
+    DefaultTerminal, Frame,
+};
+use serde_yml::{Mapping, Value};
+
+use crate::{
+    compose,
+    config::{Config, ZoneInfo},
+    edits,
+    hooks::Event,
+    idn,
+    provider::{self, DnsCreateUpdate, DnsListResponse, AUTO_TTL},
+    proxy, trash,
+};
+
+/// Where a service keeps the labels switched off in the tui, so they can be
+/// switched on again.
+pub const DISABLED_LABELS: &str = "x-eurus-disabled-labels";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Zones,
+    Records,
+    Services,
+    Labels,
+}
+
+enum Mode {
+    Browse,
+    /// Typing the new content of the selected record.
+    Edit(String),
+    /// Asking whether to delete the selected record.
+    ConfirmDelete,
+}
+
+/// An api call, made after the screen shows that it's running.
+enum Action {
+    Load,
+    Save(DnsCreateUpdate),
+    Delete(DnsListResponse),
+}
+
+struct App {
+    config: Config,
+    pane: Pane,
+    mode: Mode,
+    zones: ListState,
+    records: Vec<DnsListResponse>,
+    record_state: TableState,
+    compose: Option<(PathBuf, Compose)>,
+    services: ListState,
+    labels: ListState,
+    pending: Option<Action>,
+    status: String,
+    quit: bool,
+}
+
+/// Runs the tui until it's quit. `compose_file` adds the panes for its
+/// services.
+pub async fn run(config: Config, compose_file: Option<PathBuf>) -> Result<()> {
+    let compose = match compose_file {
+        Some(file) => {
+            let contents = std::fs::read_to_string(&file)
+                .with_context(|| format!("Could not read {}.", file.display()))?;
+            let compose = compose::parse(&contents).context("The compose yaml was invalid.")?;
+            Some((file, compose))
+        }
+        None => None,
+    };
+
+    let mut app = App {
+        zones: ListState::default().with_selected(Some(0)),
+        services: ListState::default().with_selected(Some(0)),
+        pending: Some(Action::Load),
+        config,
+        pane: Pane::Zones,
+        mode: Mode::Browse,
+        records: vec![],
+        record_state: TableState::default(),
+        compose,
+        labels: ListState::default(),
+        status: String::new(),
+        quit: false,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal).await;
+    ratatui::restore();
+
+    result
+}
+
+impl App {
+    async fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        while !self.quit {
+            if let Some(action) = &self.pending {
+                self.status = match action {
+                    Action::Load => "Loading records...".to_string(),
+                    Action::Save(record) => format!("Saving {}...", record.name),
+                    Action::Delete(record) => format!("Deleting {}...", record.name),
+                };
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if let Some(action) = self.pending.take() {
+                // Api failures are shown rather than ending the tui.
+                self.status = self
+                    .perform(action)
+                    .await
+                    .unwrap_or_else(|e| format!("{e}"));
+                continue;
+            }
+            if !event::poll(Duration::from_millis(250))? {
+                continue;
+            }
+            if let TermEvent::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    self.status.clear();
+                    self.key(key);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn zone(&self) -> Option<&ZoneInfo> {
+        self.config.zones.get(self.zones.selected()?)
+    }
+
+    fn record(&self) -> Option<&DnsListResponse> {
+        self.records.get(self.record_state.selected()?)
+    }
+
+    fn service_names(&self) -> Vec<String> {
+        self.compose
+            .as_ref()
+            .map(|(_, c)| c.services.0.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn service(&self) -> Option<&Service> {
+        let name = self.service_names().get(self.services.selected()?)?.clone();
+        self.compose.as_ref()?.1.services.0.get(&name)?.as_ref()
+    }
+
+    async fn perform(&mut self, action: Action) -> Result<String> {
+        let zone = self
+            .zone()
+            .context("There are no zones configured.")?
+            .clone();
+        let provider = provider::for_zone(&self.config, &zone)?;
+
+        match action {
+            Action::Load => {
+                let mut records = provider.list_records(&zone.id).await?;
+                records.sort_by(|a, b| (&a.name, &a.record_type).cmp(&(&b.name, &b.record_type)));
+                self.record_state.select((!records.is_empty()).then_some(0));
+                self.records = records;
+                Ok(format!(
+                    "{} has {} records.",
+                    idn::to_unicode(&zone.name),
+                    self.records.len()
+                ))
+            }
+            Action::Save(record) => {
+                let payload = serde_json::json!({ "zone": zone, "record": record });
+                self.config.hooks.run(Event::PreDns, &payload)?;
+                let saved = provider.upsert_record(&zone.id, &record).await?;
+                self.config.hooks.run(Event::PostDns, &payload)?;
+
+                if let Some(current) = self.records.iter_mut().find(|r| r.id == saved.id) {
+                    *current = saved;
+                }
+                Ok(format!("Saved {}.", idn::to_unicode(&record.name)))
+            }
+            Action::Delete(record) => {
+                let payload = serde_json::json!({ "zone": zone, "deleted": [record] });
+                self.config.hooks.run(Event::PreDns, &payload)?;
+                if self.config.trash {
+                    trash::keep(&zone, std::slice::from_ref(&record))?;
+                }
+                provider.delete_record(&zone.id, &record.id).await?;
+                self.config.hooks.run(Event::PostDns, &payload)?;
+
+                self.records.retain(|r| r.id != record.id);
+                if self.record_state.selected() >= Some(self.records.len()) {
+                    self.record_state.select(self.records.len().checked_sub(1));
+                }
+                Ok(format!("Deleted {}.", idn::to_unicode(&record.name)))
+            }
+        }
+    }
+
+    fn key(&mut self, key: KeyEvent) {
+        match &mut self.mode {
+            Mode::Edit(input) => match key.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Enter => {
+                    let content = input.trim().to_string();
+                    self.mode = Mode::Browse;
+                    if let Some(record) = self.record().filter(|r| r.content != content) {
+                        self.pending = Some(Action::Save(DnsCreateUpdate {
+                            content,
+                            ..record.to_update()
+                        }));
+                    }
+                }
+                KeyCode::Esc => self.mode = Mode::Browse,
+                _ => {}
+            },
+            Mode::ConfirmDelete => {
+                if key.code == KeyCode::Char('y') {
+                    self.pending = self.record().cloned().map(Action::Delete);
+                }
+                self.mode = Mode::Browse;
+            }
+            Mode::Browse => self.browse(key),
+        }
+    }
+
+    fn browse(&mut self, key: KeyEvent) {
+        let panes: &[Pane] = match self.compose {
+            Some(_) => &[Pane::Zones, Pane::Records, Pane::Services],
+            None => &[Pane::Zones, Pane::Records],
+        };
+        let index = panes.iter().position(|p| *p == self.pane).unwrap_or(2);
+
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Esc if self.pane == Pane::Labels => self.pane = Pane::Services,
+            KeyCode::Esc => self.quit = true,
+            KeyCode::Tab => self.pane = panes[(index + 1) % panes.len()],
+            KeyCode::BackTab => self.pane = panes[(index + panes.len() - 1) % panes.len()],
+            KeyCode::Up | KeyCode::Char('k') => self.step(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.step(1),
+            _ => match self.pane {
+                Pane::Zones | Pane::Records => self.record_key(key),
+                Pane::Services | Pane::Labels => self.service_key(key),
+            },
+        }
+    }
+
+    fn step(&mut self, by: isize) {
+        let move_in = |state: &mut ListState, len: usize| {
+            let next = state.selected().map_or(0, |i| i.saturating_add_signed(by));
+            state.select((len > 0).then(|| next.min(len - 1)));
+        };
+
+        match self.pane {
+            Pane::Zones => {
+                let previous = self.zones.selected();
+                move_in(&mut self.zones, self.config.zones.len());
+                if self.zones.selected() != previous {
+                    self.pending = Some(Action::Load);
+                }
+            }
+            Pane::Records => {
+                let next = self
+                    .record_state
+                    .selected()
+                    .map_or(0, |i| i.saturating_add_signed(by));
+                self.record_state
+                    .select((!self.records.is_empty()).then(|| next.min(self.records.len() - 1)));
+            }
+            Pane::Services => {
+                let len = self.service_names().len();
+                move_in(&mut self.services, len);
+                self.labels.select(None);
+            }
+            Pane::Labels => {
+                let len = self.service().map_or(0, |s| labels(s).len());
+                move_in(&mut self.labels, len);
+            }
+        }
+    }
+
+    fn record_key(&mut self, key: KeyEvent) {
+        let Some(record) = self.record().cloned() else {
+            return;
+        };
+
+        match key.code {
+            KeyCode::Char('r') => self.pending = Some(Action::Load),
+            KeyCode::Char('e') if self.pane == Pane::Records => {
+                self.mode = Mode::Edit(record.content.clone());
+            }
+            KeyCode::Char('p') if self.pane == Pane::Records => {
+                if !matches!(record.record_type.as_str(), "A" | "AAAA" | "CNAME") {
+                    self.status = format!("{} records can't be proxied.", record.record_type);
+                    return;
+                }
+                let mut update = record.to_update();
+                update.proxied = !record.proxied;
+                // Proxied records always have the automatic TTL.
+                if update.proxied {
+                    update.ttl = AUTO_TTL;
+                }
+                self.pending = Some(Action::Save(update));
+            }
+            KeyCode::Char('d') if self.pane == Pane::Records => self.mode = Mode::ConfirmDelete,
+            _ => {}
+        }
+    }
+
+    fn service_key(&mut self, key: KeyEvent) {
+        match (self.pane, key.code) {
+            (Pane::Services, KeyCode::Enter | KeyCode::Right) => {
+                self.pane = Pane::Labels;
+                self.labels.select(Some(0));
+            }
+            (Pane::Labels, KeyCode::Left) => self.pane = Pane::Services,
+            (Pane::Labels, KeyCode::Char(' ') | KeyCode::Enter) => {
+                self.status = self
+                    .toggle_selected_label()
+                    .unwrap_or_else(|e| format!("{e:#}"));
+            }
+            _ => {}
+        }
+    }
+
+    /// Switches the selected label off or on, and writes the compose file.
+    fn toggle_selected_label(&mut self) -> Result<String> {
+        let name = self
+            .service_names()
+            .get(self.services.selected().unwrap_or_default())
+            .cloned()
+            .context("There is no service selected.")?;
+        let index = self
+            .labels
+            .selected()
+            .context("There is no label selected.")?;
+        let (file, compose) = self.compose.as_mut().context("There is no compose file.")?;
+        let service = compose
+            .services
+            .0
+            .get_mut(&name)
+            .and_then(Option::as_mut)
+            .context("The service has no configuration.")?;
+        let (key, _, enabled) = labels(service)
+            .get(index)
+            .cloned()
+            .context("There is no label selected.")?;
+
+        toggle_label(service, &key);
+        edits::write(file, compose)?;
+
+        Ok(format!(
+            "Switched {key} {} in {}.",
+            if enabled { "off" } else { "on" },
+            file.display()
+        ))
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(30), Constraint::Fill(1)]).areas(main);
+        let (zones, services) = match self.compose {
+            Some(_) => {
+                let [zones, services] =
+                    Layout::vertical([Constraint::Percentage(40), Constraint::Fill(1)]).areas(left);
+                (zones, Some(services))
+            }
+            None => (left, None),
+        };
+
+        self.draw_zones(frame, zones);
+        if let Some(area) = services {
+            self.draw_services(frame, area);
+        }
+        match self.pane {
+            Pane::Services | Pane::Labels => self.draw_labels(frame, right),
+            Pane::Zones | Pane::Records => self.draw_records(frame, right),
+        }
+        self.draw_status(frame, status);
+    }
+
+    fn block(&self, title: &str, pane: Pane) -> Block<'static> {
+        let block = Block::bordered().title(format!(" {title} "));
+        match self.pane == pane {
+            true => block.border_style(Style::new().fg(Color::Cyan)),
+            false => block,
+        }
+    }
+
+    fn draw_zones(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<_> = self
+            .config
+            .zones
+            .iter()
+            .map(|z| ListItem::new(idn::to_unicode(&z.name)))
+            .collect();
+        let list = List::new(items)
+            .block(self.block("Zones", Pane::Zones))
+            .highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(list, area, &mut self.zones);
+    }
+
+    fn draw_records(&mut self, frame: &mut Frame, area: Rect) {
+        let rows = self.records.iter().map(|r| {
+            Row::new([
+                r.record_type.clone(),
+                idn::to_unicode(&r.name),
+                r.content.clone(),
+                if r.proxied { "yes" } else { "no" }.to_string(),
+                match r.ttl == AUTO_TTL {
+                    true => "auto".to_string(),
+                    false => r.ttl.to_string(),
+                },
+            ])
+        });
+        let widths = [
+            Constraint::Length(6),
+            Constraint::Percentage(35),
+            Constraint::Fill(1),
+            Constraint::Length(7),
+            Constraint::Length(6),
+        ];
+        let table = Table::new(rows, widths)
+            .header(Row::new(["Type", "Name", "Content", "Proxied", "TTL"]).bold())
+            .block(self.block("Records", Pane::Records))
+            .row_highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(table, area, &mut self.record_state);
+    }
+
+    fn draw_services(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<_> = self
+            .compose
+            .iter()
+            .flat_map(|(_, c)| &c.services.0)
+            .map(|(name, service)| {
+                let domains = service.as_ref().map(proxy::domains).unwrap_or_default();
+                match domains.is_empty() {
+                    true => ListItem::new(name.clone()),
+                    false => ListItem::new(Line::from(vec![
+                        name.clone().into(),
+                        format!(" {}", domains.join(", ")).dim(),
+                    ])),
+                }
+            })
+            .collect();
+        let list = List::new(items)
+            .block(self.block("Services", Pane::Services))
+            .highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(list, area, &mut self.services);
+    }
+
+    fn draw_labels(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<_> = self
+            .service()
+            .map(labels)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, value, enabled)| match enabled {
+                true => ListItem::new(format!("{key}: {value}")),
+                false => ListItem::new(format!("{key}: {value} (off)").dim()),
+            })
+            .collect();
+        let list = List::new(items)
+            .block(self.block("Labels", Pane::Labels))
+            .highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(list, area, &mut self.labels);
+    }
+
+    fn draw_status(&self, frame: &mut Frame, area: Rect) {
+        let line = match &self.mode {
+            Mode::Edit(input) => format!("New content: {input}_  (enter saves, esc cancels)"),
+            Mode::ConfirmDelete => match self.record() {
+                Some(r) => format!(
+                    "Delete the {} record {}? y/n",
+                    r.record_type,
+                    idn::to_unicode(&r.name)
+                ),
+                None => String::new(),
+            },
+            Mode::Browse if !self.status.is_empty() => self.status.clone(),
+            Mode::Browse => match self.pane {
+                Pane::Zones => "↑↓ zone  tab next pane  r reload  q quit",
+                Pane::Records => "e edit  p proxy  d delete  r reload  tab next pane  q quit",
+                Pane::Services => "enter labels  tab next pane  q quit",
+                Pane::Labels => "space switch on/off  esc back  q quit",
+            }
+            .to_string(),
+        };
+        frame.render_widget(Paragraph::new(line), area);
+    }
+}
+
+/// The service's labels with whether each is switched on, the ones switched
+/// off after the others.
+fn labels(service: &Service) -> Vec<(String, String, bool)> {
+    let enabled = proxy::label_pairs(&service.labels)
+        .into_iter()
+        .map(|(key, value)| (key, value, true));
+    let disabled = disabled_labels(service)
+        .into_iter()
+        .map(|(key, value)| (key, value, false));
+
+    enabled.chain(disabled).collect()
+}
+
+fn disabled_extension() -> Extension {
+    DISABLED_LABELS.parse().expect("starts with x-")
+}
+
+fn disabled_labels(service: &Service) -> Vec<(String, String)> {
+    service
+        .extensions
+        .get(&disabled_extension())
+        .and_then(Value::as_mapping)
+        .map(|mapping| {
+            mapping
+                .iter()
+                .filter_map(|(k, v)| Some((k.as_str()?.to_string(), v.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Removes the label from the service and keeps it under
+/// [`DISABLED_LABELS`], or puts a label kept there back.
+pub fn toggle_label(service: &mut Service, key: &str) {
+    let extension = disabled_extension();
+    let mut disabled: Mapping = disabled_labels(service)
+        .into_iter()
+        .map(|(k, v)| (Value::String(k), Value::String(v)))
+        .collect();
+
+    match disabled.remove(key) {
+        Some(value) => {
+            proxy::set_label(&mut service.labels, key, value.as_str().unwrap_or_default());
+        }
+        None => {
+            let value = proxy::label_pairs(&service.labels)
+                .into_iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v)
+                .unwrap_or_default();
+            proxy::remove_label(&mut service.labels, key);
+            disabled.insert(Value::String(key.to_string()), Value::String(value));
+        }
+    }
+
+    match disabled.is_empty() {
+        true => {
+            service.extensions.shift_remove(&extension);
+        }
+        false => {
+            service
+                .extensions
+                .insert(extension, Value::Mapping(disabled));
+        }
+    }
+}
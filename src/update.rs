@@ -0,0 +1,162 @@
+//! `eurus self-update`, which replaces the running binary with the one from
+//! the latest GitHub release.
+
+use std::path::PathBuf;
+
+use color_eyre::eyre::{bail, ensure, Context, ContextCompat, Result};
+use reqwest::header::USER_AGENT;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{logging, CLIENT};
+
+static RELEASES: &str = "https://api.github.com/repos/imvaskel/eurus/releases/latest";
+
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl Release {
+    /// The version without the tag's `v`.
+    pub fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|a| a.name == name)
+    }
+}
+
+/// The name of the release binary for this platform, e.g.
+/// `eurus-x86_64-linux`.
+pub fn asset_name() -> String {
+    format!(
+        "eurus-{}-{}{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        std::env::consts::EXE_SUFFIX
+    )
+}
+
+pub async fn latest() -> Result<Release> {
+    let response = logging::send(CLIENT.get(RELEASES).header(USER_AGENT, "eurus")).await?;
+    ensure!(
+        response.status().is_success(),
+        "GitHub returned {} for the latest release.",
+        response.status()
+    );
+
+    Ok(response.json().await?)
+}
+
+/// Whether `latest` is a later version than `current`, comparing the
+/// numbers of each part.
+pub fn is_newer(latest: &str, current: &str) -> bool {
+    let parts = |version: &str| -> Vec<u64> {
+        version
+            .split(['.', '-', '+'])
+            .map_while(|p| p.parse().ok())
+            .collect()
+    };
+
+    parts(latest) > parts(current)
+}
+
+async fn download(url: &str) -> Result<Vec<u8>> {
+    let response = logging::send(CLIENT.get(url).header(USER_AGENT, "eurus")).await?;
+    ensure!(
+        response.status().is_success(),
+        "Downloading {url} failed with {}.",
+        response.status()
+    );
+
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Downloads the binary for this platform from the release and checks it
+/// against the release's checksum, either `<binary>.sha256` or a
+/// `SHA256SUMS` list.
+pub async fn fetch_binary(release: &Release) -> Result<Vec<u8>> {
+    let name = asset_name();
+    let asset = release
+        .asset(&name)
+        .with_context(|| format!("Release {} has no {name}.", release.tag_name))?;
+    let sums = release
+        .asset(&format!("{name}.sha256"))
+        .or_else(|| release.asset("SHA256SUMS"))
+        .with_context(|| {
+            format!(
+                "Release {} has no checksum for {name}, so it wasn't installed.",
+                release.tag_name
+            )
+        })?;
+
+    let binary = download(&asset.browser_download_url).await?;
+    let sums = String::from_utf8(download(&sums.browser_download_url).await?)
+        .context("The checksum file isn't text.")?;
+    let expected = checksum_for(&sums, &name)
+        .with_context(|| format!("The checksum file has no entry for {name}."))?;
+    let actual = format!("{:x}", Sha256::digest(&binary));
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!("The checksum of {name} doesn't match, so it wasn't installed.");
+    }
+
+    Ok(binary)
+}
+
+/// The checksum of `name` in a `sha256sum` style file, or the only one when
+/// the file has a single bare checksum.
+fn checksum_for<'a>(sums: &'a str, name: &str) -> Option<&'a str> {
+    let lines: Vec<Vec<&str>> = sums
+        .lines()
+        .map(|l| l.split_whitespace().collect())
+        .filter(|l: &Vec<&str>| !l.is_empty())
+        .collect();
+
+    match lines.as_slice() {
+        [line] if line.len() == 1 => Some(line[0]),
+        _ => lines
+            .iter()
+            .find(|l| l.get(1).map(|f| f.trim_start_matches('*')) == Some(name))
+            .map(|l| l[0]),
+    }
+}
+
+/// Puts `binary` in place of the running executable, returning its path.
+/// The new file is written next to it first and then renamed over it, so a
+/// failure never leaves a half written binary.
+pub fn replace_current(binary: &[u8]) -> Result<PathBuf> {
+    let current = std::env::current_exe()?;
+    let current = current.canonicalize().unwrap_or(current);
+    let staged = current.with_extension("new");
+    let write = || -> std::io::Result<()> {
+        std::fs::write(&staged, binary)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))?;
+        }
+        // Windows can't replace a running executable, but can rename it.
+        #[cfg(windows)]
+        std::fs::rename(&current, current.with_extension("old"))?;
+        std::fs::rename(&staged, &current)
+    };
+
+    write().with_context(|| {
+        let _ = std::fs::remove_file(&staged);
+        format!(
+            "Could not replace {}, it may need to be run as a user that can write there.",
+            current.display()
+        )
+    })?;
+
+    Ok(current)
+}
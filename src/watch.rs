@@ -0,0 +1,137 @@
+//! Watching a directory of compose projects for services added without a
+//! proxy, for `web --watch`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+
+use ::notify::{recommended_watcher, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use color_eyre::eyre::{Context, Result};
+use tokio::sync::mpsc;
+
+use crate::{compose, proxy};
+
+/// How long changes are collected before looking at them, as editors and
+/// `git pull` write files in several steps.
+static SETTLE: Duration = Duration::from_millis(500);
+
+pub struct ProjectWatcher {
+    root: PathBuf,
+    // Stops watching when dropped.
+    _watcher: RecommendedWatcher,
+    changes: mpsc::UnboundedReceiver<PathBuf>,
+    /// The services of each compose file when it was last read.
+    known: BTreeMap<PathBuf, BTreeSet<String>>,
+}
+
+/// A service that appeared in a compose file without labels for any proxy.
+#[derive(Debug, Clone)]
+pub struct NewService {
+    pub file: PathBuf,
+    pub service: String,
+}
+
+impl ProjectWatcher {
+    /// Starts watching `root`. The services already in its projects are only
+    /// remembered, so just ones added from now on are reported.
+    pub fn new(root: &Path) -> Result<Self> {
+        let (sender, changes) = mpsc::unbounded_channel();
+        let mut watcher = recommended_watcher(move |event: ::notify::Result<Event>| {
+            for path in event.map(|e| e.paths).unwrap_or_default() {
+                if compose::is_compose_file(&path) {
+                    let _ = sender.send(path);
+                }
+            }
+        })?;
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .with_context(|| format!("Could not watch {}.", root.display()))?;
+
+        let known = compose::find_projects(root)
+            .into_iter()
+            .filter_map(|file| Some((file.clone(), services(&file)?.into_keys().collect())))
+            .collect();
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            _watcher: watcher,
+            changes,
+            known,
+        })
+    }
+
+    /// The compose files being watched.
+    pub fn projects(&self) -> impl Iterator<Item = &PathBuf> {
+        self.known.keys()
+    }
+
+    /// Waits for compose files to change, returning the services added to
+    /// them that no proxy serves yet.
+    pub async fn next(&mut self) -> Vec<NewService> {
+        let Some(first) = self.changes.recv().await else {
+            return vec![];
+        };
+        tokio::time::sleep(SETTLE).await;
+        let mut changed = BTreeSet::from([first]);
+        while let Ok(path) = self.changes.try_recv() {
+            changed.insert(path);
+        }
+
+        changed.retain(|f| !self.ignored(f));
+
+        let mut found = vec![];
+        for file in changed {
+            // A file caught half written is read again on its next change.
+            let Some(services) = services(&file) else {
+                continue;
+            };
+            let known = self.known.entry(file.clone()).or_default();
+            found.extend(
+                services
+                    .iter()
+                    .filter(|(name, proxied)| !known.contains(*name) && !**proxied)
+                    .map(|(name, _)| NewService {
+                        file: file.clone(),
+                        service: name.clone(),
+                    }),
+            );
+            *known = services.into_keys().collect();
+        }
+
+        found
+    }
+
+    /// Whether the file is somewhere [`compose::find_projects`] skips, like
+    /// the backups eurus keeps in `.eurus-backups`.
+    fn ignored(&self, file: &Path) -> bool {
+        file.strip_prefix(&self.root)
+            .unwrap_or(file)
+            .components()
+            .any(|c| match c {
+                Component::Normal(name) => {
+                    let name = name.to_string_lossy();
+                    name.starts_with('.') || name == "node_modules"
+                }
+                _ => false,
+            })
+    }
+}
+
+/// The services of the compose file, with whether a proxy serves each.
+fn services(file: &Path) -> Option<BTreeMap<String, bool>> {
+    let compose = compose::parse(&std::fs::read_to_string(file).ok()?).ok()?;
+
+    Some(
+        compose
+            .services
+            .0
+            .into_iter()
+            .map(|(name, service)| {
+                let proxied = service.as_ref().and_then(proxy::backend).is_some();
+                (name, proxied)
+            })
+            .collect(),
+    )
+}
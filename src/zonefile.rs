@@ -0,0 +1,443 @@
+//! Records in the BIND zone file format.
+
+use color_eyre::eyre::{bail, ensure, eyre, Context, ContextCompat, Result};
+
+use crate::provider::{DnsCreateUpdate, DnsListResponse, AUTO_TTL};
+
+/// Cloudflare's automatic TTL, written instead of [`AUTO_TTL`] since other
+/// servers would take a TTL of 1 literally.
+const AUTO_TTL_SECONDS: u32 = 300;
+
+/// The comment Cloudflare's own export and import mark proxied records with.
+pub const PROXIED_TAG: &str = "cf_tags=cf-proxied:true";
+
+/// The records as a zone file for `zone`, with names relative to its origin.
+/// Proxied records are marked the way Cloudflare does, so importing the file
+/// there keeps them proxied.
+pub fn render(zone: &str, records: &[DnsListResponse]) -> String {
+    let mut records: Vec<_> = records.iter().collect();
+    records.sort_by(|a, b| (&a.name, &a.record_type).cmp(&(&b.name, &b.record_type)));
+
+    let mut out = format!(
+        ";; {zone}, exported by eurus on {}\n$ORIGIN {zone}.\n$TTL {AUTO_TTL_SECONDS}\n\n",
+        chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    for record in records {
+        let name = match record.name.strip_suffix(zone) {
+            Some("") => "@".to_string(),
+            Some(prefix) if prefix.ends_with('.') => prefix.trim_end_matches('.').to_string(),
+            _ => format!("{}.", record.name),
+        };
+        let ttl = match record.ttl == AUTO_TTL {
+            true => AUTO_TTL_SECONDS,
+            false => record.ttl,
+        };
+        out.push_str(&format!(
+            "{name}\t{ttl}\tIN\t{}\t{}",
+            record.record_type,
+            rdata(record)
+        ));
+        if record.proxied {
+            out.push_str(&format!(" ; {PROXIED_TAG}"));
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+/// The record's data as a zone file writes it, with host names fully
+/// qualified and TXT values quoted.
+fn rdata(record: &DnsListResponse) -> String {
+    let content = match record.record_type.as_str() {
+        "CNAME" | "NS" | "MX" | "PTR" if !record.content.ends_with('.') => {
+            format!("{}.", record.content)
+        }
+        "TXT" if !record.content.starts_with('"') => {
+            format!("\"{}\"", record.content.replace('"', "\\\""))
+        }
+        _ => record.content.clone(),
+    };
+
+    match record.priority {
+        Some(priority) => format!("{priority} {content}"),
+        None => content,
+    }
+}
+
+/// The records in a zone file, with absolute names. `origin` is used until the
+/// file sets its own with `$ORIGIN`. The SOA and the NS records of the origin
+/// are left out since the provider manages those itself.
+pub fn parse(text: &str, origin: &str) -> Result<Vec<DnsCreateUpdate>> {
+    let mut origin = origin.trim_end_matches('.').to_lowercase();
+    let mut default_ttl = AUTO_TTL;
+    let mut owner: Option<String> = None;
+    let mut records = vec![];
+
+    for entry in entries(text)? {
+        let context = || format!("Line {} of the zone file", entry.line);
+        let mut tokens = entry.tokens.iter().map(String::as_str).peekable();
+        let Some(&first) = tokens.peek() else {
+            continue;
+        };
+
+        if let Some(directive) = first.strip_prefix('$') {
+            tokens.next();
+            let value = tokens
+                .next()
+                .with_context(|| format!("{}: ${directive} needs a value.", context()))?;
+            match directive.to_uppercase().as_str() {
+                "ORIGIN" => origin = absolute(value, &origin),
+                "TTL" => default_ttl = parse_ttl(value).with_context(context)?,
+                _ => bail!("{}: ${directive} isn't supported.", context()),
+            }
+            continue;
+        }
+
+        if !entry.indented {
+            owner = Some(absolute(first, &origin));
+            tokens.next();
+        }
+        let name = owner
+            .clone()
+            .with_context(|| format!("{}: the record has no name.", context()))?;
+
+        let mut ttl = default_ttl;
+        let record_type = loop {
+            let token = tokens
+                .next()
+                .with_context(|| format!("{}: the record has no type.", context()))?;
+            match token.to_uppercase().as_str() {
+                "IN" | "CH" | "HS" => {}
+                _ if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                    ttl = parse_ttl(token).with_context(context)?;
+                }
+                record_type => break record_type.to_string(),
+            }
+        };
+        let rdata: Vec<_> = tokens.collect();
+        ensure!(
+            !rdata.is_empty(),
+            "{}: the {record_type} record has no value.",
+            context()
+        );
+
+        if record_type == "SOA" || (record_type == "NS" && name == origin) {
+            continue;
+        }
+        let field = |i: usize| {
+            rdata.get(i).copied().with_context(|| {
+                format!(
+                    "{}: the {record_type} record is missing a field.",
+                    context()
+                )
+            })
+        };
+        let number = |i: usize| -> Result<u16> {
+            field(i)?
+                .parse()
+                .with_context(|| format!("{}: {} isn't a number.", context(), rdata[i]))
+        };
+
+        let mut record = DnsCreateUpdate {
+            name,
+            record_type: record_type.clone(),
+            ttl,
+            proxied: entry.comment.contains(PROXIED_TAG),
+            ..Default::default()
+        };
+        match record_type.as_str() {
+            "CNAME" | "NS" | "PTR" => record.content = absolute(field(0)?, &origin),
+            "MX" => {
+                record.priority = Some(number(0)?);
+                record.content = absolute(field(1)?, &origin);
+            }
+            "SRV" => {
+                let priority = number(0)?;
+                let (weight, port) = (number(1)?, number(2)?);
+                let target = absolute(field(3)?, &origin);
+                record.content = format!("{weight} {port} {target}");
+                record.priority = Some(priority);
+                record.data = Some(serde_json::json!({
+                    "priority": priority,
+                    "weight": weight,
+                    "port": port,
+                    "target": target,
+                }));
+            }
+            "CAA" => {
+                let flags = number(0)?;
+                let tag = field(1)?;
+                let value = unquote(&rdata[2..].join(" "));
+                record.content = format!("{flags} {tag} \"{value}\"");
+                record.data =
+                    Some(serde_json::json!({ "flags": flags, "tag": tag, "value": value }));
+            }
+            // Long values are split into several strings, which together are
+            // the value.
+            "TXT" => record.content = rdata.iter().map(|s| unquote(s)).collect(),
+            _ => record.content = rdata.join(" "),
+        }
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// A record's text, with the lines of parentheses joined.
+struct Entry {
+    /// Where it starts, from 1.
+    line: usize,
+    /// Whether it starts with whitespace, and so has the previous name.
+    indented: bool,
+    tokens: Vec<String>,
+    comment: String,
+}
+
+fn entries(text: &str) -> Result<Vec<Entry>> {
+    let mut entries = vec![];
+    let mut current: Option<Entry> = None;
+    let mut depth = 0;
+
+    for (i, line) in text.lines().enumerate() {
+        let entry = current.get_or_insert_with(|| Entry {
+            line: i + 1,
+            indented: line.starts_with([' ', '\t']),
+            tokens: vec![],
+            comment: String::new(),
+        });
+
+        let mut chars = line.chars().peekable();
+        let mut token = String::new();
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if quoted => {
+                    token.push(c);
+                    token.extend(chars.next());
+                }
+                '"' => {
+                    token.push(c);
+                    quoted = !quoted;
+                }
+                _ if quoted => token.push(c),
+                ';' => {
+                    entry.comment.push_str(&chars.by_ref().collect::<String>());
+                    break;
+                }
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                c if c.is_whitespace() => {
+                    if !token.is_empty() {
+                        entry.tokens.push(std::mem::take(&mut token));
+                    }
+                }
+                c => token.push(c),
+            }
+        }
+        ensure!(!quoted, "Line {}: a quote isn't closed.", i + 1);
+        if !token.is_empty() {
+            entry.tokens.push(token);
+        }
+
+        if depth == 0 {
+            entries.extend(current.take());
+        }
+    }
+    if depth != 0 {
+        return Err(eyre!("A parenthesis in the zone file isn't closed."));
+    }
+
+    Ok(entries)
+}
+
+/// The name relative to `origin` made absolute, without the trailing dot.
+fn absolute(name: &str, origin: &str) -> String {
+    let name = name.to_lowercase();
+    match name.as_str() {
+        "@" => origin.to_string(),
+        _ => match name.strip_suffix('.') {
+            Some(absolute) => absolute.to_string(),
+            None => format!("{name}.{origin}"),
+        },
+    }
+}
+
+/// A TTL in seconds, or with units like `1h30m`.
+fn parse_ttl(value: &str) -> Result<u32> {
+    if let Ok(seconds) = value.parse() {
+        return Ok(seconds);
+    }
+
+    let mut total = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        match c.to_ascii_lowercase() {
+            c if c.is_ascii_digit() => number.push(c),
+            unit @ ('s' | 'm' | 'h' | 'd' | 'w') if !number.is_empty() => {
+                let seconds = match unit {
+                    's' => 1,
+                    'm' => 60,
+                    'h' => 3600,
+                    'd' => 86400,
+                    _ => 604800,
+                };
+                total = std::mem::take(&mut number)
+                    .parse::<u32>()
+                    .ok()
+                    .and_then(|n| n.checked_mul(seconds))
+                    .and_then(|n| n.checked_add(total))
+                    .with_context(|| format!("{value} isn't a TTL."))?;
+            }
+            _ => bail!("{value} isn't a TTL."),
+        }
+    }
+    ensure!(number.is_empty(), "{value} isn't a TTL.");
+
+    Ok(total)
+}
+
+/// A string without its zone file quotes and escapes.
+fn unquote(value: &str) -> String {
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value);
+    value.replace("\\\"", "\"").replace("\\\\", "\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record<'a>(records: &'a [DnsCreateUpdate], name: &str, kind: &str) -> &'a DnsCreateUpdate {
+        records
+            .iter()
+            .find(|r| r.name == name && r.record_type == kind)
+            .unwrap_or_else(|| panic!("no {kind} record for {name}"))
+    }
+
+    #[test]
+    fn parses_ttls_with_units() {
+        assert_eq!(parse_ttl("300").unwrap(), 300);
+        assert_eq!(parse_ttl("1h30m").unwrap(), 5400);
+        assert_eq!(parse_ttl("1W2d").unwrap(), 777600);
+        assert!(parse_ttl("10x").is_err());
+        assert!(parse_ttl("5h3").is_err());
+    }
+
+    #[test]
+    fn rejects_ttls_that_overflow() {
+        assert!(parse_ttl("10000w").is_err());
+        assert!(parse_ttl("4294967295s1s").is_err());
+        assert!(parse_ttl("99999999999").is_err());
+    }
+
+    #[test]
+    fn makes_relative_names_absolute() {
+        let records = parse(
+            "@ 300 IN A 192.0.2.1\n\
+             www IN CNAME @\n\
+             api IN CNAME other.example.net.\n\
+             \t600 IN AAAA 2001:db8::1\n",
+            "example.com.",
+        )
+        .unwrap();
+
+        assert_eq!(record(&records, "example.com", "A").ttl, 300);
+        assert_eq!(
+            record(&records, "www.example.com", "CNAME").content,
+            "example.com"
+        );
+        assert_eq!(
+            record(&records, "api.example.com", "CNAME").content,
+            "other.example.net"
+        );
+        // An indented line belongs to the name before it.
+        assert_eq!(record(&records, "api.example.com", "AAAA").ttl, 600);
+    }
+
+    #[test]
+    fn follows_origin_and_ttl_directives() {
+        let records = parse(
+            "$TTL 1h\n\
+             $ORIGIN sub.example.com.\n\
+             app IN A 192.0.2.1\n\
+             $ORIGIN other\n\
+             app IN A 192.0.2.2\n",
+            "example.com",
+        )
+        .unwrap();
+
+        assert_eq!(record(&records, "app.sub.example.com", "A").ttl, 3600);
+        assert_eq!(
+            record(&records, "app.other.sub.example.com", "A").content,
+            "192.0.2.2"
+        );
+    }
+
+    #[test]
+    fn joins_lines_in_parentheses_and_skips_the_soa() {
+        let records = parse(
+            "@ IN SOA ns1.example.com. admin.example.com. (\n\
+             \t2024010101 ; serial\n\
+             \t3600 600 86400 300 )\n\
+             @ IN NS ns1.example.com.\n\
+             mail IN MX ( 10\n\
+             \tmx.example.com. )\n",
+            "example.com",
+        )
+        .unwrap();
+
+        assert_eq!(records.len(), 1);
+        let mx = record(&records, "mail.example.com", "MX");
+        assert_eq!(mx.priority, Some(10));
+        assert_eq!(mx.content, "mx.example.com");
+    }
+
+    #[test]
+    fn unquotes_txt_values() {
+        let records = parse(
+            "txt IN TXT \"v=spf1 include:example.net ~all\"\n\
+             long IN TXT \"first \" \"second; not a comment\"\n\
+             escaped IN TXT \"say \\\"hi\\\"\"\n",
+            "example.com",
+        )
+        .unwrap();
+
+        assert_eq!(
+            record(&records, "txt.example.com", "TXT").content,
+            "v=spf1 include:example.net ~all"
+        );
+        assert_eq!(
+            record(&records, "long.example.com", "TXT").content,
+            "first second; not a comment"
+        );
+        assert_eq!(
+            record(&records, "escaped.example.com", "TXT").content,
+            "say \"hi\""
+        );
+        assert!(parse("txt IN TXT \"open\n", "example.com").is_err());
+        assert!(parse("mx IN MX ( 10\n", "example.com").is_err());
+    }
+
+    #[test]
+    fn parses_srv_and_caa_into_data() {
+        let records = parse(
+            "_sip._tcp IN SRV 10 5 5060 sip\n\
+             @ IN CAA 0 issue \"letsencrypt.org\"\n\
+             proxied IN A 192.0.2.1 ; cf_tags=cf-proxied:true\n",
+            "example.com",
+        )
+        .unwrap();
+
+        let srv = record(&records, "_sip._tcp.example.com", "SRV");
+        assert_eq!(srv.priority, Some(10));
+        assert_eq!(srv.content, "5 5060 sip.example.com");
+        assert_eq!(srv.data.as_ref().unwrap()["port"], 5060);
+        let caa = record(&records, "example.com", "CAA");
+        assert_eq!(caa.content, "0 issue \"letsencrypt.org\"");
+        assert_eq!(caa.data.as_ref().unwrap()["value"], "letsencrypt.org");
+        assert!(record(&records, "proxied.example.com", "A").proxied);
+    }
+}
@@ -0,0 +1,291 @@
+use eurus::{
+    cloudflare::{self, Cloudflare, BASE_URL_VAR},
+    config::Config,
+    error::EurusError,
+    provider::{DnsCreateUpdate, DnsProvider, AUTO_TTL},
+};
+use serde_json::json;
+use wiremock::{
+    matchers::{body_json, header, method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn record(id: &str, name: &str) -> serde_json::Value {
+    json!({
+        "id": id,
+        "name": name,
+        "type": "A",
+        "content": "192.0.2.1",
+        "proxied": true,
+        "ttl": 1,
+    })
+}
+
+fn ok(result: serde_json::Value) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "errors": [], "result": result }))
+}
+
+fn client(server: &MockServer) -> Cloudflare {
+    Cloudflare::new("token").with_base_url(&server.uri())
+}
+
+fn body(id: Option<&str>) -> DnsCreateUpdate {
+    DnsCreateUpdate {
+        name: "app.example.com".to_string(),
+        id: id.map(str::to_string),
+        record_type: "A".to_string(),
+        proxied: true,
+        content: "192.0.2.1".to_string(),
+        ttl: AUTO_TTL,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn creates_records_with_post() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/zones/zone/dns_records"))
+        .and(header("authorization", "Bearer token"))
+        .and(body_json(json!({
+            "name": "app.example.com",
+            "type": "A",
+            "proxied": true,
+            "content": "192.0.2.1",
+            "ttl": 1,
+        })))
+        .respond_with(ok(record("new", "app.example.com")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let created = client(&server)
+        .upsert_record("zone", &body(None))
+        .await
+        .unwrap();
+
+    assert_eq!(created.id, "new");
+}
+
+#[tokio::test]
+async fn does_not_resend_creates_after_server_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/zones/zone/dns_records"))
+        .respond_with(ResponseTemplate::new(502))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let created = client(&server).upsert_record("zone", &body(None)).await;
+
+    assert!(created.is_err());
+}
+
+#[tokio::test]
+async fn updates_records_with_patch() {
+    let server = MockServer::start().await;
+    Mock::given(method("PATCH"))
+        .and(path("/zones/zone/dns_records/existing"))
+        .and(body_json(json!({
+            "name": "app.example.com",
+            "id": "existing",
+            "type": "A",
+            "proxied": true,
+            "content": "192.0.2.1",
+            "ttl": 1,
+        })))
+        .respond_with(ok(record("existing", "app.example.com")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let updated = client(&server)
+        .upsert_record("zone", &body(Some("existing")))
+        .await
+        .unwrap();
+
+    assert_eq!(updated.id, "existing");
+}
+
+#[tokio::test]
+async fn clears_comments_and_tags() {
+    let server = MockServer::start().await;
+    Mock::given(method("PATCH"))
+        .and(path("/zones/zone/dns_records/existing"))
+        .and(body_json(json!({
+            "name": "app.example.com",
+            "id": "existing",
+            "type": "A",
+            "proxied": true,
+            "content": "192.0.2.1",
+            "ttl": 1,
+            "comment": "",
+            "tags": [],
+        })))
+        .respond_with(ok(record("existing", "app.example.com")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let cleared = DnsCreateUpdate {
+        comment: Some(String::new()),
+        tags: Some(vec![]),
+        ..body(Some("existing"))
+    };
+    client(&server)
+        .upsert_record("zone", &cleared)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn authenticates_with_the_global_key() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/zones/zone"))
+        .and(header("x-auth-email", "me@example.com"))
+        .and(header("x-auth-key", "token"))
+        .respond_with(ok(json!({ "id": "zone", "name": "example.com" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let zone = client(&server)
+        .with_global_key("me@example.com")
+        .get_zone("zone")
+        .await
+        .unwrap();
+
+    assert_eq!(zone.name, "example.com");
+}
+
+#[tokio::test]
+async fn returns_api_errors_with_their_codes() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/zones/zone/dns_records"))
+        .respond_with(
+            ResponseTemplate::new(400)
+                .insert_header("cf-ray", "8a1b2c3d4e5f6a7b-AMS")
+                .set_body_json(json!({
+                    "errors": [{ "code": 81057, "message": "The record already exists." }],
+                    "result": null,
+                })),
+        )
+        .mount(&server)
+        .await;
+
+    let err = client(&server)
+        .upsert_record("zone", &body(None))
+        .await
+        .unwrap_err();
+
+    match err {
+        EurusError::ApiError {
+            status,
+            codes,
+            message,
+            ray,
+        } => {
+            assert_eq!(status, Some(400));
+            assert_eq!(codes, vec![81057]);
+            assert!(message.contains("The record already exists."));
+            assert_eq!(ray.as_deref(), Some("8a1b2c3d4e5f6a7b-AMS"));
+        }
+        e => panic!("expected an api error, got {e:?}"),
+    }
+}
+
+#[tokio::test]
+async fn missing_zones_are_gone() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/zones/zone"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errors": [{ "code": 7003, "message": "Could not route to /zones/zone." }],
+            "result": null,
+        })))
+        .mount(&server)
+        .await;
+
+    let err = client(&server).get_zone("zone").await.unwrap_err();
+
+    assert!(err.is_gone());
+}
+
+#[tokio::test]
+async fn lists_every_page_of_records() {
+    let server = MockServer::start().await;
+    for (page, id) in [(1, "first"), (2, "second")] {
+        Mock::given(method("GET"))
+            .and(path("/zones/zone/dns_records"))
+            .and(query_param("page", page.to_string()))
+            .and(query_param("per_page", "100"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "errors": [],
+                "result": [record(id, &format!("{id}.example.com"))],
+                "result_info": { "page": page, "total_pages": 2 },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+    }
+
+    let records = client(&server).list_records("zone").await.unwrap();
+
+    let ids: Vec<_> = records.iter().map(|r| r.id.as_str()).collect();
+    assert_eq!(ids, ["first", "second"]);
+}
+
+async fn serves_records(server: &MockServer, id: &str) {
+    Mock::given(method("GET"))
+        .and(path("/zones/zone/dns_records"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "errors": [],
+            "result": [record(id, "app.example.com")],
+            "result_info": { "page": 1, "total_pages": 1 },
+        })))
+        .expect(1)
+        .mount(server)
+        .await;
+}
+
+// One test, since the environment variable is shared by the whole process.
+#[tokio::test]
+async fn sends_requests_to_the_configured_base_url() {
+    let (configured, overridden) = (MockServer::start().await, MockServer::start().await);
+    serves_records(&configured, "configured").await;
+    serves_records(&overridden, "overridden").await;
+    let config = Config {
+        cloudflare_key: "token".to_string(),
+        api_base_url: format!("{}/", configured.uri()),
+        ..Default::default()
+    };
+
+    let records = Cloudflare::from_config(&config)
+        .unwrap()
+        .list_records("zone")
+        .await
+        .unwrap();
+    assert_eq!(records[0].id, "configured");
+
+    Mock::given(method("GET"))
+        .and(path("/ips"))
+        .respond_with(ok(
+            json!({ "ipv4_cidrs": ["192.0.2.0/24"], "ipv6_cidrs": [] }),
+        ))
+        .expect(1)
+        .mount(&configured)
+        .await;
+    let edge = cloudflare::edge_ips(&config).await.unwrap();
+    assert_eq!(edge.ipv4_cidrs, ["192.0.2.0/24"]);
+
+    std::env::set_var(BASE_URL_VAR, overridden.uri());
+    let records = Cloudflare::from_config(&config)
+        .unwrap()
+        .list_records("zone")
+        .await;
+    std::env::remove_var(BASE_URL_VAR);
+    assert_eq!(records.unwrap()[0].id, "overridden");
+}